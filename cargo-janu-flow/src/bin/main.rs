@@ -42,6 +42,8 @@ pub enum ZFCtl {
         release: bool,
         #[structopt(short = "t", long = "tag", default_value = "latest")]
         version_tag: String,
+        #[structopt(short = "s", long = "signing-key")]
+        signing_key: Option<std::path::PathBuf>,
         cargo_build_flags: Vec<String>,
     },
     New {
@@ -50,6 +52,10 @@ pub enum ZFCtl {
         kind: NodeKind,
     },
     List,
+    Header {
+        #[structopt(short, long, default_value = "janu_flow.h")]
+        output: std::path::PathBuf,
+    },
     Push {
         graph_id: String,
     },
@@ -138,6 +144,7 @@ async fn main() {
             manifest_path,
             release,
             version_tag,
+            signing_key,
             mut cargo_build_flags,
         } => {
             // `cargo janu-flow` invocation passes the `janu-flow` arg through.
@@ -175,6 +182,37 @@ async fn main() {
             };
             let uri = format!("file://{}", target);
 
+            println!(
+                "{} Node {} - Kind {}",
+                "Compiling".green().bold(),
+                node_info.id,
+                node_info.kind.to_string()
+            );
+
+            match cargo_janu_flow::utils::cargo_build(&cargo_build_flags, release, &manifest_dir) {
+                Ok(_) => (),
+                Err(_) => {
+                    println!("{}: cargo build failed", "error".red().bold());
+                    exit(-1);
+                }
+            }
+
+            // The checksum and the signature can only be computed once the library is built.
+            let (checksum, signature) = match cargo_janu_flow::utils::checksum_library(
+                std::path::Path::new(&target),
+                signing_key.as_deref(),
+            ) {
+                Ok(res) => res,
+                Err(e) => {
+                    println!(
+                        "{}: unable to compute the checksum of the library {:?}",
+                        "error".red().bold(),
+                        e
+                    );
+                    exit(-1);
+                }
+            };
+
             let (metadata_graph, _metadata_arch, descriptor) = match node_info.kind {
                 NodeKind::Operator => {
                     if node_info.inputs.is_none() {
@@ -211,17 +249,22 @@ async fn main() {
                         inputs: inputs.clone(),
                         outputs: outputs.clone(),
                         uri: Some(uri.clone()),
+                        checksum: Some(checksum.clone()),
+                        signature: signature.clone(),
+                        isolated: false,
                         configuration: None,
                         runtime: None,
                         deadline: None,
+                        input_rule: None,
+                        restart_policy: None,
                     };
 
                     let metadata_arch = RegistryNodeArchitecture {
                         arch: String::from(std::env::consts::ARCH),
                         os: String::from(std::env::consts::OS),
                         uri,
-                        checksum: String::from(""),
-                        signature: String::from(""),
+                        checksum: checksum.clone(),
+                        signature: signature.clone().unwrap_or_default(),
                     };
 
                     let metadata_tag = RegistryNodeTag {
@@ -280,17 +323,22 @@ async fn main() {
                         id: NodeId::from(node_info.id.clone()),
                         output: output.clone(),
                         uri: Some(uri.clone()),
+                        checksum: Some(checksum.clone()),
+                        signature: signature.clone(),
+                        isolated: false,
                         configuration: None,
                         runtime: None,
                         period: None,
+                        missed_ticks: None,
+                        restart_policy: None,
                     };
 
                     let metadata_arch = RegistryNodeArchitecture {
                         arch: String::from(std::env::consts::ARCH),
                         os: String::from(std::env::consts::OS),
                         uri,
-                        checksum: String::from(""),
-                        signature: String::from(""),
+                        checksum: checksum.clone(),
+                        signature: signature.clone().unwrap_or_default(),
                     };
 
                     let metadata_tag = RegistryNodeTag {
@@ -349,16 +397,20 @@ async fn main() {
                         id: NodeId::from(node_info.id.clone()),
                         input: input.clone(),
                         uri: Some(uri.clone()),
+                        checksum: Some(checksum.clone()),
+                        signature: signature.clone(),
+                        isolated: false,
                         configuration: None,
                         runtime: None,
+                        restart_policy: None,
                     };
 
                     let metadata_arch = RegistryNodeArchitecture {
                         arch: String::from(std::env::consts::ARCH),
                         os: String::from(std::env::consts::OS),
                         uri,
-                        checksum: String::from(""),
-                        signature: String::from(""),
+                        checksum: checksum.clone(),
+                        signature: signature.clone().unwrap_or_default(),
                     };
 
                     let metadata_tag = RegistryNodeTag {
@@ -386,20 +438,6 @@ async fn main() {
                     (metadata_graph, metadata_arch, yml_descriptor)
                 }
             };
-            println!(
-                "{} Node {} - Kind {}",
-                "Compiling".green().bold(),
-                node_info.id,
-                node_info.kind.to_string()
-            );
-
-            match cargo_janu_flow::utils::cargo_build(&cargo_build_flags, release, &manifest_dir) {
-                Ok(_) => (),
-                Err(_) => {
-                    println!("{}: cargo build failed", "error".red().bold());
-                    exit(-1);
-                }
-            }
             match cargo_janu_flow::utils::store_zf_metadata(&metadata_graph, &target_dir) {
                 Ok(res) => {
                     println!("{} stored in {}", "Metadata".green().bold(), res.bold());
@@ -504,6 +542,20 @@ async fn main() {
             #[cfg(not(feature = "local_registry"))]
             println!("Offline mode!")
         }
+        ZFCtl::Header { output } => {
+            match std::fs::write(&output, janu_flow::runtime::dataflow::cabi::C_HEADER) {
+                Ok(_) => println!("{} {}", "Written".green().bold(), output.display()),
+                Err(e) => {
+                    println!(
+                        "{}: failed to write {}: {}",
+                        "error".red().bold(),
+                        output.display(),
+                        e
+                    );
+                    exit(-1);
+                }
+            }
+        }
         _ => unimplemented!("Not yet..."),
     }
 }
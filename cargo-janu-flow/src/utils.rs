@@ -21,6 +21,7 @@ use serde::Deserialize;
 use std::process::Command;
 use janu_flow::model::link::PortDescriptor;
 use janu_flow::model::{NodeKind, RegistryNode};
+use janu_flow::runtime::dataflow::integrity;
 
 pub static ZF_OUTPUT_DIRECTORY: &str = "janu-flow";
 
@@ -243,6 +244,25 @@ pub fn cargo_build(flags: &[String], release: bool, manifest_dir: &Path) -> CZFR
     Ok(())
 }
 
+/// Computes the checksum of the library `target` and, if a `signing_key` file is given, signs it
+/// with the hex-encoded ed25519 secret key it contains.
+pub fn checksum_library(
+    target: &Path,
+    signing_key: Option<&Path>,
+) -> CZFResult<(String, Option<String>)> {
+    let checksum = integrity::compute_checksum(target)?;
+    let signature = match signing_key {
+        Some(path) => {
+            let key = std::fs::read_to_string(path)
+                .map_err(|e| CZFError::IoFile("Unable to read signing key", e, path.into()))?;
+            Some(integrity::sign_checksum(&checksum, key.trim())?)
+        }
+        None => None,
+    };
+
+    Ok((checksum, signature))
+}
+
 pub fn store_zf_metadata(metadata: &RegistryNode, target_dir: &Path) -> CZFResult<String> {
     let metadata_dir = PathBuf::from(format!("{}/{}", target_dir.display(), ZF_OUTPUT_DIRECTORY));
 
@@ -29,6 +29,7 @@ use std::fs::read_to_string;
 use structopt::StructOpt;
 use uuid::Uuid;
 use janu_flow::async_std::sync::Arc;
+use janu_flow::model::node::OperatorDescriptor;
 use janu_flow::runtime::resources::DataStore;
 use janu_flow::runtime::RuntimeClient;
 const GIT_VERSION: &str = git_version!(prefix = "v", cargo_prefix = "v");
@@ -89,9 +90,45 @@ pub enum StopKind {
 pub enum GetKind {
     Flow { id: Option<String> },
     Instance { id: Option<Uuid> },
+    Node { instance_id: Uuid, node_id: String },
     Runtime { id: Option<Uuid> },
 }
 
+#[derive(StructOpt, Debug)]
+pub enum SetKind {
+    Mode {
+        instance_id: Uuid,
+        node_id: String,
+        mode: usize,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum ReloadKind {
+    Node {
+        instance_id: Uuid,
+        node_id: String,
+        #[structopt(parse(from_os_str), name = "Operator descriptor path")]
+        descriptor_path: std::path::PathBuf,
+        #[structopt(short = "s", long = "transfer-state")]
+        transfer_state: bool,
+    },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum CheckpointKind {
+    Node { instance_id: Uuid, node_id: String },
+}
+
+#[derive(StructOpt, Debug)]
+pub enum MigrateKind {
+    Node {
+        instance_id: Uuid,
+        node_id: String,
+        runtime: String,
+    },
+}
+
 #[derive(StructOpt, Debug)]
 pub enum DeleteKind {
     Flow { id: String },
@@ -105,6 +142,10 @@ pub enum ZFCtl {
     Delete(DeleteKind),
     Start(StartKind),
     Stop(StopKind),
+    Set(SetKind),
+    Reload(ReloadKind),
+    Checkpoint(CheckpointKind),
+    Migrate(MigrateKind),
 }
 
 #[async_std::main]
@@ -164,6 +205,9 @@ async fn main() {
                 match id {
                     Some(rid) => {
                         let instance = store.get_flow_by_instance(&rid).await.unwrap();
+                        let mut node_statuses =
+                            store.get_instance_node_statuses(&rid).await.unwrap();
+                        node_statuses.sort_by(|a, b| a.id.cmp(&b.id));
                         table.add_row(row![
                             "UUID",
                             "Flow",
@@ -172,6 +216,7 @@ async fn main() {
                             "Sources",
                             "Connectors",
                             "Links",
+                            "Nodes State",
                         ]);
                         table.add_row(row![
                             instance.uuid,
@@ -205,6 +250,14 @@ async fn main() {
                                 .iter()
                                 .map(|o| format!("{}", o))
                                 .collect::<Vec<String>>()
+                                .join("\n"),
+                            node_statuses
+                                .iter()
+                                .map(|n| format!(
+                                    "{} - {} - Restarts: {}",
+                                    n.id, n.state, n.restarts
+                                ))
+                                .collect::<Vec<String>>()
                                 .join("\n")
                         ]);
                     }
@@ -235,6 +288,40 @@ async fn main() {
                 }
                 table.printstd();
             }
+            GetKind::Node {
+                instance_id,
+                node_id,
+            } => {
+                let mut table = Table::new();
+                table.add_row(row![
+                    "UUID",
+                    "Name",
+                    "State",
+                    "Mode",
+                    "Restarts",
+                    "Last Error",
+                ]);
+                let node_status = client
+                    .get_node_status(instance_id, node_id.into())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                table.add_row(row![
+                    instance_id,
+                    node_status.id,
+                    node_status.state,
+                    node_status
+                        .mode
+                        .map(|mode| mode.to_string())
+                        .unwrap_or_else(|| String::from("-")),
+                    node_status.restarts,
+                    node_status
+                        .last_error
+                        .map(|error| format!("{:?}", error))
+                        .unwrap_or_else(|| String::from("-")),
+                ]);
+                table.printstd();
+            }
             GetKind::Runtime { id } => {
                 let mut table = Table::new();
                 match id {
@@ -249,7 +336,8 @@ async fn main() {
                             "Running Operators",
                             "Running Sources",
                             "Running Sinks",
-                            "Running Connectors"
+                            "Running Connectors",
+                            "Failed Nodes"
                         ]);
                         table.add_row(row![
                             runtime_status.id,
@@ -260,6 +348,7 @@ async fn main() {
                             runtime_status.running_sources,
                             runtime_status.running_sinks,
                             runtime_status.running_connectors,
+                            runtime_status.failed_nodes,
                         ]);
                     }
                     None => {
@@ -378,5 +467,81 @@ async fn main() {
                 table.printstd();
             }
         },
+        ZFCtl::Set(sk) => match sk {
+            SetKind::Mode {
+                instance_id,
+                node_id,
+                mode,
+            } => {
+                let mut table = Table::new();
+                table.add_row(row!["UUID", "Name", "Mode",]);
+                client
+                    .change_mode(instance_id, node_id.clone().into(), mode)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                table.add_row(row![instance_id, node_id, mode,]);
+                table.printstd();
+            }
+        },
+        ZFCtl::Reload(rk) => match rk {
+            ReloadKind::Node {
+                instance_id,
+                node_id,
+                descriptor_path,
+                transfer_state,
+            } => {
+                // The descriptor is the one of the operator, as generated by `cargo janu-flow
+                // build`: its id is replaced by the one of the node in the instance.
+                let yaml_operator = read_to_string(descriptor_path).unwrap();
+                let mut operator =
+                    serde_yaml::from_str::<OperatorDescriptor>(&yaml_operator).unwrap();
+                operator.id = node_id.clone().into();
+
+                let mut table = Table::new();
+                table.add_row(row!["UUID", "Name", "Status",]);
+                client
+                    .reload_node(instance_id, operator, transfer_state)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                table.add_row(row![instance_id, node_id, String::from("Reloaded"),]);
+                table.printstd();
+            }
+        },
+        ZFCtl::Checkpoint(ck) => match ck {
+            CheckpointKind::Node {
+                instance_id,
+                node_id,
+            } => {
+                let mut table = Table::new();
+                table.add_row(row!["UUID", "Name", "Checkpointed",]);
+                let checkpointed = client
+                    .checkpoint_node(instance_id, node_id.clone().into())
+                    .await
+                    .unwrap()
+                    .unwrap();
+                table.add_row(row![instance_id, node_id, checkpointed,]);
+                table.printstd();
+            }
+        },
+        ZFCtl::Migrate(mk) => match mk {
+            MigrateKind::Node {
+                instance_id,
+                node_id,
+                runtime,
+            } => {
+                let mut table = Table::new();
+                table.add_row(row!["UUID", "Name", "Runtime",]);
+                let record = client
+                    .migrate_node(instance_id, node_id.clone().into(), runtime)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                let runtime = record.find_node_runtime(&node_id).unwrap();
+                table.add_row(row![instance_id, node_id, runtime,]);
+                table.printstd();
+            }
+        },
     }
 }
@@ -12,31 +12,48 @@
 //
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::time::Duration;
 use uhlc::HLC;
 use uuid::Uuid;
 use janu::prelude::*;
+use janu_flow::async_std::net::{TcpListener, TcpStream};
+use janu_flow::async_std::prelude::*;
 use janu_flow::async_std::sync::{Arc, Mutex};
 use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
 use janu_flow::model::{
     dataflow::record::DataFlowRecord,
+    link::LinkDescriptor,
     node::{OperatorDescriptor, SinkDescriptor, SourceDescriptor},
 };
+use janu_flow::runtime::checkpoint::Checkpoints;
 use janu_flow::runtime::dataflow::instance::DataflowInstance;
 use janu_flow::runtime::dataflow::loader::Loader;
 use janu_flow::runtime::dataflow::Dataflow;
-use janu_flow::runtime::message::ControlMessage;
+use janu_flow::runtime::message::{ControlMessage, Message};
+use janu_flow::runtime::metrics::{encode_prometheus, NodeMetrics};
 use janu_flow::runtime::resources::DataStore;
 use janu_flow::runtime::RuntimeClient;
 use janu_flow::runtime::RuntimeContext;
-use janu_flow::runtime::{Runtime, RuntimeConfig, RuntimeInfo, RuntimeStatus, RuntimeStatusKind};
+use janu_flow::runtime::{
+    DrainReport, NodeState, NodeStatus, Runtime, RuntimeConfig, RuntimeInfo, RuntimeStatus,
+    RuntimeStatusKind,
+};
 use janu_flow::types::{ZFError, ZFResult};
-use janu_flow::NodeId;
+use janu_flow::{NodeId, PortId, RuntimeId};
 use jnrpc_macros::znserver;
 use jrpc::ZNServe;
 
+// How often the runtime status is refreshed while the runtime is running.
+const STATUS_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long a client of the metrics endpoint has to send its request and receive the response.
+const METRICS_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub struct RTState {
     pub graphs: HashMap<Uuid, DataflowInstance>,
     pub config: RuntimeConfig,
+    // Last status of the nodes published in the DataStore, per instance.
+    pub node_statuses: HashMap<Uuid, HashMap<NodeId, NodeStatus>>,
 }
 
 #[derive(Clone)]
@@ -51,6 +68,7 @@ impl Daemon {
         let state = Arc::new(Mutex::new(RTState {
             graphs: HashMap::new(),
             config,
+            node_statuses: HashMap::new(),
         }));
 
         Self {
@@ -114,7 +132,17 @@ impl Daemon {
 
         let session = Arc::new(janu::open(zconfig).wait()?);
         let hlc = Arc::new(HLC::default());
-        let loader = Arc::new(Loader::new(config.loader.clone()));
+        let mut loader = Loader::new(config.loader.clone())
+            .with_trusted_keys(&config.trusted_keys)?
+            .with_wasm_limits(config.wasm_limits.clone());
+        if let Some(node_host) = &config.node_host {
+            loader = loader.with_node_host(node_host);
+        }
+        let loader = Arc::new(loader);
+        let checkpoints = config
+            .checkpoints
+            .as_ref()
+            .map(|checkpoints| Arc::new(Checkpoints::from_config(checkpoints, session.clone())));
 
         let ctx = RuntimeContext {
             session: session.clone(),
@@ -122,6 +150,7 @@ impl Daemon {
             loader,
             runtime_name: name.into(),
             runtime_uuid: uuid,
+            checkpoints,
         };
 
         Ok(Self::new(session, ctx, config))
@@ -170,12 +199,37 @@ impl Daemon {
             .add_runtime_status(&self.ctx.runtime_uuid, &rt_status)
             .await?;
 
+        let metrics_endpoint = self.state.lock().await.config.metrics_endpoint.clone();
+        let metrics_server = metrics_endpoint.map(|address| {
+            let rt = self.clone();
+            async_std::task::spawn(async move {
+                if let Err(e) = rt.serve_metrics(&address).await {
+                    log::error!("Unable to serve the metrics on {}: {:?}", address, e);
+                }
+            })
+        });
+
         log::trace!("Running...");
 
-        let _ = stop
-            .recv()
-            .await
-            .map_err(|e| ZFError::RecvError(format!("{}", e)))?;
+        // While running, the status is periodically refreshed to report the nodes that ended up
+        // in a terminal error state.
+        loop {
+            match async_std::future::timeout(STATUS_REFRESH_INTERVAL, stop.recv()).await {
+                Ok(res) => {
+                    res.map_err(|e| ZFError::RecvError(format!("{}", e)))?;
+                    break;
+                }
+                Err(_) => {
+                    if let Err(e) = self.update_status().await {
+                        log::warn!("Unable to refresh the runtime status: {:?}", e);
+                    }
+                }
+            }
+        }
+
+        if let Some(metrics_server) = metrics_server {
+            metrics_server.cancel().await;
+        }
 
         rt_server
             .stop(srt)
@@ -194,6 +248,340 @@ impl Daemon {
         Ok(())
     }
 
+    async fn update_status(&self) -> ZFResult<()> {
+        let mut _state = self.state.lock().await;
+
+        let mut failed_nodes = 0;
+        for instance in _state.graphs.values() {
+            failed_nodes += instance.get_failed_nodes().await.len();
+        }
+
+        let mut rt_status = self
+            .store
+            .get_runtime_status(&self.ctx.runtime_uuid)
+            .await?;
+        if rt_status.failed_nodes != failed_nodes {
+            rt_status.failed_nodes = failed_nodes;
+            self.store
+                .add_runtime_status(&self.ctx.runtime_uuid, &rt_status)
+                .await?;
+        }
+
+        let instances: Vec<Uuid> = _state.graphs.keys().cloned().collect();
+        for instance_id in instances {
+            self.publish_node_statuses(&mut _state, &instance_id)
+                .await?;
+        }
+
+        let metrics: Vec<NodeMetrics> = _state
+            .graphs
+            .values()
+            .flat_map(|instance| instance.get_metrics())
+            .collect();
+        self.store
+            .add_runtime_metrics(&self.ctx.runtime_uuid, &metrics)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn get_metrics(&self) -> Vec<NodeMetrics> {
+        let _state = self.state.lock().await;
+        _state
+            .graphs
+            .values()
+            .flat_map(|instance| instance.get_metrics())
+            .collect()
+    }
+
+    // Minimal HTTP server answering `GET /metrics` with the metrics of the nodes running on this
+    // runtime, in the Prometheus text format.
+    async fn serve_metrics(&self, address: &str) -> ZFResult<()> {
+        let listener = TcpListener::bind(address).await?;
+        log::info!("Serving metrics on http://{}/metrics", address);
+
+        let mut incoming = listener.incoming();
+        while let Some(stream) = incoming.next().await {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Metrics endpoint, unable to accept connection: {:?}", e);
+                    continue;
+                }
+            };
+
+            // A slow client only holds its own connection.
+            let rt = self.clone();
+            async_std::task::spawn(async move {
+                match async_std::future::timeout(
+                    METRICS_REQUEST_TIMEOUT,
+                    rt.serve_metrics_request(stream),
+                )
+                .await
+                {
+                    Ok(Err(e)) => log::warn!("Metrics endpoint, unable to serve request: {:?}", e),
+                    Err(_) => log::warn!("Metrics endpoint, request timed out"),
+                    Ok(Ok(())) => (),
+                }
+            });
+        }
+
+        Ok(())
+    }
+
+    async fn serve_metrics_request(&self, mut stream: TcpStream) -> ZFResult<()> {
+        // Only the request line matters: "GET /metrics HTTP/1.1".
+        let mut buffer = [0u8; 1024];
+        let read = stream.read(&mut buffer).await?;
+        let request = String::from_utf8_lossy(&buffer[..read]);
+        let mut request_line = request.split_whitespace();
+
+        let response = match (request_line.next(), request_line.next()) {
+            (Some("GET"), Some("/metrics")) => {
+                let body = encode_prometheus(&self.get_metrics().await);
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            }
+            _ => String::from(
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            ),
+        };
+
+        stream.write_all(response.as_bytes()).await?;
+        Ok(())
+    }
+
+    // Publishes in the DataStore the status of the nodes of the instance that changed since they
+    // were last published, and removes the status of the nodes that no longer exist (e.g. replays).
+    async fn publish_node_statuses(&self, state: &mut RTState, instance_id: &Uuid) -> ZFResult<()> {
+        let instance = state
+            .graphs
+            .get(instance_id)
+            .ok_or(ZFError::InstanceNotFound(*instance_id))?;
+        let published = state.node_statuses.entry(*instance_id).or_default();
+        let flow = instance.get_flow();
+        let nodes = instance.get_nodes();
+
+        for node_id in nodes.iter() {
+            let node_status = instance.get_node_status(node_id).await?;
+            if published.get(node_id) != Some(&node_status) {
+                self.store
+                    .add_runtime_flow_node_status(
+                        &self.ctx.runtime_uuid,
+                        &flow,
+                        instance_id,
+                        &node_status,
+                    )
+                    .await?;
+                published.insert(node_id.clone(), node_status);
+            }
+        }
+
+        let removed: Vec<NodeId> = published
+            .keys()
+            .filter(|node_id| !nodes.contains(node_id))
+            .cloned()
+            .collect();
+        for node_id in removed {
+            self.store
+                .remove_runtime_flow_node_status(
+                    &self.ctx.runtime_uuid,
+                    &flow,
+                    instance_id,
+                    &node_id,
+                )
+                .await?;
+            published.remove(&node_id);
+        }
+
+        Ok(())
+    }
+
+    async fn get_runtime_id(&self, runtime: &str) -> ZFResult<Uuid> {
+        Ok(self.store.get_runtime_info_by_name(runtime).await?.id)
+    }
+
+    // Stops the node of the instance on `runtime` if it is running, in which case it is added to
+    // `paused`. Used when a node is migrated, see `migrate_node`.
+    async fn pause_migrated_edge(
+        &self,
+        instance_id: Uuid,
+        node: &NodeId,
+        runtime: &RuntimeId,
+        paused: &mut Vec<(NodeId, RuntimeId)>,
+    ) -> ZFResult<()> {
+        if paused.iter().any(|(id, rt)| id == node && rt == runtime) {
+            return Ok(());
+        }
+
+        let rt = self.get_runtime_id(runtime).await?;
+        let was_running = if rt == self.ctx.runtime_uuid {
+            self.pause_node(instance_id, node.clone()).await?
+        } else {
+            RuntimeClient::new(self.ctx.session.clone(), rt)
+                .pause_node(instance_id, node.clone())
+                .await??
+        };
+        if was_running {
+            paused.push((node.clone(), runtime.clone()));
+        }
+        Ok(())
+    }
+
+    // Waits until the links of the instance are drained, on each of the runtimes in turn.
+    async fn drain_migrated_edges(
+        &self,
+        instance_id: Uuid,
+        runtimes: &[RuntimeId],
+        links: Vec<LinkDescriptor>,
+    ) -> ZFResult<()> {
+        for runtime in runtimes {
+            let rt = self.get_runtime_id(runtime).await?;
+            if rt == self.ctx.runtime_uuid {
+                self.drain_links(instance_id, links.clone()).await?;
+            } else {
+                RuntimeClient::new(self.ctx.session.clone(), rt)
+                    .drain_links(instance_id, links.clone())
+                    .await??;
+            }
+        }
+        Ok(())
+    }
+
+    // Pauses the edges of the node migrated from `record` to `migrated`, then takes its state
+    // and the messages left in its input links. The nodes stopped are added to `paused`, even if
+    // it fails, so that they can be started again.
+    async fn quiesce_migrated_node(
+        &self,
+        record: &DataFlowRecord,
+        migrated: &DataFlowRecord,
+        node: &NodeId,
+        paused: &mut Vec<(NodeId, RuntimeId)>,
+    ) -> ZFResult<(Option<Vec<u8>>, Vec<(PortId, Message)>)> {
+        let instance_id = record.uuid;
+        let runtime = record
+            .find_node_runtime(node)
+            .ok_or_else(|| ZFError::NodeNotFound(node.clone()))?;
+        let runtimes = record.get_runtimes_in_order();
+
+        // Nothing is sent to the node any more, it consumes what was sent before.
+        for (id, rt) in record.get_upstream_nodes(node) {
+            self.pause_migrated_edge(instance_id, &id, &rt, paused)
+                .await?;
+        }
+        let upstream_links: Vec<LinkDescriptor> = record
+            .get_upstream_links(node)
+            .into_iter()
+            .filter(|link| link.to.node != *node)
+            .collect();
+        self.drain_migrated_edges(instance_id, &runtimes, upstream_links)
+            .await?;
+        self.pause_migrated_edge(instance_id, node, &runtime, paused)
+            .await?;
+
+        // What the node sent is consumed before its links change.
+        self.drain_migrated_edges(instance_id, &runtimes, record.get_downstream_links(node))
+            .await?;
+        for (id, rt) in record.get_rewired_nodes(migrated) {
+            self.pause_migrated_edge(instance_id, &id, &rt, paused)
+                .await?;
+        }
+
+        let rt = self.get_runtime_id(&runtime).await?;
+        if rt == self.ctx.runtime_uuid {
+            Ok((
+                self.save_node_state(instance_id, node.clone()).await?,
+                self.take_node_inputs(instance_id, node.clone()).await?,
+            ))
+        } else {
+            let client = RuntimeClient::new(self.ctx.session.clone(), rt);
+            Ok((
+                client.save_node_state(instance_id, node.clone()).await??,
+                client.take_node_inputs(instance_id, node.clone()).await??,
+            ))
+        }
+    }
+
+    // Rewires the runtimes following `record`, then gives `node` the state and the messages
+    // taken from it, on the runtime it is mapped to in `record`. See `migrate_node`.
+    async fn install_migrated_node(
+        &self,
+        record: &DataFlowRecord,
+        runtimes: &[Uuid],
+        node: &NodeId,
+        state: Option<Vec<u8>>,
+        inputs: Vec<(PortId, Message)>,
+    ) -> ZFResult<()> {
+        let instance_id = record.uuid;
+        for rt in runtimes {
+            if *rt == self.ctx.runtime_uuid {
+                self.rewire(record.clone()).await?;
+            } else {
+                RuntimeClient::new(self.ctx.session.clone(), *rt)
+                    .rewire(record.clone())
+                    .await??;
+            }
+        }
+
+        let runtime = record
+            .find_node_runtime(node)
+            .ok_or_else(|| ZFError::NodeNotFound(node.clone()))?;
+        let rt = self.get_runtime_id(&runtime).await?;
+        if rt == self.ctx.runtime_uuid {
+            if let Some(state) = state {
+                self.transfer_node_state(instance_id, node.clone(), state)
+                    .await?;
+            }
+            if !inputs.is_empty() {
+                self.forward_node_inputs(instance_id, node.clone(), inputs)
+                    .await?;
+            }
+        } else {
+            let client = RuntimeClient::new(self.ctx.session.clone(), rt);
+            if let Some(state) = state {
+                client
+                    .transfer_node_state(instance_id, node.clone(), state)
+                    .await??;
+            }
+            if !inputs.is_empty() {
+                client
+                    .forward_node_inputs(instance_id, node.clone(), inputs)
+                    .await??;
+            }
+        }
+        Ok(())
+    }
+
+    // Starts again the nodes paused by a migration that failed, in the reverse order.
+    async fn resume_paused_nodes(&self, instance_id: Uuid, mut paused: Vec<(NodeId, RuntimeId)>) {
+        paused.reverse();
+        if let Err(e) = self.resume_nodes(instance_id, &paused).await {
+            log::error!(
+                "Unable to resume the nodes of instance {}: {:?}",
+                instance_id,
+                e
+            );
+        }
+    }
+
+    // Starts the nodes of the instance, each on its runtime, in the given order.
+    async fn resume_nodes(&self, instance_id: Uuid, nodes: &[(NodeId, RuntimeId)]) -> ZFResult<()> {
+        for (id, runtime) in nodes {
+            let rt = self.get_runtime_id(runtime).await?;
+            if rt == self.ctx.runtime_uuid {
+                self.start_node(instance_id, id.to_string()).await?;
+            } else {
+                RuntimeClient::new(self.ctx.session.clone(), rt)
+                    .start_node(instance_id, id.to_string())
+                    .await??;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn start(
         &self,
     ) -> ZFResult<(
@@ -219,6 +607,7 @@ impl Daemon {
             running_sources: 0,
             running_sinks: 0,
             running_connectors: 0,
+            failed_nodes: 0,
         };
 
         let self_state = self.state.lock().await;
@@ -254,6 +643,9 @@ impl Daemon {
         self.store
             .remove_runtime_status(&self.ctx.runtime_uuid)
             .await?;
+        self.store
+            .remove_runtime_metrics(&self.ctx.runtime_uuid)
+            .await?;
 
         Ok(())
     }
@@ -332,49 +724,59 @@ impl Runtime for Daemon {
         log::info!("Tearing down Instance UUID: {}", record_id);
         let record = self.store.get_flow_by_instance(&record_id).await?;
 
-        let mut rt_clients = vec![];
-
         let all_involved_runtimes = self.store.get_flow_instance_runtimes(&record_id).await?;
 
-        let is_also_local = all_involved_runtimes.contains(&self.ctx.runtime_uuid);
-
-        let remote_involved_runtimes = all_involved_runtimes
-            .into_iter()
-            .filter(|rt| *rt != self.ctx.runtime_uuid);
-
-        for rt in remote_involved_runtimes {
-            let client = RuntimeClient::new(self.ctx.session.clone(), rt);
-            rt_clients.push(client);
+        // The runtimes are stopped following the dataflow: the messages a runtime sends through
+        // its connectors are then consumed before the receiving runtime stops its nodes.
+        let mut ordered_runtimes = Vec::with_capacity(all_involved_runtimes.len());
+        for rt in record.get_runtimes_in_order() {
+            let rt = self.store.get_runtime_info_by_name(&rt).await?.id;
+            if all_involved_runtimes.contains(&rt) {
+                ordered_runtimes.push(rt);
+            }
         }
-
-        // remote stop sources
-        for client in rt_clients.iter() {
-            client.stop_sources(record_id).await??;
+        for rt in all_involved_runtimes {
+            if !ordered_runtimes.contains(&rt) {
+                ordered_runtimes.push(rt);
+            }
         }
 
-        // local stop sources
-        if is_also_local {
-            self.stop_sources(record_id).await?;
-        }
+        let client = |rt: &Uuid| RuntimeClient::new(self.ctx.session.clone(), *rt);
 
-        // remote stop
-        for client in rt_clients.iter() {
-            client.stop(record_id).await??;
+        // stop sources, on every runtime before any of them is drained
+        for rt in ordered_runtimes.iter() {
+            if *rt == self.ctx.runtime_uuid {
+                self.stop_sources(record_id).await?;
+            } else {
+                client(rt).stop_sources(record_id).await??;
+            }
         }
 
-        // local stop
-        if is_also_local {
-            Runtime::stop(self, record_id).await?;
+        // stop, in order
+        let mut drain_report = DrainReport::default();
+        for rt in ordered_runtimes.iter() {
+            if *rt == self.ctx.runtime_uuid {
+                drain_report.merge(Runtime::stop(self, record_id).await?);
+            } else {
+                drain_report.merge(client(rt).stop(record_id).await??);
+            }
         }
 
-        // remote stop clean
-        for client in rt_clients.iter() {
-            client.clean(record_id).await??;
+        if drain_report.total_dropped() > 0 || drain_report.timed_out {
+            log::warn!(
+                "Teardown of Instance UUID: {} - {}",
+                record_id,
+                drain_report
+            );
         }
 
-        // local clean
-        if is_also_local {
-            self.clean(record_id).await;
+        // clean
+        for rt in ordered_runtimes.iter() {
+            if *rt == self.ctx.runtime_uuid {
+                self.clean(record_id).await;
+            } else {
+                client(rt).clean(record_id).await??;
+            }
         }
 
         log::info!("Done teardown down Instance UUID: {}", record_id);
@@ -398,6 +800,8 @@ impl Runtime for Daemon {
 
         let mut self_state = self.state.lock().await;
         self_state.graphs.insert(dfr.uuid, instance);
+        self.publish_node_statuses(&mut self_state, &dfr.uuid)
+            .await?;
         drop(self_state);
         self.store
             .add_runtime_flow(&self.ctx.runtime_uuid, &dfr)
@@ -432,6 +836,19 @@ impl Runtime for Daemon {
                     )
                     .await?;
 
+                if let Some(published) = _state.node_statuses.remove(&record_id) {
+                    for node_id in published.keys() {
+                        self.store
+                            .remove_runtime_flow_node_status(
+                                &self.ctx.runtime_uuid,
+                                &record.flow,
+                                &record.uuid,
+                                node_id,
+                            )
+                            .await?;
+                    }
+                }
+
                 Ok(record)
             }
             None => Err(ZFError::InstanceNotFound(record_id)),
@@ -453,23 +870,11 @@ impl Runtime for Daemon {
 
         match _state.graphs.get_mut(&record_id) {
             Some(mut instance) => {
-                let mut sinks = instance.get_sinks();
-                for id in sinks.drain(..) {
-                    instance.start_node(&id).await?;
-                    rt_status.running_sinks += 1;
-                }
-
-                let mut operators = instance.get_operators();
-                for id in operators.drain(..) {
-                    instance.start_node(&id).await?;
-                    rt_status.running_operators += 1;
-                }
+                instance.start_nodes().await?;
 
-                let mut connectors = instance.get_connectors();
-                for id in connectors.drain(..) {
-                    instance.start_node(&id).await?;
-                    rt_status.running_connectors += 1;
-                }
+                rt_status.running_sinks += instance.get_sinks().len();
+                rt_status.running_operators += instance.get_operators().len();
+                rt_status.running_connectors += instance.get_connectors().len();
 
                 self.store
                     .add_runtime_status(&self.ctx.runtime_uuid, &rt_status)
@@ -492,11 +897,9 @@ impl Runtime for Daemon {
 
         match _state.graphs.get_mut(&record_id) {
             Some(mut instance) => {
-                let mut sources = instance.get_sources();
-                for id in sources.drain(..) {
-                    instance.start_node(&id).await?;
-                    rt_status.running_sources += 1;
-                }
+                instance.start_sources().await?;
+
+                rt_status.running_sources += instance.get_sources().len();
 
                 rt_status.running_flows += 1;
 
@@ -509,7 +912,7 @@ impl Runtime for Daemon {
             None => Err(ZFError::InstanceNotFound(record_id)),
         }
     }
-    async fn stop(&self, record_id: Uuid) -> ZFResult<()> {
+    async fn stop(&self, record_id: Uuid) -> ZFResult<DrainReport> {
         log::info!(
             "Stopping nodes (not sources) for Instance UUID: {}",
             record_id
@@ -522,31 +925,21 @@ impl Runtime for Daemon {
             .get_runtime_status(&self.ctx.runtime_uuid)
             .await?;
 
+        let drain_timeout = _state.config.get_drain_timeout();
+
         match _state.graphs.get_mut(&record_id) {
             Some(mut instance) => {
-                let mut sinks = instance.get_sinks();
-                for id in sinks.drain(..) {
-                    instance.stop_node(&id).await?;
-                    rt_status.running_sinks -= 1;
-                }
+                let drain_report = instance.stop_nodes(drain_timeout).await?;
 
-                let mut operators = instance.get_operators();
-                for id in operators.drain(..) {
-                    instance.stop_node(&id).await?;
-                    rt_status.running_operators -= 1;
-                }
-
-                let mut connectors = instance.get_connectors();
-                for id in connectors.drain(..) {
-                    instance.stop_node(&id).await?;
-                    rt_status.running_connectors -= 1;
-                }
+                rt_status.running_sinks -= instance.get_sinks().len();
+                rt_status.running_operators -= instance.get_operators().len();
+                rt_status.running_connectors -= instance.get_connectors().len();
 
                 self.store
                     .add_runtime_status(&self.ctx.runtime_uuid, &rt_status)
                     .await?;
 
-                Ok(())
+                Ok(drain_report)
             }
             None => Err(ZFError::InstanceNotFound(record_id)),
         }
@@ -562,11 +955,9 @@ impl Runtime for Daemon {
 
         match _state.graphs.get_mut(&record_id) {
             Some(mut instance) => {
-                let mut sources = instance.get_sources();
-                for id in sources.drain(..) {
-                    instance.stop_node(&id).await?;
-                    rt_status.running_sources -= 1;
-                }
+                instance.stop_sources().await?;
+
+                rt_status.running_sources -= instance.get_sources().len();
 
                 rt_status.running_flows -= 1;
 
@@ -682,6 +1073,347 @@ impl Runtime for Daemon {
         }
     }
 
+    async fn change_mode(&self, instance_id: Uuid, node: NodeId, mode: usize) -> ZFResult<()> {
+        let _state = self.state.lock().await;
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => {
+                instance.change_mode(&node, mode).await?;
+                log::info!(
+                    "Node {} of instance {} requested to switch to mode {}",
+                    node,
+                    instance_id,
+                    mode
+                );
+                Ok(())
+            }
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn checkpoint_node(&self, instance_id: Uuid, node: NodeId) -> ZFResult<bool> {
+        let _state = self.state.lock().await;
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => {
+                let checkpointed = instance.checkpoint_node(&node).await?;
+                log::info!(
+                    "Node {} of instance {} checkpointed: {}",
+                    node,
+                    instance_id,
+                    checkpointed
+                );
+                Ok(checkpointed)
+            }
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn migrate_node(
+        &self,
+        instance_id: Uuid,
+        node: NodeId,
+        runtime: String,
+    ) -> ZFResult<DataFlowRecord> {
+        let record = self.store.get_flow_by_instance(&instance_id).await?;
+        let current_runtime = record
+            .find_node_runtime(&node)
+            .ok_or_else(|| ZFError::NodeNotFound(node.clone()))?;
+        if current_runtime.as_ref() == runtime {
+            return Ok(record);
+        }
+
+        log::info!(
+            "Migrating node {} of instance {} from {} to {}",
+            node,
+            instance_id,
+            current_runtime,
+            runtime
+        );
+
+        let mut migrated = record.clone();
+        migrated.try_migrate_node(&node, runtime.clone().into())?;
+
+        let old_runtimes = self.store.get_flow_instance_runtimes(&instance_id).await?;
+        let mut all_runtimes = old_runtimes.clone();
+        for rt in migrated.get_runtimes() {
+            let rt = self.get_runtime_id(&rt).await?;
+            if !all_runtimes.contains(&rt) {
+                all_runtimes.push(rt);
+            }
+        }
+
+        let client = |rt: &Uuid| RuntimeClient::new(self.ctx.session.clone(), *rt);
+
+        // pause the edges of the node, so that no message is lost, roll back on failure
+        let mut paused = Vec::new();
+        let (state, inputs) = match self
+            .quiesce_migrated_node(&record, &migrated, &node, &mut paused)
+            .await
+        {
+            Ok(quiesced) => quiesced,
+            Err(e) => {
+                log::error!(
+                    "Migration of node {} of instance {} failed, rolling back: {:?}",
+                    node,
+                    instance_id,
+                    e
+                );
+                self.resume_paused_nodes(instance_id, paused).await;
+                return Err(e);
+            }
+        };
+
+        // rewire, the target runtime prepares the node and gets its state and its messages,
+        // on failure the node is put back on its runtime along with them
+        if let Err(e) = self
+            .install_migrated_node(
+                &migrated,
+                &all_runtimes,
+                &node,
+                state.clone(),
+                inputs.clone(),
+            )
+            .await
+        {
+            log::error!(
+                "Migration of node {} of instance {} failed, rolling back: {:?}",
+                node,
+                instance_id,
+                e
+            );
+            if let Err(rollback_error) = self
+                .install_migrated_node(&record, &all_runtimes, &node, state, inputs)
+                .await
+            {
+                log::error!(
+                    "Unable to restore node {} of instance {}: {:?}",
+                    node,
+                    instance_id,
+                    rollback_error
+                );
+            }
+            self.resume_paused_nodes(instance_id, paused).await;
+            return Err(e);
+        }
+
+        // resume the nodes created and the nodes paused, the nodes sending messages to the
+        // migrated node once the others are running
+        let upstream = record.get_upstream_nodes(&node);
+        let old_nodes = record.get_node_runtimes();
+        let was_running = paused.iter().any(|(id, _)| *id == node);
+        let resumed: Vec<(NodeId, RuntimeId)> = migrated
+            .get_node_runtimes()
+            .into_iter()
+            .filter(|n| {
+                !upstream.contains(n)
+                    && (paused.contains(n)
+                        || (!old_nodes.contains(n) && (n.0 != node || was_running)))
+            })
+            .collect();
+        for runtime in migrated.get_runtimes_in_order().iter().rev() {
+            let nodes: Vec<(NodeId, RuntimeId)> = resumed
+                .iter()
+                .filter(|(_, rt)| rt == runtime)
+                .cloned()
+                .collect();
+            self.resume_nodes(instance_id, &nodes).await?;
+
+            let nodes: Vec<NodeId> = nodes.into_iter().map(|(id, _)| id).collect();
+            let rt = self.get_runtime_id(runtime).await?;
+            if rt == self.ctx.runtime_uuid {
+                self.wait_for_nodes(instance_id, nodes).await?;
+            } else {
+                client(&rt).wait_for_nodes(instance_id, nodes).await??;
+            }
+        }
+
+        let upstream: Vec<(NodeId, RuntimeId)> = paused
+            .into_iter()
+            .filter(|n| upstream.contains(n))
+            .collect();
+        self.resume_nodes(instance_id, &upstream).await?;
+
+        log::info!(
+            "Done migrating node {} of instance {} to {}",
+            node,
+            instance_id,
+            runtime
+        );
+
+        Ok(migrated)
+    }
+
+    async fn save_node_state(&self, instance_id: Uuid, node: NodeId) -> ZFResult<Option<Vec<u8>>> {
+        let _state = self.state.lock().await;
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => instance.save_node_state(&node).await,
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn transfer_node_state(
+        &self,
+        instance_id: Uuid,
+        node: NodeId,
+        state: Vec<u8>,
+    ) -> ZFResult<()> {
+        let _state = self.state.lock().await;
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => instance.transfer_node_state(&node, &state).await,
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn rewire(&self, record: DataFlowRecord) -> ZFResult<()> {
+        log::info!("Rewiring for Instance UUID: {}", record.uuid);
+
+        let mut self_state = self.state.lock().await;
+        match self_state.graphs.get_mut(&record.uuid) {
+            Some(instance) => instance.try_rewire(record.clone()).await?,
+            None => {
+                let dataflow = Dataflow::try_new(self.ctx.clone(), record.clone())?;
+                let instance = DataflowInstance::try_instantiate(dataflow)?;
+                self_state.graphs.insert(record.uuid, instance);
+            }
+        }
+
+        let is_involved = self_state
+            .graphs
+            .get(&record.uuid)
+            .map_or(false, |instance| !instance.get_nodes().is_empty());
+        if !is_involved {
+            drop(self_state);
+            self.clean(record.uuid).await?;
+            return Ok(());
+        }
+
+        self.publish_node_statuses(&mut self_state, &record.uuid)
+            .await?;
+        drop(self_state);
+        self.store
+            .add_runtime_flow(&self.ctx.runtime_uuid, &record)
+            .await?;
+
+        log::info!("Done rewiring for Instance UUID: {}", record.uuid);
+
+        Ok(())
+    }
+
+    async fn reload_node(
+        &self,
+        instance_id: Uuid,
+        operator: OperatorDescriptor,
+        transfer_state: bool,
+    ) -> ZFResult<()> {
+        let mut _state = self.state.lock().await;
+        let drain_timeout = _state.config.get_drain_timeout();
+
+        match _state.graphs.get_mut(&instance_id) {
+            Some(instance) => {
+                let mut record = self
+                    .store
+                    .get_runtime_flow_by_instance(&self.ctx.runtime_uuid, &instance_id)
+                    .await?;
+                let operator_record = record.try_update_operator(&operator)?;
+
+                instance
+                    .reload_operator(operator_record, transfer_state, drain_timeout)
+                    .await?;
+                log::info!(
+                    "Node {} of instance {} reloaded from {:?}",
+                    operator.id,
+                    instance_id,
+                    operator.uri
+                );
+
+                self.store
+                    .add_runtime_flow(&self.ctx.runtime_uuid, &record)
+                    .await?;
+
+                Ok(())
+            }
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn pause_node(&self, instance_id: Uuid, node: NodeId) -> ZFResult<bool> {
+        let mut _state = self.state.lock().await;
+        let drain_timeout = _state.config.get_drain_timeout();
+
+        match _state.graphs.get_mut(&instance_id) {
+            Some(instance) => instance.pause_node(&node, drain_timeout).await,
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn drain_links(&self, instance_id: Uuid, links: Vec<LinkDescriptor>) -> ZFResult<()> {
+        let _state = self.state.lock().await;
+        let drain_timeout = _state.config.get_drain_timeout();
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => instance.drain_links(&links, drain_timeout).await,
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn take_node_inputs(
+        &self,
+        instance_id: Uuid,
+        node: NodeId,
+    ) -> ZFResult<Vec<(PortId, Message)>> {
+        let _state = self.state.lock().await;
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => instance.take_node_inputs(&node),
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn forward_node_inputs(
+        &self,
+        instance_id: Uuid,
+        node: NodeId,
+        messages: Vec<(PortId, Message)>,
+    ) -> ZFResult<()> {
+        let _state = self.state.lock().await;
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => instance.forward_node_inputs(&node, messages).await,
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn wait_for_nodes(&self, instance_id: Uuid, nodes: Vec<NodeId>) -> ZFResult<()> {
+        let _state = self.state.lock().await;
+        let timeout = _state.config.get_drain_timeout();
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => instance.wait_for_nodes(&nodes, timeout).await,
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn get_node_status(&self, instance_id: Uuid, node: NodeId) -> ZFResult<NodeStatus> {
+        let _state = self.state.lock().await;
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => instance.get_node_status(&node).await,
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
+    async fn get_node_state(&self, instance_id: Uuid, node: NodeId) -> ZFResult<NodeState> {
+        let _state = self.state.lock().await;
+
+        match _state.graphs.get(&instance_id) {
+            Some(instance) => instance.get_node_state(&node).await,
+            None => Err(ZFError::InstanceNotFound(instance_id)),
+        }
+    }
+
     async fn notify_runtime(
         &self,
         record_id: Uuid,
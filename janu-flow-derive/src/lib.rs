@@ -14,7 +14,7 @@
 
 use proc_macro::TokenStream;
 use quote::quote;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, DeriveInput, Meta, NestedMeta};
 
 #[proc_macro_derive(ZFData)]
 pub fn zf_data_derive(input: TokenStream) -> TokenStream {
@@ -35,10 +35,32 @@ pub fn zf_data_derive(input: TokenStream) -> TokenStream {
     gen.into()
 }
 
-#[proc_macro_derive(ZFState)]
+/// Implements `ZFState`.
+///
+/// With the `#[zf_state(serializable)]` attribute, the state is also serialized with `bincode`
+/// (it must then implement `Serialize` and `Deserialize`) so that it can be checkpointed.
+#[proc_macro_derive(ZFState, attributes(zf_state))]
 pub fn zf_state_derive(input: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(input as DeriveInput);
     let ident = &ast.ident;
+
+    let serialization = match is_serializable(&ast) {
+        true => quote! {
+            fn try_serialize(&self) -> janu_flow::ZFResult<Option<Vec<u8>>> {
+                janu_flow::bincode::serialize(self)
+                    .map(Some)
+                    .map_err(|_| janu_flow::ZFError::SerializationError)
+            }
+
+            fn try_deserialize(&mut self, bytes: &[u8]) -> janu_flow::ZFResult<()> {
+                *self = janu_flow::bincode::deserialize(bytes)
+                    .map_err(|_| janu_flow::ZFError::DeseralizationError)?;
+                Ok(())
+            }
+        },
+        false => quote! {},
+    };
+
     let gen = quote! {
 
         impl janu_flow::ZFState for #ident {
@@ -49,7 +71,24 @@ pub fn zf_state_derive(input: TokenStream) -> TokenStream {
             fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
                 self
             }
+
+            #serialization
         }
     };
     gen.into()
 }
+
+// Looks for `#[zf_state(serializable)]`.
+fn is_serializable(ast: &DeriveInput) -> bool {
+    ast.attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("zf_state"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::List(list)) => Some(list.nested),
+            _ => None,
+        })
+        .flatten()
+        .any(|nested| {
+            matches!(nested, NestedMeta::Meta(Meta::Path(path)) if path.is_ident("serializable"))
+        })
+}
@@ -15,4 +15,18 @@
 fn main() {
     let version = rustc_version::version().unwrap();
     println!("cargo:rustc-env=RUSTC_VERSION={}", version);
+
+    // The C header only declares the items of the C ABI, see `runtime::dataflow::cabi`.
+    let out_dir = std::path::PathBuf::from(std::env::var("OUT_DIR").unwrap());
+    let config = cbindgen::Config::from_file("cbindgen.toml").unwrap();
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src("src/runtime/dataflow/cabi.rs")
+        .generate()
+        .expect("Unable to generate the C header.")
+        .write_to_file(out_dir.join("janu_flow.h"));
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    println!("cargo:rerun-if-changed=src/runtime/dataflow/cabi.rs");
 }
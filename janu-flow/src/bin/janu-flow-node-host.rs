@@ -0,0 +1,49 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+//! Hosts an isolated node, spawned by the runtime.
+
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "janu-flow-node-host")]
+struct NodeHostOpt {
+    /// The socket the runtime listens on.
+    socket: PathBuf,
+    /// The hex-encoded public keys the library must be signed with, those of the runtime.
+    #[structopt(long = "trusted-key")]
+    trusted_keys: Vec<String>,
+}
+
+#[cfg(target_family = "unix")]
+#[async_std::main]
+async fn main() {
+    env_logger::init();
+
+    let args = NodeHostOpt::from_args();
+    if let Err(e) =
+        janu_flow::runtime::dataflow::isolation::host(&args.socket, &args.trusted_keys).await
+    {
+        log::error!("Unable to host the node: {:?}", e);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(target_family = "unix"))]
+fn main() {
+    let _ = NodeHostOpt::from_args();
+    eprintln!("Isolated nodes are only supported on unix.");
+    std::process::exit(1);
+}
@@ -39,12 +39,16 @@ pub enum ZFError {
     IOError(String),
     JanuError(String),
     LoadingError(String),
+    IntegrityError(String),
+    ForeignError(String),
     ParsingError(String),
     #[serde(skip_serializing, skip_deserializing)]
     RunnerStopError(crate::async_std::channel::RecvError),
     #[serde(skip_serializing, skip_deserializing)]
     RunnerStopSendError(crate::async_std::channel::SendError<()>),
     InstanceNotFound(Uuid),
+    // The links towards the node were not drained in time.
+    DrainTimeout(NodeId),
     RPCError(JRPCError),
     SourceDoNotHaveInputs,
     ReceiverDoNotHaveInputs,
@@ -25,8 +25,11 @@ pub use ::typetag;
 pub mod model;
 pub mod runtime;
 pub use runtime::deadline::LocalDeadlineMiss;
+pub use runtime::joiner::{Joiner, UnmatchedPolicy};
 pub use runtime::message::*;
+pub use runtime::timer::{Timer, TimerKind};
 pub use runtime::token::*;
+pub use runtime::window::{Window, WindowKind, Windows, WINDOWS_TIMER};
 pub mod types;
 pub use types::*;
 pub mod traits;
@@ -26,6 +26,21 @@ macro_rules! export_operator {
     };
 }
 
+#[macro_export]
+macro_rules! export_async_operator {
+    ($register:expr) => {
+        #[doc(hidden)]
+        #[no_mangle]
+        pub static zfasyncoperator_declaration:
+            $crate::runtime::dataflow::loader::AsyncOperatorDeclaration =
+            $crate::runtime::dataflow::loader::AsyncOperatorDeclaration {
+                rustc_version: $crate::runtime::dataflow::loader::RUSTC_VERSION,
+                core_version: $crate::runtime::dataflow::loader::CORE_VERSION,
+                register: $register,
+            };
+    };
+}
+
 #[macro_export]
 macro_rules! export_source {
     ($register:expr) => {
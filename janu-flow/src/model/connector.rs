@@ -12,7 +12,7 @@
 //   open
 //
 
-use crate::model::link::PortDescriptor;
+use crate::model::link::{PortDescriptor, Reliability};
 use crate::serde::{Deserialize, Serialize};
 use crate::{NodeId, RuntimeId};
 
@@ -38,6 +38,9 @@ pub struct ZFConnectorRecord {
     pub resource: String,
     pub link_id: PortDescriptor,
     pub runtime: RuntimeId,
+    // Reliability of the link the connector is part of, both ends of it must agree.
+    #[serde(default)]
+    pub reliability: Reliability,
 }
 
 impl std::fmt::Display for ZFConnectorRecord {
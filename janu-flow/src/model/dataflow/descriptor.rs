@@ -15,12 +15,18 @@
 use crate::model::dataflow::validator::DataflowValidator;
 use crate::model::deadline::E2EDeadlineDescriptor;
 use crate::model::link::LinkDescriptor;
-use crate::model::node::{OperatorDescriptor, SinkDescriptor, SourceDescriptor};
+use crate::model::node::{
+    CompositeNodeDescriptor, CompositePortDescriptor, OperatorDescriptor, SinkDescriptor,
+    SourceDescriptor,
+};
+use crate::model::{make_file_path, InputDescriptor, OutputDescriptor};
 use crate::serde::{Deserialize, Serialize};
 use crate::types::{NodeId, RuntimeId, ZFError, ZFResult};
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use url::Url;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Mapping {
@@ -37,19 +43,22 @@ pub struct DataFlowDescriptor {
     pub links: Vec<LinkDescriptor>,
     pub mapping: Option<Vec<Mapping>>,
     pub deadlines: Option<Vec<E2EDeadlineDescriptor>>,
+    pub composites: Option<Vec<CompositeNodeDescriptor>>,
 }
 
 impl DataFlowDescriptor {
     pub fn from_yaml(data: &str) -> ZFResult<Self> {
         let dataflow_descriptor = serde_yaml::from_str::<DataFlowDescriptor>(data)
-            .map_err(|e| ZFError::ParsingError(format!("{}", e)))?;
+            .map_err(|e| ZFError::ParsingError(format!("{}", e)))?
+            .flatten()?;
         dataflow_descriptor.validate()?;
         Ok(dataflow_descriptor)
     }
 
     pub fn from_json(data: &str) -> ZFResult<Self> {
         let dataflow_descriptor = serde_json::from_str::<DataFlowDescriptor>(data)
-            .map_err(|e| ZFError::ParsingError(format!("{}", e)))?;
+            .map_err(|e| ZFError::ParsingError(format!("{}", e)))?
+            .flatten()?;
         dataflow_descriptor.validate()?;
         Ok(dataflow_descriptor)
     }
@@ -92,16 +101,175 @@ impl DataFlowDescriptor {
         runtimes.into_iter().collect()
     }
 
+    /// Replaces all the composite nodes with the content of the dataflow they point to.
+    ///
+    /// The ids of the inner nodes are prefixed with the id of their composite node. The links,
+    /// mappings and end-to-end deadlines of the inner dataflow are carried over, and the links and
+    /// deadlines of this dataflow that use ports of a composite node are rewired to the inner
+    /// ports it exposes. A mapping of a composite node applies to all its inner nodes that are not
+    /// mapped.
+    ///
+    /// Calling this method on a dataflow without composite nodes is a no-op.
+    pub fn flatten(self) -> ZFResult<Self> {
+        self.flatten_nested(&mut vec![])
+    }
+
+    fn flatten_nested(mut self, parents: &mut Vec<PathBuf>) -> ZFResult<Self> {
+        let composites = match self.composites.take() {
+            Some(composites) => composites,
+            None => return Ok(self),
+        };
+
+        for composite in composites.iter() {
+            let uri = Url::parse(&composite.uri)
+                .map_err(|err| ZFError::ParsingError(format!("{}", err)))?;
+            if uri.scheme() != "file" {
+                return Err(ZFError::Unimplemented);
+            }
+            let path = make_file_path(&uri)?;
+
+            // A composite node including itself, directly or not, would be flattened forever.
+            if parents.contains(&path) {
+                return Err(ZFError::ParsingError(format!(
+                    "Composite node < {} > includes itself through {:?}",
+                    composite.id, path
+                )));
+            }
+
+            let data = std::fs::read_to_string(&path)?;
+            let inner = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("json") => serde_json::from_str::<DataFlowDescriptor>(&data)
+                    .map_err(|e| ZFError::ParsingError(format!("{}", e)))?,
+                _ => serde_yaml::from_str::<DataFlowDescriptor>(&data)
+                    .map_err(|e| ZFError::ParsingError(format!("{}", e)))?,
+            };
+
+            parents.push(path);
+            let inner = inner.flatten_nested(parents)?;
+            parents.pop();
+
+            self.merge_composite(composite, inner)?;
+        }
+
+        Ok(self)
+    }
+
+    fn merge_composite(
+        &mut self,
+        composite: &CompositeNodeDescriptor,
+        inner: DataFlowDescriptor,
+    ) -> ZFResult<()> {
+        let prefix = |id: &NodeId| -> NodeId { format!("{}/{}", composite.id, id).into() };
+
+        // Rewire what, in this dataflow, points to the ports of the composite node.
+        for link in self.links.iter_mut() {
+            if link.from.node == composite.id {
+                link.from = composite_output(composite, &link.from, &prefix)?;
+            }
+            if link.to.node == composite.id {
+                link.to = composite_input(composite, &link.to, &prefix)?;
+            }
+        }
+
+        if let Some(deadlines) = self.deadlines.as_mut() {
+            for deadline in deadlines.iter_mut() {
+                if deadline.from.node == composite.id {
+                    deadline.from = composite_output(composite, &deadline.from, &prefix)?;
+                }
+                if deadline.to.node == composite.id {
+                    deadline.to = composite_input(composite, &deadline.to, &prefix)?;
+                }
+            }
+        }
+
+        // Carry over the inner dataflow.
+        let mut inner_ids =
+            Vec::with_capacity(inner.operators.len() + inner.sources.len() + inner.sinks.len());
+
+        for mut operator in inner.operators.into_iter() {
+            operator.id = prefix(&operator.id);
+            inner_ids.push(operator.id.clone());
+            self.operators.push(operator);
+        }
+
+        for mut source in inner.sources.into_iter() {
+            source.id = prefix(&source.id);
+            inner_ids.push(source.id.clone());
+            self.sources.push(source);
+        }
+
+        for mut sink in inner.sinks.into_iter() {
+            sink.id = prefix(&sink.id);
+            inner_ids.push(sink.id.clone());
+            self.sinks.push(sink);
+        }
+
+        for mut link in inner.links.into_iter() {
+            link.from.node = prefix(&link.from.node);
+            link.to.node = prefix(&link.to.node);
+            self.links.push(link);
+        }
+
+        if let Some(inner_deadlines) = inner.deadlines {
+            let deadlines = self.deadlines.get_or_insert_with(Vec::new);
+            for mut deadline in inner_deadlines.into_iter() {
+                deadline.from.node = prefix(&deadline.from.node);
+                deadline.to.node = prefix(&deadline.to.node);
+                deadlines.push(deadline);
+            }
+        }
+
+        // Mappings declared in this dataflow take precedence over the ones of the inner dataflow.
+        for inner_mapping in inner.mapping.unwrap_or_default().into_iter() {
+            let id = prefix(&inner_mapping.id);
+            if self.get_mapping(&id).is_none() {
+                self.add_mapping(Mapping {
+                    id,
+                    runtime: inner_mapping.runtime,
+                });
+            }
+        }
+
+        if let Some(runtime) = self.get_mapping(&composite.id) {
+            for id in inner_ids.into_iter() {
+                if self.get_mapping(&id).is_none() {
+                    self.add_mapping(Mapping {
+                        id,
+                        runtime: runtime.clone(),
+                    });
+                }
+            }
+        }
+
+        if let Some(mapping) = self.mapping.as_mut() {
+            mapping.retain(|m| m.id != composite.id);
+        }
+
+        Ok(())
+    }
+
     // This method checks that the dataflow graph is correct.
     //
     // In particular it verifies that:
     // - each node has a unique id,
     // - each port (input and output) is connected,
     // - an input port is connected only once (i.e. it receives data from a single output port),
-    // - connected ports are declared with the same type.
+    // - connected ports are declared with the same type,
+    // - the queueing policy and the reliability of each link are known,
+    // - the input rule policy of each operator applies to its inputs.
     fn validate(&self) -> ZFResult<()> {
         let validator = DataflowValidator::try_from(self)?;
         validator.validate_ports()?;
+        self.operators
+            .iter()
+            .try_for_each(|operator| match &operator.input_rule {
+                Some(input_rule) => input_rule.validate(&operator.id, operator.inputs.len()),
+                None => Ok(()),
+            })?;
+        self.links.iter().try_for_each(|link| {
+            link.get_queueing_policy()?;
+            link.get_reliability().map(|_| ())
+        })?;
         if let Some(deadlines) = &self.deadlines {
             deadlines.iter().try_for_each(|deadline| {
                 validator.validate_deadline(&deadline.from, &deadline.to)
@@ -112,6 +280,41 @@ impl DataFlowDescriptor {
     }
 }
 
+fn find_composite_port<'a>(
+    ports: &'a [CompositePortDescriptor],
+    composite_id: &NodeId,
+    port_id: &str,
+) -> ZFResult<&'a CompositePortDescriptor> {
+    ports
+        .iter()
+        .find(|port| port.id.as_ref() == port_id)
+        .ok_or_else(|| ZFError::PortNotFound((composite_id.clone(), port_id.into())))
+}
+
+fn composite_output(
+    composite: &CompositeNodeDescriptor,
+    output: &OutputDescriptor,
+    prefix: &dyn Fn(&NodeId) -> NodeId,
+) -> ZFResult<OutputDescriptor> {
+    let port = find_composite_port(&composite.outputs, &composite.id, &output.output)?;
+    Ok(OutputDescriptor {
+        node: prefix(&port.node),
+        output: port.port.clone(),
+    })
+}
+
+fn composite_input(
+    composite: &CompositeNodeDescriptor,
+    input: &InputDescriptor,
+    prefix: &dyn Fn(&NodeId) -> NodeId,
+) -> ZFResult<InputDescriptor> {
+    let port = find_composite_port(&composite.inputs, &composite.id, &input.input)?;
+    Ok(InputDescriptor {
+        node: prefix(&port.node),
+        input: port.port.clone(),
+    })
+}
+
 impl Hash for DataFlowDescriptor {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.flow.hash(state);
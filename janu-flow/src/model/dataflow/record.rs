@@ -16,11 +16,12 @@ use crate::model::connector::{ZFConnectorKind, ZFConnectorRecord};
 use crate::model::dataflow::descriptor::DataFlowDescriptor;
 use crate::model::deadline::E2EDeadlineRecord;
 use crate::model::link::{LinkDescriptor, PortDescriptor};
-use crate::model::node::{OperatorRecord, SinkRecord, SourceRecord};
+use crate::model::node::{OperatorDescriptor, OperatorRecord, SinkRecord, SourceRecord};
 use crate::model::{InputDescriptor, OutputDescriptor};
 use crate::serde::{Deserialize, Serialize};
-use crate::types::{RuntimeId, ZFError, ZFResult};
+use crate::types::{NodeId, RuntimeId, ZFError, ZFResult};
 use crate::PortType;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
 use std::hash::{Hash, Hasher};
 use uuid::Uuid;
@@ -69,6 +70,182 @@ impl DataFlowRecord {
         }
     }
 
+    /// Returns the runtimes the nodes are mapped to.
+    pub fn get_runtimes(&self) -> Vec<RuntimeId> {
+        let runtimes: HashSet<RuntimeId> = self
+            .sources
+            .iter()
+            .map(|s| s.runtime.clone())
+            .chain(self.operators.iter().map(|o| o.runtime.clone()))
+            .chain(self.sinks.iter().map(|s| s.runtime.clone()))
+            .collect();
+        runtimes.into_iter().collect()
+    }
+
+    /// Returns the runtimes the nodes are mapped to, a runtime coming after the runtimes sending
+    /// it messages through connectors. The runtimes that are part of a cycle are returned in an
+    /// arbitrary order.
+    pub fn get_runtimes_in_order(&self) -> Vec<RuntimeId> {
+        let mut upstream: HashMap<RuntimeId, HashSet<RuntimeId>> = self
+            .get_runtimes()
+            .into_iter()
+            .map(|runtime| (runtime, HashSet::new()))
+            .collect();
+        for receiver in self
+            .connectors
+            .iter()
+            .filter(|c| c.kind == ZFConnectorKind::Receiver)
+        {
+            for sender in self.connectors.iter().filter(|c| {
+                c.kind == ZFConnectorKind::Sender
+                    && c.resource == receiver.resource
+                    && c.runtime != receiver.runtime
+            }) {
+                upstream
+                    .entry(receiver.runtime.clone())
+                    .or_default()
+                    .insert(sender.runtime.clone());
+            }
+        }
+
+        let mut runtimes = Vec::with_capacity(upstream.len());
+        while !upstream.is_empty() {
+            let mut ready: Vec<RuntimeId> = upstream
+                .iter()
+                .filter(|(_, senders)| senders.iter().all(|rt| !upstream.contains_key(rt)))
+                .map(|(runtime, _)| runtime.clone())
+                .collect();
+            if ready.is_empty() {
+                // A cycle, any of its runtimes can come first.
+                ready.extend(upstream.keys().next().cloned());
+            }
+            for runtime in ready {
+                upstream.remove(&runtime);
+                runtimes.push(runtime);
+            }
+        }
+        runtimes
+    }
+
+    /// Returns the nodes and connectors, along with the runtime they are mapped to.
+    pub fn get_node_runtimes(&self) -> Vec<(NodeId, RuntimeId)> {
+        self.sources
+            .iter()
+            .map(|s| (s.id.clone(), s.runtime.clone()))
+            .chain(
+                self.operators
+                    .iter()
+                    .map(|o| (o.id.clone(), o.runtime.clone())),
+            )
+            .chain(self.sinks.iter().map(|s| (s.id.clone(), s.runtime.clone())))
+            .chain(
+                self.connectors
+                    .iter()
+                    .map(|c| (c.id.clone(), c.runtime.clone())),
+            )
+            .collect()
+    }
+
+    /// Returns the links the messages go through to reach the node `id`: its input links and,
+    /// for the inputs fed through connectors, the links feeding the matching senders.
+    pub fn get_upstream_links(&self, id: &str) -> Vec<LinkDescriptor> {
+        let mut upstream = Vec::new();
+        for link in self.links.iter().filter(|l| l.to.node.as_ref() == id) {
+            for sender in self.get_peer_connectors(&link.from.node, ZFConnectorKind::Sender) {
+                upstream.extend(
+                    self.links
+                        .iter()
+                        .filter(|l| l.to.node == sender.id)
+                        .cloned(),
+                );
+            }
+            upstream.push(link.clone());
+        }
+        upstream
+    }
+
+    /// Returns the links the messages sent by the node `id` go through: its output links and,
+    /// for the outputs sent through connectors, the links leaving the matching receivers.
+    pub fn get_downstream_links(&self, id: &str) -> Vec<LinkDescriptor> {
+        let mut downstream = Vec::new();
+        for link in self.links.iter().filter(|l| l.from.node.as_ref() == id) {
+            downstream.push(link.clone());
+            for receiver in self.get_peer_connectors(&link.to.node, ZFConnectorKind::Receiver) {
+                downstream.extend(
+                    self.links
+                        .iter()
+                        .filter(|l| l.from.node == receiver.id)
+                        .cloned(),
+                );
+            }
+        }
+        downstream
+    }
+
+    /// Returns the nodes sending messages to the node `id`, directly or through connectors,
+    /// along with the runtime they are mapped to.
+    pub fn get_upstream_nodes(&self, id: &str) -> Vec<(NodeId, RuntimeId)> {
+        let mut upstream: Vec<(NodeId, RuntimeId)> = self
+            .get_upstream_links(id)
+            .into_iter()
+            .filter_map(|link| {
+                self.find_node_runtime(&link.from.node)
+                    .map(|runtime| (link.from.node, runtime))
+            })
+            .collect();
+        upstream.sort();
+        upstream.dedup();
+        upstream
+    }
+
+    /// Returns the nodes and connectors of this record that are wired differently in `other`,
+    /// along with the runtime they are mapped to in this record: the ones that are not mapped to
+    /// the same runtime in `other` and the ones at either end of a link that is not in both.
+    pub fn get_rewired_nodes(&self, other: &DataFlowRecord) -> Vec<(NodeId, RuntimeId)> {
+        let runtimes = self.get_node_runtimes();
+        let other_runtimes = other.get_node_runtimes();
+
+        let mut rewired: Vec<(NodeId, RuntimeId)> = runtimes
+            .iter()
+            .filter(|node| !other_runtimes.contains(node))
+            .cloned()
+            .collect();
+        let changed_links = self
+            .links
+            .iter()
+            .filter(|l| !other.links.iter().any(|o| o.from == l.from && o.to == l.to))
+            .chain(
+                other
+                    .links
+                    .iter()
+                    .filter(|o| !self.links.iter().any(|l| l.from == o.from && l.to == o.to)),
+            );
+        for link in changed_links {
+            rewired.extend(
+                runtimes
+                    .iter()
+                    .filter(|(id, _)| *id == link.from.node || *id == link.to.node)
+                    .cloned(),
+            );
+        }
+        rewired.sort();
+        rewired.dedup();
+        rewired
+    }
+
+    // Returns the connectors of `kind` sharing the resource of the connector `id`, none if `id`
+    // is not a connector.
+    fn get_peer_connectors(&self, id: &str, kind: ZFConnectorKind) -> Vec<&ZFConnectorRecord> {
+        match self.connectors.iter().find(|c| c.id.as_ref() == id) {
+            Some(connector) => self
+                .connectors
+                .iter()
+                .filter(|c| c.kind == kind && c.resource == connector.resource)
+                .collect(),
+            None => vec![],
+        }
+    }
+
     pub fn find_node_output_type(&self, id: &str, output: &str) -> Option<PortType> {
         log::trace!("find_node_output_type({:?},{:?})", id, output);
         match self.get_operator(id) {
@@ -91,6 +268,107 @@ impl DataFlowRecord {
         }
     }
 
+    /// Updates the operator `descriptor.id` with the ports, URI, checksum, signature, isolation
+    /// and configuration of `descriptor`, returning its updated record.
+    ///
+    /// This is used when the implementation of an operator is reloaded.
+    pub fn try_update_operator(
+        &mut self,
+        descriptor: &OperatorDescriptor,
+    ) -> ZFResult<OperatorRecord> {
+        let record = self
+            .operators
+            .iter_mut()
+            .find(|o| o.id == descriptor.id)
+            .ok_or_else(|| ZFError::NodeNotFound(descriptor.id.clone()))?;
+
+        record.inputs = descriptor.inputs.clone();
+        record.outputs = descriptor.outputs.clone();
+        record.uri = descriptor.uri.clone();
+        record.checksum = descriptor.checksum.clone();
+        record.signature = descriptor.signature.clone();
+        record.isolated = descriptor.isolated;
+        record.configuration = descriptor.configuration.clone();
+
+        Ok(record.clone())
+    }
+
+    /// Maps the node `id` to `runtime`, updating the connectors and the links accordingly.
+    ///
+    /// The links declared between the nodes are recovered from the current links and
+    /// connectors, the connectors are then created again for the new mapping. As their ids
+    /// only depend on the ports they connect, the connectors of the links that do not involve
+    /// the node keep theirs.
+    ///
+    /// This is used when a node is migrated to another runtime.
+    pub fn try_migrate_node(&mut self, id: &str, runtime: RuntimeId) -> ZFResult<()> {
+        if let Some(operator) = self.operators.iter_mut().find(|o| o.id.as_ref() == id) {
+            operator.runtime = runtime;
+        } else if let Some(source) = self.sources.iter_mut().find(|s| s.id.as_ref() == id) {
+            source.runtime = runtime;
+        } else if let Some(sink) = self.sinks.iter_mut().find(|s| s.id.as_ref() == id) {
+            sink.runtime = runtime;
+        } else {
+            return Err(ZFError::NodeNotFound(id.into()));
+        }
+
+        let links = self.get_declared_links()?;
+        self.connectors.clear();
+        self.links.clear();
+        self.add_links(&links)
+    }
+
+    // Returns the links between the nodes, as declared in the descriptor: the links going
+    // through a pair of connectors are merged back into one.
+    fn get_declared_links(&self) -> ZFResult<Vec<LinkDescriptor>> {
+        let is_connector = |node: &str| self.connectors.iter().any(|c| c.id.as_ref() == node);
+        let mut links: Vec<LinkDescriptor> = self
+            .links
+            .iter()
+            .filter(|l| !is_connector(l.from.node.as_ref()) && !is_connector(l.to.node.as_ref()))
+            .cloned()
+            .collect();
+
+        for receiver in self
+            .connectors
+            .iter()
+            .filter(|c| c.kind == ZFConnectorKind::Receiver)
+        {
+            let sender = self
+                .connectors
+                .iter()
+                .find(|c| c.kind == ZFConnectorKind::Sender && c.resource == receiver.resource)
+                .ok_or_else(|| {
+                    ZFError::Uncompleted(format!("Missing sender for {}", &receiver.resource))
+                })?;
+            let from = self
+                .links
+                .iter()
+                .find(|l| l.to.node == sender.id)
+                .ok_or_else(|| ZFError::Uncompleted(format!("Missing link to {}", &sender.id)))?;
+            // The link from the receiver is the one keeping the size, queueing policy, priority
+            // and reliability declared by the user, see `add_links`.
+            let to = self
+                .links
+                .iter()
+                .find(|l| l.from.node == receiver.id)
+                .ok_or_else(|| {
+                    ZFError::Uncompleted(format!("Missing link from {}", &receiver.id))
+                })?;
+
+            links.push(LinkDescriptor {
+                from: from.from.clone(),
+                to: to.to.clone(),
+                size: to.size,
+                queueing_policy: to.queueing_policy.clone(),
+                priority: to.priority,
+                reliability: to.reliability.clone(),
+            });
+        }
+
+        Ok(links)
+    }
+
     fn get_operator(&self, id: &str) -> Option<OperatorRecord> {
         self.operators
             .iter()
@@ -160,11 +438,45 @@ impl DataFlowRecord {
                 // here we have to create the connectors information
                 // and add the new links
 
-                // creating janu resource name
-                let z_resource_name = format!(
-                    "/zf/data/{}/{}/{}/{}",
-                    &self.flow, &self.uuid, &l.from.node, &l.from.output
-                );
+                let reliability = l.get_reliability()?;
+
+                // creating janu resource name and sender id
+                //
+                // A reliable link has a sender of its own: the sender keeps the messages until
+                // they are acknowledged, by a single receiver.
+                let (z_resource_name, sender_id) = if reliability.is_reliable() {
+                    (
+                        format!(
+                            "/zf/data/{}/{}/{}/{}/{}/{}",
+                            &self.flow,
+                            &self.uuid,
+                            &l.from.node,
+                            &l.from.output,
+                            &l.to.node,
+                            &l.to.input
+                        ),
+                        format!(
+                            "sender-{}-{}-{}-{}-{}-{}",
+                            &self.flow,
+                            &self.uuid,
+                            &l.from.node,
+                            &l.from.output,
+                            &l.to.node,
+                            &l.to.input
+                        ),
+                    )
+                } else {
+                    (
+                        format!(
+                            "/zf/data/{}/{}/{}/{}",
+                            &self.flow, &self.uuid, &l.from.node, &l.from.output
+                        ),
+                        format!(
+                            "sender-{}-{}-{}-{}",
+                            &self.flow, &self.uuid, &l.from.node, &l.from.output
+                        ),
+                    )
+                };
 
                 // We only create a sender if none was created for the same resource. The rationale
                 // is to avoid creating multiple publisher for the same resource in case an operator
@@ -175,10 +487,6 @@ impl DataFlowRecord {
                     .any(|c| c.kind == ZFConnectorKind::Sender && c.resource == z_resource_name)
                 {
                     // creating sender
-                    let sender_id = format!(
-                        "sender-{}-{}-{}-{}",
-                        &self.flow, &self.uuid, &l.from.node, &l.from.output
-                    );
                     let sender = ZFConnectorRecord {
                         kind: ZFConnectorKind::Sender,
                         id: sender_id.clone().into(),
@@ -189,6 +497,7 @@ impl DataFlowRecord {
                         },
 
                         runtime: from_runtime,
+                        reliability,
                     };
 
                     // creating link between node and sender
@@ -201,6 +510,7 @@ impl DataFlowRecord {
                         size: None,
                         queueing_policy: None,
                         priority: None,
+                        reliability: None,
                     };
 
                     // storing info in the dataflow record
@@ -223,18 +533,22 @@ impl DataFlowRecord {
                     },
 
                     runtime: to_runtime,
+                    reliability,
                 };
 
-                // Creating link between receiver and node
+                // Creating link between receiver and node. This link keeps the size, queueing
+                // policy, priority and reliability declared by the user as it is the one feeding
+                // the input.
                 let link_receiver = LinkDescriptor {
                     from: OutputDescriptor {
                         node: receiver_id.into(),
                         output: l.to.input.clone(),
                     },
                     to: l.to.clone(),
-                    size: None,
-                    queueing_policy: None,
-                    priority: None,
+                    size: l.size,
+                    queueing_policy: l.queueing_policy.clone(),
+                    priority: l.priority,
+                    reliability: l.reliability.clone(),
                 };
 
                 // storing info in the data flow record
@@ -252,6 +566,7 @@ impl TryFrom<(DataFlowDescriptor, Uuid)> for DataFlowRecord {
 
     fn try_from(d: (DataFlowDescriptor, Uuid)) -> Result<Self, Self::Error> {
         let (d, id) = d;
+        let d = d.flatten()?;
 
         let deadlines = d
             .deadlines
@@ -277,9 +592,14 @@ impl TryFrom<(DataFlowDescriptor, Uuid)> for DataFlowRecord {
                         inputs: o.inputs.clone(),
                         outputs: o.outputs.clone(),
                         uri: o.uri.clone(),
+                        checksum: o.checksum.clone(),
+                        signature: o.signature.clone(),
+                        isolated: o.isolated,
                         configuration: o.configuration.clone(),
                         runtime: m,
                         deadline: o.deadline.as_ref().map(|period| period.to_duration()),
+                        input_rule: o.input_rule.clone(),
+                        restart_policy: o.restart_policy.clone().unwrap_or_default(),
                     };
                     dfr.operators.push(or)
                 }
@@ -298,10 +618,15 @@ impl TryFrom<(DataFlowDescriptor, Uuid)> for DataFlowRecord {
                     let sr = SourceRecord {
                         id: s.id.clone(),
                         period: s.period.clone(),
+                        missed_ticks: s.missed_ticks.unwrap_or_default(),
                         output: s.output.clone(),
                         uri: s.uri.clone(),
+                        checksum: s.checksum.clone(),
+                        signature: s.signature.clone(),
+                        isolated: s.isolated,
                         configuration: s.configuration.clone(),
                         runtime: m,
+                        restart_policy: s.restart_policy.clone().unwrap_or_default(),
                     };
                     dfr.sources.push(sr)
                 }
@@ -322,8 +647,12 @@ impl TryFrom<(DataFlowDescriptor, Uuid)> for DataFlowRecord {
                         // name: s.name.clone(),
                         input: s.input.clone(),
                         uri: s.uri.clone(),
+                        checksum: s.checksum.clone(),
+                        signature: s.signature.clone(),
+                        isolated: s.isolated,
                         configuration: s.configuration.clone(),
                         runtime: m,
+                        restart_policy: s.restart_policy.clone().unwrap_or_default(),
                     };
                     dfr.sinks.push(sr)
                 }
@@ -13,8 +13,9 @@
 //
 
 use crate::model::{InputDescriptor, OutputDescriptor};
-use crate::{PortId, PortType};
+use crate::{PortId, PortType, ZFError, ZFResult};
 use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LinkDescriptor {
@@ -23,6 +24,7 @@ pub struct LinkDescriptor {
     pub size: Option<usize>,
     pub queueing_policy: Option<String>,
     pub priority: Option<usize>,
+    pub reliability: Option<String>,
 }
 
 impl std::fmt::Display for LinkDescriptor {
@@ -31,6 +33,121 @@ impl std::fmt::Display for LinkDescriptor {
     }
 }
 
+impl LinkDescriptor {
+    /// Returns the queueing policy declared for this link, `block` if none was specified.
+    pub fn get_queueing_policy(&self) -> ZFResult<QueueingPolicy> {
+        match &self.queueing_policy {
+            Some(policy) => policy.parse(),
+            None => Ok(QueueingPolicy::default()),
+        }
+    }
+
+    /// Returns the priority declared for this link, `0` (the lowest) if none was specified.
+    pub fn get_priority(&self) -> usize {
+        self.priority.unwrap_or(0)
+    }
+
+    /// Returns the reliability declared for this link, `at-most-once` if none was specified.
+    pub fn get_reliability(&self) -> ZFResult<Reliability> {
+        match &self.reliability {
+            Some(reliability) => reliability.parse(),
+            None => Ok(Reliability::default()),
+        }
+    }
+}
+
+/// What a bounded link does when a message is sent while it is full.
+///
+/// The policy has no effect on unbounded links, i.e. links without a `size`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum QueueingPolicy {
+    /// The sender waits until there is room in the link.
+    #[default]
+    Block,
+    /// The oldest message in the link is discarded to make room for the new one.
+    DropOldest,
+    /// The new message is discarded.
+    DropNewest,
+}
+
+impl FromStr for QueueingPolicy {
+    type Err = ZFError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "block" => Ok(Self::Block),
+            "drop-oldest" => Ok(Self::DropOldest),
+            "drop-newest" => Ok(Self::DropNewest),
+            _ => Err(ZFError::ParsingError(format!(
+                "Unknown queueing policy < {} >, expected one of: block, drop-oldest, drop-newest",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for QueueingPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Block => write!(f, "block"),
+            Self::DropOldest => write!(f, "drop-oldest"),
+            Self::DropNewest => write!(f, "drop-newest"),
+        }
+    }
+}
+
+/// What a link guarantees on the delivery of its messages when it connects two runtimes.
+///
+/// The messages between nodes of the same runtime are never lost, the reliability only applies to
+/// the connectors exchanging them through janu.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Reliability {
+    /// Lost messages are not sent again, late and duplicated ones are discarded.
+    #[default]
+    AtMostOnce,
+    /// Lost messages are sent again until they are acknowledged, they may be received more than
+    /// once and out of order.
+    AtLeastOnce,
+    /// Lost messages are sent again until they are acknowledged, the duplicates are discarded and
+    /// the messages are received in order.
+    ExactlyOnce,
+}
+
+impl Reliability {
+    /// Whether the lost messages are sent again.
+    pub fn is_reliable(&self) -> bool {
+        !matches!(self, Self::AtMostOnce)
+    }
+}
+
+impl FromStr for Reliability {
+    type Err = ZFError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "at-most-once" => Ok(Self::AtMostOnce),
+            "at-least-once" => Ok(Self::AtLeastOnce),
+            "exactly-once" => Ok(Self::ExactlyOnce),
+            _ => Err(ZFError::ParsingError(format!(
+                "Unknown reliability < {} >, expected one of: at-most-once, at-least-once, exactly-once",
+                s
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Reliability {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::AtMostOnce => write!(f, "at-most-once"),
+            Self::AtLeastOnce => write!(f, "at-least-once"),
+            Self::ExactlyOnce => write!(f, "exactly-once"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PortDescriptor {
     #[serde(alias = "id")]
@@ -21,8 +21,10 @@ pub mod node;
 use crate::model::link::PortDescriptor;
 use crate::serde::{Deserialize, Serialize};
 use crate::ZFError;
-use crate::{DurationDescriptor, NodeId, PortId};
+use crate::{DurationDescriptor, NodeId, PortId, ZFResult};
 use std::fmt;
+use std::path::PathBuf;
+use url::Url;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct OutputDescriptor {
@@ -150,3 +152,16 @@ pub struct RegistryNodeArchitecture {
     pub checksum: String,
     pub signature: String,
 }
+
+/// Returns the canonical path of the file a `file://` URI points to, e.g. the library of a node
+/// or the descriptor of a composite node.
+pub(crate) fn make_file_path(uri: &Url) -> ZFResult<PathBuf> {
+    let mut path = PathBuf::new();
+    let file_path = match uri.host_str() {
+        Some(h) => format!("{}{}", h, uri.path()),
+        None => uri.path().to_string(),
+    };
+    path.push(file_path);
+    let path = std::fs::canonicalize(path)?;
+    Ok(path)
+}
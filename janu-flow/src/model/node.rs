@@ -14,10 +14,199 @@
 
 use crate::model::link::PortDescriptor;
 use crate::types::{Configuration, NodeId, RuntimeId};
-use crate::{DurationDescriptor, PortType};
+use crate::{
+    any_input_rule, default_input_rule, k_of_n_input_rule, latest_input_rule, DurationDescriptor,
+    PortId, PortType, State, Token, ZFError, ZFResult,
+};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::time::Duration;
 
+/// Default delay before the first restart of a node with the `always` or `on-failure` restart
+/// policy.
+pub const DEFAULT_RESTART_BACKOFF: Duration = Duration::from_millis(100);
+/// Default upper bound of the delay between two restarts of a node with the `always` or
+/// `on-failure` restart policy.
+pub const DEFAULT_RESTART_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// What the runtime does when the `run` loop of a node exits with an error.
+///
+/// When a node is not restarted anymore, it is put in a terminal error state. The failures of a
+/// node are forgotten once it ran for `max_backoff` without failing, see `get_healthy_run`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "policy", rename_all = "kebab-case")]
+pub enum RestartPolicy {
+    /// The node is never restarted.
+    Never,
+    /// The node is always restarted. The runtime waits `backoff` before the first restart, this
+    /// delay is then doubled after each new failure up to `max_backoff`.
+    Always {
+        backoff: Option<DurationDescriptor>,
+        max_backoff: Option<DurationDescriptor>,
+    },
+    /// The node is restarted at most `max_retries` times in a row, with the same delays as
+    /// `Always`.
+    OnFailure {
+        max_retries: usize,
+        backoff: Option<DurationDescriptor>,
+        max_backoff: Option<DurationDescriptor>,
+    },
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self::Always {
+            backoff: None,
+            max_backoff: None,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Returns the delay to wait before restarting a node that already restarted `restarts`
+    /// times, or `None` if the node should not be restarted.
+    pub fn next_restart(&self, restarts: usize) -> Option<Duration> {
+        match self {
+            Self::Never => None,
+            Self::Always {
+                backoff,
+                max_backoff,
+            } => Some(Self::backoff(backoff, max_backoff, restarts)),
+            Self::OnFailure {
+                max_retries,
+                backoff,
+                max_backoff,
+            } => {
+                if restarts >= *max_retries {
+                    return None;
+                }
+
+                Some(Self::backoff(backoff, max_backoff, restarts))
+            }
+        }
+    }
+
+    /// Returns how long a node must run without failing for its previous restarts to be
+    /// forgotten: the delays and the retries start over. `None` if it is never restarted.
+    pub fn get_healthy_run(&self) -> Option<Duration> {
+        match self {
+            Self::Never => None,
+            Self::Always { max_backoff, .. } | Self::OnFailure { max_backoff, .. } => Some(
+                max_backoff
+                    .as_ref()
+                    .map_or(DEFAULT_RESTART_MAX_BACKOFF, |max_backoff| {
+                        max_backoff.to_duration()
+                    }),
+            ),
+        }
+    }
+
+    fn backoff(
+        backoff: &Option<DurationDescriptor>,
+        max_backoff: &Option<DurationDescriptor>,
+        restarts: usize,
+    ) -> Duration {
+        let backoff = backoff
+            .as_ref()
+            .map_or(DEFAULT_RESTART_BACKOFF, |backoff| backoff.to_duration());
+        let max_backoff = max_backoff
+            .as_ref()
+            .map_or(DEFAULT_RESTART_MAX_BACKOFF, |max_backoff| {
+                max_backoff.to_duration()
+            });
+
+        let delay = u32::try_from(restarts)
+            .ok()
+            .and_then(|restarts| 2u32.checked_pow(restarts))
+            .and_then(|factor| backoff.checked_mul(factor))
+            .unwrap_or(max_backoff);
+
+        delay.min(max_backoff)
+    }
+}
+
+impl std::fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Never => write!(f, "never"),
+            Self::Always { .. } => write!(f, "always"),
+            Self::OnFailure { max_retries, .. } => {
+                write!(f, "on-failure (max retries: {})", max_retries)
+            }
+        }
+    }
+}
+
+/// What the runner of a periodic Source does when it misses ticks, i.e. when an execution of
+/// `run` lasts longer than the period.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissedTickPolicy {
+    /// The missed ticks are dropped: the next execution happens at the next tick to come.
+    #[default]
+    Skip,
+    /// The missed ticks are executed back-to-back, until the Source is on schedule again.
+    CatchUp,
+}
+
+impl std::fmt::Display for MissedTickPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Skip => write!(f, "skip"),
+            Self::CatchUp => write!(f, "catch-up"),
+        }
+    }
+}
+
+/// An input rule provided by Janu Flow, used by the runtime in place of the `input_rule` of an
+/// Operator.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "policy", rename_all = "kebab-case")]
+pub enum InputRulePolicy {
+    /// `run` is triggered when data is available on all inputs.
+    All,
+    /// `run` is triggered when data is available on any input.
+    Any,
+    /// `run` is triggered when data is available on at least `k` inputs.
+    KOfN { k: usize },
+    /// `run` is triggered each time new data is received, with the latest data of every input.
+    Latest,
+}
+
+impl InputRulePolicy {
+    /// Checks that the policy can be applied to an Operator with `inputs` inputs.
+    pub fn validate(&self, id: &NodeId, inputs: usize) -> ZFResult<()> {
+        match self {
+            Self::KOfN { k } if *k == 0 || *k > inputs => Err(ZFError::ParsingError(format!(
+                "Operator < {} > has {} input(s), input rule `k-of-n` requires 1 <= k <= {} (k = {})",
+                id, inputs, inputs, k
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn apply(&self, state: &mut State, tokens: &mut HashMap<PortId, Token>) -> ZFResult<bool> {
+        match self {
+            Self::All => default_input_rule(state, tokens),
+            Self::Any => any_input_rule(state, tokens),
+            Self::KOfN { k } => k_of_n_input_rule(state, tokens, *k),
+            Self::Latest => latest_input_rule(state, tokens),
+        }
+    }
+}
+
+impl std::fmt::Display for InputRulePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::All => write!(f, "all"),
+            Self::Any => write!(f, "any"),
+            Self::KOfN { k } => write!(f, "k-of-n (k: {})", k),
+            Self::Latest => write!(f, "latest"),
+        }
+    }
+}
+
 // Descriptors
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -25,7 +214,12 @@ pub struct SinkDescriptor {
     pub id: NodeId,
     pub input: PortDescriptor,
     pub uri: Option<String>,
+    pub checksum: Option<String>,
+    pub signature: Option<String>,
+    #[serde(default)]
+    pub isolated: bool,
     pub configuration: Option<Configuration>,
+    pub restart_policy: Option<RestartPolicy>,
     pub runtime: Option<RuntimeId>, // to be removed
 }
 
@@ -40,8 +234,14 @@ pub struct SourceDescriptor {
     pub id: NodeId,
     pub output: PortDescriptor,
     pub period: Option<DurationDescriptor>,
+    pub missed_ticks: Option<MissedTickPolicy>,
     pub uri: Option<String>,
+    pub checksum: Option<String>,
+    pub signature: Option<String>,
+    #[serde(default)]
+    pub isolated: bool,
     pub configuration: Option<Configuration>,
+    pub restart_policy: Option<RestartPolicy>,
     pub runtime: Option<RuntimeId>, // to be removed
 }
 
@@ -57,8 +257,14 @@ pub struct OperatorDescriptor {
     pub inputs: Vec<PortDescriptor>,
     pub outputs: Vec<PortDescriptor>,
     pub uri: Option<String>,
+    pub checksum: Option<String>,
+    pub signature: Option<String>,
+    #[serde(default)]
+    pub isolated: bool,
     pub configuration: Option<Configuration>,
     pub deadline: Option<DurationDescriptor>,
+    pub input_rule: Option<InputRulePolicy>,
+    pub restart_policy: Option<RestartPolicy>,
     pub runtime: Option<RuntimeId>, // to be removed
 }
 
@@ -68,6 +274,32 @@ impl std::fmt::Display for OperatorDescriptor {
     }
 }
 
+/// A node whose implementation is another dataflow, described in the file pointed by `uri`.
+///
+/// Composite nodes only exist in descriptors: they are flattened before the dataflow is validated,
+/// the nodes of the inner dataflow being renamed `<composite id>/<inner id>`.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompositeNodeDescriptor {
+    pub id: NodeId,
+    pub uri: String,
+    pub inputs: Vec<CompositePortDescriptor>,
+    pub outputs: Vec<CompositePortDescriptor>,
+}
+
+impl std::fmt::Display for CompositeNodeDescriptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} - Kind: Composite", self.id)
+    }
+}
+
+/// Exposes the port `port` of the inner node `node` as the port `id` of a composite node.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CompositePortDescriptor {
+    pub id: PortId,
+    pub node: NodeId,
+    pub port: PortId,
+}
+
 // Records
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -75,7 +307,13 @@ pub struct SinkRecord {
     pub id: NodeId,
     pub input: PortDescriptor,
     pub uri: Option<String>,
+    pub checksum: Option<String>,
+    pub signature: Option<String>,
+    #[serde(default)]
+    pub isolated: bool,
     pub configuration: Option<Configuration>,
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
     pub runtime: RuntimeId,
 }
 
@@ -100,8 +338,16 @@ pub struct SourceRecord {
     pub id: NodeId,
     pub output: PortDescriptor,
     pub period: Option<DurationDescriptor>,
+    #[serde(default)]
+    pub missed_ticks: MissedTickPolicy,
     pub uri: Option<String>,
+    pub checksum: Option<String>,
+    pub signature: Option<String>,
+    #[serde(default)]
+    pub isolated: bool,
     pub configuration: Option<Configuration>,
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
     pub runtime: RuntimeId,
 }
 
@@ -127,8 +373,16 @@ pub struct OperatorRecord {
     pub(crate) inputs: Vec<PortDescriptor>,
     pub(crate) outputs: Vec<PortDescriptor>,
     pub(crate) uri: Option<String>,
+    pub(crate) checksum: Option<String>,
+    pub(crate) signature: Option<String>,
+    #[serde(default)]
+    pub(crate) isolated: bool,
     pub(crate) configuration: Option<Configuration>,
     pub(crate) deadline: Option<Duration>,
+    #[serde(default)]
+    pub(crate) input_rule: Option<InputRulePolicy>,
+    #[serde(default)]
+    pub(crate) restart_policy: RestartPolicy,
     pub(crate) runtime: RuntimeId,
 }
 
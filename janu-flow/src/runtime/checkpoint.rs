@@ -0,0 +1,213 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+//! Checkpoints of the states of the nodes.
+//!
+//! A checkpoint is the state of a node as saved by `Node::save_state`, by default the serialized
+//! state if it is marked `#[zf_state(serializable)]`. Only the latest checkpoint of a node is
+//! kept, under the id of its flow and of the node: another instance of the flow, e.g. after the
+//! daemon restarted or the flow was redeployed, restores it when the node is first started.
+//!
+//! Checkpoints are taken periodically, if an interval is configured, when a node is stopped and
+//! on demand.
+
+use crate::runtime::resources::DataStore;
+use crate::runtime::InstanceContext;
+use crate::serde::{Deserialize, Serialize};
+use crate::{DurationDescriptor, Node, NodeId, State, ZFError, ZFResult};
+use async_std::sync::{Arc, Mutex};
+use janu::Session;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// Where the checkpoints are stored.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointStorage {
+    /// In files, under the given directory.
+    Local(PathBuf),
+    /// In janu, see `DataStore`. A janu storage must be configured for them to persist.
+    Janu,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointConfig {
+    pub storage: CheckpointStorage,
+    // How often the states are checkpointed, only on demand and when the nodes are stopped if not
+    // set.
+    #[serde(default)]
+    pub interval: Option<DurationDescriptor>,
+}
+
+enum Storage {
+    Local(PathBuf),
+    Janu(DataStore),
+}
+
+/// The checkpoints of the nodes of a runtime.
+pub struct Checkpoints {
+    storage: Storage,
+    interval: Option<Duration>,
+}
+
+impl Checkpoints {
+    /// Checkpoints stored as files under `path`.
+    pub fn local(path: impl Into<PathBuf>) -> Self {
+        Self {
+            storage: Storage::Local(path.into()),
+            interval: None,
+        }
+    }
+
+    /// Checkpoints stored in janu, through `session`.
+    pub fn janu(session: Arc<Session>) -> Self {
+        Self {
+            storage: Storage::Janu(DataStore::new(session)),
+            interval: None,
+        }
+    }
+
+    pub fn from_config(config: &CheckpointConfig, session: Arc<Session>) -> Self {
+        let checkpoints = match &config.storage {
+            CheckpointStorage::Local(path) => Self::local(path),
+            CheckpointStorage::Janu => Self::janu(session),
+        };
+        match &config.interval {
+            Some(interval) => checkpoints.with_interval(interval.to_duration()),
+            None => checkpoints,
+        }
+    }
+
+    /// Sets how often the states of the running nodes are checkpointed.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = Some(interval);
+        self
+    }
+
+    pub fn get_interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    /// Saves `checkpoint` as the latest checkpoint of the node `node_id` of the flow `flow_id`.
+    pub async fn save(&self, flow_id: &str, node_id: &str, checkpoint: &[u8]) -> ZFResult<()> {
+        match &self.storage {
+            Storage::Local(path) => {
+                let file = Self::file(path, flow_id, node_id)?;
+                if let Some(parent) = file.parent() {
+                    async_std::fs::create_dir_all(parent).await?;
+                }
+                // The previous checkpoint is replaced at once, it is never left half-written, even
+                // by two saves running concurrently.
+                let tmp_file = file.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+                async_std::fs::write(&tmp_file, checkpoint).await?;
+                Ok(async_std::fs::rename(&tmp_file, &file).await?)
+            }
+            Storage::Janu(store) => {
+                store
+                    .add_node_checkpoint(flow_id, node_id, checkpoint)
+                    .await
+            }
+        }
+    }
+
+    /// Loads the latest checkpoint of the node `node_id` of the flow `flow_id`, if any.
+    pub async fn load(&self, flow_id: &str, node_id: &str) -> ZFResult<Option<Vec<u8>>> {
+        match &self.storage {
+            Storage::Local(path) => {
+                match async_std::fs::read(Self::file(path, flow_id, node_id)?).await {
+                    Ok(checkpoint) => Ok(Some(checkpoint)),
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            Storage::Janu(store) => store.get_node_checkpoint(flow_id, node_id).await,
+        }
+    }
+
+    // Node ids of composites contain '/', their checkpoints end up in sub-directories. The ids
+    // cannot lead out of `path`: they are made of plain names only.
+    fn file(path: &Path, flow_id: &str, node_id: &str) -> ZFResult<PathBuf> {
+        for id in [flow_id, node_id] {
+            let mut components = Path::new(id).components().peekable();
+            if components.peek().is_none()
+                || !components.all(|component| matches!(component, Component::Normal(_)))
+            {
+                return Err(ZFError::InvalidData(format!(
+                    "< {} > cannot be used in the path of a checkpoint.",
+                    id
+                )));
+            }
+        }
+        Ok(path.join(flow_id).join(format!("{}.checkpoint", node_id)))
+    }
+}
+
+/// Checkpoints the state of the node `node_id`, returns `false` if `node` did not save anything.
+pub(crate) async fn checkpoint_node<N>(
+    context: &InstanceContext,
+    node_id: &NodeId,
+    node: &N,
+    state: &Mutex<State>,
+) -> ZFResult<bool>
+where
+    N: Node + ?Sized,
+{
+    let checkpoints = context
+        .runtime
+        .checkpoints
+        .as_ref()
+        .ok_or(ZFError::MissingConfiguration)?;
+
+    let checkpoint = {
+        let mut state = state.lock().await;
+        node.save_state(&mut state)?
+    };
+
+    match checkpoint {
+        Some(checkpoint) => {
+            checkpoints
+                .save(&context.flow_id, node_id, &checkpoint)
+                .await?;
+            log::debug!("[Node: {}] State checkpointed.", node_id);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+/// Restores the state of the node `node_id` from its latest checkpoint, returns `false` if there
+/// is none or if checkpoints are not configured.
+pub(crate) async fn restore_node<N>(
+    context: &InstanceContext,
+    node_id: &NodeId,
+    node: &N,
+    state: &Mutex<State>,
+) -> ZFResult<bool>
+where
+    N: Node + ?Sized,
+{
+    let checkpoints = match context.runtime.checkpoints.as_ref() {
+        Some(checkpoints) => checkpoints,
+        None => return Ok(false),
+    };
+
+    match checkpoints.load(&context.flow_id, node_id).await? {
+        Some(checkpoint) => {
+            let mut state = state.lock().await;
+            node.restore_state(&mut state, &checkpoint)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
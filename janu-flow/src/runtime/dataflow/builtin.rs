@@ -0,0 +1,455 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+//! Nodes compiled into the runtime, loaded with the `builtin` scheme: `builtin://<name>`.
+//!
+//! Operators (one input and one output, the data is forwarded untouched):
+//! - `pass-through`: forwards all the data,
+//! - `throttle`: forwards at most one data every `interval` (a duration, e.g.
+//!   `interval: { length: 100, unit: ms }`), the rest is dropped,
+//! - `sample`: forwards one data out of `rate`,
+//! - `dedup`: drops the data identical to the previous one.
+//!
+//! Sources:
+//! - `timer`: produces, every `period` of the Source, the number of the tick — starting at 1 — as
+//!   an unsigned 64 bits little-endian integer.
+//!
+//! Sinks:
+//! - `log`: logs the data received,
+//! - `null`: discards the data received.
+
+use crate::model::node::{OperatorRecord, SinkRecord, SourceRecord};
+use crate::runtime::message::DataMessage;
+use crate::serde::Deserialize;
+use crate::{
+    default_output_rule, Configuration, Context, Data, DurationDescriptor, EmptyState,
+    LocalDeadlineMiss, Node, NodeId, NodeOutput, Operator, PortId, Sink, Source, State, Token,
+    ZFError, ZFResult, ZFState,
+};
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use url::Url;
+
+pub const BUILTIN_SCHEME: &str = "builtin";
+
+/// Returns the name of the built-in node `uri` refers to.
+pub(crate) fn name(uri: &Url) -> ZFResult<&str> {
+    uri.host_str().ok_or_else(|| {
+        ZFError::ParsingError(format!(
+            "Missing the name of the built-in node: < {} >.",
+            uri
+        ))
+    })
+}
+
+/// Creates the built-in Operator `name`.
+pub(crate) fn operator(name: &str, record: &OperatorRecord) -> ZFResult<Arc<dyn Operator>> {
+    if record.inputs.len() != 1 || record.outputs.len() != 1 {
+        return Err(ZFError::LoadingError(format!(
+            "The built-in Operator < {} > of < {} > must have exactly one input and one output.",
+            name, record.id
+        )));
+    }
+
+    let ports = ForwardPorts {
+        input: record.inputs[0].port_id.clone(),
+        output: record.outputs[0].port_id.clone(),
+    };
+
+    match name {
+        "pass-through" => Ok(Arc::new(PassThrough(ports))),
+        "throttle" => Ok(Arc::new(Throttle(ports))),
+        "sample" => Ok(Arc::new(Sample(ports))),
+        "dedup" => Ok(Arc::new(Dedup(ports))),
+        _ => Err(ZFError::LoadingError(format!(
+            "Unknown built-in Operator < {} > for < {} >.",
+            name, record.id
+        ))),
+    }
+}
+
+/// Creates the built-in Source `name`.
+pub(crate) fn source(name: &str, record: &SourceRecord) -> ZFResult<Arc<dyn Source>> {
+    match name {
+        "timer" => {
+            if record.period.is_none() {
+                return Err(ZFError::LoadingError(format!(
+                    "The built-in Source < timer > of < {} > requires a period.",
+                    record.id
+                )));
+            }
+            Ok(Arc::new(TimerSource))
+        }
+        _ => Err(ZFError::LoadingError(format!(
+            "Unknown built-in Source < {} > for < {} >.",
+            name, record.id
+        ))),
+    }
+}
+
+/// Creates the built-in Sink `name`.
+pub(crate) fn sink(name: &str, record: &SinkRecord) -> ZFResult<Arc<dyn Sink>> {
+    match name {
+        "log" => Ok(Arc::new(LogSink {
+            id: record.id.clone(),
+        })),
+        "null" => Ok(Arc::new(NullSink)),
+        _ => Err(ZFError::LoadingError(format!(
+            "Unknown built-in Sink < {} > for < {} >.",
+            name, record.id
+        ))),
+    }
+}
+
+fn try_parse_configuration<T>(node: &str, configuration: &Option<Configuration>) -> ZFResult<T>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let configuration = configuration
+        .as_ref()
+        .ok_or(ZFError::MissingConfiguration)?;
+    serde_json::from_value::<T>(configuration.clone()).map_err(|e| {
+        ZFError::ParsingError(format!(
+            "Invalid configuration for the built-in node < {} >: {}",
+            node, e
+        ))
+    })
+}
+
+// OPERATORS
+
+struct ForwardPorts {
+    input: PortId,
+    output: PortId,
+}
+
+impl ForwardPorts {
+    fn forward(
+        &self,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let data_message = inputs
+            .remove(&self.input)
+            .ok_or_else(|| ZFError::MissingInput(self.input.to_string()))?;
+
+        let mut results = HashMap::with_capacity(1);
+        results.insert(self.output.clone(), data_message.data);
+        Ok(results)
+    }
+
+    // Applies `keep` to the data received, the data is dropped if it returns `false`.
+    fn filter(
+        &self,
+        tokens: &mut HashMap<PortId, Token>,
+        keep: impl FnOnce(&mut Data) -> ZFResult<bool>,
+    ) -> ZFResult<bool> {
+        match tokens.get_mut(&self.input) {
+            Some(Token::Ready(ready_token)) => {
+                if keep(ready_token.get_data_mut())? {
+                    return Ok(true);
+                }
+
+                ready_token.set_action_drop();
+                Ok(false)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Implements the parts common to all the built-in operators, only the input rule differs.
+macro_rules! builtin_operator {
+    ($operator:ident, $initialize:ident, $input_rule:ident) => {
+        impl Node for $operator {
+            fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+                $initialize(configuration)
+            }
+
+            fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+                Ok(())
+            }
+        }
+
+        impl Operator for $operator {
+            fn input_rule(
+                &self,
+                _context: &mut Context,
+                state: &mut State,
+                tokens: &mut HashMap<PortId, Token>,
+            ) -> ZFResult<bool> {
+                $input_rule(&self.0, state, tokens)
+            }
+
+            fn run(
+                &self,
+                _context: &mut Context,
+                _state: &mut State,
+                inputs: &mut HashMap<PortId, DataMessage>,
+            ) -> ZFResult<HashMap<PortId, Data>> {
+                self.0.forward(inputs)
+            }
+
+            fn output_rule(
+                &self,
+                _context: &mut Context,
+                state: &mut State,
+                outputs: HashMap<PortId, Data>,
+                _deadline_miss: Option<LocalDeadlineMiss>,
+            ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+                default_output_rule(state, outputs)
+            }
+        }
+    };
+}
+
+/// Implements `ZFState` for the states of the built-in nodes.
+macro_rules! builtin_state {
+    ($state:ident) => {
+        impl ZFState for $state {
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+
+            fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+                self
+            }
+        }
+    };
+}
+
+fn empty_state(_configuration: &Option<Configuration>) -> ZFResult<State> {
+    Ok(State::from(EmptyState))
+}
+
+struct PassThrough(ForwardPorts);
+
+fn pass_through(
+    ports: &ForwardPorts,
+    _state: &mut State,
+    tokens: &mut HashMap<PortId, Token>,
+) -> ZFResult<bool> {
+    ports.filter(tokens, |_| Ok(true))
+}
+
+builtin_operator!(PassThrough, empty_state, pass_through);
+
+struct Throttle(ForwardPorts);
+
+#[derive(Deserialize)]
+struct ThrottleConfiguration {
+    interval: DurationDescriptor,
+}
+
+#[derive(Debug)]
+struct ThrottleState {
+    interval: Duration,
+    last_forwarded: Option<Instant>,
+}
+
+builtin_state!(ThrottleState);
+
+fn throttle_state(configuration: &Option<Configuration>) -> ZFResult<State> {
+    let configuration =
+        try_parse_configuration::<ThrottleConfiguration>("throttle", configuration)?;
+    Ok(State::from(ThrottleState {
+        interval: configuration.interval.to_duration(),
+        last_forwarded: None,
+    }))
+}
+
+fn throttle(
+    ports: &ForwardPorts,
+    state: &mut State,
+    tokens: &mut HashMap<PortId, Token>,
+) -> ZFResult<bool> {
+    let state = state.try_get::<ThrottleState>()?;
+    ports.filter(tokens, |_| {
+        let now = Instant::now();
+        if matches!(state.last_forwarded, Some(last) if now - last < state.interval) {
+            return Ok(false);
+        }
+
+        state.last_forwarded = Some(now);
+        Ok(true)
+    })
+}
+
+builtin_operator!(Throttle, throttle_state, throttle);
+
+struct Sample(ForwardPorts);
+
+#[derive(Deserialize)]
+struct SampleConfiguration {
+    rate: u64,
+}
+
+#[derive(Debug)]
+struct SampleState {
+    rate: u64,
+    received: u64,
+}
+
+builtin_state!(SampleState);
+
+fn sample_state(configuration: &Option<Configuration>) -> ZFResult<State> {
+    let configuration = try_parse_configuration::<SampleConfiguration>("sample", configuration)?;
+    if configuration.rate == 0 {
+        return Err(ZFError::ParsingError(
+            "The rate of the built-in node < sample > must be strictly positive.".to_string(),
+        ));
+    }
+
+    Ok(State::from(SampleState {
+        rate: configuration.rate,
+        received: 0,
+    }))
+}
+
+fn sample(
+    ports: &ForwardPorts,
+    state: &mut State,
+    tokens: &mut HashMap<PortId, Token>,
+) -> ZFResult<bool> {
+    let state = state.try_get::<SampleState>()?;
+    ports.filter(tokens, |_| {
+        // The first data received is forwarded, then one out of `rate`.
+        let keep = state.received % state.rate == 0;
+        state.received += 1;
+        Ok(keep)
+    })
+}
+
+builtin_operator!(Sample, sample_state, sample);
+
+struct Dedup(ForwardPorts);
+
+#[derive(Debug)]
+struct DedupState {
+    previous: Option<Arc<Vec<u8>>>,
+}
+
+builtin_state!(DedupState);
+
+fn dedup_state(_configuration: &Option<Configuration>) -> ZFResult<State> {
+    Ok(State::from(DedupState { previous: None }))
+}
+
+fn dedup(
+    ports: &ForwardPorts,
+    state: &mut State,
+    tokens: &mut HashMap<PortId, Token>,
+) -> ZFResult<bool> {
+    let state = state.try_get::<DedupState>()?;
+    ports.filter(tokens, |data| {
+        let bytes = data.try_as_bytes()?;
+        if state.previous.as_ref() == Some(&bytes) {
+            return Ok(false);
+        }
+
+        state.previous = Some(bytes);
+        Ok(true)
+    })
+}
+
+builtin_operator!(Dedup, dedup_state, dedup);
+
+// SOURCES
+
+struct TimerSource;
+
+#[derive(Debug)]
+struct TimerState {
+    ticks: u64,
+}
+
+builtin_state!(TimerState);
+
+impl Node for TimerSource {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from(TimerState { ticks: 0 }))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Source for TimerSource {
+    async fn run(&self, _context: &mut Context, state: &mut State) -> ZFResult<Data> {
+        let state = state.try_get::<TimerState>()?;
+        state.ticks += 1;
+        Ok(Data::from_bytes(state.ticks.to_le_bytes().to_vec()))
+    }
+}
+
+// SINKS
+
+struct LogSink {
+    id: NodeId,
+}
+
+impl Node for LogSink {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        empty_state(configuration)
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for LogSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        input: DataMessage,
+    ) -> ZFResult<()> {
+        log::info!(
+            "[Sink: {}] Received < {:?} > at < {} >.",
+            self.id,
+            input.data,
+            input.timestamp
+        );
+        Ok(())
+    }
+}
+
+struct NullSink;
+
+impl Node for NullSink {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        empty_state(configuration)
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for NullSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        _input: DataMessage,
+    ) -> ZFResult<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,403 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+//! Stable C ABI for nodes.
+//!
+//! A library implements a node by exporting a vtable of `extern "C"` functions under the symbol
+//! `zfc_operator_vtable`, `zfc_source_vtable` or `zfc_sink_vtable`. Contrary to the declarations
+//! exported by `export_operator!` and its siblings, the vtables do not depend on the version of
+//! `rustc` nor on the one of Janu Flow: only `abi_version` must be equal to `ZFC_ABI_VERSION`.
+//!
+//! Data crosses the boundary as bytes. The buffers given to a node are borrowed for the duration
+//! of the call, the outputs of a node are copied by the runtime through the `emit` callback.
+//! Every function returns `ZFC_OK` on success, any other value is an error.
+//!
+//! The C header declaring these types is generated when building Janu Flow and is available as
+//! `C_HEADER`.
+
+use crate::{
+    default_input_rule, default_output_rule, Configuration, Context, Data, DataMessage,
+    LocalDeadlineMiss, Node, NodeOutput, Operator, PortId, Sink, Source, State, Token, ZFError,
+    ZFResult, ZFState,
+};
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_void};
+
+/// The content of the generated C header, `janu_flow.h`.
+pub static C_HEADER: &str = include_str!(concat!(env!("OUT_DIR"), "/janu_flow.h"));
+
+/// The version of the vtables, increased whenever their layout or semantic changes.
+pub const ZFC_ABI_VERSION: u32 = 1;
+
+/// Returned by the functions of a node, and by `emit`, on success.
+pub const ZFC_OK: i32 = 0;
+
+/// Returned by `emit` when the port id is not valid UTF-8.
+pub const ZFC_ERROR: i32 = -1;
+
+/// Data received on the input `port_id`, borrowed for the duration of the call.
+#[repr(C)]
+pub struct ZFCInput {
+    pub port_id: *const c_char,
+    pub data: *const u8,
+    pub len: usize,
+}
+
+/// Copies `len` bytes of `data` as the output of the node on `port_id`.
+///
+/// `emitter` is the opaque pointer given alongside the callback. `port_id` is ignored, and can be
+/// `NULL`, for a Source.
+pub type ZFCEmitFn = unsafe extern "C" fn(
+    emitter: *mut c_void,
+    port_id: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> i32;
+
+/// Creates the state of the node from its JSON `configuration`, `NULL` if it has none.
+pub type ZFCInitializeFn =
+    unsafe extern "C" fn(configuration: *const c_char, state: *mut *mut c_void) -> i32;
+
+/// Releases the state created by `initialize`.
+pub type ZFCFinalizeFn = unsafe extern "C" fn(state: *mut c_void) -> i32;
+
+/// Exported as `zfc_operator_vtable`.
+///
+/// `run` is called once data was received on all the inputs, the outputs are emitted through
+/// `emit`.
+#[repr(C)]
+pub struct ZFCOperatorVTable {
+    pub abi_version: u32,
+    pub initialize: ZFCInitializeFn,
+    pub run: unsafe extern "C" fn(
+        state: *mut c_void,
+        inputs: *const ZFCInput,
+        inputs_len: usize,
+        emitter: *mut c_void,
+        emit: ZFCEmitFn,
+    ) -> i32,
+    pub finalize: ZFCFinalizeFn,
+}
+
+/// Exported as `zfc_source_vtable`.
+///
+/// `run` must emit exactly one output.
+#[repr(C)]
+pub struct ZFCSourceVTable {
+    pub abi_version: u32,
+    pub initialize: ZFCInitializeFn,
+    pub run: unsafe extern "C" fn(state: *mut c_void, emitter: *mut c_void, emit: ZFCEmitFn) -> i32,
+    pub finalize: ZFCFinalizeFn,
+}
+
+/// Exported as `zfc_sink_vtable`.
+#[repr(C)]
+pub struct ZFCSinkVTable {
+    pub abi_version: u32,
+    pub initialize: ZFCInitializeFn,
+    pub run: unsafe extern "C" fn(state: *mut c_void, input: *const ZFCInput) -> i32,
+    pub finalize: ZFCFinalizeFn,
+}
+
+// The state created by the `initialize` function of a vtable. Only the node that created it
+// accesses it, one call at a time.
+#[derive(Debug)]
+struct CState(*mut c_void);
+
+unsafe impl Send for CState {}
+unsafe impl Sync for CState {}
+
+impl ZFState for CState {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Collects the outputs emitted by a node during a call.
+#[derive(Default)]
+struct Emitter {
+    outputs: HashMap<PortId, Data>,
+}
+
+unsafe extern "C" fn emit(
+    emitter: *mut c_void,
+    port_id: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> i32 {
+    let emitter = &mut *(emitter as *mut Emitter);
+    let port_id: PortId = match port_id.is_null() {
+        true => "".into(),
+        false => match CStr::from_ptr(port_id).to_str() {
+            Ok(port_id) => port_id.into(),
+            Err(_) => return ZFC_ERROR,
+        },
+    };
+    let bytes = match len {
+        0 => vec![],
+        _ => std::slice::from_raw_parts(data, len).to_vec(),
+    };
+
+    emitter.outputs.insert(port_id, Data::from_bytes(bytes));
+    ZFC_OK
+}
+
+fn check(code: i32, function: &str) -> ZFResult<()> {
+    match code {
+        ZFC_OK => Ok(()),
+        _ => Err(ZFError::ForeignError(format!(
+            "`{}` returned the error code {}",
+            function, code
+        ))),
+    }
+}
+
+fn check_version(abi_version: u32) -> ZFResult<()> {
+    match abi_version == ZFC_ABI_VERSION {
+        true => Ok(()),
+        false => Err(ZFError::VersionMismatch),
+    }
+}
+
+/// Copies the vtable exported by a library once its `abi_version` is checked: the layout of the
+/// vtables of other versions can differ.
+///
+/// # Safety
+///
+/// `vtable` must point to one of the vtables of this module, of any version.
+pub(crate) unsafe fn read_vtable<T>(vtable: *const T) -> ZFResult<T> {
+    // All the vtables start with their `abi_version`.
+    check_version(*(vtable as *const u32))?;
+    Ok(vtable.read())
+}
+
+fn initialize(
+    initialize: ZFCInitializeFn,
+    configuration: &Option<Configuration>,
+) -> ZFResult<State> {
+    let configuration = configuration
+        .as_ref()
+        .map(|configuration| {
+            let json =
+                serde_json::to_string(configuration).map_err(|_| ZFError::SerializationError)?;
+            CString::new(json).map_err(|e| ZFError::InvalidData(e.to_string()))
+        })
+        .transpose()?;
+    let configuration = configuration
+        .as_ref()
+        .map_or(std::ptr::null(), |configuration| configuration.as_ptr());
+
+    let mut state = std::ptr::null_mut();
+    check(
+        unsafe { initialize(configuration, &mut state) },
+        "initialize",
+    )?;
+    Ok(State::from(CState(state)))
+}
+
+fn finalize(finalize: ZFCFinalizeFn, state: &mut State) -> ZFResult<()> {
+    let state = state.try_get::<CState>()?;
+    check(unsafe { finalize(state.0) }, "finalize")
+}
+
+// The port ids and the bytes referenced by a `ZFCInput`, they must outlive it.
+struct InputBuffer {
+    port_id: CString,
+    data: Arc<Vec<u8>>,
+}
+
+impl InputBuffer {
+    fn try_new(port_id: &str, data: &Data) -> ZFResult<Self> {
+        Ok(Self {
+            port_id: CString::new(port_id).map_err(|e| ZFError::InvalidData(e.to_string()))?,
+            data: data.try_as_bytes()?,
+        })
+    }
+
+    fn as_input(&self) -> ZFCInput {
+        ZFCInput {
+            port_id: self.port_id.as_ptr(),
+            data: self.data.as_ptr(),
+            len: self.data.len(),
+        }
+    }
+}
+
+/// An Operator implemented through a `ZFCOperatorVTable`.
+///
+/// Its input rule waits for data on all the inputs, its output rule forwards all the outputs.
+pub struct COperator {
+    vtable: ZFCOperatorVTable,
+}
+
+impl COperator {
+    pub fn try_new(vtable: ZFCOperatorVTable) -> ZFResult<Self> {
+        check_version(vtable.abi_version)?;
+        Ok(Self { vtable })
+    }
+}
+
+impl Node for COperator {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        initialize(self.vtable.initialize, configuration)
+    }
+
+    fn finalize(&self, state: &mut State) -> ZFResult<()> {
+        finalize(self.vtable.finalize, state)
+    }
+}
+
+impl Operator for COperator {
+    fn input_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        default_input_rule(state, tokens)
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let buffers = inputs
+            .iter()
+            .map(|(port_id, message)| InputBuffer::try_new(port_id, &message.data))
+            .collect::<ZFResult<Vec<_>>>()?;
+        let inputs = buffers
+            .iter()
+            .map(InputBuffer::as_input)
+            .collect::<Vec<_>>();
+
+        let state = state.try_get::<CState>()?;
+        let mut emitter = Emitter::default();
+        check(
+            unsafe {
+                (self.vtable.run)(
+                    state.0,
+                    inputs.as_ptr(),
+                    inputs.len(),
+                    &mut emitter as *mut Emitter as *mut c_void,
+                    emit,
+                )
+            },
+            "run",
+        )?;
+
+        Ok(emitter.outputs)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+/// A Source implemented through a `ZFCSourceVTable`.
+pub struct CSource {
+    vtable: ZFCSourceVTable,
+}
+
+impl CSource {
+    pub fn try_new(vtable: ZFCSourceVTable) -> ZFResult<Self> {
+        check_version(vtable.abi_version)?;
+        Ok(Self { vtable })
+    }
+}
+
+impl Node for CSource {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        initialize(self.vtable.initialize, configuration)
+    }
+
+    fn finalize(&self, state: &mut State) -> ZFResult<()> {
+        finalize(self.vtable.finalize, state)
+    }
+}
+
+#[async_trait]
+impl Source for CSource {
+    async fn run(&self, _context: &mut Context, state: &mut State) -> ZFResult<Data> {
+        let state = state.try_get::<CState>()?;
+        let mut emitter = Emitter::default();
+        check(
+            unsafe {
+                (self.vtable.run)(state.0, &mut emitter as *mut Emitter as *mut c_void, emit)
+            },
+            "run",
+        )?;
+
+        emitter
+            .outputs
+            .into_iter()
+            .next()
+            .map(|(_, data)| data)
+            .ok_or_else(|| ZFError::MissingOutput("The Source did not emit any data.".into()))
+    }
+}
+
+/// A Sink implemented through a `ZFCSinkVTable`.
+pub struct CSink {
+    vtable: ZFCSinkVTable,
+}
+
+impl CSink {
+    pub fn try_new(vtable: ZFCSinkVTable) -> ZFResult<Self> {
+        check_version(vtable.abi_version)?;
+        Ok(Self { vtable })
+    }
+}
+
+impl Node for CSink {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        initialize(self.vtable.initialize, configuration)
+    }
+
+    fn finalize(&self, state: &mut State) -> ZFResult<()> {
+        finalize(self.vtable.finalize, state)
+    }
+}
+
+#[async_trait]
+impl Sink for CSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        input: DataMessage,
+    ) -> ZFResult<()> {
+        // The port id of a Sink is not known by the Sink itself.
+        let buffer = InputBuffer::try_new("", &input.data)?;
+        let input = buffer.as_input();
+
+        let state = state.try_get::<CState>()?;
+        check(unsafe { (self.vtable.run)(state.0, &input) }, "run")
+    }
+}
@@ -12,35 +12,89 @@
 //   open
 //
 
-use crate::{PortId, ZFResult};
+use crate::model::link::QueueingPolicy;
+use crate::runtime::message::Message;
+use crate::{PortId, ZFError, ZFResult};
 use async_std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The items a link can carry.
+///
+/// The queueing policies only ever discard the items that are droppable, the others wait for
+/// room in the link whatever the policy.
+pub trait LinkItem {
+    fn is_droppable(&self) -> bool {
+        true
+    }
+}
+
+impl LinkItem for Message {
+    // Losing a watermark, a mode change or a migration marker would go unnoticed.
+    fn is_droppable(&self) -> bool {
+        !matches!(self, Message::Control(_))
+    }
+}
+
+macro_rules! impl_link_item {
+    ($($t:ty),*) => {
+        $(impl LinkItem for $t {})*
+    };
+}
+
+impl_link_item!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
 
 #[derive(Clone, Debug)]
 pub struct LinkSender<T> {
     pub id: PortId,
     pub sender: flume::Sender<Arc<T>>,
+    pub policy: QueueingPolicy,
+    // Only used by the `DropOldest` policy: it allows the sender to remove the oldest message
+    // when the link is full.
+    pub drop_receiver: Option<flume::Receiver<Arc<T>>>,
+    pub dropped: Arc<AtomicUsize>,
+    // Number of items in the link that cannot be dropped, shared with the receiver.
+    pub undroppable: Arc<AtomicUsize>,
 }
 
 #[derive(Clone, Debug)]
 pub struct LinkReceiver<T> {
     pub id: PortId,
     pub receiver: flume::Receiver<Arc<T>>,
+    pub priority: usize,
+    pub undroppable: Arc<AtomicUsize>,
 }
 
 pub type ZFLinkOutput<T> = ZFResult<(PortId, Arc<T>)>;
 
-impl<T: std::marker::Send + std::marker::Sync> LinkReceiver<T> {
+impl<T: LinkItem + std::marker::Send + std::marker::Sync> LinkReceiver<T> {
     pub fn recv(
         &self,
     ) -> ::core::pin::Pin<Box<dyn std::future::Future<Output = ZFLinkOutput<T>> + '_ + Send + Sync>>
     {
-        async fn __recv<T>(_self: &LinkReceiver<T>) -> ZFResult<(PortId, Arc<T>)> {
-            Ok((_self.id.clone(), _self.receiver.recv_async().await?))
+        async fn __recv<T: LinkItem>(_self: &LinkReceiver<T>) -> ZFResult<(PortId, Arc<T>)> {
+            let data = _self.receiver.recv_async().await?;
+            _self.received(&data);
+            Ok((_self.id.clone(), data))
         }
 
         Box::pin(__recv(self))
     }
 
+    /// Receives the data available on the link, if any, without waiting.
+    pub fn try_recv(&self) -> Option<Arc<T>> {
+        let data = self.receiver.try_recv().ok()?;
+        self.received(&data);
+        Some(data)
+    }
+
+    fn received(&self, data: &T) {
+        if !data.is_droppable() {
+            let _ = self
+                .undroppable
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+        }
+    }
+
     pub async fn discard(&self) -> ZFResult<()> {
         Ok(())
     }
@@ -49,14 +103,99 @@ impl<T: std::marker::Send + std::marker::Sync> LinkReceiver<T> {
         self.id.clone()
     }
 
+    pub fn priority(&self) -> usize {
+        self.priority
+    }
+
     pub fn is_disconnected(&self) -> bool {
         self.receiver.is_disconnected()
     }
+
+    pub fn len(&self) -> usize {
+        self.receiver.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.receiver.is_empty()
+    }
 }
 
-impl<T> LinkSender<T> {
+impl<T: LinkItem> LinkSender<T> {
+    /// Sends the data on the link, following its queueing policy if the link is full.
+    ///
+    /// A message discarded because of the policy is not an error, it is only accounted for in
+    /// `dropped`. Items that are not droppable, such as control messages, are never discarded:
+    /// the sender waits until there is room in the link for them, whatever the policy.
     pub async fn send(&self, data: Arc<T>) -> ZFResult<()> {
-        Ok(self.sender.send_async(data).await?)
+        if !data.is_droppable() {
+            return self.send_undroppable(data).await;
+        }
+
+        match self.policy {
+            QueueingPolicy::Block => Ok(self.sender.send_async(data).await?),
+            QueueingPolicy::DropNewest => match self.sender.try_send(data) {
+                Ok(()) => Ok(()),
+                Err(flume::TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    Ok(())
+                }
+                Err(flume::TrySendError::Disconnected(_)) => Err(ZFError::Disconnected),
+            },
+            QueueingPolicy::DropOldest => {
+                let mut data = data;
+                loop {
+                    match self.sender.try_send(data) {
+                        Ok(()) => return Ok(()),
+                        Err(flume::TrySendError::Full(d)) => {
+                            data = d;
+                            let receiver = match &self.drop_receiver {
+                                Some(receiver) => receiver,
+                                None => {
+                                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                                    return Ok(());
+                                }
+                            };
+
+                            // The oldest message may not be droppable: as the link cannot be
+                            // peeked, wait for room as the `Block` policy does.
+                            if self.undroppable.load(Ordering::Acquire) > 0 {
+                                return Ok(self.sender.send_async(data).await?);
+                            }
+
+                            // The receiver may have consumed the message in between, in which case
+                            // there is nothing to drop.
+                            if let Ok(oldest) = receiver.try_recv() {
+                                if oldest.is_droppable() {
+                                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                                } else {
+                                    // An undroppable item was sent and reached the head of the
+                                    // link in between: it is delayed, never lost.
+                                    self.sender.send_async(oldest).await?;
+                                }
+                            }
+                        }
+                        Err(flume::TrySendError::Disconnected(_)) => {
+                            return Err(ZFError::Disconnected)
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn send_undroppable(&self, data: Arc<T>) -> ZFResult<()> {
+        // Accounted for before sending, the receiver could otherwise get it first.
+        self.undroppable.fetch_add(1, Ordering::AcqRel);
+        let res = self.sender.send_async(data).await;
+        if res.is_err() {
+            self.undroppable.fetch_sub(1, Ordering::AcqRel);
+        }
+        Ok(res?)
+    }
+
+    /// Returns the number of messages discarded because of the queueing policy.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
     }
 
     pub fn len(&self) -> usize {
@@ -80,8 +219,23 @@ impl<T> LinkSender<T> {
     }
 }
 
-pub fn link<T>(
+pub fn link<T: LinkItem>(
+    capacity: Option<usize>,
+    send_id: PortId,
+    recv_id: PortId,
+) -> (LinkSender<T>, LinkReceiver<T>) {
+    link_with_policy(capacity, QueueingPolicy::Block, 0, send_id, recv_id)
+}
+
+/// Creates a link whose behavior, when full, follows the provided `policy`. The `priority` is
+/// used by the receiving side to favor this link when several have data available.
+///
+/// The `DropOldest` policy keeps a receiver on the sender side, hence the sender will not detect
+/// a disconnection of the receiving side.
+pub fn link_with_policy<T: LinkItem>(
     capacity: Option<usize>,
+    policy: QueueingPolicy,
+    priority: usize,
     send_id: PortId,
     recv_id: PortId,
 ) -> (LinkSender<T>, LinkReceiver<T>) {
@@ -90,14 +244,27 @@ pub fn link<T>(
         Some(cap) => flume::bounded(cap),
     };
 
+    // With a capacity of 0 there is never an "oldest" message to drop: the new one is dropped.
+    let drop_receiver = match (capacity, policy) {
+        (Some(cap), QueueingPolicy::DropOldest) if cap > 0 => Some(receiver.clone()),
+        _ => None,
+    };
+    let undroppable = Arc::new(AtomicUsize::new(0));
+
     (
         LinkSender {
             id: send_id,
             sender,
+            policy,
+            drop_receiver,
+            dropped: Arc::new(AtomicUsize::new(0)),
+            undroppable: undroppable.clone(),
         },
         LinkReceiver {
             id: recv_id,
             receiver,
+            priority,
+            undroppable,
         },
     )
 }
@@ -16,8 +16,10 @@ pub mod link;
 pub mod runners;
 
 use crate::model::connector::ZFConnectorKind;
+use crate::model::dataflow::record::DataFlowRecord;
 use crate::model::link::LinkDescriptor;
-use crate::runtime::dataflow::instance::link::link;
+use crate::model::node::OperatorRecord;
+use crate::runtime::dataflow::instance::link::{link_with_policy, LinkReceiver, LinkSender};
 use crate::runtime::dataflow::instance::runners::connector::{JanuReceiver, JanuSender};
 use crate::runtime::dataflow::instance::runners::operator::{OperatorIO, OperatorRunner};
 use crate::runtime::dataflow::instance::runners::replay::JanuReplay;
@@ -25,25 +27,52 @@ use crate::runtime::dataflow::instance::runners::sink::SinkRunner;
 use crate::runtime::dataflow::instance::runners::source::SourceRunner;
 use crate::runtime::dataflow::instance::runners::{NodeRunner, RunnerKind};
 use crate::runtime::dataflow::Dataflow;
-use crate::runtime::InstanceContext;
+use crate::runtime::metrics::NodeMetrics;
+use crate::runtime::{DrainReport, InstanceContext, NodeState, NodeStatus};
 use crate::{Message, NodeId, PortId, PortType, ZFError, ZFResult};
 use async_std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 use self::runners::RunnerManager;
 
+/// Interval at which the links, or the nodes, are checked when waiting for them to be drained, or
+/// to be running.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 pub struct DataflowInstance {
     pub(crate) context: InstanceContext,
     pub(crate) runners: HashMap<NodeId, NodeRunner>,
     pub(crate) managers: HashMap<NodeId, RunnerManager>,
+    // The nodes sorted in topological order: a node always comes after the nodes feeding it.
+    pub(crate) order: Vec<NodeId>,
+    // A copy of both sides of the links between the nodes, used to check if they are drained.
+    pub(crate) links: Vec<InstanceLink>,
+}
+
+/// A link between two nodes of the instance.
+pub(crate) struct InstanceLink {
+    pub(crate) descriptor: LinkDescriptor,
+    pub(crate) sender: LinkSender<Message>,
+    pub(crate) receiver: LinkReceiver<Message>,
+}
+
+impl InstanceLink {
+    fn connects(&self, descriptor: &LinkDescriptor) -> bool {
+        self.descriptor.from == descriptor.from && self.descriptor.to == descriptor.to
+    }
 }
 
+/// Creates the links between `nodes`, the links in `existing` that connect the same ports are
+/// kept along with the messages they hold.
 fn create_links(
     nodes: &[NodeId],
     links: &[LinkDescriptor],
-) -> ZFResult<HashMap<NodeId, OperatorIO>> {
+    existing: &[InstanceLink],
+) -> ZFResult<(HashMap<NodeId, OperatorIO>, Vec<InstanceLink>)> {
     let mut io: HashMap<NodeId, OperatorIO> = HashMap::with_capacity(nodes.len());
+    let mut instance_links: Vec<InstanceLink> = Vec::with_capacity(links.len());
 
     for link_desc in links {
         let upstream_node = link_desc.from.node.clone();
@@ -55,11 +84,21 @@ fn create_links(
             continue;
         }
 
-        let (tx, rx) = link::<Message>(
-            None,
-            link_desc.from.output.clone(),
-            link_desc.to.input.clone(),
-        );
+        let (tx, rx) = match existing.iter().find(|link| link.connects(link_desc)) {
+            Some(link) => (link.sender.clone(), link.receiver.clone()),
+            None => link_with_policy::<Message>(
+                link_desc.size,
+                link_desc.get_queueing_policy()?,
+                link_desc.get_priority(),
+                link_desc.from.output.clone(),
+                link_desc.to.input.clone(),
+            ),
+        };
+        instance_links.push(InstanceLink {
+            descriptor: link_desc.clone(),
+            sender: tx.clone(),
+            receiver: rx.clone(),
+        });
 
         match io.get_mut(&upstream_node) {
             Some(operator_io) => operator_io.add_output(tx),
@@ -80,7 +119,169 @@ fn create_links(
         }
     }
 
-    Ok(io)
+    Ok((io, instance_links))
+}
+
+/// Sorts the nodes in topological order, following Kahn's algorithm.
+///
+/// Links whose ends are not both in `nodes` are ignored. Should the links contain a cycle, the
+/// nodes that are part of it are appended at the end, in lexicographical order.
+fn topological_sort(nodes: &[NodeId], links: &[LinkDescriptor]) -> Vec<NodeId> {
+    let mut in_degrees: HashMap<NodeId, usize> =
+        nodes.iter().map(|node| (node.clone(), 0)).collect();
+    let mut downstreams: HashMap<NodeId, Vec<NodeId>> = HashMap::with_capacity(nodes.len());
+
+    for link_desc in links {
+        if !in_degrees.contains_key(&link_desc.from.node)
+            || !in_degrees.contains_key(&link_desc.to.node)
+        {
+            continue;
+        }
+
+        downstreams
+            .entry(link_desc.from.node.clone())
+            .or_default()
+            .push(link_desc.to.node.clone());
+        if let Some(in_degree) = in_degrees.get_mut(&link_desc.to.node) {
+            *in_degree += 1;
+        }
+    }
+
+    let mut roots: Vec<NodeId> = in_degrees
+        .iter()
+        .filter(|(_, in_degree)| **in_degree == 0)
+        .map(|(node, _)| node.clone())
+        .collect();
+    roots.sort();
+    let mut queue: VecDeque<NodeId> = roots.into();
+
+    let mut order: Vec<NodeId> = Vec::with_capacity(nodes.len());
+    while let Some(node) = queue.pop_front() {
+        if let Some(children) = downstreams.get(&node) {
+            for child in children {
+                if let Some(in_degree) = in_degrees.get_mut(child) {
+                    *in_degree -= 1;
+                    if *in_degree == 0 {
+                        queue.push_back(child.clone());
+                    }
+                }
+            }
+        }
+        order.push(node);
+    }
+
+    if order.len() < nodes.len() {
+        let sorted: HashSet<NodeId> = order.iter().cloned().collect();
+        let mut remaining: Vec<NodeId> = nodes
+            .iter()
+            .filter(|node| !sorted.contains(*node))
+            .cloned()
+            .collect();
+        remaining.sort();
+        log::warn!(
+            "The links between the nodes {:?} form a cycle, their order is arbitrary.",
+            remaining
+        );
+        order.append(&mut remaining);
+    }
+
+    order
+}
+
+/// Creates the runners of the nodes of `dataflow`, the links of each node must be in `links`.
+fn try_create_runners(
+    context: &InstanceContext,
+    dataflow: Dataflow,
+    links: &mut HashMap<NodeId, OperatorIO>,
+) -> ZFResult<HashMap<NodeId, NodeRunner>> {
+    let mut runners: HashMap<NodeId, NodeRunner> = HashMap::with_capacity(
+        dataflow.sources.len()
+            + dataflow.operators.len()
+            + dataflow.sinks.len()
+            + dataflow.connectors.len(),
+    );
+
+    for (id, source) in dataflow.sources.into_iter() {
+        let io = links.remove(&id).ok_or_else(|| {
+            ZFError::IOError(format!(
+                "Links for Source < {} > were not created.",
+                &source.id
+            ))
+        })?;
+        let restart_policy = source.restart_policy.clone();
+        runners.insert(
+            id,
+            NodeRunner::new(
+                Arc::new(SourceRunner::try_new(context.clone(), source, io)?),
+                context.clone(),
+            )
+            .with_restart_policy(restart_policy),
+        );
+    }
+
+    for (id, operator) in dataflow.operators.into_iter() {
+        let io = links.remove(&operator.id).ok_or_else(|| {
+            ZFError::IOError(format!(
+                "Links for Operator < {} > were not created.",
+                &operator.id
+            ))
+        })?;
+        let restart_policy = operator.restart_policy.clone();
+        runners.insert(
+            id,
+            NodeRunner::new(
+                Arc::new(OperatorRunner::try_new(context.clone(), operator, io)?),
+                context.clone(),
+            )
+            .with_restart_policy(restart_policy),
+        );
+    }
+
+    for (id, sink) in dataflow.sinks.into_iter() {
+        let io = links.remove(&id).ok_or_else(|| {
+            ZFError::IOError(format!("Links for Sink < {} > were not created.", &sink.id))
+        })?;
+        let restart_policy = sink.restart_policy.clone();
+        runners.insert(
+            id,
+            NodeRunner::new(
+                Arc::new(SinkRunner::try_new(context.clone(), sink, io)?),
+                context.clone(),
+            )
+            .with_restart_policy(restart_policy),
+        );
+    }
+
+    for (id, connector) in dataflow.connectors.into_iter() {
+        let io = links.remove(&id).ok_or_else(|| {
+            ZFError::IOError(format!(
+                "Links for Connector < {} > were not created.",
+                &connector.id
+            ))
+        })?;
+        match connector.kind {
+            ZFConnectorKind::Sender => {
+                runners.insert(
+                    id,
+                    NodeRunner::new(
+                        Arc::new(JanuSender::try_new(context.clone(), connector, io)?),
+                        context.clone(),
+                    ),
+                );
+            }
+            ZFConnectorKind::Receiver => {
+                runners.insert(
+                    id,
+                    NodeRunner::new(
+                        Arc::new(JanuReceiver::try_new(context.clone(), connector, io)?),
+                        context.clone(),
+                    ),
+                );
+            }
+        }
+    }
+
+    Ok(runners)
 }
 
 impl DataflowInstance {
@@ -99,95 +300,24 @@ impl DataflowInstance {
         node_ids.append(&mut dataflow.sinks.keys().cloned().collect::<Vec<_>>());
         node_ids.append(&mut dataflow.connectors.keys().cloned().collect::<Vec<_>>());
 
-        let mut links = create_links(&node_ids, &dataflow.links)?;
+        let (mut io, links) = create_links(&node_ids, &dataflow.links, &[])?;
+        let order = topological_sort(&node_ids, &dataflow.links);
 
         let context = InstanceContext {
-            flow_id: dataflow.flow_id,
+            flow_id: dataflow.flow_id.clone(),
             instance_id: dataflow.uuid,
-            runtime: dataflow.context,
+            runtime: dataflow.context.clone(),
         };
 
         // The links were created, we can generate the Runners.
-        let mut runners: HashMap<NodeId, NodeRunner> = HashMap::with_capacity(node_ids.len());
-
-        for (id, source) in dataflow.sources.into_iter() {
-            let io = links.remove(&id).ok_or_else(|| {
-                ZFError::IOError(format!(
-                    "Links for Source < {} > were not created.",
-                    &source.id
-                ))
-            })?;
-            runners.insert(
-                id,
-                NodeRunner::new(
-                    Arc::new(SourceRunner::try_new(context.clone(), source, io)?),
-                    context.clone(),
-                ),
-            );
-        }
-
-        for (id, operator) in dataflow.operators.into_iter() {
-            let io = links.remove(&operator.id).ok_or_else(|| {
-                ZFError::IOError(format!(
-                    "Links for Operator < {} > were not created.",
-                    &operator.id
-                ))
-            })?;
-            runners.insert(
-                id,
-                NodeRunner::new(
-                    Arc::new(OperatorRunner::try_new(context.clone(), operator, io)?),
-                    context.clone(),
-                ),
-            );
-        }
-
-        for (id, sink) in dataflow.sinks.into_iter() {
-            let io = links.remove(&id).ok_or_else(|| {
-                ZFError::IOError(format!("Links for Sink < {} > were not created.", &sink.id))
-            })?;
-            runners.insert(
-                id,
-                NodeRunner::new(
-                    Arc::new(SinkRunner::try_new(context.clone(), sink, io)?),
-                    context.clone(),
-                ),
-            );
-        }
-
-        for (id, connector) in dataflow.connectors.into_iter() {
-            let io = links.remove(&id).ok_or_else(|| {
-                ZFError::IOError(format!(
-                    "Links for Connector < {} > were not created.",
-                    &connector.id
-                ))
-            })?;
-            match connector.kind {
-                ZFConnectorKind::Sender => {
-                    runners.insert(
-                        id,
-                        NodeRunner::new(
-                            Arc::new(JanuSender::try_new(context.clone(), connector, io)?),
-                            context.clone(),
-                        ),
-                    );
-                }
-                ZFConnectorKind::Receiver => {
-                    runners.insert(
-                        id,
-                        NodeRunner::new(
-                            Arc::new(JanuReceiver::try_new(context.clone(), connector, io)?),
-                            context.clone(),
-                        ),
-                    );
-                }
-            }
-        }
+        let runners = try_create_runners(&context, dataflow, &mut io)?;
 
         Ok(Self {
             context,
             runners,
             managers: HashMap::with_capacity(node_ids.len()),
+            order,
+            links,
         })
     }
 
@@ -242,20 +372,124 @@ impl DataflowInstance {
             .collect()
     }
 
+    /// Returns the nodes sorted in topological order: a node always comes after the nodes that
+    /// feed it.
+    pub fn get_topological_order(&self) -> Vec<NodeId> {
+        self.order.clone()
+    }
+
+    /// Starts the sources.
+    ///
+    /// Note that this should be called only after `start_nodes` has returned successfully,
+    /// otherwise data may be lost.
     pub async fn start_sources(&mut self) -> ZFResult<()> {
-        Err(ZFError::Unimplemented)
+        for id in self.get_sources_in_order() {
+            self.start_node(&id).await?;
+        }
+        Ok(())
     }
 
+    /// Starts the operators, sinks and connectors, in reverse topological order: a node is
+    /// started only after all the nodes it feeds.
     pub async fn start_nodes(&mut self) -> ZFResult<()> {
-        Err(ZFError::Unimplemented)
+        let mut nodes = self.get_nodes_in_order();
+        nodes.reverse();
+        for id in nodes {
+            self.start_node(&id).await?;
+        }
+        Ok(())
     }
 
+    /// Stops the sources.
     pub async fn stop_sources(&mut self) -> ZFResult<()> {
-        Err(ZFError::Unimplemented)
+        for id in self.get_sources_in_order() {
+            if self.managers.contains_key(&id) {
+                self.stop_node(&id).await?;
+            }
+        }
+        Ok(())
     }
 
-    pub async fn stop_nodes(&mut self) -> ZFResult<()> {
-        Err(ZFError::Unimplemented)
+    /// Stops the operators, sinks and connectors, in topological order.
+    ///
+    /// Before being stopped, a node consumes the messages that are still in its input links,
+    /// unless `drain_timeout` has expired. It is then stopped once its current iteration
+    /// completes, and only killed if `drain_timeout` expires first. The messages left in the
+    /// links of a node when it is stopped are accounted for in the returned `DrainReport`.
+    /// Note that this should be called after `stop_sources` has returned successfully, otherwise
+    /// the links may never be drained.
+    pub async fn stop_nodes(&mut self, drain_timeout: Duration) -> ZFResult<DrainReport> {
+        let deadline = Instant::now() + drain_timeout;
+        let mut report = DrainReport::default();
+
+        for id in self.get_nodes_in_order() {
+            if self.managers.contains_key(&id) {
+                report.timed_out |= !self.wait_for_drain(&id, deadline).await;
+                report.timed_out |= !self.finish_node(&id, deadline).await?;
+
+                let dropped = self.get_queued_messages(&id);
+                if dropped > 0 {
+                    log::warn!(
+                        "[Instance: {}] Node < {} > stopped with {} message(s) in its links.",
+                        self.context.instance_id,
+                        id,
+                        dropped
+                    );
+                    report.dropped.insert(id, dropped);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn get_sources_in_order(&self) -> Vec<NodeId> {
+        let sources = self.get_sources();
+        self.order
+            .iter()
+            .filter(|id| sources.contains(id))
+            .cloned()
+            .collect()
+    }
+
+    fn get_nodes_in_order(&self) -> Vec<NodeId> {
+        let sources = self.get_sources();
+        self.order
+            .iter()
+            .filter(|id| self.runners.contains_key(*id) && !sources.contains(id))
+            .cloned()
+            .collect()
+    }
+
+    // Waits until the input links of the node are empty and, for the sender of a reliable link,
+    // until the messages it sent are acknowledged. A node that is not running will never consume
+    // its inputs, hence we stop waiting as soon as it is not.
+    // Returns `false` if the deadline expired before the links were drained.
+    async fn wait_for_drain(&self, node_id: &NodeId, deadline: Instant) -> bool {
+        while (self.get_queued_messages(node_id) > 0 || self.get_unacked(node_id).await > 0)
+            && self.is_node_running(node_id).await.unwrap_or(false)
+        {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            async_std::task::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+        true
+    }
+
+    fn get_queued_messages(&self, node_id: &NodeId) -> usize {
+        self.links
+            .iter()
+            .filter(|link| link.descriptor.to.node == *node_id)
+            .map(|link| link.receiver.len())
+            .sum()
+    }
+
+    async fn get_unacked(&self, node_id: &NodeId) -> usize {
+        match self.runners.get(node_id) {
+            Some(runner) => runner.get_unacked().await,
+            None => 0,
+        }
     }
 
     pub async fn is_node_running(&self, node_id: &NodeId) -> ZFResult<bool> {
@@ -269,6 +503,77 @@ impl DataflowInstance {
         }
     }
 
+    pub async fn change_mode(&self, node_id: &NodeId, mode: usize) -> ZFResult<()> {
+        let runner = self
+            .runners
+            .get(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        runner.change_mode(mode).await
+    }
+
+    /// Checkpoints the state of the node, see `runtime::checkpoint`. Returns `false` if the node
+    /// did not save anything.
+    pub async fn checkpoint_node(&self, node_id: &NodeId) -> ZFResult<bool> {
+        let runner = self
+            .runners
+            .get(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        runner.checkpoint().await
+    }
+
+    pub async fn get_node_state(&self, node_id: &NodeId) -> ZFResult<NodeState> {
+        let runner = self
+            .runners
+            .get(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        Ok(runner.get_state().await)
+    }
+
+    pub async fn get_node_status(&self, node_id: &NodeId) -> ZFResult<NodeStatus> {
+        let runner = self
+            .runners
+            .get(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        let lifecycle = runner.get_lifecycle().await;
+
+        Ok(NodeStatus {
+            id: node_id.clone(),
+            state: lifecycle.state,
+            mode: runner.get_mode(),
+            restarts: lifecycle.restarts,
+            last_error: lifecycle.last_error,
+        })
+    }
+
+    /// Returns a snapshot of the metrics of the nodes, sorted by node id.
+    pub fn get_metrics(&self) -> Vec<NodeMetrics> {
+        let mut metrics: Vec<NodeMetrics> = self
+            .runners
+            .iter()
+            .map(|(id, runner)| {
+                runner.get_metrics().snapshot(
+                    self.context.flow_id.clone(),
+                    self.context.instance_id,
+                    id.clone(),
+                )
+            })
+            .collect();
+        metrics.sort_by(|a, b| a.node_id.cmp(&b.node_id));
+        metrics
+    }
+
+    /// Returns the nodes in a terminal error state, i.e. whose restart policy does not allow any
+    /// more restart, along with the error that put them in it.
+    pub async fn get_failed_nodes(&self) -> Vec<(NodeId, ZFError)> {
+        let mut failed_nodes = Vec::new();
+        for (node_id, runner) in self.runners.iter() {
+            if let NodeState::Error(error) = runner.get_state().await {
+                failed_nodes.push((node_id.clone(), error));
+            }
+        }
+        failed_nodes
+    }
+
     pub async fn start_node(&mut self, node_id: &NodeId) -> ZFResult<()> {
         let runner = self
             .runners
@@ -288,6 +593,352 @@ impl DataflowInstance {
         Ok(manager.await?)
     }
 
+    // Stops the node once its current iteration completes, see
+    // `RunnerManager::stop_after_iteration`, or kills it once `deadline` has expired.
+    // Returns `false` if the node had to be killed.
+    async fn finish_node(&mut self, node_id: &NodeId, deadline: Instant) -> ZFResult<bool> {
+        let mut manager = self
+            .managers
+            .remove(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        manager.stop_after_iteration().await?;
+
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        if let Ok(result) = async_std::future::timeout(timeout, &mut manager).await {
+            result?;
+            return Ok(true);
+        }
+
+        log::warn!(
+            "[Instance: {}] Node < {} > did not complete its iteration in time, killing it.",
+            self.context.instance_id,
+            node_id
+        );
+        manager.kill().await?;
+        manager.await?;
+        Ok(false)
+    }
+
+    /// Reloads the implementation of the operator `record.id` from `record`, keeping its links.
+    ///
+    /// The implementation is loaded before the operator is stopped: if it cannot be loaded, the
+    /// operator keeps running the current one. The operator is stopped once its current
+    /// iteration completes, keeping the tokens it holds, or killed if it does not complete
+    /// before `drain_timeout`. It is then reloaded, see `Runner::reload`, and started again if it
+    /// was running. The ports of the operator cannot change; should the new implementation fail
+    /// to initialize or to restore the state, the operator is started again with its previous
+    /// implementation.
+    pub async fn reload_operator(
+        &mut self,
+        record: OperatorRecord,
+        transfer_state: bool,
+        drain_timeout: Duration,
+    ) -> ZFResult<()> {
+        let runner = self
+            .runners
+            .get(&record.id)
+            .ok_or_else(|| ZFError::NodeNotFound(record.id.clone()))?
+            .clone();
+        if runner.get_kind() != RunnerKind::Operator {
+            return Err(ZFError::Unsupported);
+        }
+
+        let inputs: HashMap<PortId, PortType> = record
+            .inputs
+            .iter()
+            .map(|desc| (desc.port_id.clone(), desc.port_type.clone()))
+            .collect();
+        let outputs: HashMap<PortId, PortType> = record
+            .outputs
+            .iter()
+            .map(|desc| (desc.port_id.clone(), desc.port_type.clone()))
+            .collect();
+        if inputs != runner.get_inputs() || outputs != runner.get_outputs() {
+            return Err(ZFError::InvalidData(format!(
+                "The ports of Operator < {} > cannot change when it is reloaded.",
+                record.id
+            )));
+        }
+
+        let (record, implementation) = self
+            .context
+            .runtime
+            .loader
+            .load_operator_implementation(record)?;
+
+        let was_running = self.managers.contains_key(&record.id);
+        if was_running {
+            self.finish_node(&record.id, Instant::now() + drain_timeout)
+                .await?;
+        }
+
+        let reloaded = runner
+            .reload(implementation, &record.configuration, transfer_state)
+            .await;
+        if let Err(e) = &reloaded {
+            log::error!(
+                "Operator < {} > could not be reloaded, keeping its previous implementation: {:?}",
+                record.id,
+                e
+            );
+        }
+
+        if was_running {
+            self.start_node(&record.id).await?;
+        }
+
+        reloaded
+    }
+
+    /// Rewires the instance to follow `record`, an updated record of its flow, e.g. after a node
+    /// was migrated to another runtime, see `DataFlowRecord::try_migrate_node`.
+    ///
+    /// The nodes no longer mapped to this runtime are finalized and removed, the nodes newly
+    /// mapped to it are created and the nodes whose links change are wired again. The links that
+    /// connect the same ports in `record` are kept, along with the messages they hold: the nodes
+    /// whose links do not change are left untouched and can keep running.
+    ///
+    /// ## Error
+    ///
+    /// This function will return error if a node that is removed, or whose links change, is
+    /// running: it must be stopped first, after its links were drained. See `drain_links` and
+    /// `pause_node`.
+    pub async fn try_rewire(&mut self, mut record: DataFlowRecord) -> ZFResult<()> {
+        let node_ids = self.get_record_nodes(&record);
+        let links: Vec<LinkDescriptor> = record
+            .links
+            .iter()
+            .filter(|link| node_ids.contains(&link.from.node) && node_ids.contains(&link.to.node))
+            .cloned()
+            .collect();
+
+        let removed: Vec<NodeId> = self
+            .runners
+            .keys()
+            .filter(|id| !node_ids.contains(id))
+            .cloned()
+            .collect();
+        let mut rewired: HashSet<NodeId> = removed.iter().cloned().collect();
+        for link in self
+            .links
+            .iter()
+            .filter(|link| !links.iter().any(|desc| link.connects(desc)))
+        {
+            rewired.insert(link.descriptor.from.node.clone());
+            rewired.insert(link.descriptor.to.node.clone());
+        }
+        for desc in links
+            .iter()
+            .filter(|desc| !self.links.iter().any(|link| link.connects(desc)))
+        {
+            rewired.insert(desc.from.node.clone());
+            rewired.insert(desc.to.node.clone());
+        }
+        if rewired.iter().any(|id| self.managers.contains_key(id)) {
+            return Err(ZFError::InvalidState);
+        }
+
+        for id in removed {
+            if let Some(runner) = self.runners.remove(&id) {
+                runner.clean().await?;
+            }
+        }
+
+        let (mut io, links) = create_links(&node_ids, &record.links, &self.links)?;
+        self.links = links;
+        self.order = topological_sort(&node_ids, &record.links);
+
+        // The nodes newly mapped to this runtime are created along with their links.
+        record
+            .sources
+            .retain(|source| !self.runners.contains_key(&source.id));
+        record
+            .operators
+            .retain(|operator| !self.runners.contains_key(&operator.id));
+        record
+            .sinks
+            .retain(|sink| !self.runners.contains_key(&sink.id));
+        record
+            .connectors
+            .retain(|connector| !self.runners.contains_key(&connector.id));
+        record.links.clear();
+        let dataflow = Dataflow::try_new(self.context.runtime.clone(), record)?;
+        let added = try_create_runners(&self.context, dataflow, &mut io)?;
+
+        for (id, runner) in self.runners.iter().filter(|(id, _)| rewired.contains(*id)) {
+            runner.take_input_links().await;
+            runner.take_output_links().await;
+            if let Some(io) = io.remove(id) {
+                let (inputs, outputs) = io.take();
+                for (_, input) in inputs {
+                    runner.add_input(input).await?;
+                }
+                for output in outputs.into_iter().flat_map(|(_, outputs)| outputs) {
+                    runner.add_output(output).await?;
+                }
+            }
+        }
+        self.runners.extend(added);
+
+        Ok(())
+    }
+
+    // Returns the nodes and connectors of `record` that are mapped to this runtime.
+    fn get_record_nodes(&self, record: &DataFlowRecord) -> Vec<NodeId> {
+        let runtime = &self.context.runtime.runtime_name;
+        record
+            .get_node_runtimes()
+            .into_iter()
+            .filter(|(_, node_runtime)| node_runtime == runtime)
+            .map(|(id, _)| id)
+            .collect()
+    }
+
+    /// Stops the node if it is running, once its current iteration completes: an operator keeps
+    /// the tokens it holds and resumes from them when started again. The node is killed if it
+    /// does not complete its iteration before `drain_timeout`. Returns `true` if it was running.
+    pub async fn pause_node(
+        &mut self,
+        node_id: &NodeId,
+        drain_timeout: Duration,
+    ) -> ZFResult<bool> {
+        self.runners
+            .get(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        if !self.managers.contains_key(node_id) {
+            return Ok(false);
+        }
+
+        self.finish_node(node_id, Instant::now() + drain_timeout)
+            .await?;
+        Ok(true)
+    }
+
+    /// Waits until the links of the instance that connect the same ports as one of `links` are
+    /// empty, the other links are ignored. When a link leads to the sender of a reliable link,
+    /// the messages it sent must also be acknowledged.
+    ///
+    /// ## Error
+    ///
+    /// This function will return a `DrainTimeout` error, with the node at the receiving end of the
+    /// link, if a link is still not empty once `drain_timeout` has expired.
+    pub async fn drain_links(
+        &self,
+        links: &[LinkDescriptor],
+        drain_timeout: Duration,
+    ) -> ZFResult<()> {
+        let deadline = Instant::now() + drain_timeout;
+        for link in self
+            .links
+            .iter()
+            .filter(|link| links.iter().any(|desc| link.connects(desc)))
+        {
+            while !link.receiver.is_empty() || self.get_unacked(&link.descriptor.to.node).await > 0
+            {
+                if Instant::now() >= deadline {
+                    return Err(ZFError::DrainTimeout(link.descriptor.to.node.clone()));
+                }
+                async_std::task::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Takes the messages left in the input links of the node, along with the input they were
+    /// sent to, so that they are forwarded to the node once it is migrated. Their data is
+    /// serialized. See `forward_node_inputs`.
+    ///
+    /// ## Error
+    ///
+    /// This function will return error if the node is running.
+    pub fn take_node_inputs(&self, node_id: &NodeId) -> ZFResult<Vec<(PortId, Message)>> {
+        self.runners
+            .get(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        if self.managers.contains_key(node_id) {
+            return Err(ZFError::InvalidState);
+        }
+
+        let mut messages = Vec::new();
+        for link in self
+            .links
+            .iter()
+            .filter(|link| link.descriptor.to.node == *node_id)
+        {
+            while let Some(message) = link.receiver.try_recv() {
+                messages.push((
+                    link.descriptor.to.input.clone(),
+                    message.try_to_serialized()?,
+                ));
+            }
+        }
+        Ok(messages)
+    }
+
+    /// Sends `messages`, as taken by `take_node_inputs` on the runtime the node was migrated
+    /// from, on the input links of the node. It must be called before the nodes feeding it are
+    /// started, so that these messages come first.
+    pub async fn forward_node_inputs(
+        &self,
+        node_id: &NodeId,
+        messages: Vec<(PortId, Message)>,
+    ) -> ZFResult<()> {
+        for (input, message) in messages {
+            let link = self
+                .links
+                .iter()
+                .find(|link| {
+                    link.descriptor.to.node == *node_id && link.descriptor.to.input == input
+                })
+                .ok_or_else(|| ZFError::PortNotConnected((node_id.clone(), input.clone())))?;
+            link.sender.send(Arc::new(message)).await?;
+        }
+        Ok(())
+    }
+
+    /// Waits until the nodes are running, e.g. until the receivers have subscribed to their
+    /// resource, so that the nodes sending them messages can be started.
+    ///
+    /// ## Error
+    ///
+    /// This function will return an `Uncompleted` error if a node is still not running once
+    /// `timeout` has expired.
+    pub async fn wait_for_nodes(&self, nodes: &[NodeId], timeout: Duration) -> ZFResult<()> {
+        let deadline = Instant::now() + timeout;
+        for id in nodes {
+            while !self.is_node_running(id).await? {
+                if Instant::now() >= deadline {
+                    return Err(ZFError::Uncompleted(format!(
+                        "Node < {} > is not running.",
+                        id
+                    )));
+                }
+                async_std::task::sleep(DRAIN_POLL_INTERVAL).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the state of the node, see `Node::save_state`. Returns `None` if the node did not
+    /// save anything.
+    pub async fn save_node_state(&self, node_id: &NodeId) -> ZFResult<Option<Vec<u8>>> {
+        let runner = self
+            .runners
+            .get(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        runner.save_state().await
+    }
+
+    /// Restores the state of the node from `state`, as saved by `save_node_state` on the runtime
+    /// it was migrated from. It must be called before the node is first started.
+    pub async fn transfer_node_state(&self, node_id: &NodeId, state: &[u8]) -> ZFResult<()> {
+        let runner = self
+            .runners
+            .get(node_id)
+            .ok_or_else(|| ZFError::NodeNotFound(node_id.clone()))?;
+        runner.transfer_state(state).await
+    }
+
     pub async fn start_recording(&self, node_id: &NodeId) -> ZFResult<String> {
         let manager = self
             .managers
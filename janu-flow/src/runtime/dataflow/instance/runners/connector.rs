@@ -12,18 +12,55 @@
 //   open
 //
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::async_std::sync::{Arc, Mutex};
 use crate::model::connector::ZFConnectorRecord;
+use crate::model::link::Reliability;
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
 use crate::runtime::dataflow::instance::runners::operator::OperatorIO;
-use crate::runtime::dataflow::instance::runners::{Runner, RunnerKind};
-use crate::runtime::message::Message;
+use crate::runtime::dataflow::instance::runners::{until_stopped, Runner, RunnerKind};
+use crate::runtime::dataflow::node::OperatorImplementation;
+use crate::runtime::message::{Ack, Message, SequencedMessage};
+use crate::runtime::metrics::RunnerMetrics;
 use crate::runtime::InstanceContext;
-use crate::{NodeId, PortId, PortType, ZFError, ZFResult};
+use crate::{Configuration, NodeId, PortId, PortType, ZFError, ZFResult};
 use async_trait::async_trait;
 use futures::prelude::*;
+use futures_lite::future::FutureExt as _;
+use uuid::Uuid;
+use janu_util::sync::Signal;
+
+/// Number of messages the sender of a reliable link keeps until they are acknowledged, it stops
+/// reading its input link while it keeps that many.
+pub const RETRANSMISSION_BUFFER_SIZE: usize = 1024;
+
+/// How long the sender of a reliable link waits for an acknowledgement before sending again the
+/// messages it keeps.
+pub const RETRANSMISSION_TIMEOUT: Duration = Duration::from_millis(100);
+
+// The receiver of a reliable link publishes its acknowledgements under the resource of the link.
+fn ack_resource(resource: &str) -> String {
+    format!("{}/ack", resource)
+}
+
+// The messages sent by a `JanuSender`, kept across the restarts of its run loop so that the
+// numbering goes on.
+#[derive(Default)]
+struct SenderState {
+    next: u64,
+    // The serialized messages not acknowledged yet, by sequence number. Only on reliable links.
+    unacked: VecDeque<(u64, Vec<u8>)>,
+}
+
+enum SenderEvent {
+    Message(ZFResult<(PortId, Arc<Message>)>),
+    Ack(Option<ZFResult<Ack>>),
+    Timeout,
+    Stop,
+}
 
 #[derive(Clone)]
 pub struct JanuSender {
@@ -32,6 +69,11 @@ pub struct JanuSender {
     pub(crate) record: ZFConnectorRecord,
     pub(crate) is_running: Arc<Mutex<bool>>,
     pub(crate) link: Arc<Mutex<Option<LinkReceiver<Message>>>>,
+    pub(crate) metrics: Arc<RunnerMetrics>,
+    uuid: Uuid,
+    state: Arc<Mutex<SenderState>>,
+    // The number of messages in `state` not acknowledged yet, readable while the sender runs.
+    unacked: Arc<AtomicUsize>,
 }
 
 impl JanuSender {
@@ -48,6 +90,7 @@ impl JanuSender {
                 &port_id, &record.id
             ))
         })?;
+        let metrics = Arc::new(RunnerMetrics::new(&[port_id]).with_link_stats());
 
         Ok(Self {
             id: record.id.clone(),
@@ -55,6 +98,10 @@ impl JanuSender {
             record,
             is_running: Arc::new(Mutex::new(false)),
             link: Arc::new(Mutex::new(Some(link))),
+            metrics,
+            uuid: Uuid::new_v4(),
+            state: Arc::new(Mutex::new(SenderState::default())),
+            unacked: Arc::new(AtomicUsize::new(0)),
         })
     }
 
@@ -62,25 +109,177 @@ impl JanuSender {
         *self.is_running.lock().await = true;
     }
 
-    async fn iteration(&self) -> ZFResult<()> {
+    // Returns once the link is disconnected or, while waiting for a message, once `stop` is
+    // triggered.
+    async fn iteration(&self, stop: &Signal) -> ZFResult<()> {
         log::debug!("JanuSender - {} - Started", self.record.resource);
+        let mut state = self.state.lock().await;
         if let Some(link) = &*self.link.lock().await {
-            while let Ok((_, message)) = link.recv().await {
-                log::debug!("JanuSender IN <= {:?} ", message);
+            if self.record.reliability.is_reliable() {
+                return self.iteration_reliable(link, &mut state, stop).await;
+            }
 
-                let serialized = message.serialize_bincode()?;
-                log::debug!("JanuSender - {}=>{:?} ", self.record.resource, serialized);
-                self.context
-                    .runtime
-                    .session
-                    .put(&self.record.resource, serialized)
-                    .await?;
+            while let Some(Ok((port_id, message))) = until_stopped(stop, link.recv()).await {
+                // Nothing is kept, the receiver can only detect the gaps.
+                let sequence_number = state.next;
+                state.next += 1;
+                let serialized =
+                    self.serialize(&port_id, link, &message, sequence_number, sequence_number)?;
+                self.put(serialized).await?;
+                self.metrics.inc_messages_out();
             }
         } else {
             return Err(ZFError::Disconnected);
         }
         Ok(())
     }
+
+    // Sends the messages of `link` and keeps them until they are acknowledged. They are all sent
+    // again (go-back-N) when the receiver reports a gap or when none is acknowledged in time.
+    async fn iteration_reliable(
+        &self,
+        link: &LinkReceiver<Message>,
+        state: &mut SenderState,
+        stop: &Signal,
+    ) -> ZFResult<()> {
+        let mut acks = self
+            .context
+            .runtime
+            .session
+            .subscribe(&ack_resource(&self.record.resource))
+            .await?;
+        let mut last_progress = Instant::now();
+        let mut disconnected = false;
+
+        loop {
+            self.unacked.store(state.unacked.len(), Ordering::Relaxed);
+
+            // The link was disconnected, as for the unreliable links, once all the messages sent
+            // are acknowledged.
+            if disconnected && state.unacked.is_empty() {
+                return Ok(());
+            }
+
+            let is_full = state.unacked.len() >= RETRANSMISSION_BUFFER_SIZE;
+            let timeout = (!state.unacked.is_empty())
+                .then(|| RETRANSMISSION_TIMEOUT.saturating_sub(last_progress.elapsed()));
+
+            let message = async {
+                if is_full || disconnected {
+                    future::pending().await
+                } else {
+                    SenderEvent::Message(link.recv().await)
+                }
+            };
+            let ack = async {
+                SenderEvent::Ack(acks.receiver().next().await.map(|sample| {
+                    bincode::deserialize::<Ack>(&sample.value.payload.contiguous())
+                        .map_err(|_| ZFError::DeseralizationError)
+                }))
+            };
+            let retransmission = async {
+                match timeout {
+                    Some(timeout) => {
+                        async_std::task::sleep(timeout).await;
+                        SenderEvent::Timeout
+                    }
+                    None => future::pending().await,
+                }
+            };
+
+            // The messages not acknowledged yet are kept in `state` for the next run.
+            let stopped = async {
+                stop.wait().await;
+                SenderEvent::Stop
+            };
+
+            match message.race(ack).race(retransmission).race(stopped).await {
+                SenderEvent::Message(Ok((port_id, message))) => {
+                    let sequence_number = state.next;
+                    state.next += 1;
+                    let first = state
+                        .unacked
+                        .front()
+                        .map_or(sequence_number, |(first, _)| *first);
+                    let serialized =
+                        self.serialize(&port_id, link, &message, sequence_number, first)?;
+                    self.put(serialized.clone()).await?;
+                    self.metrics.inc_messages_out();
+                    if state.unacked.is_empty() {
+                        last_progress = Instant::now();
+                    }
+                    state.unacked.push_back((sequence_number, serialized));
+                }
+                SenderEvent::Message(Err(_)) => disconnected = true,
+                SenderEvent::Ack(Some(ack)) => {
+                    let ack = ack?;
+                    // An acknowledgement for a previous sender of the link.
+                    if ack.sender != self.uuid {
+                        continue;
+                    }
+
+                    let unacked = state.unacked.len();
+                    state
+                        .unacked
+                        .retain(|(sequence_number, _)| *sequence_number >= ack.next);
+                    if state.unacked.len() < unacked {
+                        last_progress = Instant::now();
+                    }
+                    if ack.retransmit {
+                        self.retransmit(state).await?;
+                        last_progress = Instant::now();
+                    }
+                }
+                SenderEvent::Ack(None) => return Err(ZFError::Disconnected),
+                SenderEvent::Timeout => {
+                    log::debug!(
+                        "JanuSender - {} - {} message(s) not acknowledged in time",
+                        self.record.resource,
+                        state.unacked.len()
+                    );
+                    self.retransmit(state).await?;
+                    last_progress = Instant::now();
+                }
+                SenderEvent::Stop => return Ok(()),
+            }
+        }
+    }
+
+    // Serializes `message`, received on `link`, along with its sequence number.
+    fn serialize(
+        &self,
+        port_id: &PortId,
+        link: &LinkReceiver<Message>,
+        message: &Message,
+        sequence_number: u64,
+        first: u64,
+    ) -> ZFResult<Vec<u8>> {
+        log::debug!("JanuSender IN <= {:?} ", message);
+        self.metrics.inc_messages_in();
+        self.metrics.observe_queue_depth(port_id, link.len());
+
+        let serialized = message.serialize_bincode_sequenced(self.uuid, sequence_number, first)?;
+        log::debug!("JanuSender - {}=>{:?} ", self.record.resource, serialized);
+        Ok(serialized)
+    }
+
+    async fn put(&self, serialized: Vec<u8>) -> ZFResult<()> {
+        Ok(self
+            .context
+            .runtime
+            .session
+            .put(&self.record.resource, serialized)
+            .await?)
+    }
+
+    // Sends again all the messages not acknowledged yet.
+    async fn retransmit(&self, state: &SenderState) -> ZFResult<()> {
+        for (_, serialized) in &state.unacked {
+            self.put(serialized.clone()).await?;
+        }
+        self.metrics.inc_retransmissions(state.unacked.len() as u64);
+        Ok(())
+    }
 }
 #[async_trait]
 impl Runner for JanuSender {
@@ -90,7 +289,7 @@ impl Runner for JanuSender {
     fn get_kind(&self) -> RunnerKind {
         RunnerKind::Connector
     }
-    async fn run(&self) -> ZFResult<()> {
+    async fn run(&self, stop: Signal) -> ZFResult<()> {
         self.start().await;
 
         // Looping on iteration, each iteration is a single
@@ -98,7 +297,12 @@ impl Runner for JanuSender {
         // stops and returns the error to the caller (the RunnerManager)
 
         loop {
-            match self.iteration().await {
+            if stop.is_triggered() {
+                log::debug!("[JanuSender: {}] Stopped in between two messages.", self.id);
+                break Ok(());
+            }
+
+            match self.iteration(&stop).await {
                 Ok(_) => {
                     log::debug!("[JanuSender: {}] iteration ok", self.id);
                     continue;
@@ -153,6 +357,10 @@ impl Runner for JanuSender {
         HashMap::with_capacity(0)
     }
 
+    async fn take_output_links(&self) -> HashMap<PortId, Vec<LinkSender<Message>>> {
+        HashMap::with_capacity(0)
+    }
+
     async fn start_recording(&self) -> ZFResult<String> {
         Err(ZFError::Unsupported)
     }
@@ -173,11 +381,163 @@ impl Runner for JanuSender {
         *self.is_running.lock().await = false;
     }
 
+    async fn change_mode(&self, _mode: usize) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    fn get_mode(&self) -> Option<usize> {
+        None
+    }
+
+    fn get_metrics(&self) -> Arc<RunnerMetrics> {
+        self.metrics.clone()
+    }
+
+    async fn reload(
+        &self,
+        _implementation: OperatorImplementation,
+        _configuration: &Option<Configuration>,
+        _transfer_state: bool,
+    ) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    async fn checkpoint(&self) -> ZFResult<bool> {
+        Ok(false)
+    }
+
+    async fn restore(&self) -> ZFResult<bool> {
+        Ok(false)
+    }
+
+    async fn save_state(&self) -> ZFResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    async fn restore_state(&self, _state: &[u8]) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    async fn get_unacked(&self) -> usize {
+        self.unacked.load(Ordering::Relaxed)
+    }
+
     async fn clean(&self) -> ZFResult<()> {
         Ok(())
     }
 }
 
+/// What the receiving end of a link knows of the messages sent on it.
+#[derive(Default)]
+pub(crate) struct ReceiverState {
+    sender: Option<Uuid>,
+    // The previous senders of the link, what they still send is discarded.
+    replaced: HashSet<Uuid>,
+    // Sequence number of the next message to deliver in order.
+    next: u64,
+    // One past the highest sequence number received.
+    end: u64,
+    // The messages received after a gap on a reliable link, `None` once delivered.
+    pending: BTreeMap<u64, Option<Message>>,
+    // `next` when a retransmission was last requested, it is requested once per gap.
+    requested: Option<u64>,
+}
+
+/// What to do after receiving a message.
+#[derive(Default)]
+pub(crate) struct Reception {
+    /// The messages to deliver, in that order.
+    pub(crate) messages: Vec<Message>,
+    /// The acknowledgement to send back, only on reliable links.
+    pub(crate) ack: Option<Ack>,
+    pub(crate) gaps: u64,
+    pub(crate) duplicates: u64,
+    /// Received after a later one on a link that is not reliable, or from a sender that was
+    /// replaced: the message is discarded.
+    pub(crate) late: u64,
+}
+
+impl ReceiverState {
+    pub(crate) fn receive(
+        &mut self,
+        reliability: Reliability,
+        received: SequencedMessage,
+    ) -> Reception {
+        let mut reception = Reception::default();
+        let sequence_number = received.sequence_number;
+
+        // A retransmission of a sender that was replaced, it must not reset the numbering.
+        if self.replaced.contains(&received.sender) {
+            reception.late = 1;
+            return reception;
+        }
+
+        // The numbering starts over with a new sender, e.g. after a migration.
+        if self.sender != Some(received.sender) {
+            let mut replaced = std::mem::take(&mut self.replaced);
+            replaced.extend(self.sender);
+            *self = Self {
+                sender: Some(received.sender),
+                replaced,
+                next: received.first,
+                end: received.first,
+                ..Self::default()
+            };
+        }
+
+        if !reliability.is_reliable() {
+            if sequence_number < self.next {
+                reception.late = 1;
+            } else {
+                reception.gaps = sequence_number - self.next;
+                self.next = sequence_number + 1;
+                self.end = self.next;
+                reception.messages.push(received.message);
+            }
+            return reception;
+        }
+
+        // The messages before `first` were acknowledged, they will not be sent again.
+        if received.first > self.next {
+            self.pending = self.pending.split_off(&received.first);
+            self.next = received.first;
+            self.end = self.end.max(self.next);
+        }
+
+        if sequence_number < self.next || self.pending.contains_key(&sequence_number) {
+            reception.duplicates = 1;
+            if reliability == Reliability::AtLeastOnce {
+                reception.messages.push(received.message);
+            }
+        } else {
+            reception.gaps = sequence_number.saturating_sub(self.end);
+            self.end = self.end.max(sequence_number + 1);
+            if reliability == Reliability::AtLeastOnce {
+                reception.messages.push(received.message);
+                self.pending.insert(sequence_number, None);
+            } else {
+                self.pending.insert(sequence_number, Some(received.message));
+            }
+
+            while let Some(message) = self.pending.remove(&self.next) {
+                reception.messages.extend(message);
+                self.next += 1;
+            }
+        }
+
+        let retransmit = self.next < self.end && self.requested != Some(self.next);
+        if retransmit {
+            self.requested = Some(self.next);
+        }
+        reception.ack = Some(Ack {
+            sender: received.sender,
+            next: self.next,
+            retransmit,
+        });
+        reception
+    }
+}
+
 #[derive(Clone)]
 pub struct JanuReceiver {
     pub(crate) id: NodeId,
@@ -185,6 +545,8 @@ pub struct JanuReceiver {
     pub(crate) record: ZFConnectorRecord,
     pub(crate) is_running: Arc<Mutex<bool>>,
     pub(crate) link: Arc<Mutex<Option<LinkSender<Message>>>>,
+    pub(crate) metrics: Arc<RunnerMetrics>,
+    state: Arc<Mutex<ReceiverState>>,
 }
 
 impl JanuReceiver {
@@ -219,6 +581,8 @@ impl JanuReceiver {
             record,
             is_running: Arc::new(Mutex::new(false)),
             link: Arc::new(Mutex::new(link)),
+            metrics: Arc::new(RunnerMetrics::default().with_link_stats()),
+            state: Arc::new(Mutex::new(ReceiverState::default())),
         })
     }
 
@@ -236,25 +600,60 @@ impl Runner for JanuReceiver {
         RunnerKind::Connector
     }
 
-    async fn run(&self) -> ZFResult<()> {
-        self.start().await;
-
+    async fn run(&self, stop: Signal) -> ZFResult<()> {
         let res = {
             log::debug!("JanuReceiver - {} - Started", self.record.resource);
             if let Some(link) = &*self.link.lock().await {
+                let mut state = self.state.lock().await;
                 let mut subscriber = self
                     .context
                     .runtime
                     .session
                     .subscribe(&self.record.resource)
                     .await?;
-
-                while let Some(msg) = subscriber.receiver().next().await {
-                    log::debug!("JanuSender - {}<={:?} ", self.record.resource, msg);
-                    let de: Message = bincode::deserialize(&msg.value.payload.contiguous())
-                        .map_err(|_| ZFError::DeseralizationError)?;
-                    log::debug!("JanuSender - OUT =>{:?} ", de);
-                    link.send(Arc::new(de)).await?;
+                // Running only once subscribed: nothing sent before would be received.
+                self.start().await;
+                let ack_resource = ack_resource(&self.record.resource);
+
+                loop {
+                    let msg = match until_stopped(&stop, subscriber.receiver().next()).await {
+                        Some(Some(msg)) => msg,
+                        Some(None) => break,
+                        None => {
+                            log::debug!(
+                                "[JanuReceiver: {}] Stopped in between two messages.",
+                                self.id
+                            );
+                            self.stop().await;
+                            return Ok(());
+                        }
+                    };
+                    log::debug!("JanuReceiver - {}<={:?} ", self.record.resource, msg);
+                    self.metrics.inc_messages_in();
+                    let de: SequencedMessage =
+                        bincode::deserialize(&msg.value.payload.contiguous())
+                            .map_err(|_| ZFError::DeseralizationError)?;
+
+                    let reception = state.receive(self.record.reliability, de);
+                    self.metrics.inc_gaps(reception.gaps);
+                    self.metrics.inc_duplicates(reception.duplicates);
+                    self.metrics.inc_late(reception.late);
+                    for message in reception.messages {
+                        log::debug!("JanuReceiver - OUT =>{:?} ", message);
+                        link.send(Arc::new(message)).await?;
+                        self.metrics.inc_messages_out();
+                    }
+
+                    // Acknowledged once delivered.
+                    if let Some(ack) = reception.ack {
+                        let serialized =
+                            bincode::serialize(&ack).map_err(|_| ZFError::SerializationError)?;
+                        self.context
+                            .runtime
+                            .session
+                            .put(&ack_resource, serialized)
+                            .await?;
+                    }
                 }
             }
 
@@ -300,6 +699,16 @@ impl Runner for JanuReceiver {
         HashMap::with_capacity(0)
     }
 
+    async fn take_output_links(&self) -> HashMap<PortId, Vec<LinkSender<Message>>> {
+        let mut link_guard = self.link.lock().await;
+        if let Some(link) = link_guard.take() {
+            let mut outputs = HashMap::with_capacity(1);
+            outputs.insert(self.record.link_id.port_id.clone(), vec![link]);
+            return outputs;
+        }
+        HashMap::with_capacity(0)
+    }
+
     async fn clean(&self) -> ZFResult<()> {
         Ok(())
     }
@@ -323,4 +732,49 @@ impl Runner for JanuReceiver {
     async fn stop(&self) {
         *self.is_running.lock().await = false;
     }
+
+    async fn change_mode(&self, _mode: usize) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    fn get_mode(&self) -> Option<usize> {
+        None
+    }
+
+    fn get_metrics(&self) -> Arc<RunnerMetrics> {
+        self.metrics.clone()
+    }
+
+    async fn reload(
+        &self,
+        _implementation: OperatorImplementation,
+        _configuration: &Option<Configuration>,
+        _transfer_state: bool,
+    ) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    async fn checkpoint(&self) -> ZFResult<bool> {
+        Ok(false)
+    }
+
+    async fn restore(&self) -> ZFResult<bool> {
+        Ok(false)
+    }
+
+    async fn save_state(&self) -> ZFResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    async fn restore_state(&self, _state: &[u8]) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    async fn get_unacked(&self) -> usize {
+        0
+    }
 }
+
+#[cfg(test)]
+#[path = "./tests/connector_test.rs"]
+mod tests;
@@ -19,20 +19,26 @@ pub mod sink;
 pub mod source;
 
 use crate::async_std::prelude::*;
-use crate::async_std::sync::Arc;
+use crate::async_std::sync::{Arc, Mutex};
 use crate::async_std::task::JoinHandle;
 
+use crate::model::node::RestartPolicy;
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
+use crate::runtime::dataflow::node::OperatorImplementation;
 use crate::runtime::message::Message;
-use crate::runtime::InstanceContext;
+use crate::runtime::metrics::RunnerMetrics;
+use crate::runtime::{InstanceContext, NodeState};
 use crate::types::{NodeId, ZFResult};
-use crate::{PortId, PortType, ZFError};
+use crate::{Configuration, PortId, PortType, ZFError};
 use async_trait::async_trait;
+use futures::future;
 use futures_lite::future::FutureExt;
 use std::collections::HashMap;
 use std::ops::Deref;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 use janu_util::sync::Signal;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -45,6 +51,7 @@ pub enum RunnerKind {
 
 pub struct RunnerManager {
     stopper: Signal,
+    iteration_stopper: Signal,
     handler: JoinHandle<ZFResult<()>>,
     runner: Arc<dyn Runner>,
     ctx: InstanceContext,
@@ -53,12 +60,14 @@ pub struct RunnerManager {
 impl RunnerManager {
     pub fn new(
         stopper: Signal,
+        iteration_stopper: Signal,
         handler: JoinHandle<ZFResult<()>>,
         runner: Arc<dyn Runner>,
         ctx: InstanceContext,
     ) -> Self {
         Self {
             stopper,
+            iteration_stopper,
             handler,
             runner,
             ctx,
@@ -73,6 +82,16 @@ impl RunnerManager {
         Ok(())
     }
 
+    /// Stops the node once its current iteration completes, where `kill` interrupts it. The
+    /// messages the node received and did not process yet are kept for its next start.
+    pub async fn stop_after_iteration(&self) -> ZFResult<()> {
+        if self.runner.is_recording().await {
+            self.runner.stop_recording().await?;
+        }
+        self.iteration_stopper.trigger();
+        Ok(())
+    }
+
     pub fn get_handler(&self) -> &JoinHandle<ZFResult<()>> {
         &self.handler
     }
@@ -113,7 +132,12 @@ pub enum RunAction {
 
 #[async_trait]
 pub trait Runner: Send + Sync {
-    async fn run(&self) -> ZFResult<()>;
+    /// Runs the node until it fails or until `stop` is triggered.
+    ///
+    /// `stop` is only honoured in between two iterations, or while the node waits for its
+    /// inputs: an iteration that started always completes. The inputs received and not processed
+    /// yet are kept for the next run.
+    async fn run(&self, stop: Signal) -> ZFResult<()>;
     async fn add_input(&self, input: LinkReceiver<Message>) -> ZFResult<()>;
 
     async fn add_output(&self, output: LinkSender<Message>) -> ZFResult<()>;
@@ -132,6 +156,8 @@ pub trait Runner: Send + Sync {
 
     async fn take_input_links(&self) -> HashMap<PortId, LinkReceiver<Message>>;
 
+    async fn take_output_links(&self) -> HashMap<PortId, Vec<LinkSender<Message>>>;
+
     async fn start_recording(&self) -> ZFResult<String>;
     async fn stop_recording(&self) -> ZFResult<String>;
 
@@ -140,22 +166,118 @@ pub trait Runner: Send + Sync {
     async fn is_running(&self) -> bool;
 
     async fn stop(&self);
+
+    async fn change_mode(&self, mode: usize) -> ZFResult<()>;
+
+    fn get_mode(&self) -> Option<usize>;
+
+    fn get_metrics(&self) -> Arc<RunnerMetrics>;
+
+    /// Replaces the implementation of the node, keeping its links, while it is not running.
+    ///
+    /// The new implementation is initialized with `configuration` and, if `transfer_state` is
+    /// set, restores the state saved by the previous one. Only then is the previous
+    /// implementation finalized and replaced: on error, the node keeps its implementation and its
+    /// state. The tokens the node held when it was stopped are kept. Only operators can be
+    /// reloaded, and not from the library they already run: opening it again, from the same path
+    /// or once overwritten in place, gives back the library already loaded.
+    async fn reload(
+        &self,
+        implementation: OperatorImplementation,
+        configuration: &Option<Configuration>,
+        transfer_state: bool,
+    ) -> ZFResult<()>;
+
+    /// Checkpoints the state of the node, see `runtime::checkpoint`. Returns `false` if the node
+    /// did not save anything, connectors never do.
+    async fn checkpoint(&self) -> ZFResult<bool>;
+
+    /// Restores the state of the node from its latest checkpoint. Returns `false` if there is
+    /// none.
+    async fn restore(&self) -> ZFResult<bool>;
+
+    /// Saves the state of the node, see `Node::save_state`. Connectors have none.
+    async fn save_state(&self) -> ZFResult<Option<Vec<u8>>>;
+
+    /// Restores the state of the node from `state`, as returned by `save_state`.
+    async fn restore_state(&self, state: &[u8]) -> ZFResult<()>;
+
+    /// Returns the number of messages sent and not acknowledged yet. Only the senders of the
+    /// reliable links keep any, see `JanuSender`.
+    async fn get_unacked(&self) -> usize;
+}
+
+/// The lifecycle of a node, as tracked by its `NodeRunner`.
+///
+/// The number of restarts is reset each time the node is started, and once it ran without failing
+/// for long enough, see `RestartPolicy::get_healthy_run`. The last error is kept.
+#[derive(Clone, Debug)]
+pub struct NodeLifecycle {
+    pub state: NodeState,
+    pub restarts: usize,
+    pub last_error: Option<ZFError>,
+}
+
+impl Default for NodeLifecycle {
+    fn default() -> Self {
+        Self {
+            state: NodeState::Stopped,
+            restarts: 0,
+            last_error: None,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct NodeRunner {
     inner: Arc<dyn Runner>,
     ctx: InstanceContext,
+    restart_policy: RestartPolicy,
+    lifecycle: Arc<Mutex<NodeLifecycle>>,
+    // Whether the state was restored from its latest checkpoint, it is only when the node is
+    // first started.
+    restored: Arc<AtomicBool>,
 }
 
 impl NodeRunner {
     pub fn new(inner: Arc<dyn Runner>, ctx: InstanceContext) -> Self {
-        Self { inner, ctx }
+        Self {
+            inner,
+            ctx,
+            restart_policy: RestartPolicy::default(),
+            lifecycle: Arc::new(Mutex::new(NodeLifecycle::default())),
+            restored: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    pub async fn get_lifecycle(&self) -> NodeLifecycle {
+        self.lifecycle.lock().await.clone()
+    }
+
+    pub async fn get_state(&self) -> NodeState {
+        self.lifecycle.lock().await.state.clone()
+    }
+
+    pub fn with_restart_policy(mut self, restart_policy: RestartPolicy) -> Self {
+        self.restart_policy = restart_policy;
+        self
+    }
+
+    pub fn get_restart_policy(&self) -> &RestartPolicy {
+        &self.restart_policy
     }
 
-    fn run_stoppable(&self, signal: Signal) -> ZFResult<()> {
-        async fn run(runner: &NodeRunner) -> RunAction {
-            match runner.run().await {
+    /// Restores the state of the node from `state` in place of its latest checkpoint, it must be
+    /// called before the node is first started.
+    pub async fn transfer_state(&self, state: &[u8]) -> ZFResult<()> {
+        self.inner.restore_state(state).await?;
+        self.restored.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    fn run_stoppable(&self, signal: Signal, iteration_signal: Signal) -> ZFResult<()> {
+        async fn run(runner: &NodeRunner, iteration_signal: Signal) -> RunAction {
+            match runner.run(iteration_signal).await {
                 Ok(_) => RunAction::Stop,
                 Err(e) => RunAction::RestartRun(Some(e)),
             }
@@ -165,16 +287,119 @@ impl NodeRunner {
             signal.wait().await;
             RunAction::Stop
         }
+
+        async fn backoff(delay: Duration) -> RunAction {
+            async_std::task::sleep(delay).await;
+            RunAction::RestartRun(None)
+        }
+
+        // Never completes, checkpoints the state every `interval` if there is one.
+        async fn checkpoints(runner: &NodeRunner) -> RunAction {
+            let interval = runner
+                .ctx
+                .runtime
+                .checkpoints
+                .as_ref()
+                .and_then(|checkpoints| checkpoints.get_interval());
+            match interval {
+                Some(interval) => loop {
+                    async_std::task::sleep(interval).await;
+                    if let Err(e) = runner.checkpoint().await {
+                        log::error!("[Node: {}] Checkpoint failed: {:?}", runner.get_id(), e);
+                    }
+                },
+                None => future::pending().await,
+            }
+        }
+
         async_std::task::block_on(async move {
+            if !self.restored.swap(true, Ordering::AcqRel) {
+                match self.restore().await {
+                    Ok(true) => log::info!(
+                        "[Node: {}] State restored from its latest checkpoint.",
+                        self.get_id()
+                    ),
+                    Ok(false) => (),
+                    Err(e) => log::error!(
+                        "[Node: {}] Unable to restore the latest checkpoint: {:?}",
+                        self.get_id(),
+                        e
+                    ),
+                }
+            }
+
+            {
+                let mut lifecycle = self.lifecycle.lock().await;
+                lifecycle.state = NodeState::Running;
+                lifecycle.restarts = 0;
+            }
+
             loop {
+                let started = Instant::now();
                 let cloned_signal = signal.clone();
-                match stop(cloned_signal).race(run(self)).await {
+                match stop(cloned_signal)
+                    .race(run(self, iteration_signal.clone()))
+                    .race(checkpoints(self))
+                    .await
+                {
                     RunAction::RestartRun(e) => {
+                        let e = e.unwrap_or(ZFError::GenericError);
+                        self.get_metrics().inc_errors();
+                        let mut lifecycle = self.lifecycle.lock().await;
+                        lifecycle.last_error = Some(e.clone());
+                        if let Some(healthy_run) = self.restart_policy.get_healthy_run() {
+                            if started.elapsed() >= healthy_run {
+                                lifecycle.restarts = 0;
+                            }
+                        }
+
+                        let delay = match self.restart_policy.next_restart(lifecycle.restarts) {
+                            Some(delay) => delay,
+                            None => {
+                                log::error!(
+                                    "[Node: {}] The run loop exited with {:?}, restart policy < {} > does not allow any more restart ({} so far), giving up.",
+                                    self.get_id(),
+                                    e,
+                                    self.restart_policy,
+                                    lifecycle.restarts
+                                );
+                                lifecycle.state = NodeState::Error(e);
+                                drop(lifecycle);
+                                self.stop().await;
+                                return Ok(());
+                            }
+                        };
+
+                        lifecycle.restarts += 1;
+                        self.get_metrics().inc_restarts();
+                        let restarts = lifecycle.restarts;
+                        drop(lifecycle);
+
                         log::error!(
-                            "[Node: {}] The run loop exited with {:?}, restarting…",
+                            "[Node: {}] The run loop exited with {:?}, restarting in {:?} (restart #{})…",
                             self.get_id(),
-                            e
+                            e,
+                            delay,
+                            restarts
                         );
+
+                        // The stop signal must be honoured while waiting before a restart.
+                        if !delay.is_zero() {
+                            let cloned_signal = signal.clone();
+                            if let RunAction::Stop = stop(cloned_signal)
+                                .race(stop(iteration_signal.clone()))
+                                .race(backoff(delay))
+                                .await
+                            {
+                                log::trace!(
+                                    "[Node: {}] Received kill command while waiting to restart",
+                                    self.get_id()
+                                );
+                                self.stop().await;
+                                self.lifecycle.lock().await.state = NodeState::Stopped;
+                                return Ok(());
+                            }
+                        }
                     }
                     RunAction::Stop => {
                         log::trace!(
@@ -182,20 +407,37 @@ impl NodeRunner {
                             self.get_id()
                         );
                         self.stop().await;
+                        if self.ctx.runtime.checkpoints.is_some() {
+                            if let Err(e) = self.checkpoint().await {
+                                log::error!("[Node: {}] Checkpoint failed: {:?}", self.get_id(), e);
+                            }
+                        }
+                        self.lifecycle.lock().await.state = NodeState::Stopped;
                         return Ok(());
                     }
                 }
             }
         })
     }
+
     pub fn start(&self) -> RunnerManager {
         let signal = Signal::new();
+        let iteration_signal = Signal::new();
         let cloned_self = self.clone();
         let cloned_signal = signal.clone();
-
-        let h = async_std::task::spawn_blocking(move || cloned_self.run_stoppable(cloned_signal));
-
-        RunnerManager::new(signal, h, self.inner.clone(), self.ctx.clone())
+        let cloned_iteration_signal = iteration_signal.clone();
+
+        let h = async_std::task::spawn_blocking(move || {
+            cloned_self.run_stoppable(cloned_signal, cloned_iteration_signal)
+        });
+
+        RunnerManager::new(
+            signal,
+            iteration_signal,
+            h,
+            self.inner.clone(),
+            self.ctx.clone(),
+        )
     }
 }
 
@@ -207,6 +449,17 @@ impl Deref for NodeRunner {
     }
 }
 
+/// Awaits `future` unless `stop` is triggered first, in which case `None` is returned. Only
+/// cancel-safe futures can be stopped, such as receiving on a link.
+pub async fn until_stopped<T>(stop: &Signal, future: impl Future<Output = T>) -> Option<T> {
+    async { Some(future.await) }
+        .or(async {
+            stop.wait().await;
+            None
+        })
+        .await
+}
+
 #[macro_export]
 macro_rules! run_input_rules {
     ($node: expr, $tokens : expr, $links : expr, $state: expr, $context: expr) => {
@@ -236,8 +489,11 @@ macro_rules! run_input_rules {
                                 }
                             }
                         }
-                        Message::Control(_) => {
-                            //control message receiver, we should handle it
+                        Message::Control(ctrl) => {
+                            // The node reacts to the control message, forwarding it (if the node
+                            // asks to) is left to the caller as the macro has no access to the
+                            // outputs. An error is returned to the caller, as the runners do.
+                            $node.on_control_message($context, $state, &id, ctrl)?;
                             $links = remaining;
                         }
                     };
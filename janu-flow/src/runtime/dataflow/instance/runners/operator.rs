@@ -12,28 +12,29 @@
 //   open
 //
 
-use crate::async_std::sync::{Arc, Mutex};
+use crate::async_std::sync::{Arc, Mutex, RwLock};
 use crate::model::deadline::E2EDeadlineRecord;
-use crate::model::node::OperatorRecord;
+use crate::model::node::{InputRulePolicy, OperatorRecord};
+use crate::runtime::checkpoint;
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
-use crate::runtime::dataflow::instance::runners::{Runner, RunnerKind};
-use crate::runtime::dataflow::node::OperatorLoaded;
+use crate::runtime::dataflow::instance::runners::{until_stopped, Runner, RunnerKind};
+use crate::runtime::dataflow::node::{OperatorImplementation, OperatorLoaded};
 use crate::runtime::deadline::E2EDeadline;
 use crate::runtime::message::Message;
+use crate::runtime::metrics::RunnerMetrics;
 use crate::runtime::InstanceContext;
 use crate::{
-    Context, DataMessage, LocalDeadlineMiss, NodeId, Operator, PortId, PortType, State, Token,
-    TokenAction, ZFError, ZFResult,
+    Configuration, Context, ControlMessage, DataMessage, LocalDeadlineMiss, NodeId, PortId,
+    PortType, State, Token, TokenAction, ZFError, ZFResult,
 };
 use async_trait::async_trait;
+use futures::future::Either;
 use futures::{future, Future};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::{Duration, Instant};
-
-#[cfg(target_family = "unix")]
-use libloading::os::unix::Library;
-#[cfg(target_family = "windows")]
-use libloading::Library;
+use uhlc::Timestamp;
+use janu_util::sync::Signal;
 
 #[derive(Default)]
 pub struct OperatorIO {
@@ -46,17 +47,39 @@ type LinkRecvFut<'a> = std::pin::Pin<
 >;
 
 impl OperatorIO {
-    fn poll_input(&self, node_id: &NodeId, port_id: &PortId) -> ZFResult<LinkRecvFut> {
-        let rx = self.inputs.get(port_id).ok_or_else(|| {
-            ZFError::IOError(format!(
-                "[Operator: {}] Link < {} > no longer exists.",
-                node_id, port_id
-            ))
-        })?;
-        Ok(rx.recv())
+    // The futures are returned by decreasing priority of their link: as `select_all` polls them
+    // in order, when several links have data available the one with the highest priority wins.
+    fn poll_inputs(&self, node_id: &NodeId, port_ids: &[PortId]) -> ZFResult<Vec<LinkRecvFut>> {
+        let mut receivers = port_ids
+            .iter()
+            .map(|port_id| {
+                self.inputs.get(port_id).ok_or_else(|| {
+                    ZFError::IOError(format!(
+                        "[Operator: {}] Link < {} > no longer exists.",
+                        node_id, port_id
+                    ))
+                })
+            })
+            .collect::<ZFResult<Vec<_>>>()?;
+        receivers.sort_by(|rx_a, rx_b| rx_b.priority().cmp(&rx_a.priority()));
+
+        Ok(receivers.into_iter().map(|rx| rx.recv()).collect())
     }
 }
 
+enum Wakeup {
+    Link(ZFResult<(PortId, Arc<Message>)>),
+    Timers,
+    Stop,
+}
+
+// What an iteration hands over to the next one: the context, the tokens and the data.
+type Carried = (
+    Context,
+    HashMap<PortId, Token>,
+    HashMap<PortId, DataMessage>,
+);
+
 pub type InputsLink = HashMap<PortId, LinkReceiver<Message>>;
 pub type OutputsLinks = HashMap<PortId, Vec<LinkSender<Message>>>;
 
@@ -99,11 +122,45 @@ impl OperatorIO {
     }
 }
 
+/// The latest watermark received on each input of an operator. The operator forwards the
+/// minimum across its inputs, once each of them received one and whenever that minimum advances.
+#[derive(Debug, Default)]
+pub(crate) struct Watermarks {
+    inputs: HashMap<PortId, Timestamp>,
+    forwarded: Option<Timestamp>,
+}
+
+impl Watermarks {
+    /// Records the watermark received on `port_id`, returns the watermark to forward, if any.
+    pub(crate) fn advance<'a>(
+        &mut self,
+        port_id: &PortId,
+        timestamp: Timestamp,
+        inputs: impl Iterator<Item = &'a PortId>,
+    ) -> Option<Timestamp> {
+        let latest = self.inputs.entry(port_id.clone()).or_insert(timestamp);
+        *latest = timestamp.max(*latest);
+
+        let mut minimum: Option<Timestamp> = None;
+        for input in inputs {
+            let watermark = *self.inputs.get(input)?;
+            minimum = Some(minimum.map_or(watermark, |minimum| minimum.min(watermark)));
+        }
+        let minimum = minimum?;
+
+        if self.forwarded.map_or(true, |forwarded| minimum > forwarded) {
+            self.forwarded = Some(minimum);
+            return Some(minimum);
+        }
+        None
+    }
+}
+
 // Do not reorder the fields in this struct.
 // Rust drops fields in a struct in the same order they are declared.
 // Ref: https://doc.rust-lang.org/reference/destructors.html
 // We need the state to be dropped before the operator/lib, otherwise we
-// will have a SIGSEV.
+// will have a SIGSEV. The same holds when the implementation is reloaded, see `reload`.
 #[derive(Clone)]
 pub struct OperatorRunner {
     pub(crate) id: NodeId,
@@ -112,11 +169,16 @@ pub struct OperatorRunner {
     pub(crate) inputs: HashMap<PortId, PortType>,
     pub(crate) outputs: HashMap<PortId, PortType>,
     pub(crate) local_deadline: Option<Duration>,
+    pub(crate) input_rule: Option<InputRulePolicy>,
     pub(crate) end_to_end_deadlines: Vec<E2EDeadlineRecord>,
     pub(crate) is_running: Arc<Mutex<bool>>,
+    pub(crate) mode: Arc<AtomicUsize>,
+    pub(crate) metrics: Arc<RunnerMetrics>,
     pub(crate) state: Arc<Mutex<State>>,
-    pub(crate) operator: Arc<dyn Operator>,
-    pub(crate) _library: Option<Arc<Library>>,
+    pub(crate) implementation: Arc<RwLock<OperatorImplementation>>,
+    // Set when the operator is stopped in between two iterations, the next run resumes from it.
+    pub(crate) suspended: Arc<Mutex<Option<Carried>>>,
+    pub(crate) watermarks: Arc<Mutex<Watermarks>>,
 }
 
 impl OperatorRunner {
@@ -126,6 +188,7 @@ impl OperatorRunner {
         operator_io: OperatorIO,
     ) -> ZFResult<Self> {
         // TODO Check that all ports are used.
+        let metrics = Arc::new(RunnerMetrics::new(operator.inputs.keys()));
         Ok(Self {
             id: operator.id,
             context,
@@ -134,10 +197,17 @@ impl OperatorRunner {
             outputs: operator.outputs,
             state: operator.state,
             is_running: Arc::new(Mutex::new(false)),
-            operator: operator.operator,
-            _library: operator.library,
+            mode: Arc::new(AtomicUsize::new(0)),
+            metrics,
+            implementation: Arc::new(RwLock::new(OperatorImplementation::new(
+                operator.operator,
+                operator.library,
+            ))),
             local_deadline: operator.local_deadline,
+            input_rule: operator.input_rule,
             end_to_end_deadlines: operator.end_to_end_deadlines,
+            suspended: Arc::new(Mutex::new(None)),
+            watermarks: Arc::new(Mutex::new(Watermarks::default())),
         })
     }
 
@@ -145,37 +215,134 @@ impl OperatorRunner {
         *self.is_running.lock().await = true;
     }
 
+    // The requested mode is only applied in between two executions of `run`: an operator never
+    // sees its mode change while it is running.
+    fn apply_mode(&self, context: &mut Context) {
+        let mode = self.mode.load(Ordering::Acquire);
+        if context.mode != mode {
+            log::info!(
+                "[Operator: {}] Switching from mode < {} > to mode < {} >.",
+                self.id,
+                context.mode,
+                mode
+            );
+            context.mode = mode;
+        }
+    }
+
+    // Waits for a message on one of the `links` or, if it comes first, for the next deadline of
+    // the timers of the operator, unless `stop` is triggered in between.
+    async fn wait_inputs_or_timers(
+        &self,
+        context: &Context,
+        links: Vec<LinkRecvFut<'_>>,
+        stop: &Signal,
+    ) -> Wakeup {
+        let links = future::select_all(links);
+        let wakeup = async {
+            match context.timers.next_deadline() {
+                Some(deadline) => {
+                    let timers =
+                        async_std::task::sleep(deadline.saturating_duration_since(Instant::now()));
+                    match future::select(links, Box::pin(timers)).await {
+                        Either::Left(((result, _index, _remaining), _)) => Wakeup::Link(result),
+                        Either::Right(_) => Wakeup::Timers,
+                    }
+                }
+                None => Wakeup::Link(links.await.0),
+            }
+        };
+        until_stopped(stop, wakeup).await.unwrap_or(Wakeup::Stop)
+    }
+
+    async fn forward_control_message(&self, io: &OperatorIO, message: Arc<Message>) {
+        for link_senders in io.outputs.values() {
+            for link_sender in link_senders {
+                if let Err(e) = link_sender.send(message.clone()).await {
+                    log::error!(
+                        "[Operator: {}] Could not forward control message on link < {} >: {:?}",
+                        self.id,
+                        link_sender.id,
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    // Forwards the control message received on `port_id`, a watermark once the minimum across
+    // the inputs advances, see `Watermarks`.
+    async fn forward_received(&self, io: &OperatorIO, port_id: &PortId, message: Arc<Message>) {
+        if let Message::Control(ControlMessage::Watermark(timestamp)) = message.as_ref() {
+            let watermark =
+                self.watermarks
+                    .lock()
+                    .await
+                    .advance(port_id, *timestamp, self.inputs.keys());
+            if let Some(watermark) = watermark {
+                let watermark = Message::Control(ControlMessage::Watermark(watermark));
+                self.forward_control_message(io, Arc::new(watermark)).await;
+            }
+            return;
+        }
+        self.forward_control_message(io, message).await;
+    }
+
+    // Returns early, before the input rule is satisfied, if `stop` is triggered while waiting for
+    // the inputs.
     async fn iteration(
         &self,
         mut context: Context,
         mut tokens: HashMap<PortId, Token>,
         mut data: HashMap<PortId, DataMessage>,
-    ) -> ZFResult<(
-        Context,
-        HashMap<PortId, Token>,
-        HashMap<PortId, DataMessage>,
-    )> {
+        stop: &Signal,
+    ) -> ZFResult<Carried> {
         // Guards are taken at the beginning of each iteration to allow interleaving.
         let io = self.io.lock().await;
         let mut state = self.state.lock().await;
+        let implementation = self.implementation.read().await;
+        let operator = &implementation.operator;
 
-        let mut links = Vec::with_capacity(tokens.len());
+        let mut pending_ports = Vec::with_capacity(tokens.len());
 
         // Only call `recv` on links where the corresponding Token is `Pending`. If a
         // `ReadyToken` has its action set to `Keep` then it will stay as a `ReadyToken` (i.e.
-        // it won’t be resetted later on) and we should not poll data.
+        // it won’t be resetted later on) and we should not poll data — unless the `latest` input
+        // rule policy is used: new data then replaces the kept data.
+        let replace_kept = matches!(self.input_rule, Some(InputRulePolicy::Latest));
         for (port_id, token) in tokens.iter() {
-            if let Token::Pending = token {
-                links.push(io.poll_input(&self.id, port_id)?);
+            if token.is_pending() || replace_kept {
+                pending_ports.push(port_id.clone());
             }
         }
 
         'input_rule: loop {
-            if !links.is_empty() {
-                match future::select_all(links).await {
-                    (Ok((port_id, message)), _index, remaining) => {
+            if !pending_ports.is_empty() {
+                // The futures are recreated at each loop so that the priority of the links is
+                // respected: receiving on a link is cancel-safe, no message is lost.
+                let links = io.poll_inputs(&self.id, &pending_ports)?;
+                match self.wait_inputs_or_timers(&context, links, stop).await {
+                    Wakeup::Stop => return Ok((context, tokens, data)),
+
+                    Wakeup::Timers => {
+                        context.timers.expire(Instant::now());
+                        log::debug!(
+                            "[Operator: {}] Timers expired: {:?}",
+                            self.id,
+                            context.get_expired_timers()
+                        );
+                    }
+
+                    Wakeup::Link(Ok((port_id, message))) => {
+                        pending_ports.retain(|pending_port| *pending_port != port_id);
+
                         match message.as_ref() {
                             Message::Data(data_message) => {
+                                self.metrics.inc_messages_in();
+                                if let Some(link) = io.inputs.get(&port_id) {
+                                    self.metrics.observe_queue_depth(&port_id, link.len());
+                                }
+
                                 // In order to check for E2EDeadlines we first have to update
                                 // the HLC. There is indeed a possibility that the timestamp
                                 // associated with the data is "ahead" of the hlc on this
@@ -229,18 +396,54 @@ impl OperatorRunner {
                                         }
                                     });
 
-                                tokens.insert(port_id, Token::from(data_msg));
+                                tokens.insert(port_id.clone(), Token::from(data_msg));
+                                context.set_ready(&port_id);
+                                if replace_kept {
+                                    pending_ports.push(port_id);
+                                }
                             }
 
-                            Message::Control(_) => {
-                                return Err(ZFError::Unimplemented);
+                            Message::Control(control_message) => {
+                                if let Err(error) = self
+                                    .context
+                                    .runtime
+                                    .hlc
+                                    .update_with_timestamp(&control_message.get_timestamp())
+                                {
+                                    log::error!(
+                                        "[Operator: {}][HLC] Could not update HLC with timestamp {:?}: {:?}",
+                                        self.id,
+                                        control_message.get_timestamp(),
+                                        error
+                                    );
+                                }
+
+                                if let ControlMessage::ChangeMode(mode, targets, _) =
+                                    control_message
+                                {
+                                    if targets.contains(&self.id) {
+                                        self.mode.store(*mode, Ordering::Release);
+                                    }
+                                }
+
+                                if operator.on_control_message(
+                                    &mut context,
+                                    &mut state,
+                                    &port_id,
+                                    control_message,
+                                )? {
+                                    self.forward_received(&io, &port_id, message.clone()).await;
+                                }
+
+                                // A control message does not change the tokens: there is no need
+                                // to check the input rule, we keep on waiting on that link.
+                                pending_ports.push(port_id);
+                                continue 'input_rule;
                             }
                         }
-
-                        links = remaining;
                     }
 
-                    (Err(e), _index, _remaining) => {
+                    Wakeup::Link(Err(e)) => {
                         let err_msg =
                             format!("[Operator: {}] Link returned an error: {:?}", self.id, e);
                         log::error!("{}", &err_msg);
@@ -249,10 +452,16 @@ impl OperatorRunner {
                 }
             }
 
-            match self
-                .operator
-                .input_rule(&mut context, &mut state, &mut tokens)
-            {
+            let input_rule = match &self.input_rule {
+                Some(policy) => policy.apply(&mut state, &mut tokens),
+                None => {
+                    operator
+                        .input_rule(&mut context, &mut state, &mut tokens)
+                        .await
+                }
+            };
+
+            match input_rule {
                 Ok(true) => {
                     log::debug!("[Operator: {}] Input Rule returned < true >.", self.id);
                     break 'input_rule;
@@ -274,9 +483,19 @@ impl OperatorRunner {
             for (port_id, token) in tokens.iter_mut() {
                 if token.should_drop() {
                     *token = Token::Pending;
-                    links.push(io.poll_input(&self.id, port_id)?);
+                    context.set_pending(port_id);
+                    if !pending_ports.contains(port_id) {
+                        pending_ports.push(port_id.clone());
+                    }
                 }
             }
+
+            // All the data is kept, yet the input rule is not satisfied: only new data can change
+            // its decision. New data then replaces the kept data, as with the `latest` input rule
+            // policy, instead of calling the input rule again and again on the same tokens.
+            if pending_ports.is_empty() {
+                pending_ports.extend(tokens.keys().cloned());
+            }
         } // end < 'input_rule: loop >
 
         let mut earliest_source_timestamp = None;
@@ -321,6 +540,7 @@ impl OperatorRunner {
                             );
                             data.insert(port_id.clone(), ready_token.data.clone());
                             *token = Token::Pending;
+                            context.set_pending(port_id);
                         }
                         TokenAction::Keep => {
                             log::debug!("[Operator: {}] Keeping < {} >.", self.id, port_id);
@@ -338,6 +558,7 @@ impl OperatorRunner {
                             log::debug!("[Operator: {}] Dropping < {} >.", self.id, port_id);
                             data.remove(port_id);
                             *token = Token::Pending;
+                            context.set_pending(port_id);
                         }
                     }
                 }
@@ -351,10 +572,13 @@ impl OperatorRunner {
             }
         };
 
+        self.apply_mode(&mut context);
+
         // Running
         let start = Instant::now();
-        let run_outputs = self.operator.run(&mut context, &mut state, &mut data)?;
+        let run_outputs = operator.run(&mut context, &mut state, &mut data).await?;
         let elapsed = start.elapsed();
+        self.metrics.observe_run_duration(elapsed);
 
         log::debug!(
             "[Operator: {}] `run` executed in {} ms",
@@ -381,9 +605,10 @@ impl OperatorRunner {
         }
 
         // Output rules
-        let mut outputs =
-            self.operator
-                .output_rule(&mut context, &mut state, run_outputs, deadline_miss)?;
+        let mut outputs = operator
+            .output_rule(&mut context, &mut state, run_outputs, deadline_miss)
+            .await?;
+        context.timers.clear_expired();
 
         // E2EDeadlines management: add deadlines that start at that operator.
         let now = self.context.runtime.hlc.new_timestamp();
@@ -411,14 +636,15 @@ impl OperatorRunner {
                     let res = link_sender.send(zf_message.clone()).await;
 
                     // TODO: Maybe we want to process somehow the error, not simply log it.
-                    if let Err(e) = res {
-                        log::error!(
+                    match res {
+                        Ok(()) => self.metrics.inc_messages_out(),
+                        Err(e) => log::error!(
                             "[Operator: {}] Could not send output < {} > on link < {} >: {:?}",
                             self.id,
                             port_id,
                             link_sender.id,
                             e
-                        );
+                        ),
                     }
                 }
             }
@@ -474,6 +700,10 @@ impl Runner for OperatorRunner {
         current_inputs
     }
 
+    async fn take_output_links(&self) -> HashMap<PortId, Vec<LinkSender<Message>>> {
+        std::mem::take(&mut self.io.lock().await.outputs)
+    }
+
     async fn start_recording(&self) -> ZFResult<String> {
         Err(ZFError::Unsupported)
     }
@@ -494,28 +724,156 @@ impl Runner for OperatorRunner {
         *self.is_running.lock().await
     }
 
+    async fn change_mode(&self, mode: usize) -> ZFResult<()> {
+        log::debug!("[Operator: {}] Mode < {} > requested.", self.id, mode);
+        self.mode.store(mode, Ordering::Release);
+        Ok(())
+    }
+
+    fn get_mode(&self) -> Option<usize> {
+        Some(self.mode.load(Ordering::Acquire))
+    }
+
+    fn get_metrics(&self) -> Arc<RunnerMetrics> {
+        self.metrics.clone()
+    }
+
+    async fn reload(
+        &self,
+        implementation: OperatorImplementation,
+        configuration: &Option<Configuration>,
+        transfer_state: bool,
+    ) -> ZFResult<()> {
+        if self.is_running().await {
+            return Err(ZFError::InvalidState);
+        }
+
+        let mut current = self.implementation.write().await;
+        if current.shares_library(&implementation) {
+            return Err(ZFError::LoadingError(format!(
+                "The library of Operator < {} > is already loaded, a library loaded again from the same path or overwritten in place cannot be reloaded.",
+                self.id
+            )));
+        }
+        let mut state = self.state.lock().await;
+
+        let saved_state = match transfer_state {
+            true => current.operator.save_state(&mut state)?,
+            false => None,
+        };
+
+        // The new implementation is set up aside: should it fail, the operator keeps the current
+        // implementation and its state, untouched.
+        let mut new_state = implementation.operator.initialize(configuration)?;
+        if let Some(saved_state) = saved_state {
+            if let Err(e) = implementation
+                .operator
+                .restore_state(&mut new_state, &saved_state)
+            {
+                if let Err(e) = implementation.operator.finalize(&mut new_state) {
+                    log::error!(
+                        "[Operator: {}] `finalize` of the new implementation failed: {:?}",
+                        self.id,
+                        e
+                    );
+                }
+                return Err(e);
+            }
+        }
+
+        if let Err(e) = current.operator.finalize(&mut state) {
+            log::error!(
+                "[Operator: {}] `finalize` returned an error before reloading: {:?}",
+                self.id,
+                e
+            );
+        }
+
+        // The previous state is dropped first, then the previous operator and, last, its library.
+        *state = new_state;
+        *current = implementation;
+
+        // The operator resumes with the tokens it holds, under the timers of the new
+        // implementation.
+        if let Some((context, _, _)) = self.suspended.lock().await.as_mut() {
+            context.timers = Default::default();
+            for timer in current.operator.timers() {
+                context.set_timer(timer);
+            }
+        }
+
+        log::info!("[Operator: {}] Implementation reloaded.", self.id);
+        Ok(())
+    }
+
+    async fn checkpoint(&self) -> ZFResult<bool> {
+        let operator = self.implementation.read().await.operator.clone();
+        checkpoint::checkpoint_node(&self.context, &self.id, operator.as_ref(), &self.state).await
+    }
+
+    async fn restore(&self) -> ZFResult<bool> {
+        let operator = self.implementation.read().await.operator.clone();
+        checkpoint::restore_node(&self.context, &self.id, operator.as_ref(), &self.state).await
+    }
+
+    async fn save_state(&self) -> ZFResult<Option<Vec<u8>>> {
+        let operator = self.implementation.read().await.operator.clone();
+        let mut state = self.state.lock().await;
+        operator.save_state(&mut state)
+    }
+
+    async fn restore_state(&self, state: &[u8]) -> ZFResult<()> {
+        let operator = self.implementation.read().await.operator.clone();
+        let mut current = self.state.lock().await;
+        operator.restore_state(&mut current, state)
+    }
+
+    async fn get_unacked(&self) -> usize {
+        0
+    }
+
     async fn clean(&self) -> ZFResult<()> {
         let mut state = self.state.lock().await;
-        self.operator.finalize(&mut state)
+        self.implementation
+            .read()
+            .await
+            .operator
+            .finalize(&mut state)
     }
 
-    async fn run(&self) -> ZFResult<()> {
+    async fn run(&self, stop: Signal) -> ZFResult<()> {
         self.start().await;
 
-        let mut context = Context::default();
-        let mut tokens: HashMap<PortId, Token> = self
-            .inputs
-            .keys()
-            .map(|input_id| (input_id.clone(), Token::Pending))
-            .collect();
-        let mut data: HashMap<PortId, DataMessage> = HashMap::with_capacity(tokens.len());
+        let suspended = self.suspended.lock().await.take();
+        let (mut context, mut tokens, mut data) = match suspended {
+            Some(carried) => carried,
+            None => {
+                let mut context = Context::default();
+                for timer in self.implementation.read().await.operator.timers() {
+                    context.set_timer(timer);
+                }
+                let mut tokens: HashMap<PortId, Token> = HashMap::with_capacity(self.inputs.len());
+                for input_id in self.inputs.keys() {
+                    tokens.insert(input_id.clone(), Token::Pending);
+                    context.set_pending(input_id);
+                }
+                let data: HashMap<PortId, DataMessage> = HashMap::with_capacity(tokens.len());
+                (context, tokens, data)
+            }
+        };
 
         // Looping on iteration, each iteration is a single
         // run of the source, as a run can fail in case of error it
         // stops and returns the error to the caller (the RunnerManager)
 
         loop {
-            match self.iteration(context, tokens, data).await {
+            if stop.is_triggered() {
+                log::debug!("[Operator: {}] Stopped in between two iterations.", self.id);
+                *self.suspended.lock().await = Some((context, tokens, data));
+                break Ok(());
+            }
+
+            match self.iteration(context, tokens, data, &stop).await {
                 Ok((ctx, tkn, d)) => {
                     log::debug!(
                         "[Operator: {}] iteration ok with new context {:?}",
@@ -15,15 +15,18 @@
 use super::{Runner, RunnerKind};
 use crate::async_std::sync::{Arc, Mutex};
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
+use crate::runtime::dataflow::node::OperatorImplementation;
 use crate::runtime::message::Message;
+use crate::runtime::metrics::RunnerMetrics;
 use crate::runtime::InstanceContext;
-use crate::{ControlMessage, NodeId, PortId, PortType, ZFError, ZFResult};
+use crate::{Configuration, ControlMessage, NodeId, PortId, PortType, ZFError, ZFResult};
 use async_std::task;
 use async_trait::async_trait;
 use futures::prelude::*;
 use std::collections::HashMap;
 use janu::query::*;
 use janu::*;
+use janu_util::sync::Signal;
 
 #[derive(Clone)]
 pub struct JanuReplay {
@@ -35,6 +38,7 @@ pub struct JanuReplay {
     pub(crate) resource_name: String,
     pub(crate) is_running: Arc<Mutex<bool>>,
     pub(crate) links: Arc<Mutex<Vec<LinkSender<Message>>>>,
+    pub(crate) metrics: Arc<RunnerMetrics>,
 }
 
 impl JanuReplay {
@@ -65,6 +69,7 @@ impl JanuReplay {
             resource_name,
             is_running: Arc::new(Mutex::new(false)),
             links: Arc::new(Mutex::new(links)),
+            metrics: Arc::new(RunnerMetrics::default()),
         })
     }
 
@@ -75,6 +80,7 @@ impl JanuReplay {
         for link in links.iter() {
             log::debug!("JanuReplay - OUT =>{:?} ", msg);
             link.send(msg.clone()).await?;
+            self.metrics.inc_messages_out();
         }
         Ok(())
     }
@@ -124,6 +130,15 @@ impl Runner for JanuReplay {
         HashMap::with_capacity(0)
     }
 
+    async fn take_output_links(&self) -> HashMap<PortId, Vec<LinkSender<Message>>> {
+        let mut outputs = HashMap::with_capacity(1);
+        outputs.insert(
+            self.port_id.clone(),
+            std::mem::take(&mut *self.links.lock().await),
+        );
+        outputs
+    }
+
     async fn start_recording(&self) -> ZFResult<String> {
         Err(ZFError::Unsupported)
     }
@@ -144,7 +159,48 @@ impl Runner for JanuReplay {
         *self.is_running.lock().await = false;
     }
 
-    async fn run(&self) -> ZFResult<()> {
+    async fn change_mode(&self, _mode: usize) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    fn get_mode(&self) -> Option<usize> {
+        None
+    }
+
+    fn get_metrics(&self) -> Arc<RunnerMetrics> {
+        self.metrics.clone()
+    }
+
+    async fn reload(
+        &self,
+        _implementation: OperatorImplementation,
+        _configuration: &Option<Configuration>,
+        _transfer_state: bool,
+    ) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    async fn checkpoint(&self) -> ZFResult<bool> {
+        Ok(false)
+    }
+
+    async fn restore(&self) -> ZFResult<bool> {
+        Ok(false)
+    }
+
+    async fn save_state(&self) -> ZFResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    async fn restore_state(&self, _state: &[u8]) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    async fn get_unacked(&self) -> usize {
+        0
+    }
+
+    async fn run(&self, stop: Signal) -> ZFResult<()> {
         self.start().await;
 
         let res = {
@@ -178,6 +234,9 @@ impl Runner for JanuReplay {
             log::debug!("JanuReplay - Total samples {} ", zf_data.len());
 
             for de in zf_data {
+                if stop.is_triggered() {
+                    break;
+                }
                 log::debug!("JanuReplay - {}<={:?} ", self.resource_name, de);
                 match &de {
                     Message::Control(ref ctrl_msg) => match &ctrl_msg {
@@ -187,10 +246,10 @@ impl Runner for JanuReplay {
                         }
                         ControlMessage::RecordingStop(ref rs) => {
                             log::debug!("JanuReplay - Recording Stop {:?} ", rs);
-                        } // Commented because Control messages are not yet defined.
-                          // _ => {
-                          //     self.send_data(de).await?;
-                          // }
+                        }
+                        _ => {
+                            self.send_data(de).await?;
+                        }
                     },
                     Message::Data(ref data_msg) => {
                         let data_ts = data_msg.timestamp;
@@ -17,15 +17,22 @@ use std::collections::HashMap;
 use crate::async_std::sync::{Arc, Mutex};
 use crate::model::deadline::E2EDeadlineRecord;
 use crate::model::link::PortDescriptor;
+use crate::runtime::checkpoint;
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
 use crate::runtime::dataflow::instance::runners::operator::OperatorIO;
-use crate::runtime::dataflow::instance::runners::{Runner, RunnerKind};
-use crate::runtime::dataflow::node::SinkLoaded;
+use crate::runtime::dataflow::instance::runners::{until_stopped, Runner, RunnerKind};
+use crate::runtime::dataflow::node::{OperatorImplementation, SinkLoaded};
 use crate::runtime::message::Message;
+use crate::runtime::metrics::RunnerMetrics;
 use crate::runtime::InstanceContext;
 use crate::types::ZFResult;
-use crate::{Context, NodeId, PortId, PortType, Sink, State, ZFError};
+use crate::{
+    Configuration, Context, ControlMessage, NodeId, PortId, PortType, Sink, State, ZFError,
+};
 use async_trait::async_trait;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use janu_util::sync::Signal;
 
 #[cfg(target_family = "unix")]
 use libloading::os::unix::Library;
@@ -45,6 +52,8 @@ pub struct SinkRunner {
     pub(crate) link: Arc<Mutex<Option<LinkReceiver<Message>>>>,
     pub(crate) _end_to_end_deadlines: Vec<E2EDeadlineRecord>, //FIXME
     pub(crate) is_running: Arc<Mutex<bool>>,
+    pub(crate) mode: Arc<AtomicUsize>,
+    pub(crate) metrics: Arc<RunnerMetrics>,
     pub(crate) state: Arc<Mutex<State>>,
     pub(crate) sink: Arc<dyn Sink>,
     pub(crate) _library: Option<Arc<Library>>,
@@ -61,6 +70,8 @@ impl SinkRunner {
             ))
         })?;
 
+        let metrics = Arc::new(RunnerMetrics::new(&[port_id]));
+
         Ok(Self {
             id: sink.id,
             context,
@@ -68,6 +79,8 @@ impl SinkRunner {
             link: Arc::new(Mutex::new(Some(link))),
             _end_to_end_deadlines: sink.end_to_end_deadlines,
             is_running: Arc::new(Mutex::new(false)),
+            mode: Arc::new(AtomicUsize::new(0)),
+            metrics,
             state: sink.state,
             sink: sink.sink,
             _library: sink.library,
@@ -77,14 +90,36 @@ impl SinkRunner {
     async fn start(&self) {
         *self.is_running.lock().await = true;
     }
-    async fn iteration(&self, mut context: Context) -> ZFResult<Context> {
+
+    // The requested mode is only applied in between two executions of `run`.
+    fn apply_mode(&self, context: &mut Context) {
+        let mode = self.mode.load(Ordering::Acquire);
+        if context.mode != mode {
+            log::info!(
+                "[Sink: {}] Switching from mode < {} > to mode < {} >.",
+                self.id,
+                context.mode,
+                mode
+            );
+            context.mode = mode;
+        }
+    }
+
+    // Returns early, without running the Sink, if `stop` is triggered while waiting for an input.
+    async fn iteration(&self, mut context: Context, stop: &Signal) -> ZFResult<Context> {
         // Guards are taken at the beginning of each iteration to allow interleaving.
         if let Some(link) = &*self.link.lock().await {
             let mut state = self.state.lock().await;
 
-            let (port_id, message) = link.recv().await?;
+            let (port_id, message) = match until_stopped(stop, link.recv()).await {
+                Some(received) => received?,
+                None => return Ok(context),
+            };
             let input = match message.as_ref() {
                 Message::Data(data_message) => {
+                    self.metrics.inc_messages_in();
+                    self.metrics.observe_queue_depth(&port_id, link.len());
+
                     if let Err(error) = self
                         .context
                         .runtime
@@ -113,10 +148,42 @@ impl SinkRunner {
                     input
                 }
 
-                Message::Control(_) => return Err(ZFError::Unimplemented),
+                Message::Control(control_message) => {
+                    if let Err(error) = self
+                        .context
+                        .runtime
+                        .hlc
+                        .update_with_timestamp(&control_message.get_timestamp())
+                    {
+                        log::error!(
+                            "[Sink: {}][HLC] Could not update HLC with timestamp {:?}: {:?}",
+                            self.id,
+                            control_message.get_timestamp(),
+                            error
+                        );
+                    }
+
+                    if let ControlMessage::ChangeMode(mode, targets, _) = control_message {
+                        if targets.contains(&self.id) {
+                            self.mode.store(*mode, Ordering::Release);
+                        }
+                    }
+
+                    // A Sink has no output, there is nothing to forward.
+                    self.sink.on_control_message(
+                        &mut context,
+                        &mut state,
+                        &port_id,
+                        control_message,
+                    )?;
+                    return Ok(context);
+                }
             };
 
+            self.apply_mode(&mut context);
+            let start = Instant::now();
             self.sink.run(&mut context, &mut state, input).await?;
+            self.metrics.observe_run_duration(start.elapsed());
         }
         Ok(context)
     }
@@ -169,6 +236,10 @@ impl Runner for SinkRunner {
         HashMap::with_capacity(0)
     }
 
+    async fn take_output_links(&self) -> HashMap<PortId, Vec<LinkSender<Message>>> {
+        HashMap::with_capacity(0)
+    }
+
     async fn start_recording(&self) -> ZFResult<String> {
         Err(ZFError::Unsupported)
     }
@@ -189,7 +260,52 @@ impl Runner for SinkRunner {
         *self.is_running.lock().await = false;
     }
 
-    async fn run(&self) -> ZFResult<()> {
+    async fn change_mode(&self, mode: usize) -> ZFResult<()> {
+        log::debug!("[Sink: {}] Mode < {} > requested.", self.id, mode);
+        self.mode.store(mode, Ordering::Release);
+        Ok(())
+    }
+
+    fn get_mode(&self) -> Option<usize> {
+        Some(self.mode.load(Ordering::Acquire))
+    }
+
+    fn get_metrics(&self) -> Arc<RunnerMetrics> {
+        self.metrics.clone()
+    }
+
+    async fn reload(
+        &self,
+        _implementation: OperatorImplementation,
+        _configuration: &Option<Configuration>,
+        _transfer_state: bool,
+    ) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    async fn checkpoint(&self) -> ZFResult<bool> {
+        checkpoint::checkpoint_node(&self.context, &self.id, self.sink.as_ref(), &self.state).await
+    }
+
+    async fn restore(&self) -> ZFResult<bool> {
+        checkpoint::restore_node(&self.context, &self.id, self.sink.as_ref(), &self.state).await
+    }
+
+    async fn save_state(&self) -> ZFResult<Option<Vec<u8>>> {
+        let mut state = self.state.lock().await;
+        self.sink.save_state(&mut state)
+    }
+
+    async fn restore_state(&self, state: &[u8]) -> ZFResult<()> {
+        let mut current = self.state.lock().await;
+        self.sink.restore_state(&mut current, state)
+    }
+
+    async fn get_unacked(&self) -> usize {
+        0
+    }
+
+    async fn run(&self, stop: Signal) -> ZFResult<()> {
         self.start().await;
 
         let mut context = Context::default();
@@ -198,7 +314,12 @@ impl Runner for SinkRunner {
         // stops and returns the error to the caller (the RunnerManager)
 
         loop {
-            match self.iteration(context).await {
+            if stop.is_triggered() {
+                log::debug!("[Sink: {}] Stopped in between two iterations.", self.id);
+                break Ok(());
+            }
+
+            match self.iteration(context, &stop).await {
                 Ok(ctx) => {
                     log::debug!(
                         "[Sink: {}] iteration ok with new context {:?}",
@@ -16,26 +16,79 @@ use super::operator::OperatorIO;
 use crate::async_std::sync::{Arc, Mutex};
 use crate::model::deadline::E2EDeadlineRecord;
 use crate::model::link::PortDescriptor;
+use crate::model::node::MissedTickPolicy;
+use crate::runtime::checkpoint;
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
-use crate::runtime::dataflow::instance::runners::{Runner, RunnerKind};
-use crate::runtime::dataflow::node::SourceLoaded;
+use crate::runtime::dataflow::instance::runners::{until_stopped, Runner, RunnerKind};
+use crate::runtime::dataflow::node::{OperatorImplementation, SourceLoaded};
 use crate::runtime::deadline::E2EDeadline;
 use crate::runtime::message::Message;
+use crate::runtime::metrics::RunnerMetrics;
+use crate::runtime::timer::add_periods;
 use crate::runtime::InstanceContext;
 use crate::types::ZFResult;
 use crate::{
-    Context, ControlMessage, NodeId, PortId, PortType, RecordingMetadata, Source, State, ZFError,
+    Configuration, Context, ControlMessage, NodeId, PortId, PortType, RecordingMetadata, Source,
+    State, ZFError,
 };
 use async_trait::async_trait;
 use std::collections::HashMap;
-use std::time::Duration;
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use uhlc::{Timestamp, NTP64};
+use janu_util::sync::Signal;
 
 #[cfg(target_family = "unix")]
 use libloading::os::unix::Library;
 #[cfg(target_family = "windows")]
 use libloading::Library;
 
+// Schedules the executions of a periodic Source.
+//
+// The ticks are computed from the time the Source started, not from the end of the previous
+// execution: the time spent in `run` does not make the Source drift.
+struct Ticker {
+    period: Duration,
+    missed_ticks: MissedTickPolicy,
+    next_tick: Instant,
+}
+
+impl Ticker {
+    fn new(period: Duration, missed_ticks: MissedTickPolicy) -> Self {
+        Self {
+            period,
+            missed_ticks,
+            next_tick: Instant::now(),
+        }
+    }
+
+    // Waits for the next tick. Returns how late the Source woke up and how many ticks were
+    // skipped because they were missed.
+    async fn tick(&mut self) -> (Duration, u64) {
+        let now = Instant::now();
+        if self.next_tick > now {
+            async_std::task::sleep(self.next_tick - now).await;
+        }
+
+        let now = Instant::now();
+        let mut skipped = 0;
+        if let MissedTickPolicy::Skip = self.missed_ticks {
+            // Only the most recent of the ticks that already passed is executed.
+            let behind =
+                now.saturating_duration_since(self.next_tick).as_nanos() / self.period.as_nanos();
+            // Never past `now`, it can be represented.
+            self.next_tick = add_periods(self.next_tick, self.period, behind).unwrap_or(now);
+            skipped = u64::try_from(behind).unwrap_or(u64::MAX);
+        }
+
+        let jitter = now.saturating_duration_since(self.next_tick);
+        self.next_tick += self.period;
+
+        (jitter, skipped)
+    }
+}
+
 // Do not reorder the fields in this struct.
 // Rust drops fields in a struct in the same order they are declared.
 // Ref: https://doc.rust-lang.org/reference/destructors.html
@@ -46,6 +99,7 @@ pub struct SourceRunner {
     pub(crate) id: NodeId,
     pub(crate) context: InstanceContext,
     pub(crate) period: Option<Duration>,
+    pub(crate) missed_ticks: MissedTickPolicy,
     pub(crate) output: PortDescriptor,
     pub(crate) links: Arc<Mutex<Vec<LinkSender<Message>>>>,
     pub(crate) state: Arc<Mutex<State>>,
@@ -54,6 +108,8 @@ pub struct SourceRunner {
     pub(crate) current_recording_resource: Arc<Mutex<Option<String>>>,
     pub(crate) is_recording: Arc<Mutex<bool>>,
     pub(crate) is_running: Arc<Mutex<bool>>,
+    pub(crate) mode: Arc<AtomicUsize>,
+    pub(crate) metrics: Arc<RunnerMetrics>,
     pub(crate) source: Arc<dyn Source>,
     pub(crate) _library: Option<Arc<Library>>,
 }
@@ -78,10 +134,16 @@ impl SourceRunner {
             &context.flow_id, &context.instance_id, source.id, port_id
         );
 
+        let metrics = match source.period {
+            Some(_) => RunnerMetrics::default().with_ticks(),
+            None => RunnerMetrics::default(),
+        };
+
         Ok(Self {
             id: source.id,
             context,
             period: source.period,
+            missed_ticks: source.missed_ticks,
             state: source.state,
             output: source.output,
             links: Arc::new(Mutex::new(links)),
@@ -91,6 +153,8 @@ impl SourceRunner {
             base_resource_name,
             is_recording: Arc::new(Mutex::new(false)),
             is_running: Arc::new(Mutex::new(false)),
+            mode: Arc::new(AtomicUsize::new(0)),
+            metrics: Arc::new(metrics),
             current_recording_resource: Arc::new(Mutex::new(None)),
         })
     }
@@ -151,8 +215,24 @@ impl SourceRunner {
         let links = self.links.lock().await;
         let mut state = self.state.lock().await;
 
+        // The requested mode is only applied in between two executions of `run`. It only
+        // concerns the Source: the nodes downstream switch through their own request, or through
+        // a `ChangeMode` control message targeting them.
+        let mode = self.mode.load(Ordering::Acquire);
+        if context.mode != mode {
+            log::info!(
+                "[Source: {}] Switching from mode < {} > to mode < {} >.",
+                self.id,
+                context.mode,
+                mode
+            );
+            context.mode = mode;
+        }
+
         // Running
+        let start = Instant::now();
         let output = self.source.run(&mut context, &mut state).await?;
+        self.metrics.observe_run_duration(start.elapsed());
 
         let timestamp = self.new_maybe_periodic_timestamp();
 
@@ -169,6 +249,7 @@ impl SourceRunner {
         for link in links.iter() {
             log::debug!("\tSending on: {:?}", link);
             link.send(zf_message.clone()).await?;
+            self.metrics.inc_messages_out();
         }
         self.record(zf_message).await?;
         Ok(context)
@@ -221,6 +302,15 @@ impl Runner for SourceRunner {
         HashMap::with_capacity(0)
     }
 
+    async fn take_output_links(&self) -> HashMap<PortId, Vec<LinkSender<Message>>> {
+        let mut outputs = HashMap::with_capacity(1);
+        outputs.insert(
+            self.output.port_id.clone(),
+            std::mem::take(&mut *self.links.lock().await),
+        );
+        outputs
+    }
+
     async fn start_recording(&self) -> ZFResult<String> {
         let mut is_recording_guard = self.is_recording.lock().await;
         if !(*is_recording_guard) {
@@ -302,14 +392,84 @@ impl Runner for SourceRunner {
         *self.is_running.lock().await = false;
     }
 
-    async fn run(&self) -> ZFResult<()> {
+    async fn change_mode(&self, mode: usize) -> ZFResult<()> {
+        log::debug!("[Source: {}] Mode < {} > requested.", self.id, mode);
+        self.mode.store(mode, Ordering::Release);
+        Ok(())
+    }
+
+    fn get_mode(&self) -> Option<usize> {
+        Some(self.mode.load(Ordering::Acquire))
+    }
+
+    fn get_metrics(&self) -> Arc<RunnerMetrics> {
+        self.metrics.clone()
+    }
+
+    async fn reload(
+        &self,
+        _implementation: OperatorImplementation,
+        _configuration: &Option<Configuration>,
+        _transfer_state: bool,
+    ) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
+
+    async fn checkpoint(&self) -> ZFResult<bool> {
+        checkpoint::checkpoint_node(&self.context, &self.id, self.source.as_ref(), &self.state)
+            .await
+    }
+
+    async fn restore(&self) -> ZFResult<bool> {
+        checkpoint::restore_node(&self.context, &self.id, self.source.as_ref(), &self.state).await
+    }
+
+    async fn save_state(&self) -> ZFResult<Option<Vec<u8>>> {
+        let mut state = self.state.lock().await;
+        self.source.save_state(&mut state)
+    }
+
+    async fn restore_state(&self, state: &[u8]) -> ZFResult<()> {
+        let mut current = self.state.lock().await;
+        self.source.restore_state(&mut current, state)
+    }
+
+    async fn get_unacked(&self) -> usize {
+        0
+    }
+
+    async fn run(&self, stop: Signal) -> ZFResult<()> {
         self.start().await;
 
         let mut context = Context::default();
+        let mut ticker = self
+            .period
+            .filter(|period| *period > Duration::ZERO)
+            .map(|period| Ticker::new(period, self.missed_ticks));
         // Looping on iteration, each iteration is a single
         // run of the source, as a run can fail in case of error it
         // stops and returns the error to the caller (the RunnerManager)
         loop {
+            if stop.is_triggered() {
+                log::debug!("[Source: {}] Stopped in between two iterations.", self.id);
+                break Ok(());
+            }
+
+            if let Some(ticker) = ticker.as_mut() {
+                let (jitter, skipped) = match until_stopped(&stop, ticker.tick()).await {
+                    Some(tick) => tick,
+                    None => continue,
+                };
+                if skipped > 0 {
+                    log::warn!(
+                        "[Source: {}] Missed < {} > tick(s), skipping them.",
+                        self.id,
+                        skipped
+                    );
+                }
+                self.metrics.observe_tick(jitter, skipped);
+            }
+
             match self.iteration(context).await {
                 Ok(ctx) => {
                     log::debug!(
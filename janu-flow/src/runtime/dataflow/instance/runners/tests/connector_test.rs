@@ -0,0 +1,162 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+use async_std::sync::Arc;
+use uhlc::HLC;
+use uuid::Uuid;
+
+use crate::{
+    model::link::Reliability,
+    runtime::{
+        dataflow::instance::runners::connector::{ReceiverState, Reception},
+        message::{Ack, SequencedMessage},
+    },
+    Data, DataMessage, Message,
+};
+
+// The message numbered `sequence_number`, its data is the sequence number itself.
+fn sequenced(hlc: &HLC, sender: Uuid, sequence_number: u64, first: u64) -> SequencedMessage {
+    SequencedMessage {
+        sender,
+        sequence_number,
+        first,
+        message: Message::Data(DataMessage::new_serialized(
+            Arc::new(vec![sequence_number as u8]),
+            hlc.new_timestamp(),
+            vec![],
+        )),
+    }
+}
+
+fn delivered(reception: &Reception) -> Vec<u8> {
+    reception
+        .messages
+        .iter()
+        .map(|message| match message {
+            Message::Data(DataMessage {
+                data: Data::Bytes(bytes),
+                ..
+            }) => bytes[0],
+            _ => panic!("Unexpected message: {:?}", message),
+        })
+        .collect()
+}
+
+fn ack(sender: Uuid, next: u64, retransmit: bool) -> Option<Ack> {
+    Some(Ack {
+        sender,
+        next,
+        retransmit,
+    })
+}
+
+#[test]
+fn receive_at_most_once() {
+    let hlc = HLC::default();
+    let sender = Uuid::new_v4();
+    let mut state = ReceiverState::default();
+
+    // The first message received sets the numbering, whatever was sent before.
+    let reception = state.receive(Reliability::AtMostOnce, sequenced(&hlc, sender, 3, 3));
+    assert_eq!(vec![3], delivered(&reception));
+    assert_eq!(0, reception.gaps);
+    assert!(reception.ack.is_none());
+
+    let reception = state.receive(Reliability::AtMostOnce, sequenced(&hlc, sender, 6, 6));
+    assert_eq!(vec![6], delivered(&reception));
+    assert_eq!(2, reception.gaps);
+
+    // Late messages are discarded.
+    let reception = state.receive(Reliability::AtMostOnce, sequenced(&hlc, sender, 5, 5));
+    assert!(delivered(&reception).is_empty());
+    assert_eq!(1, reception.late);
+    assert_eq!(0, reception.duplicates);
+
+    // The numbering starts over with another sender.
+    let other = Uuid::new_v4();
+    let reception = state.receive(Reliability::AtMostOnce, sequenced(&hlc, other, 0, 0));
+    assert_eq!(vec![0], delivered(&reception));
+    assert_eq!(0, reception.gaps);
+    assert_eq!(0, reception.duplicates);
+
+    // What the replaced sender still sends is discarded, the numbering does not change.
+    let reception = state.receive(Reliability::AtMostOnce, sequenced(&hlc, sender, 7, 7));
+    assert!(delivered(&reception).is_empty());
+    assert_eq!(1, reception.late);
+
+    let reception = state.receive(Reliability::AtMostOnce, sequenced(&hlc, other, 1, 1));
+    assert_eq!(vec![1], delivered(&reception));
+    assert_eq!(0, reception.gaps);
+}
+
+#[test]
+fn receive_exactly_once() {
+    let hlc = HLC::default();
+    let sender = Uuid::new_v4();
+    let mut state = ReceiverState::default();
+
+    let reception = state.receive(Reliability::ExactlyOnce, sequenced(&hlc, sender, 0, 0));
+    assert_eq!(vec![0], delivered(&reception));
+    assert_eq!(ack(sender, 1, false), reception.ack);
+
+    // A gap: the message is kept and a retransmission is requested, once.
+    let reception = state.receive(Reliability::ExactlyOnce, sequenced(&hlc, sender, 3, 1));
+    assert!(delivered(&reception).is_empty());
+    assert_eq!(2, reception.gaps);
+    assert_eq!(ack(sender, 1, true), reception.ack);
+
+    let reception = state.receive(Reliability::ExactlyOnce, sequenced(&hlc, sender, 4, 1));
+    assert!(delivered(&reception).is_empty());
+    assert_eq!(0, reception.gaps);
+    assert_eq!(ack(sender, 1, false), reception.ack);
+
+    // The retransmission fills the gap, the messages are delivered in order and only once.
+    let reception = state.receive(Reliability::ExactlyOnce, sequenced(&hlc, sender, 1, 1));
+    assert_eq!(vec![1], delivered(&reception));
+    assert_eq!(ack(sender, 2, true), reception.ack);
+
+    let reception = state.receive(Reliability::ExactlyOnce, sequenced(&hlc, sender, 2, 1));
+    assert_eq!(vec![2, 3, 4], delivered(&reception));
+    assert_eq!(ack(sender, 5, false), reception.ack);
+
+    let reception = state.receive(Reliability::ExactlyOnce, sequenced(&hlc, sender, 3, 1));
+    assert!(delivered(&reception).is_empty());
+    assert_eq!(1, reception.duplicates);
+    assert_eq!(ack(sender, 5, false), reception.ack);
+}
+
+#[test]
+fn receive_at_least_once() {
+    let hlc = HLC::default();
+    let sender = Uuid::new_v4();
+    let mut state = ReceiverState::default();
+
+    // A receiver starting late waits for the messages the sender still keeps, from `first`.
+    let reception = state.receive(Reliability::AtLeastOnce, sequenced(&hlc, sender, 7, 5));
+    assert_eq!(vec![7], delivered(&reception));
+    assert_eq!(2, reception.gaps);
+    assert_eq!(ack(sender, 5, true), reception.ack);
+
+    // The messages are delivered as they are received, duplicates included.
+    let reception = state.receive(Reliability::AtLeastOnce, sequenced(&hlc, sender, 6, 5));
+    assert_eq!(vec![6], delivered(&reception));
+    assert_eq!(ack(sender, 5, false), reception.ack);
+
+    let reception = state.receive(Reliability::AtLeastOnce, sequenced(&hlc, sender, 5, 5));
+    assert_eq!(vec![5], delivered(&reception));
+    assert_eq!(ack(sender, 8, false), reception.ack);
+
+    let reception = state.receive(Reliability::AtLeastOnce, sequenced(&hlc, sender, 7, 5));
+    assert_eq!(vec![7], delivered(&reception));
+    assert_eq!(1, reception.duplicates);
+}
@@ -13,19 +13,25 @@
 //
 
 use crate::model::deadline::E2EDeadlineRecord;
+use crate::model::link::QueueingPolicy;
 use crate::model::{InputDescriptor, OutputDescriptor};
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
-use crate::runtime::dataflow::instance::runners::operator::{OperatorIO, OperatorRunner};
+use crate::runtime::dataflow::instance::runners::operator::{
+    OperatorIO, OperatorRunner, Watermarks,
+};
 use crate::runtime::dataflow::instance::runners::NodeRunner;
 use crate::runtime::dataflow::loader::{Loader, LoaderConfig};
+use crate::runtime::dataflow::node::OperatorImplementation;
 use crate::runtime::deadline::E2EDeadline;
+use crate::runtime::metrics::RunnerMetrics;
 use crate::runtime::{InstanceContext, RuntimeContext};
 use crate::{
     default_output_rule, Configuration, Context, Data, DataMessage, Deserializable, DowncastAny,
     EmptyState, LocalDeadlineMiss, Message, Node, NodeId, NodeOutput, Operator, PortId, PortType,
     State, Token, ZFData, ZFError, ZFResult,
 };
-use async_std::sync::{Arc, Mutex};
+use async_std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::AtomicUsize;
 use std::time::Duration;
 use std::{collections::HashMap, convert::TryInto};
 use uhlc::HLC;
@@ -171,6 +177,7 @@ fn e2e_deadline() {
         loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
         runtime_name: "test-runtime-input-rule-keep".into(),
         runtime_uuid: uuid,
+        checkpoints: None,
     };
     let instance_context = InstanceContext {
         flow_id: "test-input-rule-keep-flow".into(),
@@ -185,10 +192,16 @@ fn e2e_deadline() {
     let receiver_input1: LinkReceiver<Message> = LinkReceiver {
         id: input1.clone(),
         receiver: rx_input1,
+        priority: 0,
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let sender_input1: LinkSender<Message> = LinkSender {
         id: input1.clone(),
         sender: tx_input1,
+        policy: QueueingPolicy::Block,
+        drop_receiver: None,
+        dropped: Arc::new(AtomicUsize::new(0)),
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
 
     let input2: PortId = "INPUT-2".into();
@@ -197,6 +210,8 @@ fn e2e_deadline() {
     let receiver_input2: LinkReceiver<Message> = LinkReceiver {
         id: input2.clone(),
         receiver: rx_input2,
+        priority: 0,
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
 
     let mut io_inputs: HashMap<PortId, LinkReceiver<Message>> = HashMap::with_capacity(2);
@@ -212,10 +227,16 @@ fn e2e_deadline() {
     let receiver_output: LinkReceiver<Message> = LinkReceiver {
         id: output.clone(),
         receiver: rx_output,
+        priority: 0,
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let sender_output: LinkSender<Message> = LinkSender {
         id: output.clone(),
         sender: tx_output,
+        policy: QueueingPolicy::Block,
+        drop_receiver: None,
+        dropped: Arc::new(AtomicUsize::new(0)),
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let mut io_outputs: HashMap<PortId, Vec<LinkSender<Message>>> = HashMap::with_capacity(1);
     io_outputs.insert(output.clone(), vec![sender_output]);
@@ -251,11 +272,18 @@ fn e2e_deadline() {
         inputs,
         outputs,
         local_deadline: None,
+        input_rule: None,
         is_running: Arc::new(Mutex::new(false)),
+        mode: Arc::new(AtomicUsize::new(0)),
+        metrics: Arc::new(RunnerMetrics::default()),
         state: Arc::new(Mutex::new(operator.initialize(&None).unwrap())),
-        operator: Arc::new(operator),
-        _library: None,
+        implementation: Arc::new(RwLock::new(OperatorImplementation::new(
+            Arc::new(operator),
+            None,
+        ))),
         end_to_end_deadlines: vec![operator_deadline.clone()],
+        suspended: Arc::new(Mutex::new(None)),
+        watermarks: Arc::new(Mutex::new(Watermarks::default())),
     };
 
     let runner = NodeRunner::new(Arc::new(operator_runner), instance_context);
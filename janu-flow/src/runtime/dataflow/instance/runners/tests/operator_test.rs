@@ -11,29 +11,35 @@
 // Contributors:
 //   open
 //
-use async_std::sync::{Arc, Mutex};
+use async_std::sync::{Arc, Mutex, RwLock};
+use std::sync::atomic::AtomicUsize;
+use std::time::{Duration, Instant};
 use std::{collections::HashMap, convert::TryInto};
-use uhlc::HLC;
+use uhlc::{Timestamp, HLC, NTP64};
 use janu::prelude::*;
 
 use crate::{
-    default_output_rule,
+    default_output_rule, latest_input_rule,
+    model::{link::QueueingPolicy, node::InputRulePolicy},
     runtime::{
         dataflow::{
             instance::{
-                link::{LinkReceiver, LinkSender},
+                link::{link, LinkReceiver, LinkSender},
                 runners::{
-                    operator::{OperatorIO, OperatorRunner},
+                    operator::{OperatorIO, OperatorRunner, Watermarks},
                     NodeRunner,
                 },
             },
             loader::{Loader, LoaderConfig},
+            node::OperatorImplementation,
         },
+        metrics::RunnerMetrics,
         InstanceContext, RuntimeContext,
     },
-    Configuration, Context, Data, DataMessage, Deserializable, DowncastAny, EmptyState,
-    LocalDeadlineMiss, Message, Node, NodeOutput, Operator, PortId, PortType, State, Token,
-    TokenAction, ZFData, ZFError, ZFResult,
+    Configuration, Context, ControlMessage, Data, DataMessage, Deserializable, DowncastAny,
+    EmptyState, LocalDeadlineMiss, Message, Node, NodeOutput, Operator, PortId, PortType, State,
+    Timer, Token, TokenAction, WindowKind, Windows, ZFData, ZFError, ZFResult, ZFState,
+    WINDOWS_TIMER,
 };
 
 // ZFUsize implements Data.
@@ -120,7 +126,7 @@ impl Operator for TestOperator {
 
     fn run(
         &self,
-        _context: &mut Context,
+        context: &mut Context,
         _state: &mut State,
         inputs: &mut HashMap<PortId, DataMessage>,
     ) -> ZFResult<HashMap<PortId, Data>> {
@@ -136,9 +142,10 @@ impl Operator for TestOperator {
             .ok_or_else(|| ZFError::InvalidData("No data".to_string()))?;
         let data2 = data2_msg.data.try_get::<ZFUsize>()?;
 
+        // In mode `n` the sum is multiplied by `n + 1`, the default mode `0` leaves it untouched.
         results.insert(
             self.output.clone(),
-            Data::from::<ZFUsize>(ZFUsize(data1.0 + data2.0)),
+            Data::from::<ZFUsize>(ZFUsize((data1.0 + data2.0) * (context.mode + 1))),
         );
         Ok(results)
     }
@@ -178,6 +185,80 @@ async fn recv_usize(receiver: &LinkReceiver<Message>) -> usize {
     panic!("Received an unexpected `Message::Control`.")
 }
 
+// Creates a runner for `operator`, driven with `input_rule` if set. Its inputs are fed through the
+// returned senders, in the order of `inputs`, and its output is received on the returned receiver.
+fn operator_runner<O: Operator + 'static>(
+    name: &str,
+    operator: O,
+    inputs: &[&str],
+    output: &str,
+    input_rule: Option<InputRulePolicy>,
+) -> (
+    Arc<HLC>,
+    Vec<LinkSender<Message>>,
+    LinkReceiver<Message>,
+    NodeRunner,
+) {
+    let session = janu::open(janu::config::Config::default())
+        .wait()
+        .unwrap();
+    let hlc = Arc::new(uhlc::HLC::default());
+    let runtime_context = RuntimeContext {
+        session: Arc::new(session),
+        hlc: hlc.clone(),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
+        runtime_name: format!("test-runtime-{}", name).into(),
+        runtime_uuid: uuid::Uuid::new_v4(),
+        checkpoints: None,
+    };
+    let instance_context = InstanceContext {
+        flow_id: format!("test-{}-flow", name).into(),
+        instance_id: uuid::Uuid::new_v4(),
+        runtime: runtime_context,
+    };
+
+    let mut operator_io = OperatorIO::default();
+    let mut senders = Vec::with_capacity(inputs.len());
+    let mut input_types: HashMap<PortId, PortType> = HashMap::with_capacity(inputs.len());
+    for input in inputs {
+        let input: PortId = (*input).into();
+        let (sender_input, receiver_input) = link::<Message>(None, input.clone(), input.clone());
+        operator_io.try_add_input(receiver_input).unwrap();
+        senders.push(sender_input);
+        input_types.insert(input, "usize".into());
+    }
+
+    let output: PortId = output.into();
+    let (sender_output, receiver_output) = link::<Message>(None, output.clone(), output.clone());
+    operator_io.add_output(sender_output);
+    let mut output_types: HashMap<PortId, PortType> = HashMap::with_capacity(1);
+    output_types.insert(output, "usize".into());
+
+    let operator_runner = OperatorRunner {
+        id: "test".into(),
+        context: instance_context.clone(),
+        io: Arc::new(Mutex::new(operator_io)),
+        inputs: input_types,
+        outputs: output_types,
+        local_deadline: None,
+        input_rule,
+        state: Arc::new(Mutex::new(operator.initialize(&None).unwrap())),
+        is_running: Arc::new(Mutex::new(false)),
+        mode: Arc::new(AtomicUsize::new(0)),
+        metrics: Arc::new(RunnerMetrics::default()),
+        implementation: Arc::new(RwLock::new(OperatorImplementation::new(
+            Arc::new(operator),
+            None,
+        ))),
+        end_to_end_deadlines: vec![],
+        suspended: Arc::new(Mutex::new(None)),
+        watermarks: Arc::new(Mutex::new(Watermarks::default())),
+    };
+
+    let runner = NodeRunner::new(Arc::new(operator_runner), instance_context);
+    (hlc, senders, receiver_output, runner)
+}
+
 #[test]
 fn input_rule_keep() {
     let session = janu::open(janu::config::Config::default())
@@ -191,6 +272,7 @@ fn input_rule_keep() {
         loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
         runtime_name: "test-runtime-input-rule-keep".into(),
         runtime_uuid: uuid,
+        checkpoints: None,
     };
     let instance_context = InstanceContext {
         flow_id: "test-input-rule-keep-flow".into(),
@@ -204,10 +286,16 @@ fn input_rule_keep() {
     let receiver_input_1: LinkReceiver<Message> = LinkReceiver {
         id: input_1.clone(),
         receiver: rx_input_1,
+        priority: 0,
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let sender_input_1: LinkSender<Message> = LinkSender {
         id: input_1.clone(),
         sender: tx_input_1,
+        policy: QueueingPolicy::Block,
+        drop_receiver: None,
+        dropped: Arc::new(AtomicUsize::new(0)),
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
 
     let input_2: PortId = "INPUT-2".into();
@@ -215,10 +303,16 @@ fn input_rule_keep() {
     let receiver_input_2: LinkReceiver<Message> = LinkReceiver {
         id: input_2.clone(),
         receiver: rx_input_2,
+        priority: 0,
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let sender_input_2: LinkSender<Message> = LinkSender {
         id: input_2.clone(),
         sender: tx_input_2,
+        policy: QueueingPolicy::Block,
+        drop_receiver: None,
+        dropped: Arc::new(AtomicUsize::new(0)),
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let mut io_inputs: HashMap<PortId, LinkReceiver<Message>> = HashMap::with_capacity(2);
     io_inputs.insert(input_1.clone(), receiver_input_1);
@@ -233,10 +327,16 @@ fn input_rule_keep() {
     let receiver_output: LinkReceiver<Message> = LinkReceiver {
         id: output.clone(),
         receiver: rx_output,
+        priority: 0,
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let sender_output: LinkSender<Message> = LinkSender {
         id: output.clone(),
         sender: tx_output,
+        policy: QueueingPolicy::Block,
+        drop_receiver: None,
+        dropped: Arc::new(AtomicUsize::new(0)),
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let mut io_outputs: HashMap<PortId, Vec<LinkSender<Message>>> = HashMap::with_capacity(1);
     io_outputs.insert(output.clone(), vec![sender_output]);
@@ -261,11 +361,18 @@ fn input_rule_keep() {
         inputs,
         outputs,
         local_deadline: None,
+        input_rule: None,
         state: Arc::new(Mutex::new(operator.initialize(&None).unwrap())),
         is_running: Arc::new(Mutex::new(false)),
-        operator: Arc::new(operator),
-        _library: None,
+        mode: Arc::new(AtomicUsize::new(0)),
+        metrics: Arc::new(RunnerMetrics::default()),
+        implementation: Arc::new(RwLock::new(OperatorImplementation::new(
+            Arc::new(operator),
+            None,
+        ))),
         end_to_end_deadlines: vec![],
+        suspended: Arc::new(Mutex::new(None)),
+        watermarks: Arc::new(Mutex::new(Watermarks::default())),
     };
 
     let runner = NodeRunner::new(Arc::new(operator_runner), instance_context);
@@ -296,3 +403,854 @@ fn input_rule_keep() {
         runner_manager.await.unwrap();
     });
 }
+
+#[test]
+fn control_message_forwarded() {
+    let operator = TestOperator {
+        input_1: "INPUT-1".into(),
+        input_2: "INPUT-2".into(),
+        output: "OUTPUT".into(),
+    };
+    let (hlc, mut senders, receiver_output, runner) = operator_runner(
+        "control-message",
+        operator,
+        &["INPUT-1", "INPUT-2"],
+        "OUTPUT",
+        None,
+    );
+    let sender_input_2 = senders.pop().unwrap();
+    let sender_input_1 = senders.pop().unwrap();
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: false -> (1 (consume), Pending)
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (1 (keep), 2 (consume))
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        // The control message is received while waiting on `INPUT-2`, it must not trigger the
+        // input rule. A watermark is only forwarded once every input received one.
+        let watermark = hlc.new_timestamp();
+        sender_input_2
+            .send(Arc::new(Message::Control(ControlMessage::Watermark(
+                watermark,
+            ))))
+            .await
+            .unwrap();
+
+        send_usize(&hlc, &sender_input_2, 4).await; // IR: true -> (1 (consume), 4 (consume))
+        assert_eq!(5, recv_usize(&receiver_output).await);
+
+        // Once `INPUT-1` received a watermark too, the minimum across the inputs is forwarded.
+        let later_watermark = hlc.new_timestamp();
+        sender_input_1
+            .send(Arc::new(Message::Control(ControlMessage::Watermark(
+                later_watermark,
+            ))))
+            .await
+            .unwrap();
+        send_usize(&hlc, &sender_input_1, 6).await; // IR: false -> (6 (consume), Pending)
+        send_usize(&hlc, &sender_input_2, 8).await; // IR: true -> (6 (keep), 8 (consume))
+        let (_, message) = receiver_output.recv().await.unwrap();
+        match message.as_ref() {
+            Message::Control(ControlMessage::Watermark(timestamp)) => {
+                assert_eq!(*timestamp, watermark)
+            }
+            _ => panic!("Expected a `ControlMessage::Watermark`."),
+        }
+        assert_eq!(14, recv_usize(&receiver_output).await);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+#[test]
+fn stop_after_iteration_keeps_tokens() {
+    let operator = TestOperator {
+        input_1: "INPUT-1".into(),
+        input_2: "INPUT-2".into(),
+        output: "OUTPUT".into(),
+    };
+    let (hlc, mut senders, receiver_output, runner) = operator_runner(
+        "stop-after-iteration",
+        operator,
+        &["INPUT-1", "INPUT-2"],
+        "OUTPUT",
+        None,
+    );
+    let sender_input_2 = senders.pop().unwrap();
+    let sender_input_1 = senders.pop().unwrap();
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: false -> (1 (consume), Pending)
+        while !sender_input_1.is_empty() {
+            async_std::task::sleep(Duration::from_millis(1)).await;
+        }
+
+        // The operator is stopped while waiting on `INPUT-2`: the token of `INPUT-1` is kept.
+        runner_manager.stop_after_iteration().await.unwrap();
+        runner_manager.await.unwrap();
+        assert!(!runner.is_running().await);
+
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (1 (keep), 2 (consume))
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+#[test]
+fn change_mode() {
+    let operator = TestOperator {
+        input_1: "INPUT-1".into(),
+        input_2: "INPUT-2".into(),
+        output: "OUTPUT".into(),
+    };
+    let (hlc, mut senders, receiver_output, runner) = operator_runner(
+        "change-mode",
+        operator,
+        &["INPUT-1", "INPUT-2"],
+        "OUTPUT",
+        None,
+    );
+    let sender_input_2 = senders.pop().unwrap();
+    let sender_input_1 = senders.pop().unwrap();
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: false -> (1 (consume), Pending)
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (1 (keep), 2 (consume))
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        // Mode change through the dataflow, for another node: only forwarded.
+        sender_input_2
+            .send(Arc::new(Message::Control(ControlMessage::ChangeMode(
+                3,
+                vec!["other".into()],
+                hlc.new_timestamp(),
+            ))))
+            .await
+            .unwrap();
+        let (_, message) = receiver_output.recv().await.unwrap();
+        assert!(matches!(
+            message.as_ref(),
+            Message::Control(ControlMessage::ChangeMode(3, _, _))
+        ));
+        assert_eq!(Some(0), runner.get_mode());
+
+        // Mode change through the dataflow, targeting the operator: applied before the next run
+        // and forwarded.
+        sender_input_2
+            .send(Arc::new(Message::Control(ControlMessage::ChangeMode(
+                1,
+                vec!["test".into()],
+                hlc.new_timestamp(),
+            ))))
+            .await
+            .unwrap();
+        let (_, message) = receiver_output.recv().await.unwrap();
+        assert!(matches!(
+            message.as_ref(),
+            Message::Control(ControlMessage::ChangeMode(1, _, _))
+        ));
+        assert_eq!(Some(1), runner.get_mode());
+
+        send_usize(&hlc, &sender_input_2, 4).await; // IR: true -> (1 (consume), 4 (consume))
+        assert_eq!(10, recv_usize(&receiver_output).await);
+
+        // Mode change requested directly on the runner (i.e. through the Runtime).
+        runner.change_mode(2).await.unwrap();
+        assert_eq!(Some(2), runner.get_mode());
+
+        send_usize(&hlc, &sender_input_1, 5).await; // IR: false -> (5 (consume), Pending)
+        send_usize(&hlc, &sender_input_2, 6).await; // IR: true -> (5 (keep), 6 (consume))
+        assert_eq!(33, recv_usize(&receiver_output).await);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+// Sums its inputs. If an input is late, the sum of the inputs received is produced once the
+// missing input has been pending for `timeout`: a periodic timer wakes the input rule to check it.
+struct TimeoutOperator {
+    output: Arc<str>,
+    timeout: Duration,
+}
+
+impl Node for TimeoutOperator {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from::<EmptyState>(EmptyState {}))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+impl Operator for TimeoutOperator {
+    fn timers(&self) -> Vec<Timer> {
+        vec![Timer::periodic("check", self.timeout / 10)]
+    }
+
+    fn input_rule(
+        &self,
+        context: &mut Context,
+        _state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        let mut ready = 0;
+        let mut late = false;
+        for (port_id, token) in tokens.iter() {
+            match token {
+                Token::Ready(_) => ready += 1,
+                Token::Pending => {
+                    late |= context
+                        .get_pending_duration(port_id)
+                        .map_or(false, |pending| pending >= self.timeout);
+                }
+            }
+        }
+
+        if ready == tokens.len() {
+            return Ok(true);
+        }
+
+        // The input rule is only called without new data when a timer expired.
+        assert!(ready > 0 || !context.get_expired_timers().is_empty());
+        Ok(ready > 0 && late)
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let mut sum = 0;
+        for data_message in inputs.values_mut() {
+            sum += data_message.data.try_get::<ZFUsize>()?.0;
+        }
+
+        let mut results: HashMap<PortId, Data> = HashMap::new();
+        results.insert(self.output.clone(), Data::from::<ZFUsize>(ZFUsize(sum)));
+        Ok(results)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+#[test]
+fn timer_input_rule_timeout() {
+    let timeout = Duration::from_millis(100);
+    let operator = TimeoutOperator {
+        output: "OUTPUT".into(),
+        timeout,
+    };
+    let (hlc, mut senders, receiver_output, runner) =
+        operator_runner("timer", operator, &["INPUT-1", "INPUT-2"], "OUTPUT", None);
+    let sender_input_2 = senders.pop().unwrap();
+    let sender_input_1 = senders.pop().unwrap();
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: false -> (1 (consume), Pending)
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (1 (consume), 2 (consume))
+        assert_eq!(3, recv_usize(&receiver_output).await);
+        let start = Instant::now();
+
+        // `INPUT-2` is late: the operator runs on `INPUT-1` alone once the timeout is reached.
+        send_usize(&hlc, &sender_input_1, 5).await; // IR: false -> (5 (consume), Pending)
+        assert_eq!(5, recv_usize(&receiver_output).await);
+        assert!(start.elapsed() >= timeout / 2);
+
+        // `INPUT-2` is still late: no need to wait.
+        send_usize(&hlc, &sender_input_1, 7).await; // IR: true -> (7 (consume), Pending)
+        assert_eq!(7, recv_usize(&receiver_output).await);
+        let start = Instant::now();
+
+        // `INPUT-1` is now the late one.
+        send_usize(&hlc, &sender_input_2, 6).await; // IR: false -> (Pending, 6 (consume))
+        assert_eq!(6, recv_usize(&receiver_output).await);
+        assert!(start.elapsed() >= timeout / 2);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+// Sums the inputs it receives. Without `input_rule`, its input rule must never be called: the
+// runner uses an input rule policy instead.
+struct SumOperator {
+    output: Arc<str>,
+    input_rule: Option<fn(&mut State, &mut HashMap<PortId, Token>) -> ZFResult<bool>>,
+}
+
+impl Node for SumOperator {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from::<EmptyState>(EmptyState {}))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+impl Operator for SumOperator {
+    fn input_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        match self.input_rule {
+            Some(input_rule) => input_rule(state, tokens),
+            None => panic!("The input rule policy should be used."),
+        }
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let mut sum = 0;
+        for data_message in inputs.values_mut() {
+            sum += data_message.data.try_get::<ZFUsize>()?.0;
+        }
+
+        let mut results: HashMap<PortId, Data> = HashMap::new();
+        results.insert(self.output.clone(), Data::from::<ZFUsize>(ZFUsize(sum)));
+        Ok(results)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+// Creates a `SumOperator` with two inputs, driven with the `input_rule` policy.
+fn sum_operator_runner(
+    input_rule: InputRulePolicy,
+) -> (
+    Arc<HLC>,
+    LinkSender<Message>,
+    LinkSender<Message>,
+    LinkReceiver<Message>,
+    NodeRunner,
+) {
+    let operator = SumOperator {
+        output: "OUTPUT".into(),
+        input_rule: None,
+    };
+    let (hlc, mut senders, receiver_output, runner) = operator_runner(
+        "input-rule-policy",
+        operator,
+        &["INPUT-1", "INPUT-2"],
+        "OUTPUT",
+        Some(input_rule),
+    );
+    let sender_input_2 = senders.pop().unwrap();
+    let sender_input_1 = senders.pop().unwrap();
+    (hlc, sender_input_1, sender_input_2, receiver_output, runner)
+}
+
+#[test]
+fn input_rule_policy_any() {
+    let (hlc, sender_input_1, sender_input_2, receiver_output, runner) =
+        sum_operator_runner(InputRulePolicy::Any);
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: true -> (1 (consume), Pending)
+        assert_eq!(1, recv_usize(&receiver_output).await);
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (Pending, 2 (consume))
+        assert_eq!(2, recv_usize(&receiver_output).await);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+#[test]
+fn input_rule_policy_k_of_n() {
+    let (hlc, sender_input_1, sender_input_2, receiver_output, runner) =
+        sum_operator_runner(InputRulePolicy::KOfN { k: 2 });
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: false -> (1 (consume), Pending)
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (1 (consume), 2 (consume))
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+#[test]
+fn input_rule_policy_latest() {
+    let (hlc, sender_input_1, sender_input_2, receiver_output, runner) =
+        sum_operator_runner(InputRulePolicy::Latest);
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: false -> (1 (keep), Pending)
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (1 (keep), 2 (keep))
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        // The latest value of `INPUT-2` is held while `INPUT-1` is updated, and vice versa.
+        send_usize(&hlc, &sender_input_1, 10).await; // IR: true -> (10 (keep), 2 (keep))
+        assert_eq!(12, recv_usize(&receiver_output).await);
+        send_usize(&hlc, &sender_input_1, 20).await; // IR: true -> (20 (keep), 2 (keep))
+        assert_eq!(22, recv_usize(&receiver_output).await);
+        send_usize(&hlc, &sender_input_2, 5).await; // IR: true -> (20 (keep), 5 (keep))
+        assert_eq!(25, recv_usize(&receiver_output).await);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+// The helper behind the `latest` input rule policy, called by the operator itself: the kept
+// data is replaced by new data even though the runner is not aware of the policy.
+#[test]
+fn latest_input_rule_from_operator() {
+    let operator = SumOperator {
+        output: "OUTPUT".into(),
+        input_rule: Some(latest_input_rule),
+    };
+    let (hlc, mut senders, receiver_output, runner) = operator_runner(
+        "latest-input-rule",
+        operator,
+        &["INPUT-1", "INPUT-2"],
+        "OUTPUT",
+        None,
+    );
+    let sender_input_2 = senders.pop().unwrap();
+    let sender_input_1 = senders.pop().unwrap();
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: false -> (1 (keep), Pending)
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (1 (keep), 2 (keep))
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        // IR: false -> (1 (keep), 2 (keep)), the runner then waits for data on both inputs.
+        send_usize(&hlc, &sender_input_1, 10).await; // IR: true -> (10 (keep), 2 (keep))
+        assert_eq!(12, recv_usize(&receiver_output).await);
+        send_usize(&hlc, &sender_input_2, 5).await; // IR: true -> (10 (keep), 5 (keep))
+        assert_eq!(15, recv_usize(&receiver_output).await);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+#[derive(Debug)]
+struct WindowsState {
+    windows: Windows<DataMessage>,
+}
+
+impl ZFState for WindowsState {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+// Sums the inputs received in tumbling windows of `size`, the sums are produced when the windows
+// close.
+struct WindowSumOperator {
+    output: Arc<str>,
+    size: Duration,
+}
+
+impl Node for WindowSumOperator {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from(WindowsState {
+            windows: Windows::try_new(WindowKind::Tumbling(self.size))?,
+        }))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+
+    fn on_control_message(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        _port_id: &PortId,
+        message: &ControlMessage,
+    ) -> ZFResult<bool> {
+        state
+            .try_get::<WindowsState>()?
+            .windows
+            .on_watermark(context, message);
+        Ok(false)
+    }
+}
+
+impl Operator for WindowSumOperator {
+    fn timers(&self) -> Vec<Timer> {
+        vec![Timer::periodic(WINDOWS_TIMER, self.size / 4)]
+    }
+
+    fn input_rule(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        state
+            .try_get::<WindowsState>()?
+            .windows
+            .input_rule(context, tokens)
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        assert!(inputs.is_empty());
+
+        let mut sum = 0;
+        for window in state.try_get::<WindowsState>()?.windows.take_closed() {
+            for mut data_message in window.items {
+                sum += data_message.data.try_get::<ZFUsize>()?.0;
+            }
+        }
+
+        let mut results: HashMap<PortId, Data> = HashMap::new();
+        results.insert(self.output.clone(), Data::from::<ZFUsize>(ZFUsize(sum)));
+        Ok(results)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+#[test]
+fn windows_watermark_and_timer() {
+    let operator = WindowSumOperator {
+        output: "OUTPUT".into(),
+        size: Duration::from_millis(200),
+    };
+    let (hlc, mut senders, receiver_output, runner) =
+        operator_runner("windows", operator, &["INPUT"], "OUTPUT", None);
+    let sender_input = senders.pop().unwrap();
+
+    // The windows used start one second in the future so that the timer does not close them
+    // before the watermark and the data are received.
+    let now = hlc.new_timestamp();
+    let now_ms = now.get_time().to_duration().as_millis() as u64;
+    let base_ms = now_ms - now_ms % 200 + 1_000;
+    let at = |offset_ms: u64| {
+        Timestamp::new(
+            NTP64::from(Duration::from_millis(base_ms + offset_ms)),
+            now.get_id().to_owned(),
+        )
+    };
+    let send = |number: usize, timestamp: Timestamp| {
+        sender_input.send(Arc::new(Message::Data(DataMessage::new(
+            Data::from::<ZFUsize>(ZFUsize(number)),
+            timestamp,
+            vec![],
+        ))))
+    };
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+
+        // The watermark closes [0, 200).
+        send(1, at(20)).await.unwrap();
+        send(2, at(40)).await.unwrap();
+        sender_input
+            .send(Arc::new(Message::Control(ControlMessage::Watermark(at(
+                200,
+            )))))
+            .await
+            .unwrap();
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        // The timestamp of the data closes [200, 400).
+        send(5, at(300)).await.unwrap();
+        send(7, at(500)).await.unwrap();
+        assert_eq!(5, recv_usize(&receiver_output).await);
+
+        // The timer closes [400, 600), once the current time reaches its end.
+        assert_eq!(7, recv_usize(&receiver_output).await);
+        assert!(hlc.new_timestamp() >= at(600));
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+#[derive(Debug)]
+struct CounterState {
+    total: usize,
+}
+
+impl ZFState for CounterState {
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn std::any::Any {
+        self
+    }
+}
+
+// Accumulates the inputs it receives and outputs the total multiplied by `factor`. The total is
+// handed over to the next version of the operator when it is reloaded, unless `fail_restore` is
+// set.
+struct CounterOperator {
+    output: Arc<str>,
+    factor: usize,
+    fail_restore: bool,
+}
+
+impl Node for CounterOperator {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from::<CounterState>(CounterState { total: 0 }))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+
+    fn save_state(&self, state: &mut State) -> ZFResult<Option<Vec<u8>>> {
+        let state = state.try_get::<CounterState>()?;
+        Ok(Some(state.total.to_ne_bytes().to_vec()))
+    }
+
+    fn restore_state(&self, state: &mut State, saved_state: &[u8]) -> ZFResult<()> {
+        if self.fail_restore {
+            return Err(ZFError::DeseralizationError);
+        }
+
+        let state = state.try_get::<CounterState>()?;
+        state.total = usize::from_ne_bytes(
+            saved_state
+                .try_into()
+                .map_err(|_| ZFError::DeseralizationError)?,
+        );
+        Ok(())
+    }
+}
+
+impl Operator for CounterOperator {
+    fn input_rule(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        _tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        Ok(true)
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let state = state.try_get::<CounterState>()?;
+        for data_message in inputs.values_mut() {
+            state.total += data_message.data.try_get::<ZFUsize>()?.0;
+        }
+
+        let mut results: HashMap<PortId, Data> = HashMap::new();
+        results.insert(
+            self.output.clone(),
+            Data::from::<ZFUsize>(ZFUsize(state.total * self.factor)),
+        );
+        Ok(results)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+#[test]
+fn reload() {
+    let operator = CounterOperator {
+        output: "OUTPUT".into(),
+        factor: 1,
+        fail_restore: false,
+    };
+    let (hlc, mut senders, receiver_output, runner) =
+        operator_runner("reload", operator, &["INPUT"], "OUTPUT", None);
+    let sender_input = senders.pop().unwrap();
+    let counter = |factor: usize| {
+        OperatorImplementation::new(
+            Arc::new(CounterOperator {
+                output: "OUTPUT".into(),
+                factor,
+                fail_restore: false,
+            }),
+            None,
+        )
+    };
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input, 1).await;
+        assert_eq!(1, recv_usize(&receiver_output).await);
+        send_usize(&hlc, &sender_input, 2).await;
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        // A running operator cannot be reloaded.
+        match runner.reload(counter(10), &None, true).await {
+            Err(ZFError::InvalidState) => (),
+            _ => panic!("Expected an `InvalidState` when reloading a running operator."),
+        }
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+
+        // The total is transferred to the new version.
+        runner.reload(counter(10), &None, true).await.unwrap();
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input, 3).await;
+        assert_eq!(60, recv_usize(&receiver_output).await);
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+
+        // Without transfer, the new version starts from its initial state.
+        runner.reload(counter(100), &None, false).await.unwrap();
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input, 4).await;
+        assert_eq!(400, recv_usize(&receiver_output).await);
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+#[test]
+fn reload_keeps_tokens() {
+    let test_operator = || TestOperator {
+        input_1: "INPUT-1".into(),
+        input_2: "INPUT-2".into(),
+        output: "OUTPUT".into(),
+    };
+    let (hlc, mut senders, receiver_output, runner) = operator_runner(
+        "reload-keeps-tokens",
+        test_operator(),
+        &["INPUT-1", "INPUT-2"],
+        "OUTPUT",
+        None,
+    );
+    let sender_input_2 = senders.pop().unwrap();
+    let sender_input_1 = senders.pop().unwrap();
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input_1, 1).await; // IR: false -> (1 (consume), Pending)
+        while !sender_input_1.is_empty() {
+            async_std::task::sleep(Duration::from_millis(1)).await;
+        }
+        runner_manager.stop_after_iteration().await.unwrap();
+        runner_manager.await.unwrap();
+
+        // The token of `INPUT-1` is handed over to the new implementation.
+        runner
+            .reload(
+                OperatorImplementation::new(Arc::new(test_operator()), None),
+                &None,
+                false,
+            )
+            .await
+            .unwrap();
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input_2, 2).await; // IR: true -> (1 (keep), 2 (consume))
+        assert_eq!(3, recv_usize(&receiver_output).await);
+
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
+
+#[test]
+fn reload_failure() {
+    let operator = CounterOperator {
+        output: "OUTPUT".into(),
+        factor: 2,
+        fail_restore: false,
+    };
+    let (hlc, mut senders, receiver_output, runner) =
+        operator_runner("reload-failure", operator, &["INPUT"], "OUTPUT", None);
+    let sender_input = senders.pop().unwrap();
+
+    async_std::task::block_on(async {
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input, 1).await;
+        assert_eq!(2, recv_usize(&receiver_output).await);
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+
+        // The new version cannot restore the state: the operator keeps its implementation and its
+        // state.
+        let failing = OperatorImplementation::new(
+            Arc::new(CounterOperator {
+                output: "OUTPUT".into(),
+                factor: 10,
+                fail_restore: true,
+            }),
+            None,
+        );
+        assert!(runner.reload(failing, &None, true).await.is_err());
+
+        let runner_manager = runner.start();
+        send_usize(&hlc, &sender_input, 2).await;
+        assert_eq!(6, recv_usize(&receiver_output).await);
+        runner_manager.kill().await.unwrap();
+        runner_manager.await.unwrap();
+    });
+}
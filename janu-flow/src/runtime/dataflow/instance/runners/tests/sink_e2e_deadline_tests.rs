@@ -13,13 +13,14 @@
 //
 
 use crate::model::deadline::E2EDeadlineRecord;
-use crate::model::link::PortDescriptor;
+use crate::model::link::{PortDescriptor, QueueingPolicy};
 use crate::model::{InputDescriptor, OutputDescriptor};
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
 use crate::runtime::dataflow::instance::runners::sink::SinkRunner;
 use crate::runtime::dataflow::instance::runners::NodeRunner;
 use crate::runtime::dataflow::loader::{Loader, LoaderConfig};
 use crate::runtime::deadline::E2EDeadline;
+use crate::runtime::metrics::RunnerMetrics;
 use crate::runtime::{InstanceContext, RuntimeContext};
 use crate::{
     Configuration, Context, Data, DataMessage, Deserializable, DowncastAny, EmptyState, Message,
@@ -28,6 +29,7 @@ use crate::{
 use async_std::sync::{Arc, Mutex};
 use async_trait::async_trait;
 use std::convert::TryInto;
+use std::sync::atomic::AtomicUsize;
 use std::time::Duration;
 use janu::prelude::*;
 
@@ -109,6 +111,7 @@ fn sink_e2e_deadline() {
         loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
         runtime_name: "runtime--SINK-e2e-deadline-tests".into(),
         runtime_uuid: uuid,
+        checkpoints: None,
     };
     let instance_context = InstanceContext {
         flow_id: "flow--SINK-e2e-deadline-tests".into(),
@@ -121,10 +124,16 @@ fn sink_e2e_deadline() {
     let receiver_input: LinkReceiver<Message> = LinkReceiver {
         id: input.clone(),
         receiver: rx_input,
+        priority: 0,
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let sender_input: LinkSender<Message> = LinkSender {
         id: input.clone(),
         sender: tx_input,
+        policy: QueueingPolicy::Block,
+        drop_receiver: None,
+        dropped: Arc::new(AtomicUsize::new(0)),
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
 
     let sink_id: NodeId = "source".into();
@@ -163,6 +172,8 @@ fn sink_e2e_deadline() {
         },
         link: Arc::new(Mutex::new(Some(receiver_input))),
         is_running: Arc::new(Mutex::new(false)),
+        mode: Arc::new(AtomicUsize::new(0)),
+        metrics: Arc::new(RunnerMetrics::default()),
         state: Arc::new(Mutex::new(sink.initialize(&None).unwrap())),
         sink: Arc::new(sink),
         _library: None,
@@ -13,12 +13,14 @@
 //
 
 use crate::model::deadline::E2EDeadlineRecord;
-use crate::model::link::PortDescriptor;
+use crate::model::link::{PortDescriptor, QueueingPolicy};
+use crate::model::node::MissedTickPolicy;
 use crate::model::{InputDescriptor, OutputDescriptor};
 use crate::runtime::dataflow::instance::link::{LinkReceiver, LinkSender};
 use crate::runtime::dataflow::instance::runners::source::SourceRunner;
 use crate::runtime::dataflow::instance::runners::NodeRunner;
 use crate::runtime::dataflow::loader::{Loader, LoaderConfig};
+use crate::runtime::metrics::RunnerMetrics;
 use crate::runtime::{InstanceContext, RuntimeContext};
 use crate::{
     Configuration, Context, Data, Deserializable, DowncastAny, EmptyState, Message, Node, NodeId,
@@ -27,6 +29,7 @@ use crate::{
 use async_std::sync::{Arc, Mutex};
 use async_trait::async_trait;
 use std::convert::TryInto;
+use std::sync::atomic::AtomicUsize;
 use std::time::Duration;
 use janu::prelude::*;
 
@@ -101,6 +104,7 @@ fn source_e2e_deadline() {
         loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
         runtime_name: "runtime--source-e2e-deadline-tests".into(),
         runtime_uuid: uuid,
+        checkpoints: None,
     };
     let instance_context = InstanceContext {
         flow_id: "flow--source-e2e-deadline-tests".into(),
@@ -113,10 +117,16 @@ fn source_e2e_deadline() {
     let receiver_output: LinkReceiver<Message> = LinkReceiver {
         id: output.clone(),
         receiver: rx_output,
+        priority: 0,
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
     let sender_output: LinkSender<Message> = LinkSender {
         id: output.clone(),
         sender: tx_output,
+        policy: QueueingPolicy::Block,
+        drop_receiver: None,
+        dropped: Arc::new(AtomicUsize::new(0)),
+        undroppable: Arc::new(AtomicUsize::new(0)),
     };
 
     let source_id: NodeId = "source".into();
@@ -150,12 +160,15 @@ fn source_e2e_deadline() {
         id: source_id,
         context: instance_context.clone(),
         period: None,
+        missed_ticks: MissedTickPolicy::default(),
         output: PortDescriptor {
             port_id: output,
             port_type: "ZFUsize".into(),
         },
         links: Arc::new(Mutex::new(vec![sender_output])),
         is_running: Arc::new(Mutex::new(false)),
+        mode: Arc::new(AtomicUsize::new(0)),
+        metrics: Arc::new(RunnerMetrics::default()),
         state: Arc::new(Mutex::new(source.initialize(&None).unwrap())),
         end_to_end_deadlines: vec![e2e_deadline_1.clone(), e2e_deadline_2.clone()],
         base_resource_name: "test".into(),
@@ -0,0 +1,252 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+//! Integrity checks of the libraries of the nodes.
+//!
+//! The checksum of a library is the SHA-256 of its content. It can be signed with an ed25519 key:
+//! the signature covers the raw SHA-256 digest. Checksums, keys and signatures are hex-encoded.
+
+use crate::{ZFError, ZFResult};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::Read;
+#[cfg(all(target_family = "unix", not(target_os = "linux")))]
+use std::os::unix::fs::PermissionsExt;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Computes the checksum of the file `path`.
+pub fn compute_checksum(path: &Path) -> ZFResult<String> {
+    Ok(hex::encode(digest(path)?))
+}
+
+/// Signs `checksum` with `signing_key`, the 32 bytes secret key.
+pub fn sign_checksum(checksum: &str, signing_key: &str) -> ZFResult<String> {
+    let signing_key = SigningKey::from_bytes(&decode::<32>("signing key", signing_key)?);
+    let digest = decode::<32>("checksum", checksum)?;
+    Ok(hex::encode(signing_key.sign(&digest).to_bytes()))
+}
+
+/// Returns the public key associated to `signing_key`, to be listed in the trusted keys of the
+/// runtimes.
+pub fn verifying_key(signing_key: &str) -> ZFResult<String> {
+    let signing_key = SigningKey::from_bytes(&decode::<32>("signing key", signing_key)?);
+    Ok(hex::encode(signing_key.verifying_key().to_bytes()))
+}
+
+pub(crate) fn try_parse_verifying_key(key: &str) -> ZFResult<VerifyingKey> {
+    VerifyingKey::from_bytes(&decode::<32>("public key", key)?)
+        .map_err(|e| ZFError::IntegrityError(format!("Invalid public key < {} >: {}", key, e)))
+}
+
+pub(crate) fn encode_verifying_key(key: &VerifyingKey) -> String {
+    hex::encode(key.to_bytes())
+}
+
+/// Checks the library `path` against its expected `checksum` and `signature`.
+///
+/// If `trusted_keys` is not empty, the library must be signed by one of them. Without trusted
+/// keys, only the checksum, if provided, is checked.
+pub(crate) fn verify(
+    path: &Path,
+    checksum: &Option<String>,
+    signature: &Option<String>,
+    trusted_keys: &[VerifyingKey],
+) -> ZFResult<()> {
+    if checksum.is_none() && trusted_keys.is_empty() {
+        return Ok(());
+    }
+
+    verify_digest(path, &digest(path)?, checksum, signature, trusted_keys)
+}
+
+/// A library checked by `verify_library`, to be opened through `path`.
+///
+/// The library opened is the one that was checked, even if the file is replaced after the check:
+/// on Linux it is opened through the file descriptor it was read from, elsewhere from a private
+/// copy of the content that was checked.
+pub(crate) struct VerifiedLibrary {
+    path: PathBuf,
+    // Kept open, the library is opened through it.
+    #[cfg(target_os = "linux")]
+    _file: Option<File>,
+    // Removed once the library is opened.
+    #[cfg(not(target_os = "linux"))]
+    private_dir: Option<PathBuf>,
+}
+
+impl VerifiedLibrary {
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Nothing to check, the library is opened from its path.
+    fn unchecked(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            #[cfg(target_os = "linux")]
+            _file: None,
+            #[cfg(not(target_os = "linux"))]
+            private_dir: None,
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    fn try_new(_path: &Path, file: File, _content: &[u8]) -> ZFResult<Self> {
+        Ok(Self {
+            path: PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd())),
+            _file: Some(file),
+        })
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn try_new(path: &Path, _file: File, content: &[u8]) -> ZFResult<Self> {
+        let private_dir =
+            std::env::temp_dir().join(format!("janu-flow-library-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&private_dir)?;
+        #[cfg(target_family = "unix")]
+        std::fs::set_permissions(&private_dir, std::fs::Permissions::from_mode(0o700))?;
+
+        let library = Self {
+            path: private_dir
+                .join(path.file_name().ok_or_else(|| {
+                    ZFError::IntegrityError(format!("{:?} is not a file.", path))
+                })?),
+            private_dir: Some(private_dir),
+        };
+        std::fs::write(&library.path, content)?;
+        Ok(library)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl Drop for VerifiedLibrary {
+    fn drop(&mut self) {
+        if let Some(private_dir) = &self.private_dir {
+            let _ = std::fs::remove_dir_all(private_dir);
+        }
+    }
+}
+
+/// Checks the library `path`, as `verify` does, and returns where to open the library that was
+/// checked from.
+pub(crate) fn verify_library(
+    path: &Path,
+    checksum: &Option<String>,
+    signature: &Option<String>,
+    trusted_keys: &[VerifyingKey],
+) -> ZFResult<VerifiedLibrary> {
+    if checksum.is_none() && trusted_keys.is_empty() {
+        return Ok(VerifiedLibrary::unchecked(path));
+    }
+
+    let mut file = File::open(path)?;
+    let mut content = vec![];
+    file.read_to_end(&mut content)?;
+    verify_digest(
+        path,
+        &digest_bytes(&content),
+        checksum,
+        signature,
+        trusted_keys,
+    )?;
+
+    VerifiedLibrary::try_new(path, file, &content)
+}
+
+/// Reads the file `path` and checks its content, as `verify` does. Returns the content that was
+/// checked.
+pub(crate) fn read_verified(
+    path: &Path,
+    checksum: &Option<String>,
+    signature: &Option<String>,
+    trusted_keys: &[VerifyingKey],
+) -> ZFResult<Vec<u8>> {
+    let content = std::fs::read(path)?;
+    if checksum.is_some() || !trusted_keys.is_empty() {
+        verify_digest(
+            path,
+            &digest_bytes(&content),
+            checksum,
+            signature,
+            trusted_keys,
+        )?;
+    }
+    Ok(content)
+}
+
+fn verify_digest(
+    path: &Path,
+    digest: &[u8; 32],
+    checksum: &Option<String>,
+    signature: &Option<String>,
+    trusted_keys: &[VerifyingKey],
+) -> ZFResult<()> {
+    if let Some(checksum) = checksum {
+        if decode::<32>("checksum", checksum)? != *digest {
+            return Err(ZFError::IntegrityError(format!(
+                "The checksum of {:?} does not match < {} >.",
+                path, checksum
+            )));
+        }
+    }
+
+    if trusted_keys.is_empty() {
+        return Ok(());
+    }
+
+    let signature = signature.as_ref().ok_or_else(|| {
+        ZFError::IntegrityError(format!(
+            "{:?} is not signed while trusted keys are configured.",
+            path
+        ))
+    })?;
+    let signature = Signature::from_bytes(&decode::<64>("signature", signature)?);
+
+    if trusted_keys
+        .iter()
+        .any(|key| key.verify(digest, &signature).is_ok())
+    {
+        return Ok(());
+    }
+
+    Err(ZFError::IntegrityError(format!(
+        "The signature of {:?} does not match any trusted key.",
+        path
+    )))
+}
+
+fn digest(path: &Path) -> ZFResult<[u8; 32]> {
+    Ok(digest_bytes(&std::fs::read(path)?))
+}
+
+fn digest_bytes(content: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(content);
+    let digest = hasher.finalize();
+
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&digest[..]);
+    bytes
+}
+
+fn decode<const N: usize>(what: &str, value: &str) -> ZFResult<[u8; N]> {
+    hex::decode(value)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ZFError::IntegrityError(format!("Invalid {}: < {} >.", what, value)))
+}
@@ -0,0 +1,867 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+//! Nodes isolated in their own process.
+//!
+//! The library of an isolated node is loaded by a child process, `janu-flow-node-host`, instead
+//! of the runtime. The runtime drives a proxy, `IsolatedOperator`, `IsolatedSource` or
+//! `IsolatedSink`, that forwards the calls to the child over a unix socket: a panic or a
+//! segmentation fault in the library only terminates the child. The child is spawned again, and
+//! the node initialized again, at the next call.
+//!
+//! All the methods of the node are executed by the child: the input and output rules, the timers
+//! and the control messages of an Operator included. The `Context` crosses the socket with each
+//! call: the node sees the mode, the timers and the pending durations of the runtime, and the
+//! timers it sets or cancels are applied by the runtime. The state of the node lives in the
+//! child, the proxy only holds an empty state.
+
+use super::integrity;
+use super::loader::{Loader, LoaderConfig};
+use super::node::{OperatorLoaded, SinkLoaded, SourceLoaded};
+use crate::model::node::{OperatorRecord, SinkRecord, SourceRecord};
+use crate::serde::{de::DeserializeOwned, Deserialize, Serialize};
+use crate::{
+    AsyncOperator, Configuration, Context, ControlMessage, Data, DataMessage, EmptyState,
+    LocalDeadlineMiss, Node, NodeOutput, PortId, ReadyToken, Sink, Source, State, Timer, TimerId,
+    Token, TokenAction, ZFError, ZFResult,
+};
+use async_std::io::prelude::*;
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use async_std::sync::Mutex;
+use async_trait::async_trait;
+use ed25519_dalek::VerifyingKey;
+use std::collections::HashMap;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+// How long the child has to connect to the runtime.
+static CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+// How often the runtime checks that the child did not exit while waiting for it to connect.
+static CONNECT_POLL: Duration = Duration::from_millis(10);
+// The largest message accepted on the socket, a larger one means the other side misbehaves.
+const MAX_MESSAGE_SIZE: u64 = 256 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Request {
+    // Loads and initializes the node.
+    LoadOperator(OperatorRecord),
+    LoadSource(SourceRecord),
+    LoadSink(SinkRecord),
+    Timers,
+    InputRule(RemoteContext, HashMap<PortId, RemoteToken>),
+    Run(RemoteContext, HashMap<PortId, DataMessage>),
+    OutputRule(
+        RemoteContext,
+        HashMap<PortId, Data>,
+        Option<RemoteDeadlineMiss>,
+    ),
+    OnControlMessage(RemoteContext, PortId, ControlMessage),
+    SaveState,
+    RestoreState(Vec<u8>),
+    Finalize,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum Reply {
+    Done,
+    Timers(Vec<Timer>),
+    InputRule(RemoteContext, bool, HashMap<PortId, RemoteToken>),
+    Run(RemoteContext, HashMap<PortId, Data>),
+    OutputRule(RemoteContext, HashMap<PortId, NodeOutput>),
+    OnControlMessage(RemoteContext, bool),
+    SaveState(Option<Vec<u8>>),
+}
+
+type Response = ZFResult<Reply>;
+
+// The `Context` of a call, as it crosses the socket in both directions. The deadlines of the
+// timers and the pending durations are relative to the time the context is sent.
+#[derive(Serialize, Deserialize, Debug)]
+struct RemoteContext {
+    mode: usize,
+    armed_timers: Vec<(Timer, Duration)>,
+    expired_timers: Vec<TimerId>,
+    pending: HashMap<PortId, Duration>,
+}
+
+impl RemoteContext {
+    fn new(context: &Context) -> Self {
+        let now = Instant::now();
+        let (armed_timers, expired_timers) = context.timers.snapshot(now);
+        Self {
+            mode: context.mode,
+            armed_timers,
+            expired_timers,
+            pending: context
+                .pending_since
+                .iter()
+                .map(|(port_id, since)| (port_id.clone(), now.saturating_duration_since(*since)))
+                .collect(),
+        }
+    }
+
+    // Applies the context to `context`, the timers set or canceled and the mode changed on the
+    // other side included.
+    fn apply(self, context: &mut Context) {
+        let now = Instant::now();
+        context.mode = self.mode;
+        context
+            .timers
+            .restore(self.armed_timers, self.expired_timers, now);
+        context.pending_since = self
+            .pending
+            .into_iter()
+            .map(|(port_id, pending)| (port_id, now.checked_sub(pending).unwrap_or(now)))
+            .collect();
+    }
+
+    fn into_context(self) -> Context {
+        let mut context = Context::default();
+        self.apply(&mut context);
+        context
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+enum RemoteToken {
+    Pending,
+    Ready(DataMessage, TokenAction),
+}
+
+impl RemoteToken {
+    fn try_new(token: &Token) -> ZFResult<Self> {
+        Ok(match token {
+            Token::Pending => Self::Pending,
+            Token::Ready(token) => {
+                Self::Ready(to_bytes_message(&token.data)?, token.action.clone())
+            }
+        })
+    }
+
+    fn into_token(self) -> Token {
+        match self {
+            Self::Pending => Token::Pending,
+            Self::Ready(data, action) => Token::Ready(ReadyToken { data, action }),
+        }
+    }
+}
+
+fn to_remote_tokens(tokens: &HashMap<PortId, Token>) -> ZFResult<HashMap<PortId, RemoteToken>> {
+    tokens
+        .iter()
+        .map(|(port_id, token)| Ok((port_id.clone(), RemoteToken::try_new(token)?)))
+        .collect()
+}
+
+fn into_tokens(tokens: HashMap<PortId, RemoteToken>) -> HashMap<PortId, Token> {
+    tokens
+        .into_iter()
+        .map(|(port_id, token)| (port_id, token.into_token()))
+        .collect()
+}
+
+// A `LocalDeadlineMiss`, whose start is relative to the time it is sent.
+#[derive(Serialize, Deserialize, Debug)]
+struct RemoteDeadlineMiss {
+    since_start: Duration,
+    deadline: Duration,
+    elapsed: Duration,
+}
+
+impl RemoteDeadlineMiss {
+    fn new(miss: &LocalDeadlineMiss) -> Self {
+        Self {
+            since_start: miss.start.elapsed(),
+            deadline: miss.deadline,
+            elapsed: miss.elapsed,
+        }
+    }
+
+    fn into_miss(self) -> LocalDeadlineMiss {
+        let now = Instant::now();
+        LocalDeadlineMiss {
+            start: now.checked_sub(self.since_start).unwrap_or(now),
+            deadline: self.deadline,
+            elapsed: self.elapsed,
+        }
+    }
+}
+
+async fn write_message<T: Serialize>(stream: &mut UnixStream, message: &T) -> ZFResult<()> {
+    let bytes = bincode::serialize(message).map_err(|_| ZFError::SerializationError)?;
+    stream
+        .write_all(&(bytes.len() as u64).to_le_bytes())
+        .await?;
+    stream.write_all(&bytes).await?;
+    Ok(())
+}
+
+async fn read_message<T: DeserializeOwned>(stream: &mut UnixStream) -> ZFResult<T> {
+    let mut len = [0u8; 8];
+    stream.read_exact(&mut len).await?;
+    let len = u64::from_le_bytes(len);
+    // Reported as an I/O error: the connection cannot be used any more.
+    if len > MAX_MESSAGE_SIZE {
+        return Err(ZFError::IOError(format!(
+            "Message of {} bytes exceeds the maximum of {} bytes",
+            len, MAX_MESSAGE_SIZE
+        )));
+    }
+    let mut bytes = vec![0u8; len as usize];
+    stream.read_exact(&mut bytes).await?;
+    bincode::deserialize(&bytes).map_err(|_| ZFError::DeseralizationError)
+}
+
+// The data crosses the socket as bytes.
+fn to_bytes(data: &Data) -> ZFResult<Data> {
+    Ok(Data::Bytes(data.try_as_bytes()?))
+}
+
+fn to_bytes_message(message: &DataMessage) -> ZFResult<DataMessage> {
+    Ok(DataMessage {
+        data: to_bytes(&message.data)?,
+        ..message.clone()
+    })
+}
+
+fn to_bytes_outputs(outputs: &HashMap<PortId, Data>) -> ZFResult<HashMap<PortId, Data>> {
+    outputs
+        .iter()
+        .map(|(port_id, data)| Ok((port_id.clone(), to_bytes(data)?)))
+        .collect()
+}
+
+fn to_bytes_node_outputs(
+    outputs: HashMap<PortId, NodeOutput>,
+) -> ZFResult<HashMap<PortId, NodeOutput>> {
+    outputs
+        .into_iter()
+        .map(|(port_id, output)| {
+            let output = match output {
+                NodeOutput::Data(data) => NodeOutput::Data(to_bytes(&data)?),
+                NodeOutput::Control(message) => NodeOutput::Control(message),
+            };
+            Ok((port_id, output))
+        })
+        .collect()
+}
+
+fn unexpected(reply: Reply) -> ZFError {
+    ZFError::InvalidData(format!("Unexpected reply of the node process: {:?}", reply))
+}
+
+// A child process hosting a node, killed when dropped.
+struct NodeProcess {
+    child: Child,
+    stream: UnixStream,
+}
+
+impl NodeProcess {
+    async fn try_spawn(host: &Path, trusted_keys: &[String], load: &Request) -> ZFResult<Self> {
+        // Only the user running the runtime can connect to a socket in a private directory.
+        let private_dir =
+            std::env::temp_dir().join(format!("janu-flow-node-{}", uuid::Uuid::new_v4()));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&private_dir)?;
+        let socket = private_dir.join("node.sock");
+        let listener = match UnixListener::bind(&socket).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                let _ = std::fs::remove_dir_all(&private_dir);
+                return Err(e.into());
+            }
+        };
+
+        let mut command = Command::new(host);
+        command.arg(&socket);
+        for key in trusted_keys {
+            command.arg("--trusted-key").arg(key);
+        }
+        let result = match command.spawn() {
+            Ok(child) => Self::try_accept(&listener, child).await,
+            Err(e) => Err(ZFError::LoadingError(format!(
+                "Unable to spawn < {:?} >: {}",
+                host, e
+            ))),
+        };
+        std::fs::remove_dir_all(&private_dir)?;
+
+        let mut process = result?;
+        process.call(load).await?;
+        Ok(process)
+    }
+
+    async fn try_accept(listener: &UnixListener, mut child: Child) -> ZFResult<Self> {
+        let start = Instant::now();
+        loop {
+            match async_std::future::timeout(CONNECT_POLL, listener.accept()).await {
+                Ok(Ok((stream, _))) => return Ok(Self { child, stream }),
+                Ok(Err(e)) => return Err(e.into()),
+                Err(_) => {
+                    if let Some(status) = child.try_wait()? {
+                        return Err(ZFError::LoadingError(format!(
+                            "The node process exited before connecting: {}",
+                            status
+                        )));
+                    }
+                    if start.elapsed() > CONNECT_TIMEOUT {
+                        let _ = child.kill();
+                        let _ = child.wait();
+                        return Err(ZFError::LoadingError(
+                            "The node process did not connect.".to_string(),
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    async fn call(&mut self, request: &Request) -> Response {
+        write_message(&mut self.stream, request).await?;
+        read_message::<Response>(&mut self.stream).await?
+    }
+}
+
+impl Drop for NodeProcess {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The process hosting an isolated node, spawned at the first call.
+pub struct IsolatedNode {
+    id: String,
+    host: PathBuf,
+    // The child checks the library again, with the keys of the runtime.
+    trusted_keys: Vec<String>,
+    load: Request,
+    process: Mutex<Option<NodeProcess>>,
+}
+
+impl IsolatedNode {
+    fn new(id: &str, host: &Path, trusted_keys: &[VerifyingKey], load: Request) -> Self {
+        Self {
+            id: id.to_string(),
+            host: host.to_path_buf(),
+            trusted_keys: trusted_keys
+                .iter()
+                .map(integrity::encode_verifying_key)
+                .collect(),
+            load,
+            process: Mutex::new(None),
+        }
+    }
+
+    async fn spawn(&self) -> ZFResult<NodeProcess> {
+        NodeProcess::try_spawn(&self.host, &self.trusted_keys, &self.load).await
+    }
+
+    async fn call(&self, request: &Request) -> Response {
+        let mut process = self.process.lock().await;
+
+        // The process is owned by the call until the reply is read: if the call is canceled in
+        // between, the process is dropped, hence killed, instead of leaving its reply to the next
+        // call.
+        let mut current = match process.take() {
+            Some(current) => current,
+            None => self.spawn().await?,
+        };
+        let result = current.call(request).await;
+
+        // The socket is closed: the process terminated, it is spawned again at the next call.
+        match &result {
+            Err(ZFError::IOError(e)) => {
+                log::error!("[Node: {}] The node process terminated: {}", self.id, e)
+            }
+            _ => *process = Some(current),
+        }
+        result
+    }
+
+    // The methods of `Node` are not asynchronous: the calls they forward block until the child
+    // replies.
+    fn call_blocking(&self, request: &Request) -> Response {
+        async_std::task::block_on(self.call(request))
+    }
+
+    fn initialize(&self) -> ZFResult<State> {
+        let mut process = async_std::task::block_on(self.process.lock());
+        if process.is_none() {
+            *process = Some(async_std::task::block_on(self.spawn())?);
+        }
+        Ok(State::from::<EmptyState>(EmptyState {}))
+    }
+
+    fn finalize(&self) -> ZFResult<()> {
+        let result = self.call_blocking(&Request::Finalize).map(|_| ());
+        // The next `initialize` starts from a new process.
+        *async_std::task::block_on(self.process.lock()) = None;
+        result
+    }
+
+    fn on_control_message(
+        &self,
+        context: &mut Context,
+        port_id: &PortId,
+        message: &ControlMessage,
+    ) -> ZFResult<bool> {
+        let request = Request::OnControlMessage(
+            RemoteContext::new(context),
+            port_id.clone(),
+            message.clone(),
+        );
+        match self.call_blocking(&request)? {
+            Reply::OnControlMessage(remote, forward) => {
+                remote.apply(context);
+                Ok(forward)
+            }
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn save_state(&self) -> ZFResult<Option<Vec<u8>>> {
+        match self.call_blocking(&Request::SaveState)? {
+            Reply::SaveState(saved_state) => Ok(saved_state),
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    fn restore_state(&self, saved_state: &[u8]) -> ZFResult<()> {
+        self.call_blocking(&Request::RestoreState(saved_state.to_vec()))
+            .map(|_| ())
+    }
+}
+
+// The methods of `Node`, forwarded to the child. The proxy has no state of its own.
+macro_rules! isolated_node {
+    ($type:ty, $node:ident) => {
+        impl Node for $type {
+            fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+                self.$node.initialize()
+            }
+
+            fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+                self.$node.finalize()
+            }
+
+            fn on_control_message(
+                &self,
+                context: &mut Context,
+                _state: &mut State,
+                port_id: &PortId,
+                message: &ControlMessage,
+            ) -> ZFResult<bool> {
+                self.$node.on_control_message(context, port_id, message)
+            }
+
+            fn save_state(&self, _state: &mut State) -> ZFResult<Option<Vec<u8>>> {
+                self.$node.save_state()
+            }
+
+            fn restore_state(&self, _state: &mut State, saved_state: &[u8]) -> ZFResult<()> {
+                self.$node.restore_state(saved_state)
+            }
+        }
+    };
+}
+
+/// An Operator whose library is loaded by a child process.
+pub struct IsolatedOperator {
+    node: IsolatedNode,
+}
+
+impl IsolatedOperator {
+    pub fn new(mut record: OperatorRecord, host: &Path, trusted_keys: &[VerifyingKey]) -> Self {
+        // The child loads the library itself.
+        record.isolated = false;
+        Self {
+            node: IsolatedNode::new(
+                &record.id.to_string(),
+                host,
+                trusted_keys,
+                Request::LoadOperator(record),
+            ),
+        }
+    }
+}
+
+isolated_node!(IsolatedOperator, node);
+
+#[async_trait]
+impl AsyncOperator for IsolatedOperator {
+    fn timers(&self) -> Vec<Timer> {
+        match self.node.call_blocking(&Request::Timers) {
+            Ok(Reply::Timers(timers)) => timers,
+            Ok(reply) => {
+                log::error!("[Node: {}] {:?}", self.node.id, unexpected(reply));
+                vec![]
+            }
+            Err(e) => {
+                log::error!("[Node: {}] Unable to get the timers: {:?}", self.node.id, e);
+                vec![]
+            }
+        }
+    }
+
+    async fn input_rule(
+        &self,
+        context: &mut Context,
+        _state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        let request = Request::InputRule(RemoteContext::new(context), to_remote_tokens(tokens)?);
+        match self.node.call(&request).await? {
+            Reply::InputRule(remote, run, remote_tokens) => {
+                remote.apply(context);
+                *tokens = into_tokens(remote_tokens);
+                Ok(run)
+            }
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn run(
+        &self,
+        context: &mut Context,
+        _state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let inputs = inputs
+            .iter()
+            .map(|(port_id, message)| Ok((port_id.clone(), to_bytes_message(message)?)))
+            .collect::<ZFResult<HashMap<_, _>>>()?;
+
+        match self
+            .node
+            .call(&Request::Run(RemoteContext::new(context), inputs))
+            .await?
+        {
+            Reply::Run(remote, outputs) => {
+                remote.apply(context);
+                Ok(outputs)
+            }
+            reply => Err(unexpected(reply)),
+        }
+    }
+
+    async fn output_rule(
+        &self,
+        context: &mut Context,
+        _state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        let request = Request::OutputRule(
+            RemoteContext::new(context),
+            to_bytes_outputs(&outputs)?,
+            deadline_miss.as_ref().map(RemoteDeadlineMiss::new),
+        );
+        match self.node.call(&request).await? {
+            Reply::OutputRule(remote, outputs) => {
+                remote.apply(context);
+                Ok(outputs)
+            }
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+/// A Source whose library is loaded by a child process.
+pub struct IsolatedSource {
+    node: IsolatedNode,
+}
+
+impl IsolatedSource {
+    pub fn new(mut record: SourceRecord, host: &Path, trusted_keys: &[VerifyingKey]) -> Self {
+        // The child loads the library itself.
+        record.isolated = false;
+        Self {
+            node: IsolatedNode::new(
+                &record.id.to_string(),
+                host,
+                trusted_keys,
+                Request::LoadSource(record),
+            ),
+        }
+    }
+}
+
+isolated_node!(IsolatedSource, node);
+
+#[async_trait]
+impl Source for IsolatedSource {
+    async fn run(&self, context: &mut Context, _state: &mut State) -> ZFResult<Data> {
+        match self
+            .node
+            .call(&Request::Run(RemoteContext::new(context), HashMap::new()))
+            .await?
+        {
+            Reply::Run(remote, outputs) => {
+                remote.apply(context);
+                outputs
+                    .into_iter()
+                    .next()
+                    .map(|(_, data)| data)
+                    .ok_or_else(|| {
+                        ZFError::MissingOutput("The Source did not return any data.".into())
+                    })
+            }
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+/// A Sink whose library is loaded by a child process.
+pub struct IsolatedSink {
+    node: IsolatedNode,
+    port_id: PortId,
+}
+
+impl IsolatedSink {
+    pub fn new(mut record: SinkRecord, host: &Path, trusted_keys: &[VerifyingKey]) -> Self {
+        // The child loads the library itself.
+        record.isolated = false;
+        Self {
+            port_id: record.input.port_id.clone(),
+            node: IsolatedNode::new(
+                &record.id.to_string(),
+                host,
+                trusted_keys,
+                Request::LoadSink(record),
+            ),
+        }
+    }
+}
+
+isolated_node!(IsolatedSink, node);
+
+#[async_trait]
+impl Sink for IsolatedSink {
+    async fn run(
+        &self,
+        context: &mut Context,
+        _state: &mut State,
+        input: DataMessage,
+    ) -> ZFResult<()> {
+        let mut inputs = HashMap::with_capacity(1);
+        inputs.insert(self.port_id.clone(), to_bytes_message(&input)?);
+        match self
+            .node
+            .call(&Request::Run(RemoteContext::new(context), inputs))
+            .await?
+        {
+            Reply::Run(remote, _) => {
+                remote.apply(context);
+                Ok(())
+            }
+            reply => Err(unexpected(reply)),
+        }
+    }
+}
+
+// CHILD PROCESS
+
+// Evaluates `$body` with `$node` bound to the node hosted, whatever its kind.
+macro_rules! with_hosted {
+    ($hosted:expr, $node:ident => $body:expr) => {
+        match $hosted {
+            HostedNode::Operator(operator) => {
+                let $node = &operator.operator;
+                $body
+            }
+            HostedNode::Source(source) => {
+                let $node = &source.source;
+                $body
+            }
+            HostedNode::Sink(sink) => {
+                let $node = &sink.sink;
+                $body
+            }
+        }
+    };
+}
+
+enum HostedNode {
+    Operator(OperatorLoaded),
+    Source(SourceLoaded),
+    Sink(SinkLoaded),
+}
+
+impl HostedNode {
+    fn state(&self) -> &Mutex<State> {
+        match self {
+            Self::Operator(operator) => &operator.state,
+            Self::Source(source) => &source.state,
+            Self::Sink(sink) => &sink.state,
+        }
+    }
+
+    fn operator(&self) -> ZFResult<&OperatorLoaded> {
+        match self {
+            Self::Operator(operator) => Ok(operator),
+            _ => Err(ZFError::Unsupported),
+        }
+    }
+
+    async fn run(
+        &self,
+        context: &mut Context,
+        mut inputs: HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let mut state = self.state().lock().await;
+        let outputs = match self {
+            Self::Operator(operator) => {
+                operator
+                    .operator
+                    .run(context, &mut state, &mut inputs)
+                    .await?
+            }
+            Self::Source(source) => {
+                let data = source.source.run(context, &mut state).await?;
+                let mut outputs = HashMap::with_capacity(1);
+                outputs.insert(source.output.port_id.clone(), data);
+                outputs
+            }
+            Self::Sink(sink) => {
+                let input = inputs
+                    .remove(&sink.input.port_id)
+                    .ok_or_else(|| ZFError::MissingInput(sink.input.port_id.to_string()))?;
+                sink.sink.run(context, &mut state, input).await?;
+                HashMap::new()
+            }
+        };
+        to_bytes_outputs(&outputs)
+    }
+}
+
+async fn handle(loader: &Loader, node: &mut Option<HostedNode>, request: Request) -> Response {
+    let hosted = match request {
+        Request::LoadOperator(record) => {
+            *node = Some(HostedNode::Operator(loader.load_operator(record)?));
+            return Ok(Reply::Done);
+        }
+        Request::LoadSource(record) => {
+            *node = Some(HostedNode::Source(loader.load_source(record)?));
+            return Ok(Reply::Done);
+        }
+        Request::LoadSink(record) => {
+            *node = Some(HostedNode::Sink(loader.load_sink(record)?));
+            return Ok(Reply::Done);
+        }
+        _ => node.as_ref().ok_or(ZFError::InvalidState)?,
+    };
+
+    match request {
+        Request::LoadOperator(_) | Request::LoadSource(_) | Request::LoadSink(_) => {
+            Err(ZFError::InvalidState)
+        }
+        Request::Timers => Ok(Reply::Timers(hosted.operator()?.operator.timers())),
+        Request::InputRule(remote, tokens) => {
+            let operator = hosted.operator()?;
+            let mut context = remote.into_context();
+            let mut tokens = into_tokens(tokens);
+            let mut state = operator.state.lock().await;
+            let run = operator
+                .operator
+                .input_rule(&mut context, &mut state, &mut tokens)
+                .await?;
+            Ok(Reply::InputRule(
+                RemoteContext::new(&context),
+                run,
+                to_remote_tokens(&tokens)?,
+            ))
+        }
+        Request::Run(remote, inputs) => {
+            let mut context = remote.into_context();
+            let outputs = hosted.run(&mut context, inputs).await?;
+            Ok(Reply::Run(RemoteContext::new(&context), outputs))
+        }
+        Request::OutputRule(remote, outputs, deadline_miss) => {
+            let operator = hosted.operator()?;
+            let mut context = remote.into_context();
+            let mut state = operator.state.lock().await;
+            let outputs = operator
+                .operator
+                .output_rule(
+                    &mut context,
+                    &mut state,
+                    outputs,
+                    deadline_miss.map(RemoteDeadlineMiss::into_miss),
+                )
+                .await?;
+            Ok(Reply::OutputRule(
+                RemoteContext::new(&context),
+                to_bytes_node_outputs(outputs)?,
+            ))
+        }
+        Request::OnControlMessage(remote, port_id, message) => {
+            let mut context = remote.into_context();
+            let mut state = hosted.state().lock().await;
+            let forward = with_hosted!(hosted, node => {
+                node.on_control_message(&mut context, &mut state, &port_id, &message)?
+            });
+            Ok(Reply::OnControlMessage(
+                RemoteContext::new(&context),
+                forward,
+            ))
+        }
+        Request::SaveState => {
+            let mut state = hosted.state().lock().await;
+            let saved_state = with_hosted!(hosted, node => node.save_state(&mut state)?);
+            Ok(Reply::SaveState(saved_state))
+        }
+        Request::RestoreState(saved_state) => {
+            let mut state = hosted.state().lock().await;
+            with_hosted!(hosted, node => node.restore_state(&mut state, &saved_state)?);
+            Ok(Reply::Done)
+        }
+        Request::Finalize => {
+            let mut state = hosted.state().lock().await;
+            with_hosted!(hosted, node => node.finalize(&mut state)?);
+            Ok(Reply::Done)
+        }
+    }
+}
+
+/// Hosts a node in the current process, serving the runtime listening on `socket` until it
+/// closes the connection.
+///
+/// The library is checked again before being loaded, against its checksum and the
+/// `trusted_keys` of the runtime.
+pub async fn host(socket: &Path, trusted_keys: &[String]) -> ZFResult<()> {
+    let mut stream = UnixStream::connect(socket).await?;
+    let loader =
+        Loader::new(LoaderConfig { extensions: vec![] }).with_trusted_keys(trusted_keys)?;
+    let mut node = None;
+
+    loop {
+        let request = match read_message::<Request>(&mut stream).await {
+            Ok(request) => request,
+            // The runtime closed the connection.
+            Err(ZFError::IOError(_)) => return Ok(()),
+            Err(e) => return Err(e),
+        };
+
+        let response = handle(&loader, &mut node, request).await;
+        if write_message(&mut stream, &response).await.is_err() {
+            // Some errors cannot be serialized.
+            let response: Response = Err(ZFError::GenericError);
+            write_message(&mut stream, &response).await?;
+        }
+    }
+}
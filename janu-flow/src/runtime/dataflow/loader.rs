@@ -12,11 +12,24 @@
 //   open
 //
 
-use super::node::{OperatorLoaded, SinkLoaded, SourceLoaded};
+use super::builtin;
+use super::cabi::{
+    self, COperator, CSink, CSource, ZFCOperatorVTable, ZFCSinkVTable, ZFCSourceVTable,
+};
+use super::integrity;
+#[cfg(target_family = "unix")]
+use super::isolation;
+use super::node::DynOperator;
+use super::node::{OperatorImplementation, OperatorLoaded, SinkLoaded, SourceLoaded};
+use super::registry::NodeRegistry;
+#[cfg(feature = "wasm")]
+use super::wasm;
+use crate::model::make_file_path;
 use crate::model::node::{OperatorRecord, SinkRecord, SourceRecord};
 use crate::serde::{Deserialize, Serialize};
-use crate::{Configuration, Operator, Sink, Source, ZFError, ZFResult};
+use crate::{AsyncOperator, Configuration, Operator, Sink, Source, ZFError, ZFResult};
 use async_std::sync::Arc;
+use ed25519_dalek::VerifyingKey;
 
 #[cfg(target_family = "unix")]
 use libloading::os::unix::Library;
@@ -43,6 +56,16 @@ pub struct OperatorDeclaration {
     pub register: OperatorRegisterFn,
 }
 
+// ASYNC OPERATOR
+
+pub type AsyncOperatorRegisterFn = fn() -> ZFResult<Arc<dyn AsyncOperator>>;
+
+pub struct AsyncOperatorDeclaration {
+    pub rustc_version: &'static str,
+    pub core_version: &'static str,
+    pub register: AsyncOperatorRegisterFn,
+}
+
 // SOURCE
 
 pub type SourceRegisterFn = fn() -> ZFResult<Arc<dyn Source>>;
@@ -74,25 +97,101 @@ pub struct ExtensibleImplementation {
     pub(crate) config_lib_key: String,
 }
 
+/// The executable hosting the isolated nodes, looked up in the `PATH` by default.
+pub static NODE_HOST: &str = "janu-flow-node-host";
+
 // Loader Config
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LoaderConfig {
     pub extensions: Vec<ExtensibleImplementation>,
 }
 
+/// The resources a WebAssembly node can use, see [`super::wasm`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WasmLimits {
+    /// The maximum size, in bytes, of the memory of a node.
+    pub max_memory: usize,
+    /// The fuel given to each call of a node, roughly the number of instructions it can execute.
+    pub fuel: u64,
+    /// The maximum number of bytes a node can emit during a call.
+    pub max_output: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        Self {
+            max_memory: 64 * 1024 * 1024,
+            fuel: 100_000_000,
+            max_output: 16 * 1024 * 1024,
+        }
+    }
+}
+
 pub struct Loader {
     pub(crate) config: LoaderConfig,
+    pub(crate) registry: NodeRegistry,
+    pub(crate) trusted_keys: Vec<VerifyingKey>,
+    pub(crate) wasm_limits: WasmLimits,
+    pub(crate) node_host: PathBuf,
 }
 
 impl Loader {
     pub fn new(config: LoaderConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            registry: NodeRegistry::default(),
+            trusted_keys: vec![],
+            wasm_limits: WasmLimits::default(),
+            node_host: PathBuf::from(NODE_HOST),
+        }
+    }
+
+    /// Sets the registry of the nodes linked into the binary, consulted before loading a node.
+    pub fn with_registry(mut self, registry: NodeRegistry) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// Sets the hex-encoded ed25519 public keys the libraries must be signed with.
+    ///
+    /// Without trusted keys, only the checksums of the libraries are checked.
+    pub fn with_trusted_keys(mut self, trusted_keys: &[String]) -> ZFResult<Self> {
+        self.trusted_keys = trusted_keys
+            .iter()
+            .map(|key| integrity::try_parse_verifying_key(key))
+            .collect::<ZFResult<Vec<_>>>()?;
+        Ok(self)
+    }
+
+    /// Sets the resources the WebAssembly nodes can use.
+    pub fn with_wasm_limits(mut self, wasm_limits: WasmLimits) -> Self {
+        self.wasm_limits = wasm_limits;
+        self
+    }
+
+    /// Sets the executable hosting the isolated nodes, `janu-flow-node-host` by default.
+    pub fn with_node_host(mut self, node_host: impl Into<PathBuf>) -> Self {
+        self.node_host = node_host.into();
+        self
     }
 
     /// # Safety
     ///
     /// TODO remove all copy-pasted code, make macros/functions instead
     pub fn load_operator(&self, record: OperatorRecord) -> ZFResult<OperatorLoaded> {
+        let (record, implementation) = self.load_operator_implementation(record)?;
+        OperatorLoaded::try_new_async(record, implementation.library, implementation.operator)
+    }
+
+    /// Loads the implementation of the operator, without initializing it.
+    ///
+    /// The record is given back as the configuration of an operator implemented through an
+    /// extension is rewritten for its wrapper.
+    pub(crate) fn load_operator_implementation(
+        &self,
+        record: OperatorRecord,
+    ) -> ZFResult<(OperatorRecord, OperatorImplementation)> {
         let uri = record.uri.clone().ok_or_else(|| {
             ZFError::LoadingError(format!(
                 "Missing URI for dynamically loaded Operator < {} >.",
@@ -104,7 +203,7 @@ impl Loader {
 
         match uri.scheme() {
             "file" => {
-                let file_path = Self::make_file_path(uri)?;
+                let file_path = make_file_path(&uri)?;
                 let file_extension = Self::get_file_extension(&file_path).ok_or_else(|| {
                     ZFError::LoadingError(format!(
                         "Missing file extension for dynamically loaded Operator < {} , {:?}>.",
@@ -113,14 +212,43 @@ impl Loader {
                     ))
                 })?;
 
+                // Only the nodes in a library can be isolated.
+                if record.isolated && !Self::is_lib(&file_extension) {
+                    return Err(ZFError::Unsupported);
+                }
+
+                #[cfg(feature = "wasm")]
+                {
+                    if file_extension == wasm::WASM_EXTENSION {
+                        let node = self
+                            .load_wasm(&file_path, &record.checksum, &record.signature)?
+                            .with_outputs(record.outputs.iter().map(|port| port.port_id.clone()));
+                        let operator = Arc::new(wasm::WasmOperator(node));
+                        return Ok((
+                            record,
+                            OperatorImplementation::new(Arc::new(DynOperator(operator)), None),
+                        ));
+                    }
+                }
+
                 match Self::is_lib(&file_extension) {
+                    true if record.isolated => self.load_isolated_operator(record, &file_path),
                     true => {
-                        let (lib, op) = unsafe { Self::load_lib_operator(file_path) }?;
-                        Ok(OperatorLoaded::try_new(record, Some(Arc::new(lib)), op)?)
+                        let (lib, op) = unsafe {
+                            self.load_lib_operator(file_path, &record.checksum, &record.signature)
+                        }?;
+                        Ok((record, OperatorImplementation::new(op, Some(Arc::new(lib)))))
                     }
                     _ => Ok(self.load_operator_from_extension(record, file_path)?),
                 }
             }
+            builtin::BUILTIN_SCHEME => {
+                let operator = builtin::operator(builtin::name(&uri)?, &record)?;
+                Ok((
+                    record,
+                    OperatorImplementation::new(Arc::new(DynOperator(operator)), None),
+                ))
+            }
             _ => Err(ZFError::Unimplemented),
         }
     }
@@ -137,7 +265,7 @@ impl Loader {
 
         match uri.scheme() {
             "file" => {
-                let file_path = Self::make_file_path(uri)?;
+                let file_path = make_file_path(&uri)?;
                 let file_extension = Self::get_file_extension(&file_path).ok_or_else(|| {
                     ZFError::LoadingError(format!(
                         "Missing file extension for dynamically loaded Source < {} , {:?}>.",
@@ -146,14 +274,40 @@ impl Loader {
                     ))
                 })?;
 
+                // Only the nodes in a library can be isolated.
+                if record.isolated && !Self::is_lib(&file_extension) {
+                    return Err(ZFError::Unsupported);
+                }
+
+                #[cfg(feature = "wasm")]
+                {
+                    if file_extension == wasm::WASM_EXTENSION {
+                        let node = self
+                            .load_wasm(&file_path, &record.checksum, &record.signature)?
+                            .with_outputs([record.output.port_id.clone()]);
+                        return SourceLoaded::try_new(
+                            record,
+                            None,
+                            Arc::new(wasm::WasmSource(node)),
+                        );
+                    }
+                }
+
                 match Self::is_lib(&file_extension) {
+                    true if record.isolated => self.load_isolated_source(record, &file_path),
                     true => {
-                        let (lib, op) = unsafe { Self::load_lib_source(file_path) }?;
+                        let (lib, op) = unsafe {
+                            self.load_lib_source(file_path, &record.checksum, &record.signature)
+                        }?;
                         Ok(SourceLoaded::try_new(record, Some(Arc::new(lib)), op)?)
                     }
                     _ => Ok(self.load_source_from_extension(record, file_path)?),
                 }
             }
+            builtin::BUILTIN_SCHEME => {
+                let source = builtin::source(builtin::name(&uri)?, &record)?;
+                Ok(SourceLoaded::try_new(record, None, source)?)
+            }
             _ => Err(ZFError::Unimplemented),
         }
     }
@@ -170,7 +324,7 @@ impl Loader {
 
         match uri.scheme() {
             "file" => {
-                let file_path = Self::make_file_path(uri)?;
+                let file_path = make_file_path(&uri)?;
                 let file_extension = Self::get_file_extension(&file_path).ok_or_else(|| {
                     ZFError::LoadingError(format!(
                         "Missing file extension for dynamically loaded Sink < {} , {:?}>.",
@@ -179,26 +333,73 @@ impl Loader {
                     ))
                 })?;
 
+                // Only the nodes in a library can be isolated.
+                if record.isolated && !Self::is_lib(&file_extension) {
+                    return Err(ZFError::Unsupported);
+                }
+
+                #[cfg(feature = "wasm")]
+                {
+                    if file_extension == wasm::WASM_EXTENSION {
+                        let node =
+                            self.load_wasm(&file_path, &record.checksum, &record.signature)?;
+                        let sink = wasm::WasmSink {
+                            node,
+                            port_id: record.input.port_id.clone(),
+                        };
+                        return SinkLoaded::try_new(record, None, Arc::new(sink));
+                    }
+                }
+
                 match Self::is_lib(&file_extension) {
+                    true if record.isolated => self.load_isolated_sink(record, &file_path),
                     true => {
-                        let (lib, op) = unsafe { Self::load_lib_sink(file_path) }?;
+                        let (lib, op) = unsafe {
+                            self.load_lib_sink(file_path, &record.checksum, &record.signature)
+                        }?;
                         Ok(SinkLoaded::try_new(record, Some(Arc::new(lib)), op)?)
                     }
                     _ => Ok(self.load_sink_from_extension(record, file_path)?),
                 }
             }
+            builtin::BUILTIN_SCHEME => {
+                let sink = builtin::sink(builtin::name(&uri)?, &record)?;
+                Ok(SinkLoaded::try_new(record, None, sink)?)
+            }
             _ => Err(ZFError::Unimplemented),
         }
     }
 
-    /// Load the library of the operator.
+    /// Load the library of the operator, after checking it against its `checksum` and
+    /// `signature`. The library loaded is the one that was checked, see
+    /// [`integrity::verify_library`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Loader::open_lib_operator`].
+    unsafe fn load_lib_operator(
+        &self,
+        path: PathBuf,
+        checksum: &Option<String>,
+        signature: &Option<String>,
+    ) -> ZFResult<(Library, Arc<dyn AsyncOperator>)> {
+        let library = integrity::verify_library(&path, checksum, signature, &self.trusted_keys)?;
+        Self::open_lib_operator(library.path().to_path_buf())
+    }
+
+    /// Open the library of the operator.
+    ///
+    /// The library can either export an `Operator`, through `export_operator!`, an
+    /// `AsyncOperator`, through `export_async_operator!`, or a `ZFCOperatorVTable`, see
+    /// [`cabi`].
     ///
     /// # Safety
     ///
     /// This function dynamically loads an external library, things can go wrong:
-    /// - it will panic if the symbol `zfoperator_declaration` is not found,
+    /// - it will panic if none of the symbols `zfoperator_declaration`,
+    ///   `zfasyncoperator_declaration` and `zfc_operator_vtable` is found,
     /// - be sure to *trust* the code you are loading.
-    unsafe fn load_lib_operator(path: PathBuf) -> ZFResult<(Library, Arc<dyn Operator>)> {
+    unsafe fn open_lib_operator(path: PathBuf) -> ZFResult<(Library, Arc<dyn AsyncOperator>)> {
         log::debug!("Operator Loading {:#?}", path);
 
         #[cfg(target_family = "unix")]
@@ -207,26 +408,68 @@ impl Loader {
         #[cfg(target_family = "windows")]
         let library = Library::new(path)?;
 
-        let decl = library
-            .get::<*mut OperatorDeclaration>(b"zfoperator_declaration\0")?
-            .read();
+        let operator: Arc<dyn AsyncOperator> = match library
+            .get::<*mut OperatorDeclaration>(b"zfoperator_declaration\0")
+        {
+            Ok(decl) => {
+                let decl = decl.read();
+
+                // version checks to prevent accidental ABI incompatibilities
+                if decl.rustc_version != RUSTC_VERSION || decl.core_version != CORE_VERSION {
+                    return Err(ZFError::VersionMismatch);
+                }
 
-        // version checks to prevent accidental ABI incompatibilities
-        if decl.rustc_version != RUSTC_VERSION || decl.core_version != CORE_VERSION {
-            return Err(ZFError::VersionMismatch);
-        }
+                Arc::new(DynOperator((decl.register)()?))
+            }
+            Err(_) => match library
+                .get::<*mut AsyncOperatorDeclaration>(b"zfasyncoperator_declaration\0")
+            {
+                Ok(decl) => {
+                    let decl = decl.read();
+
+                    // version checks to prevent accidental ABI incompatibilities
+                    if decl.rustc_version != RUSTC_VERSION || decl.core_version != CORE_VERSION {
+                        return Err(ZFError::VersionMismatch);
+                    }
+
+                    (decl.register)()?
+                }
+                Err(_) => {
+                    let vtable =
+                        library.get::<*const ZFCOperatorVTable>(b"zfc_operator_vtable\0")?;
+                    let operator = COperator::try_new(cabi::read_vtable(*vtable)?)?;
+                    Arc::new(DynOperator(Arc::new(operator)))
+                }
+            },
+        };
+
+        Ok((library, operator))
+    }
 
-        Ok((library, (decl.register)()?))
+    /// Load the library of a source, after checking it against its `checksum` and `signature`.
+    ///
+    /// # Safety
+    ///
+    /// See [`Loader::open_lib_source`].
+    unsafe fn load_lib_source(
+        &self,
+        path: PathBuf,
+        checksum: &Option<String>,
+        signature: &Option<String>,
+    ) -> ZFResult<(Library, Arc<dyn Source>)> {
+        let library = integrity::verify_library(&path, checksum, signature, &self.trusted_keys)?;
+        Self::open_lib_source(library.path().to_path_buf())
     }
 
-    /// Load the library of a source.
+    /// Open the library of a source.
     ///
     /// # Safety
     ///
     /// This function dynamically loads an external library, things can go wrong:
-    /// - it will panic if the symbol `zfsource_declaration` is not found,
+    /// - it will panic if neither the symbol `zfsource_declaration` nor the symbol
+    ///   `zfc_source_vtable` is found,
     /// - be sure to *trust* the code you are loading.
-    unsafe fn load_lib_source(path: PathBuf) -> ZFResult<(Library, Arc<dyn Source>)> {
+    unsafe fn open_lib_source(path: PathBuf) -> ZFResult<(Library, Arc<dyn Source>)> {
         log::debug!("Source Loading {:#?}", path);
 
         #[cfg(target_family = "unix")]
@@ -235,26 +478,51 @@ impl Loader {
         #[cfg(target_family = "windows")]
         let library = Library::new(path)?;
 
-        let decl = library
-            .get::<*mut SourceDeclaration>(b"zfsource_declaration\0")?
-            .read();
+        let source: Arc<dyn Source> =
+            match library.get::<*mut SourceDeclaration>(b"zfsource_declaration\0") {
+                Ok(decl) => {
+                    let decl = decl.read();
 
-        // version checks to prevent accidental ABI incompatibilities
-        if decl.rustc_version != RUSTC_VERSION || decl.core_version != CORE_VERSION {
-            return Err(ZFError::VersionMismatch);
-        }
+                    // version checks to prevent accidental ABI incompatibilities
+                    if decl.rustc_version != RUSTC_VERSION || decl.core_version != CORE_VERSION {
+                        return Err(ZFError::VersionMismatch);
+                    }
+
+                    (decl.register)()?
+                }
+                Err(_) => {
+                    let vtable = library.get::<*const ZFCSourceVTable>(b"zfc_source_vtable\0")?;
+                    Arc::new(CSource::try_new(cabi::read_vtable(*vtable)?)?)
+                }
+            };
+
+        Ok((library, source))
+    }
 
-        Ok((library, (decl.register)()?))
+    /// Load the library of a sink, after checking it against its `checksum` and `signature`.
+    ///
+    /// # Safety
+    ///
+    /// See [`Loader::open_lib_sink`].
+    unsafe fn load_lib_sink(
+        &self,
+        path: PathBuf,
+        checksum: &Option<String>,
+        signature: &Option<String>,
+    ) -> ZFResult<(Library, Arc<dyn Sink>)> {
+        let library = integrity::verify_library(&path, checksum, signature, &self.trusted_keys)?;
+        Self::open_lib_sink(library.path().to_path_buf())
     }
 
-    /// Load the library of a sink.
+    /// Open the library of a sink.
     ///
     /// # Safety
     ///
     /// This function dynamically loads an external library, things can go wrong:
-    /// - it will panic if the symbol `zfsink_declaration` is not found,
+    /// - it will panic if neither the symbol `zfsink_declaration` nor the symbol
+    ///   `zfc_sink_vtable` is found,
     /// - be sure to *trust* the code you are loading.
-    unsafe fn load_lib_sink(path: PathBuf) -> ZFResult<(Library, Arc<dyn Sink>)> {
+    unsafe fn open_lib_sink(path: PathBuf) -> ZFResult<(Library, Arc<dyn Sink>)> {
         log::debug!("Sink Loading {:#?}", path);
 
         #[cfg(target_family = "unix")]
@@ -263,26 +531,118 @@ impl Loader {
         #[cfg(target_family = "windows")]
         let library = Library::new(path)?;
 
-        let decl = library
-            .get::<*mut SinkDeclaration>(b"zfsink_declaration\0")?
-            .read();
+        let sink: Arc<dyn Sink> = match library.get::<*mut SinkDeclaration>(b"zfsink_declaration\0")
+        {
+            Ok(decl) => {
+                let decl = decl.read();
 
-        // version checks to prevent accidental ABI incompatibilities
-        if decl.rustc_version != RUSTC_VERSION || decl.core_version != CORE_VERSION {
-            return Err(ZFError::VersionMismatch);
-        }
+                // version checks to prevent accidental ABI incompatibilities
+                if decl.rustc_version != RUSTC_VERSION || decl.core_version != CORE_VERSION {
+                    return Err(ZFError::VersionMismatch);
+                }
 
-        Ok((library, (decl.register)()?))
-    }
-    fn make_file_path(uri: Url) -> ZFResult<PathBuf> {
-        let mut path = PathBuf::new();
-        let file_path = match uri.host_str() {
-            Some(h) => format!("{}{}", h, uri.path()),
-            None => uri.path().to_string(),
+                (decl.register)()?
+            }
+            Err(_) => {
+                let vtable = library.get::<*const ZFCSinkVTable>(b"zfc_sink_vtable\0")?;
+                Arc::new(CSink::try_new(cabi::read_vtable(*vtable)?)?)
+            }
         };
-        path.push(file_path);
-        let path = std::fs::canonicalize(path)?;
-        Ok(path)
+
+        Ok((library, sink))
+    }
+
+    /// Compile the WebAssembly module of a node, after checking it against its `checksum` and
+    /// `signature`. The module compiled is the content that was checked.
+    #[cfg(feature = "wasm")]
+    fn load_wasm(
+        &self,
+        path: &Path,
+        checksum: &Option<String>,
+        signature: &Option<String>,
+    ) -> ZFResult<wasm::WasmNode> {
+        log::debug!("WebAssembly module Loading {:#?}", path);
+        let bytes = integrity::read_verified(path, checksum, signature, &self.trusted_keys)?;
+        wasm::WasmNode::try_from_bytes(path, &bytes, self.wasm_limits.clone())
+    }
+
+    /// Loads the proxy of an operator whose library is loaded by a child process, see
+    /// [`super::isolation`]. The library is checked before spawning the child, and by the child
+    /// with the same trusted keys.
+    #[cfg(target_family = "unix")]
+    fn load_isolated_operator(
+        &self,
+        record: OperatorRecord,
+        path: &Path,
+    ) -> ZFResult<(OperatorRecord, OperatorImplementation)> {
+        log::debug!("Isolated Operator Loading {:#?}", path);
+        integrity::verify(
+            path,
+            &record.checksum,
+            &record.signature,
+            &self.trusted_keys,
+        )?;
+        let operator = Arc::new(isolation::IsolatedOperator::new(
+            record.clone(),
+            &self.node_host,
+            &self.trusted_keys,
+        ));
+        Ok((record, OperatorImplementation::new(operator, None)))
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    fn load_isolated_operator(
+        &self,
+        _record: OperatorRecord,
+        _path: &Path,
+    ) -> ZFResult<(OperatorRecord, OperatorImplementation)> {
+        Err(ZFError::Unsupported)
+    }
+
+    /// Loads the proxy of a source whose library is loaded by a child process.
+    #[cfg(target_family = "unix")]
+    fn load_isolated_source(&self, record: SourceRecord, path: &Path) -> ZFResult<SourceLoaded> {
+        log::debug!("Isolated Source Loading {:#?}", path);
+        integrity::verify(
+            path,
+            &record.checksum,
+            &record.signature,
+            &self.trusted_keys,
+        )?;
+        let source = Arc::new(isolation::IsolatedSource::new(
+            record.clone(),
+            &self.node_host,
+            &self.trusted_keys,
+        ));
+        SourceLoaded::try_new(record, None, source)
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    fn load_isolated_source(&self, _record: SourceRecord, _path: &Path) -> ZFResult<SourceLoaded> {
+        Err(ZFError::Unsupported)
+    }
+
+    /// Loads the proxy of a sink whose library is loaded by a child process.
+    #[cfg(target_family = "unix")]
+    fn load_isolated_sink(&self, record: SinkRecord, path: &Path) -> ZFResult<SinkLoaded> {
+        log::debug!("Isolated Sink Loading {:#?}", path);
+        integrity::verify(
+            path,
+            &record.checksum,
+            &record.signature,
+            &self.trusted_keys,
+        )?;
+        let sink = Arc::new(isolation::IsolatedSink::new(
+            record.clone(),
+            &self.node_host,
+            &self.trusted_keys,
+        ));
+        SinkLoaded::try_new(record, None, sink)
+    }
+
+    #[cfg(not(target_family = "unix"))]
+    fn load_isolated_sink(&self, _record: SinkRecord, _path: &Path) -> ZFResult<SinkLoaded> {
+        Err(ZFError::Unsupported)
     }
 
     fn is_lib(ext: &str) -> bool {
@@ -305,7 +665,7 @@ impl Loader {
         &self,
         mut record: OperatorRecord,
         file_path: PathBuf,
-    ) -> ZFResult<OperatorLoaded> {
+    ) -> ZFResult<(OperatorRecord, OperatorImplementation)> {
         let file_extension = Self::get_file_extension(&file_path).ok_or_else(|| {
             ZFError::LoadingError(format!(
                 "Missing file extension for dynamically loaded Operator < {} , {:?}>.",
@@ -321,6 +681,15 @@ impl Loader {
             .find(|e| e.file_extension == file_extension)
         {
             Some(e) => {
+                // The wrapper is part of the configuration of the runtime, only the file
+                // implementing the node is checked.
+                integrity::verify(
+                    &file_path,
+                    &record.checksum,
+                    &record.signature,
+                    &self.trusted_keys,
+                )?;
+
                 let wrapper_file_path = std::fs::canonicalize(&e.operator_lib)?;
                 record.configuration = Some(Self::generate_wrapper_config(
                     record.configuration,
@@ -328,8 +697,8 @@ impl Loader {
                     &file_path,
                 )?);
 
-                let (lib, op) = unsafe { Self::load_lib_operator(wrapper_file_path) }?;
-                Ok(OperatorLoaded::try_new(record, Some(Arc::new(lib)), op)?)
+                let (lib, op) = unsafe { Self::open_lib_operator(wrapper_file_path) }?;
+                Ok((record, OperatorImplementation::new(op, Some(Arc::new(lib)))))
             }
             _ => Err(ZFError::Unimplemented),
         }
@@ -355,6 +724,15 @@ impl Loader {
             .find(|e| e.file_extension == file_extension)
         {
             Some(e) => {
+                // The wrapper is part of the configuration of the runtime, only the file
+                // implementing the node is checked.
+                integrity::verify(
+                    &file_path,
+                    &record.checksum,
+                    &record.signature,
+                    &self.trusted_keys,
+                )?;
+
                 let wrapper_file_path = std::fs::canonicalize(&e.source_lib)?;
                 record.configuration = Some(Self::generate_wrapper_config(
                     record.configuration,
@@ -362,7 +740,7 @@ impl Loader {
                     &file_path,
                 )?);
 
-                let (lib, op) = unsafe { Self::load_lib_source(wrapper_file_path) }?;
+                let (lib, op) = unsafe { Self::open_lib_source(wrapper_file_path) }?;
                 Ok(SourceLoaded::try_new(record, Some(Arc::new(lib)), op)?)
             }
             _ => Err(ZFError::Unimplemented),
@@ -389,6 +767,15 @@ impl Loader {
             .find(|e| e.file_extension == file_extension)
         {
             Some(e) => {
+                // The wrapper is part of the configuration of the runtime, only the file
+                // implementing the node is checked.
+                integrity::verify(
+                    &file_path,
+                    &record.checksum,
+                    &record.signature,
+                    &self.trusted_keys,
+                )?;
+
                 let wrapper_file_path = std::fs::canonicalize(&e.sink_lib)?;
                 record.configuration = Some(Self::generate_wrapper_config(
                     record.configuration,
@@ -396,7 +783,7 @@ impl Loader {
                     &file_path,
                 )?);
 
-                let (lib, op) = unsafe { Self::load_lib_sink(wrapper_file_path) }?;
+                let (lib, op) = unsafe { Self::open_lib_sink(wrapper_file_path) }?;
                 Ok(SinkLoaded::try_new(record, Some(Arc::new(lib)), op)?)
             }
             _ => Err(ZFError::Unimplemented),
@@ -12,9 +12,17 @@
 //   open
 //
 
+pub mod builtin;
+pub mod cabi;
 pub mod instance;
+pub mod integrity;
+#[cfg(target_family = "unix")]
+pub mod isolation;
 pub mod loader;
 pub mod node;
+pub mod registry;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
 use async_std::sync::{Arc, Mutex};
 use std::collections::HashMap;
@@ -26,11 +34,13 @@ use crate::model::dataflow::record::DataFlowRecord;
 use crate::model::dataflow::validator::DataflowValidator;
 use crate::model::deadline::E2EDeadlineRecord;
 use crate::model::link::{LinkDescriptor, PortDescriptor};
+use crate::model::node::{InputRulePolicy, MissedTickPolicy, RestartPolicy};
 use crate::model::{InputDescriptor, OutputDescriptor};
-use crate::runtime::dataflow::node::{OperatorLoaded, SinkLoaded, SourceLoaded};
+use crate::runtime::dataflow::node::{DynOperator, OperatorLoaded, SinkLoaded, SourceLoaded};
 use crate::runtime::RuntimeContext;
 use crate::{
-    DurationDescriptor, FlowId, NodeId, Operator, PortId, PortType, Sink, Source, State, ZFResult,
+    AsyncOperator, DurationDescriptor, FlowId, NodeId, Operator, PortId, PortType, Sink, Source,
+    State, ZFError, ZFResult,
 };
 
 pub struct Dataflow {
@@ -72,12 +82,17 @@ impl Dataflow {
         }
     }
 
+    /// Creates the nodes of `record` mapped to this runtime: the nodes registered in the
+    /// `NodeRegistry` of the `Loader` are created by their factory, the others are loaded.
     pub fn try_new(context: RuntimeContext, record: DataFlowRecord) -> ZFResult<Self> {
         let res_sources: ZFResult<Vec<SourceLoaded>> = record
             .sources
             .into_iter()
             .filter(|source| source.runtime == context.runtime_name)
-            .map(|r| context.loader.load_source(r))
+            .map(|r| match context.loader.registry.source(&r) {
+                Some(source) => SourceLoaded::try_new(r, None, source?),
+                None => context.loader.load_source(r),
+            })
             .collect();
         let sources: HashMap<_, _> = res_sources?
             .into_iter()
@@ -88,7 +103,10 @@ impl Dataflow {
             .operators
             .into_iter()
             .filter(|operator| operator.runtime == context.runtime_name)
-            .map(|r| context.loader.load_operator(r))
+            .map(|r| match context.loader.registry.operator(&r) {
+                Some(operator) => OperatorLoaded::try_new_async(r, None, operator?),
+                None => context.loader.load_operator(r),
+            })
             .collect();
         let operators: HashMap<_, _> = res_operators?
             .into_iter()
@@ -99,7 +117,10 @@ impl Dataflow {
             .sinks
             .into_iter()
             .filter(|sink| sink.runtime == context.runtime_name)
-            .map(|r| context.loader.load_sink(r))
+            .map(|r| match context.loader.registry.sink(&r) {
+                Some(sink) => SinkLoaded::try_new(r, None, sink?),
+                None => context.loader.load_sink(r),
+            })
             .collect();
         let sinks: HashMap<_, _> = res_sinks?
             .into_iter()
@@ -151,8 +172,10 @@ impl Dataflow {
                 output,
                 state: Arc::new(Mutex::new(state)),
                 period: period.map(|dur_desc| dur_desc.to_duration()),
+                missed_ticks: MissedTickPolicy::default(),
                 source,
                 library: None,
+                restart_policy: RestartPolicy::default(),
                 end_to_end_deadlines: vec![],
             },
         );
@@ -168,6 +191,25 @@ impl Dataflow {
         local_deadline: Option<Duration>,
         state: State,
         operator: Arc<dyn Operator>,
+    ) -> ZFResult<()> {
+        self.try_add_static_async_operator(
+            id,
+            inputs,
+            outputs,
+            local_deadline,
+            state,
+            Arc::new(DynOperator(operator)),
+        )
+    }
+
+    pub fn try_add_static_async_operator(
+        &mut self,
+        id: NodeId,
+        inputs: Vec<PortDescriptor>,
+        outputs: Vec<PortDescriptor>,
+        local_deadline: Option<Duration>,
+        state: State,
+        operator: Arc<dyn AsyncOperator>,
     ) -> ZFResult<()> {
         self.validator
             .try_add_operator(id.clone(), &inputs, &outputs)?;
@@ -188,9 +230,11 @@ impl Dataflow {
                 inputs,
                 outputs,
                 local_deadline,
+                input_rule: None,
                 state: Arc::new(Mutex::new(state)),
                 operator,
                 library: None,
+                restart_policy: RestartPolicy::default(),
                 end_to_end_deadlines: vec![],
             },
         );
@@ -215,6 +259,7 @@ impl Dataflow {
                 state: Arc::new(Mutex::new(state)),
                 sink,
                 library: None,
+                restart_policy: RestartPolicy::default(),
                 end_to_end_deadlines: vec![],
             },
         );
@@ -222,6 +267,77 @@ impl Dataflow {
         Ok(())
     }
 
+    /// Sets the restart policy of a node previously added to the Dataflow.
+    ///
+    /// Nodes added through `try_add_static_source`, `try_add_static_sink` and
+    /// `try_add_static_operator` are always restarted.
+    ///
+    /// ## Error
+    ///
+    /// This function will return error if the node is not a Source, an Operator or a Sink of
+    /// the Dataflow.
+    pub fn try_set_restart_policy(
+        &mut self,
+        id: &NodeId,
+        restart_policy: RestartPolicy,
+    ) -> ZFResult<()> {
+        if let Some(source) = self.sources.get_mut(id) {
+            source.restart_policy = restart_policy;
+        } else if let Some(operator) = self.operators.get_mut(id) {
+            operator.restart_policy = restart_policy;
+        } else if let Some(sink) = self.sinks.get_mut(id) {
+            sink.restart_policy = restart_policy;
+        } else {
+            return Err(ZFError::NodeNotFound(id.clone()));
+        }
+
+        Ok(())
+    }
+
+    /// Sets the input rule policy of an Operator previously added to the Dataflow: the runtime
+    /// then uses it in place of the `input_rule` of the Operator.
+    ///
+    /// ## Error
+    ///
+    /// This function will return error if the node is not an Operator of the Dataflow or if the
+    /// policy does not apply to its inputs.
+    pub fn try_set_input_rule_policy(
+        &mut self,
+        id: &NodeId,
+        input_rule: InputRulePolicy,
+    ) -> ZFResult<()> {
+        let operator = self
+            .operators
+            .get_mut(id)
+            .ok_or_else(|| ZFError::NodeNotFound(id.clone()))?;
+        input_rule.validate(id, operator.inputs.len())?;
+        operator.input_rule = Some(input_rule);
+
+        Ok(())
+    }
+
+    /// Sets what the runner of a periodic Source previously added to the Dataflow does when it
+    /// misses ticks.
+    ///
+    /// Sources added through `try_add_static_source` skip the missed ticks.
+    ///
+    /// ## Error
+    ///
+    /// This function will return error if the node is not a Source of the Dataflow.
+    pub fn try_set_missed_tick_policy(
+        &mut self,
+        id: &NodeId,
+        missed_ticks: MissedTickPolicy,
+    ) -> ZFResult<()> {
+        let source = self
+            .sources
+            .get_mut(id)
+            .ok_or_else(|| ZFError::NodeNotFound(id.clone()))?;
+        source.missed_ticks = missed_ticks;
+
+        Ok(())
+    }
+
     /// Add a link, connecting two nodes.
     ///
     /// ## Error
@@ -245,6 +361,7 @@ impl Dataflow {
             size,
             queueing_policy,
             priority,
+            reliability: None,
         });
 
         Ok(())
@@ -14,8 +14,14 @@
 
 use crate::model::deadline::E2EDeadlineRecord;
 use crate::model::link::PortDescriptor;
-use crate::model::node::{OperatorRecord, SinkRecord, SourceRecord};
-use crate::{NodeId, Operator, PortId, PortType, Sink, Source, State, ZFResult};
+use crate::model::node::{
+    InputRulePolicy, MissedTickPolicy, OperatorRecord, RestartPolicy, SinkRecord, SourceRecord,
+};
+use crate::{
+    AsyncOperator, Configuration, Context, ControlMessage, Data, DataMessage, LocalDeadlineMiss,
+    Node, NodeId, NodeOutput, Operator, PortId, PortType, Sink, Source, State, Timer, Token,
+    ZFResult,
+};
 use async_std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use std::time::Duration;
@@ -29,9 +35,11 @@ pub struct SourceLoaded {
     pub(crate) id: NodeId,
     pub(crate) output: PortDescriptor,
     pub(crate) period: Option<Duration>,
+    pub(crate) missed_ticks: MissedTickPolicy,
     pub(crate) state: Arc<Mutex<State>>,
     pub(crate) source: Arc<dyn Source>,
     pub(crate) library: Option<Arc<Library>>,
+    pub(crate) restart_policy: RestartPolicy,
     pub(crate) end_to_end_deadlines: Vec<E2EDeadlineRecord>,
 }
 
@@ -48,21 +56,131 @@ impl SourceLoaded {
             output: record.output,
             state: Arc::new(Mutex::new(state)),
             period: record.period.map(|dur_desc| dur_desc.to_duration()),
+            missed_ticks: record.missed_ticks,
             source,
             library: lib,
+            restart_policy: record.restart_policy,
             end_to_end_deadlines: vec![],
         })
     }
 }
 
+/// Wraps a dynamically typed `Operator` so that it can be driven as an `AsyncOperator`.
+pub(crate) struct DynOperator(pub(crate) Arc<dyn Operator>);
+
+impl Node for DynOperator {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        self.0.initialize(configuration)
+    }
+
+    fn finalize(&self, state: &mut State) -> ZFResult<()> {
+        self.0.finalize(state)
+    }
+
+    fn on_control_message(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        port_id: &PortId,
+        message: &ControlMessage,
+    ) -> ZFResult<bool> {
+        self.0.on_control_message(context, state, port_id, message)
+    }
+
+    fn save_state(&self, state: &mut State) -> ZFResult<Option<Vec<u8>>> {
+        self.0.save_state(state)
+    }
+
+    fn restore_state(&self, state: &mut State, saved_state: &[u8]) -> ZFResult<()> {
+        self.0.restore_state(state, saved_state)
+    }
+}
+
+impl Operator for DynOperator {
+    fn timers(&self) -> Vec<Timer> {
+        self.0.timers()
+    }
+
+    fn input_rule(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        self.0.input_rule(context, state, tokens)
+    }
+
+    fn run(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        self.0.run(context, state, inputs)
+    }
+
+    fn output_rule(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        self.0.output_rule(context, state, outputs, deadline_miss)
+    }
+}
+
+/// The implementation of an operator and, if it was dynamically loaded, the library it comes
+/// from.
+// Do not reorder the fields in this struct: the operator must be dropped before its library.
+pub struct OperatorImplementation {
+    pub(crate) operator: Arc<dyn AsyncOperator>,
+    pub(crate) library: Option<Arc<Library>>,
+}
+
+impl OperatorImplementation {
+    pub fn new(operator: Arc<dyn AsyncOperator>, library: Option<Arc<Library>>) -> Self {
+        Self { operator, library }
+    }
+
+    /// Returns `true` if both implementations come from the same loaded library.
+    ///
+    /// Opening a library from the path of a library already loaded gives back the loaded one, as
+    /// does, on Linux, opening a library overwritten in place: its code is not the new one.
+    pub(crate) fn shares_library(&self, other: &Self) -> bool {
+        const SYMBOLS: [&[u8]; 3] = [
+            b"zfoperator_declaration\0",
+            b"zfasyncoperator_declaration\0",
+            b"zfc_operator_vtable\0",
+        ];
+
+        let (library, other) = match (&self.library, &other.library) {
+            (Some(library), Some(other)) => (library, other),
+            _ => return false,
+        };
+        // The same symbol is at the same address only if it is the same library.
+        SYMBOLS.iter().any(|symbol| unsafe {
+            match (
+                library.get::<*const ()>(symbol),
+                other.get::<*const ()>(symbol),
+            ) {
+                (Ok(address), Ok(other_address)) => *address == *other_address,
+                _ => false,
+            }
+        })
+    }
+}
+
 pub struct OperatorLoaded {
     pub(crate) id: NodeId,
     pub(crate) inputs: HashMap<PortId, PortType>,
     pub(crate) outputs: HashMap<PortId, PortType>,
     pub(crate) local_deadline: Option<Duration>,
+    pub(crate) input_rule: Option<InputRulePolicy>,
     pub(crate) state: Arc<Mutex<State>>,
-    pub(crate) operator: Arc<dyn Operator>,
+    pub(crate) operator: Arc<dyn AsyncOperator>,
     pub(crate) library: Option<Arc<Library>>,
+    pub(crate) restart_policy: RestartPolicy,
     pub(crate) end_to_end_deadlines: Vec<E2EDeadlineRecord>,
 }
 
@@ -71,6 +189,14 @@ impl OperatorLoaded {
         record: OperatorRecord,
         lib: Option<Arc<Library>>,
         operator: Arc<dyn Operator>,
+    ) -> ZFResult<Self> {
+        Self::try_new_async(record, lib, Arc::new(DynOperator(operator)))
+    }
+
+    pub fn try_new_async(
+        record: OperatorRecord,
+        lib: Option<Arc<Library>>,
+        operator: Arc<dyn AsyncOperator>,
     ) -> ZFResult<Self> {
         let state = operator.initialize(&record.configuration)?;
 
@@ -91,9 +217,11 @@ impl OperatorLoaded {
             inputs,
             outputs,
             local_deadline: record.deadline,
+            input_rule: record.input_rule,
             state: Arc::new(Mutex::new(state)),
             operator,
             library: lib,
+            restart_policy: record.restart_policy,
             end_to_end_deadlines: vec![],
         })
     }
@@ -105,6 +233,7 @@ pub struct SinkLoaded {
     pub(crate) state: Arc<Mutex<State>>,
     pub(crate) sink: Arc<dyn Sink>,
     pub(crate) library: Option<Arc<Library>>,
+    pub(crate) restart_policy: RestartPolicy,
     pub(crate) end_to_end_deadlines: Vec<E2EDeadlineRecord>,
 }
 
@@ -122,6 +251,7 @@ impl SinkLoaded {
             state: Arc::new(Mutex::new(state)),
             sink,
             library: lib,
+            restart_policy: record.restart_policy,
             end_to_end_deadlines: vec![],
         })
     }
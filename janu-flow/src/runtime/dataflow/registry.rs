@@ -0,0 +1,116 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use super::node::DynOperator;
+use crate::model::node::{OperatorRecord, SinkRecord, SourceRecord};
+use crate::{AsyncOperator, Operator, Sink, Source, ZFError, ZFResult};
+use async_std::sync::Arc;
+use std::collections::HashMap;
+
+pub type OperatorFactory = Arc<dyn Fn() -> ZFResult<Arc<dyn AsyncOperator>> + Send + Sync>;
+pub type SourceFactory = Arc<dyn Fn() -> ZFResult<Arc<dyn Source>> + Send + Sync>;
+pub type SinkFactory = Arc<dyn Fn() -> ZFResult<Arc<dyn Sink>> + Send + Sync>;
+
+/// Nodes linked into the binary of an application embedding a runtime.
+///
+/// Each node is registered, at startup, under a key that is either its id or its URI in the
+/// `DataFlowDescriptor`. When a dataflow is created, the registry is consulted first: a node whose
+/// URI, or else id, matches a key is created with the associated factory, the others are loaded by
+/// the `Loader`. The same descriptor can thus be used by single-binary and distributed
+/// deployments.
+#[derive(Clone, Default)]
+pub struct NodeRegistry {
+    operators: HashMap<String, OperatorFactory>,
+    sources: HashMap<String, SourceFactory>,
+    sinks: HashMap<String, SinkFactory>,
+}
+
+impl NodeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn try_register_operator<F>(&mut self, key: impl Into<String>, factory: F) -> ZFResult<()>
+    where
+        F: Fn() -> ZFResult<Arc<dyn Operator>> + Send + Sync + 'static,
+    {
+        self.try_register_async_operator(key, move || {
+            let operator: Arc<dyn AsyncOperator> = Arc::new(DynOperator(factory()?));
+            Ok(operator)
+        })
+    }
+
+    pub fn try_register_async_operator<F>(
+        &mut self,
+        key: impl Into<String>,
+        factory: F,
+    ) -> ZFResult<()>
+    where
+        F: Fn() -> ZFResult<Arc<dyn AsyncOperator>> + Send + Sync + 'static,
+    {
+        try_register(&mut self.operators, key.into(), Arc::new(factory))
+    }
+
+    pub fn try_register_source<F>(&mut self, key: impl Into<String>, factory: F) -> ZFResult<()>
+    where
+        F: Fn() -> ZFResult<Arc<dyn Source>> + Send + Sync + 'static,
+    {
+        try_register(&mut self.sources, key.into(), Arc::new(factory))
+    }
+
+    pub fn try_register_sink<F>(&mut self, key: impl Into<String>, factory: F) -> ZFResult<()>
+    where
+        F: Fn() -> ZFResult<Arc<dyn Sink>> + Send + Sync + 'static,
+    {
+        try_register(&mut self.sinks, key.into(), Arc::new(factory))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operators.is_empty() && self.sources.is_empty() && self.sinks.is_empty()
+    }
+
+    /// Creates the Operator of `record` if it is registered.
+    pub(crate) fn operator(
+        &self,
+        record: &OperatorRecord,
+    ) -> Option<ZFResult<Arc<dyn AsyncOperator>>> {
+        find(&self.operators, &record.uri, &record.id).map(|factory| factory())
+    }
+
+    /// Creates the Source of `record` if it is registered.
+    pub(crate) fn source(&self, record: &SourceRecord) -> Option<ZFResult<Arc<dyn Source>>> {
+        find(&self.sources, &record.uri, &record.id).map(|factory| factory())
+    }
+
+    /// Creates the Sink of `record` if it is registered.
+    pub(crate) fn sink(&self, record: &SinkRecord) -> Option<ZFResult<Arc<dyn Sink>>> {
+        find(&self.sinks, &record.uri, &record.id).map(|factory| factory())
+    }
+}
+
+fn try_register<T>(factories: &mut HashMap<String, T>, key: String, factory: T) -> ZFResult<()> {
+    if factories.contains_key(&key) {
+        return Err(ZFError::DuplicatedNodeId(key.into()));
+    }
+
+    factories.insert(key, factory);
+    Ok(())
+}
+
+// The URI takes precedence over the id: several nodes can share the same implementation.
+fn find<'a, T>(factories: &'a HashMap<String, T>, uri: &Option<String>, id: &str) -> Option<&'a T> {
+    uri.as_ref()
+        .and_then(|uri| factories.get(uri))
+        .or_else(|| factories.get(id))
+}
@@ -0,0 +1,424 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+//! Nodes implemented as WebAssembly modules, `.wasm` files, run in a sandbox.
+//!
+//! Each node is instantiated in its own store: it only accesses its own memory, capped to
+//! `WasmLimits::max_memory`, and the functions imported from the `janu_flow` module. Each call to
+//! the node is given `WasmLimits::fuel`, a node that exhausts it is interrupted.
+//!
+//! A module exports its `memory` and the functions `zf_initialize`, `zf_run` and `zf_finalize`,
+//! all of type `() -> i32`: `0` on success, any other value is an error. It can import:
+//! - `configuration(ptr: i32, capacity: i32) -> i32`: copies the JSON configuration of the node,
+//! - `input(port_ptr: i32, port_len: i32, ptr: i32, capacity: i32) -> i32`: copies the data
+//!   received on the input `port_id` (Operators and Sinks),
+//! - `emit(port_ptr: i32, port_len: i32, ptr: i32, len: i32) -> i32`: sends `len` bytes on the
+//!   output `port_id`, a Source must emit exactly once.
+//!
+//! `configuration` and `input` return the size of the data, or `-1` if there is none. Nothing is
+//! copied if `capacity` is lower than the size: the call can then be made again with a larger
+//! buffer. `emit` returns `0`, or `-1` if nothing was sent: the port is not an output of the node,
+//! or the bytes emitted during the call would exceed `WasmLimits::max_output`.
+
+use crate::runtime::dataflow::loader::WasmLimits;
+use crate::{
+    default_input_rule, default_output_rule, Configuration, Context, Data, DataMessage,
+    LocalDeadlineMiss, Node, NodeOutput, Operator, PortId, Sink, Source, State, Token, ZFError,
+    ZFResult, ZFState,
+};
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use wasmtime::{Caller, Engine, Extern, Instance, Linker, Module, Store, StoreLimits};
+
+pub static WASM_EXTENSION: &str = "wasm";
+
+static IMPORT_MODULE: &str = "janu_flow";
+
+// The data accessible to the module through its imports.
+struct WasmContext {
+    limits: StoreLimits,
+    configuration: Option<Vec<u8>>,
+    inputs: HashMap<PortId, Arc<Vec<u8>>>,
+    // The ports the node can emit on, and how many bytes it can emit during a call.
+    output_ports: Arc<HashSet<PortId>>,
+    max_output: usize,
+    outputs: HashMap<PortId, Data>,
+    // The bytes emitted during the current call.
+    emitted: usize,
+}
+
+struct WasmState {
+    store: Store<WasmContext>,
+    instance: Instance,
+}
+
+impl std::fmt::Debug for WasmState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WasmState").finish()
+    }
+}
+
+impl ZFState for WasmState {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_mut_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// The pointers and lengths of a wasm32 module are unsigned.
+fn guest_range(ptr: i32, len: usize) -> Option<std::ops::Range<usize>> {
+    let start = ptr as u32 as usize;
+    Some(start..start.checked_add(len)?)
+}
+
+// Copies `bytes` in the memory of the module, if they fit in `capacity`.
+fn copy_to_guest(
+    caller: &mut Caller<'_, WasmContext>,
+    bytes: impl Fn(&WasmContext) -> Option<Arc<Vec<u8>>>,
+    ptr: i32,
+    capacity: i32,
+) -> i32 {
+    let memory = match caller.get_export("memory").and_then(Extern::into_memory) {
+        Some(memory) => memory,
+        None => return -1,
+    };
+    let (memory, context) = memory.data_and_store_mut(&mut *caller);
+    let bytes = match bytes(context) {
+        Some(bytes) => bytes,
+        None => return -1,
+    };
+
+    if bytes.len() <= capacity as u32 as usize {
+        match guest_range(ptr, bytes.len()).and_then(|range| memory.get_mut(range)) {
+            Some(destination) => destination.copy_from_slice(&bytes),
+            None => return -1,
+        }
+    }
+    bytes.len() as i32
+}
+
+fn read_port_id(memory: &[u8], ptr: i32, len: i32) -> Option<PortId> {
+    let bytes = memory.get(guest_range(ptr, len as u32 as usize)?)?;
+    std::str::from_utf8(bytes).ok().map(PortId::from)
+}
+
+fn linker(engine: &Engine) -> ZFResult<Linker<WasmContext>> {
+    let mut linker = Linker::new(engine);
+    linker
+        .func_wrap(
+            IMPORT_MODULE,
+            "configuration",
+            |mut caller: Caller<'_, WasmContext>, ptr: i32, capacity: i32| -> i32 {
+                copy_to_guest(
+                    &mut caller,
+                    |context| context.configuration.clone().map(Arc::new),
+                    ptr,
+                    capacity,
+                )
+            },
+        )
+        .map_err(|e| ZFError::LoadingError(e.to_string()))?;
+    linker
+        .func_wrap(
+            IMPORT_MODULE,
+            "input",
+            |mut caller: Caller<'_, WasmContext>,
+             port_ptr: i32,
+             port_len: i32,
+             ptr: i32,
+             capacity: i32|
+             -> i32 {
+                let memory = match caller.get_export("memory").and_then(Extern::into_memory) {
+                    Some(memory) => memory,
+                    None => return -1,
+                };
+                let port_id = match read_port_id(memory.data(&caller), port_ptr, port_len) {
+                    Some(port_id) => port_id,
+                    None => return -1,
+                };
+                copy_to_guest(
+                    &mut caller,
+                    |context| context.inputs.get(&port_id).cloned(),
+                    ptr,
+                    capacity,
+                )
+            },
+        )
+        .map_err(|e| ZFError::LoadingError(e.to_string()))?;
+    linker
+        .func_wrap(
+            IMPORT_MODULE,
+            "emit",
+            |mut caller: Caller<'_, WasmContext>,
+             port_ptr: i32,
+             port_len: i32,
+             ptr: i32,
+             len: i32|
+             -> i32 {
+                let memory = match caller.get_export("memory").and_then(Extern::into_memory) {
+                    Some(memory) => memory,
+                    None => return -1,
+                };
+                let (memory, context) = memory.data_and_store_mut(&mut caller);
+                let port_id = match read_port_id(memory, port_ptr, port_len) {
+                    Some(port_id) if context.output_ports.contains(&port_id) => port_id,
+                    _ => return -1,
+                };
+                let len = len as u32 as usize;
+                let emitted = match context.emitted.checked_add(len) {
+                    Some(emitted) if emitted <= context.max_output => emitted,
+                    _ => return -1,
+                };
+                let bytes = match guest_range(ptr, len).and_then(|range| memory.get(range)) {
+                    Some(bytes) => bytes.to_vec(),
+                    None => return -1,
+                };
+
+                context.emitted = emitted;
+                context.outputs.insert(port_id, Data::from_bytes(bytes));
+                0
+            },
+        )
+        .map_err(|e| ZFError::LoadingError(e.to_string()))?;
+
+    Ok(linker)
+}
+
+/// A WebAssembly module implementing a node, see the module documentation.
+///
+/// The node has no output until they are set, see `with_outputs`.
+pub struct WasmNode {
+    engine: Engine,
+    module: Module,
+    limits: WasmLimits,
+    outputs: Arc<HashSet<PortId>>,
+}
+
+impl WasmNode {
+    pub fn try_new(path: &Path, limits: WasmLimits) -> ZFResult<Self> {
+        Self::try_from_bytes(path, &std::fs::read(path)?, limits)
+    }
+
+    /// Compiles the module `path` from its content `bytes`, e.g. once they were checked.
+    pub fn try_from_bytes(path: &Path, bytes: &[u8], limits: WasmLimits) -> ZFResult<Self> {
+        let mut config = wasmtime::Config::new();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config).map_err(|e| ZFError::LoadingError(e.to_string()))?;
+        let module = Module::new(&engine, bytes).map_err(|e| {
+            ZFError::LoadingError(format!("Unable to compile < {:?} >: {}", path, e))
+        })?;
+
+        Ok(Self {
+            engine,
+            module,
+            limits,
+            outputs: Arc::new(HashSet::new()),
+        })
+    }
+
+    /// Sets the output ports of the node, the only ones it can emit on.
+    pub fn with_outputs(mut self, outputs: impl IntoIterator<Item = PortId>) -> Self {
+        self.outputs = Arc::new(outputs.into_iter().collect());
+        self
+    }
+
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        let configuration = configuration
+            .as_ref()
+            .map(|configuration| {
+                serde_json::to_vec(configuration).map_err(|_| ZFError::SerializationError)
+            })
+            .transpose()?;
+        let context = WasmContext {
+            limits: wasmtime::StoreLimitsBuilder::new()
+                .memory_size(self.limits.max_memory)
+                .instances(1)
+                .build(),
+            configuration,
+            inputs: HashMap::new(),
+            output_ports: self.outputs.clone(),
+            max_output: self.limits.max_output,
+            outputs: HashMap::new(),
+            emitted: 0,
+        };
+
+        let mut store = Store::new(&self.engine, context);
+        store.limiter(|context| &mut context.limits);
+        self.refuel(&mut store)?;
+        let instance = linker(&self.engine)?
+            .instantiate(&mut store, &self.module)
+            .map_err(|e| ZFError::LoadingError(e.to_string()))?;
+
+        let mut state = WasmState { store, instance };
+        Self::call(&mut state, "zf_initialize")?;
+        Ok(State::from(state))
+    }
+
+    fn finalize(&self, state: &mut State) -> ZFResult<()> {
+        let state = state.try_get::<WasmState>()?;
+        self.refuel(&mut state.store)?;
+        Self::call(state, "zf_finalize")
+    }
+
+    // Calls `zf_run` with the `inputs`, returns what the module emitted.
+    fn run(
+        &self,
+        state: &mut State,
+        inputs: HashMap<PortId, Arc<Vec<u8>>>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let state = state.try_get::<WasmState>()?;
+        self.refuel(&mut state.store)?;
+        state.store.data_mut().inputs = inputs;
+
+        let result = Self::call(state, "zf_run");
+        let context = state.store.data_mut();
+        context.inputs.clear();
+        context.emitted = 0;
+        let outputs = std::mem::take(&mut context.outputs);
+
+        result.map(|_| outputs)
+    }
+
+    fn refuel(&self, store: &mut Store<WasmContext>) -> ZFResult<()> {
+        store
+            .set_fuel(self.limits.fuel)
+            .map_err(|e| ZFError::ForeignError(e.to_string()))
+    }
+
+    fn call(state: &mut WasmState, name: &str) -> ZFResult<()> {
+        let function = state
+            .instance
+            .get_typed_func::<(), i32>(&mut state.store, name)
+            .map_err(|e| ZFError::LoadingError(format!("`{}`: {}", name, e)))?;
+        match function.call(&mut state.store, ()) {
+            Ok(0) => Ok(()),
+            Ok(code) => Err(ZFError::ForeignError(format!(
+                "`{}` returned the error code {}",
+                name, code
+            ))),
+            // Traps, among which running out of fuel.
+            Err(e) => Err(ZFError::ForeignError(format!("`{}` trapped: {}", name, e))),
+        }
+    }
+}
+
+/// An Operator implemented by a WebAssembly module.
+///
+/// Its input rule waits for data on all the inputs, its output rule forwards all the outputs.
+pub struct WasmOperator(pub WasmNode);
+
+impl Node for WasmOperator {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        self.0.initialize(configuration)
+    }
+
+    fn finalize(&self, state: &mut State) -> ZFResult<()> {
+        self.0.finalize(state)
+    }
+}
+
+impl Operator for WasmOperator {
+    fn input_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        default_input_rule(state, tokens)
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let inputs = inputs
+            .iter()
+            .map(|(port_id, message)| Ok((port_id.clone(), message.data.try_as_bytes()?)))
+            .collect::<ZFResult<HashMap<_, _>>>()?;
+        self.0.run(state, inputs)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+/// A Source implemented by a WebAssembly module.
+pub struct WasmSource(pub WasmNode);
+
+impl Node for WasmSource {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        self.0.initialize(configuration)
+    }
+
+    fn finalize(&self, state: &mut State) -> ZFResult<()> {
+        self.0.finalize(state)
+    }
+}
+
+#[async_trait]
+impl Source for WasmSource {
+    async fn run(&self, _context: &mut Context, state: &mut State) -> ZFResult<Data> {
+        self.0
+            .run(state, HashMap::new())?
+            .into_iter()
+            .next()
+            .map(|(_, data)| data)
+            .ok_or_else(|| ZFError::MissingOutput("The Source did not emit any data.".into()))
+    }
+}
+
+/// A Sink implemented by a WebAssembly module, its input is available on `port_id`.
+pub struct WasmSink {
+    pub node: WasmNode,
+    pub port_id: PortId,
+}
+
+impl Node for WasmSink {
+    fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State> {
+        self.node.initialize(configuration)
+    }
+
+    fn finalize(&self, state: &mut State) -> ZFResult<()> {
+        self.node.finalize(state)
+    }
+}
+
+#[async_trait]
+impl Sink for WasmSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        input: DataMessage,
+    ) -> ZFResult<()> {
+        let mut inputs = HashMap::with_capacity(1);
+        inputs.insert(self.port_id.clone(), input.data.try_as_bytes()?);
+        self.node.run(state, inputs).map(|_| ())
+    }
+}
@@ -0,0 +1,248 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use crate::{DataMessage, PortId, Token, ZFResult};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// How many samples are buffered per input, if not specified.
+pub const DEFAULT_JOINER_CAPACITY: usize = 16;
+
+/// What the `Joiner` does with the samples that could not be matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnmatchedPolicy {
+    /// The samples are discarded, only their number is kept.
+    Drop,
+    /// The samples are kept until they are retrieved with `Joiner::take_unmatched`.
+    Report,
+}
+
+/// Aligns the data received on the inputs of an Operator on their timestamps.
+///
+/// The `Joiner` is meant to be stored in the `State` of an Operator and to be called from its
+/// `Input Rule`: each sample received is buffered, per input, and `Run` is triggered once a sample
+/// is available on every input within `window` of each other. The latest of the oldest buffered
+/// samples serves as reference: on every other input, the sample whose timestamp is the nearest,
+/// among the samples received so far, is paired with it.
+///
+/// Samples that can no longer be matched — too old for the reference, or evicted because the
+/// buffer of their input is full — are handled according to the `UnmatchedPolicy`.
+///
+/// A single set of samples is handed to `Run` per call: if the buffers hold several matches, the
+/// tokens of the match are kept so that the `Input Rule` is called again, without waiting for new
+/// data, and hands the next match.
+#[derive(Debug)]
+pub struct Joiner {
+    window: Duration,
+    capacity: usize,
+    unmatched_policy: UnmatchedPolicy,
+    buffers: HashMap<PortId, VecDeque<DataMessage>>,
+    // The match to hand on the next call, the tokens of the previous one being kept until then.
+    next_match: Option<HashMap<PortId, DataMessage>>,
+    unmatched: Vec<(PortId, DataMessage)>,
+    unmatched_count: usize,
+}
+
+impl Joiner {
+    /// Creates a `Joiner` pairing samples whose timestamps are at most `window` apart.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            capacity: DEFAULT_JOINER_CAPACITY,
+            unmatched_policy: UnmatchedPolicy::Drop,
+            buffers: HashMap::new(),
+            next_match: None,
+            unmatched: vec![],
+            unmatched_count: 0,
+        }
+    }
+
+    /// Sets how many samples are buffered per input (at least one).
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets what is done with the samples that could not be matched.
+    pub fn with_unmatched_policy(mut self, unmatched_policy: UnmatchedPolicy) -> Self {
+        self.unmatched_policy = unmatched_policy;
+        self
+    }
+
+    /// The `Input Rule` of the `Joiner`.
+    ///
+    /// The data of all the `Ready` tokens is buffered and the tokens are dropped, allowing for new
+    /// data to be received. If a match is found, the tokens are replaced with the matched samples
+    /// and `true` is returned. If another match is found, the tokens are kept: the next call
+    /// replaces them with that match.
+    pub fn input_rule(&mut self, tokens: &mut HashMap<PortId, Token>) -> ZFResult<bool> {
+        let next_match = self.next_match.take();
+        for (port_id, token) in tokens.iter_mut() {
+            if let Token::Ready(ready_token) = token {
+                // The tokens kept hold the previous match, their data was already handed.
+                if next_match.is_none() {
+                    self.push(port_id, ready_token.data.clone());
+                }
+                ready_token.set_action_drop();
+            }
+        }
+
+        let matched = match next_match {
+            Some(matched) => matched,
+            None => match self.try_match(tokens.keys()) {
+                Some(matched) => matched,
+                None => return Ok(false),
+            },
+        };
+        for (port_id, data_message) in matched {
+            tokens.insert(port_id, Token::from(data_message));
+        }
+
+        self.next_match = self.try_match(tokens.keys());
+        if self.next_match.is_some() {
+            for token in tokens.values_mut() {
+                if let Token::Ready(ready_token) = token {
+                    ready_token.set_action_keep();
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns the samples that could not be matched since the last call, if the policy is
+    /// `UnmatchedPolicy::Report`.
+    pub fn take_unmatched(&mut self) -> Vec<(PortId, DataMessage)> {
+        std::mem::take(&mut self.unmatched)
+    }
+
+    /// Returns how many samples could not be matched, whatever the policy.
+    pub fn get_unmatched_count(&self) -> usize {
+        self.unmatched_count
+    }
+
+    /// Returns how many samples are buffered for `port_id`.
+    pub fn get_buffered_count(&self, port_id: &str) -> usize {
+        self.buffers.get(port_id).map_or(0, |buffer| buffer.len())
+    }
+
+    // Inserts the sample, keeping the buffer sorted on the timestamps. The oldest sample is
+    // evicted if the buffer is full.
+    fn push(&mut self, port_id: &PortId, data_message: DataMessage) {
+        let buffer = self.buffers.entry(port_id.clone()).or_default();
+        let index = buffer
+            .iter()
+            .rposition(|buffered| buffered.timestamp <= data_message.timestamp)
+            .map_or(0, |index| index + 1);
+        buffer.insert(index, data_message);
+
+        if buffer.len() > self.capacity {
+            if let Some(evicted) = buffer.pop_front() {
+                self.discard(port_id.clone(), evicted);
+            }
+        }
+    }
+
+    fn discard(&mut self, port_id: PortId, data_message: DataMessage) {
+        log::debug!(
+            "[Joiner] Sample < {} > received on < {} > could not be matched.",
+            data_message.timestamp,
+            port_id
+        );
+        self.unmatched_count += 1;
+        if self.unmatched_policy == UnmatchedPolicy::Report {
+            self.unmatched.push((port_id, data_message));
+        }
+    }
+
+    fn try_match<'a>(
+        &mut self,
+        port_ids: impl Iterator<Item = &'a PortId> + Clone,
+    ) -> Option<HashMap<PortId, DataMessage>> {
+        'reference: loop {
+            // The reference is the latest of the oldest samples: the older samples of the other
+            // inputs can only be paired with it, or never.
+            let mut oldest_samples = Vec::new();
+            for port_id in port_ids.clone() {
+                let oldest = self.buffers.get(port_id)?.front()?;
+                oldest_samples.push((port_id, oldest.timestamp.get_time().to_duration()));
+            }
+            let (reference_port, reference_time) =
+                oldest_samples.into_iter().max_by_key(|(_, time)| *time)?;
+
+            let mut indexes: HashMap<PortId, usize> = HashMap::new();
+            for port_id in port_ids
+                .clone()
+                .filter(|&port_id| port_id != reference_port)
+            {
+                while let Some(time) = self
+                    .buffers
+                    .get(port_id)?
+                    .front()
+                    .map(|oldest| oldest.timestamp.get_time().to_duration())
+                {
+                    if time + self.window >= reference_time {
+                        break;
+                    }
+
+                    if let Some(outdated) = self.buffers.get_mut(port_id)?.pop_front() {
+                        self.discard(port_id.clone(), outdated);
+                    }
+                }
+
+                let (index, distance) = self
+                    .buffers
+                    .get(port_id)?
+                    .iter()
+                    .map(|buffered| distance(buffered, reference_time))
+                    .enumerate()
+                    .min_by_key(|(_, distance)| *distance)?;
+
+                // All the samples of this input are too recent: the reference will never be
+                // matched.
+                if distance > self.window {
+                    let reference_port = reference_port.clone();
+                    if let Some(unmatched) = self.buffers.get_mut(&reference_port)?.pop_front() {
+                        self.discard(reference_port, unmatched);
+                    }
+                    continue 'reference;
+                }
+
+                indexes.insert(port_id.clone(), index);
+            }
+            indexes.insert(reference_port.clone(), 0);
+
+            let mut matched = HashMap::with_capacity(indexes.len());
+            for (port_id, index) in indexes {
+                let mut skipped = self
+                    .buffers
+                    .get_mut(&port_id)?
+                    .drain(..=index)
+                    .collect::<Vec<_>>();
+                if let Some(data_message) = skipped.pop() {
+                    matched.insert(port_id.clone(), data_message);
+                }
+                for unmatched in skipped {
+                    self.discard(port_id.clone(), unmatched);
+                }
+            }
+
+            return Some(matched);
+        }
+    }
+}
+
+fn distance(data_message: &DataMessage, time: Duration) -> Duration {
+    let sample_time = data_message.timestamp.get_time().to_duration();
+    sample_time.max(time) - sample_time.min(time)
+}
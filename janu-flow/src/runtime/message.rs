@@ -100,16 +100,36 @@ pub struct RecordingMetadata {
     pub(crate) instance_id: Uuid,
 }
 
+/// Control messages travel on the links, in order with the data.
+///
+/// Unless the node decides otherwise (see `Node::on_control_message`), the runners forward the
+/// control messages they receive to all their outputs.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ControlMessage {
-    // These messages are not yet defined, those are some ideas
-    // ReadyToMigrate,
-    // ChangeMode(u8, u128),
-    // Watermark,
+    /// The node that emitted it has nothing to send with a timestamp lower than or equal to the
+    /// one of the watermark.
+    Watermark(Timestamp),
+    /// The nodes listed should switch to the provided mode, the others only forward it.
+    ChangeMode(usize, Vec<NodeId>, Timestamp),
+    /// The node that emitted it will not send anything else, the nodes downstream can be migrated
+    /// once they have processed what they received before.
+    ReadyToMigrate(Timestamp),
     RecordingStart(RecordingMetadata),
     RecordingStop(Timestamp),
 }
 
+impl ControlMessage {
+    pub fn get_timestamp(&self) -> Timestamp {
+        match self {
+            Self::Watermark(ref ts) => *ts,
+            Self::ChangeMode(_, _, ref ts) => *ts,
+            Self::ReadyToMigrate(ref ts) => *ts,
+            Self::RecordingStart(ref rs) => rs.timestamp,
+            Self::RecordingStop(ref ts) => *ts,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Message {
     Data(DataMessage),
@@ -167,33 +187,76 @@ impl Message {
                 Data::Bytes(_) => {
                     bincode::serialize(&self).map_err(|_| ZFError::SerializationError)
                 }
-                Data::Typed(_) => {
-                    let serialized_data = data_message.data.try_as_bytes()?;
-                    let serialized_message = Message::Data(DataMessage::new_serialized(
-                        serialized_data,
-                        data_message.timestamp,
-                        data_message.end_to_end_deadlines.clone(),
-                    ));
-
-                    bincode::serialize(&serialized_message).map_err(|_| ZFError::SerializationError)
-                }
+                Data::Typed(_) => bincode::serialize(&self.try_to_serialized()?)
+                    .map_err(|_| ZFError::SerializationError),
             },
         }
     }
 
+    /// Serializes the message along with its sequence number, see `SequencedMessage`.
+    pub fn serialize_bincode_sequenced(
+        &self,
+        sender: Uuid,
+        sequence_number: u64,
+        first: u64,
+    ) -> ZFResult<Vec<u8>> {
+        let sequenced = SequencedMessage {
+            sender,
+            sequence_number,
+            first,
+            message: self.try_to_serialized()?,
+        };
+        bincode::serialize(&sequenced).map_err(|_| ZFError::SerializationError)
+    }
+
+    // Returns the message with its data as bytes, i.e. as it is sent between runtimes.
+    pub(crate) fn try_to_serialized(&self) -> ZFResult<Self> {
+        match &self {
+            Message::Data(data_message) if matches!(data_message.data, Data::Typed(_)) => {
+                Ok(Message::Data(DataMessage::new_serialized(
+                    data_message.data.try_as_bytes()?,
+                    data_message.timestamp,
+                    data_message.end_to_end_deadlines.clone(),
+                )))
+            }
+            _ => Ok(self.clone()),
+        }
+    }
+
     pub fn get_timestamp(&self) -> Timestamp {
         match self {
-            Self::Control(ref ctrl) => match ctrl {
-                ControlMessage::RecordingStart(ref rs) => rs.timestamp,
-                ControlMessage::RecordingStop(ref ts) => *ts,
-                // Commented because Control messages are not yet defined.
-                // _ => Timestamp::new(NTP64(u64::MAX), Uuid::nil().into()),
-            },
+            Self::Control(ref ctrl) => ctrl.get_timestamp(),
             Self::Data(data) => data.timestamp,
         }
     }
 }
 
+/// A message exchanged by the connectors of a link between two runtimes.
+///
+/// The messages are numbered by their sender so that the receiver detects the ones that were
+/// lost, duplicated or reordered on the way.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SequencedMessage {
+    /// Identifies the sender, the numbering starts over with each sender.
+    pub sender: Uuid,
+    pub sequence_number: u64,
+    /// The oldest sequence number the sender can still send again: the messages before it were
+    /// acknowledged, or are not kept if the link is not reliable.
+    pub first: u64,
+    pub message: Message,
+}
+
+/// Sent by the receiver of a reliable link to its sender, on the `ack` sub-resource of the link.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Ack {
+    /// The sender the acknowledgement is for.
+    pub sender: Uuid,
+    /// All the messages before this sequence number were received.
+    pub next: u64,
+    /// Set when a gap was detected: the sender should send again its messages from `next`.
+    pub retransmit: bool,
+}
+
 // Manual Ord implementation for message ordering when replay
 impl Ord for Message {
     fn cmp(&self, other: &Self) -> Ordering {
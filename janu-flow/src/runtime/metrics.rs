@@ -0,0 +1,482 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use crate::serde::{Deserialize, Serialize};
+use crate::{FlowId, NodeId, PortId};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Upper bounds, in microseconds, of the buckets of the `run` execution time histogram.
+pub const RUN_DURATION_BUCKETS: &[u64] = &[
+    10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 500_000, 1_000_000, 5_000_000,
+];
+
+/// Upper bounds, in microseconds, of the buckets of the tick jitter histogram of periodic Sources.
+pub const TICK_JITTER_BUCKETS: &[u64] = &[
+    10, 50, 100, 250, 500, 1_000, 2_500, 5_000, 10_000, 50_000, 100_000,
+];
+
+/// Upper bounds of the buckets of the link queue depth histogram.
+pub const QUEUE_DEPTH_BUCKETS: &[u64] = &[0, 1, 2, 5, 10, 20, 50, 100, 200, 500, 1_000];
+
+/// A histogram whose buckets are fixed at creation, safe to update concurrently.
+///
+/// The observations are integers, they are divided by `divisor` when taking a snapshot: e.g. a
+/// divisor of `1e6` converts observations in microseconds to seconds.
+#[derive(Debug)]
+pub struct Histogram {
+    bounds: &'static [u64],
+    divisor: f64,
+    // One bucket per bound plus the `+Inf` one, they are not cumulative.
+    buckets: Vec<AtomicU64>,
+    count: AtomicU64,
+    sum: AtomicU64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [u64], divisor: f64) -> Self {
+        Self {
+            bounds,
+            divisor,
+            buckets: (0..=bounds.len()).map(|_| AtomicU64::new(0)).collect(),
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, value: u64) {
+        let index = self
+            .bounds
+            .iter()
+            .position(|bound| value <= *bound)
+            .unwrap_or(self.bounds.len());
+        self.buckets[index].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(value, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut cumulative = 0;
+        let buckets = self
+            .buckets
+            .iter()
+            .take(self.bounds.len())
+            .map(|bucket| {
+                cumulative += bucket.load(Ordering::Relaxed);
+                cumulative
+            })
+            .collect();
+
+        HistogramSnapshot {
+            bounds: self
+                .bounds
+                .iter()
+                .map(|bound| *bound as f64 / self.divisor)
+                .collect(),
+            buckets,
+            count: self.count.load(Ordering::Relaxed),
+            sum: self.sum.load(Ordering::Relaxed) as f64 / self.divisor,
+        }
+    }
+}
+
+/// The metrics of a runner, updated while it runs.
+///
+/// The counters are never reset: they are cumulative over the restarts of the node.
+#[derive(Debug)]
+pub struct RunnerMetrics {
+    messages_in: AtomicU64,
+    messages_out: AtomicU64,
+    errors: AtomicU64,
+    restarts: AtomicU64,
+    missed_ticks: AtomicU64,
+    run_duration: Histogram,
+    queue_depth: HashMap<PortId, Histogram>,
+    tick_jitter: Option<Histogram>,
+    link_stats: Option<LinkCounters>,
+}
+
+// The counters of a janu connector.
+#[derive(Debug, Default)]
+struct LinkCounters {
+    gaps: AtomicU64,
+    duplicates: AtomicU64,
+    late: AtomicU64,
+    retransmissions: AtomicU64,
+}
+
+impl RunnerMetrics {
+    /// Creates the metrics of a runner whose input links are connected to `inputs`.
+    pub fn new<'a>(inputs: impl IntoIterator<Item = &'a PortId>) -> Self {
+        Self {
+            messages_in: AtomicU64::new(0),
+            messages_out: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            restarts: AtomicU64::new(0),
+            missed_ticks: AtomicU64::new(0),
+            run_duration: Histogram::new(RUN_DURATION_BUCKETS, 1e6),
+            queue_depth: inputs
+                .into_iter()
+                .map(|port_id| (port_id.clone(), Histogram::new(QUEUE_DEPTH_BUCKETS, 1.0)))
+                .collect(),
+            tick_jitter: None,
+            link_stats: None,
+        }
+    }
+
+    /// Also tracks the ticks of a periodic Source.
+    pub fn with_ticks(mut self) -> Self {
+        self.tick_jitter = Some(Histogram::new(TICK_JITTER_BUCKETS, 1e6));
+        self
+    }
+
+    /// Also tracks the losses, duplicates, late messages and retransmissions of a janu connector.
+    pub fn with_link_stats(mut self) -> Self {
+        self.link_stats = Some(LinkCounters::default());
+        self
+    }
+
+    pub fn inc_messages_in(&self) {
+        self.messages_in.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_messages_out(&self) {
+        self.messages_out.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_errors(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_restarts(&self) {
+        self.restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_run_duration(&self, elapsed: Duration) {
+        self.run_duration.observe(elapsed.as_micros() as u64);
+    }
+
+    /// Records how late a periodic Source woke up compared to its tick, and how many ticks it
+    /// skipped.
+    pub fn observe_tick(&self, jitter: Duration, missed_ticks: u64) {
+        if let Some(histogram) = &self.tick_jitter {
+            histogram.observe(jitter.as_micros() as u64);
+        }
+        self.missed_ticks.fetch_add(missed_ticks, Ordering::Relaxed);
+    }
+
+    /// Records `count` messages that a connector found missing when receiving a later one.
+    pub fn inc_gaps(&self, count: u64) {
+        if let Some(stats) = &self.link_stats {
+            stats.gaps.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Records `count` messages that a connector received more than once.
+    pub fn inc_duplicates(&self, count: u64) {
+        if let Some(stats) = &self.link_stats {
+            stats.duplicates.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Records `count` messages that a connector received after a later one, and discarded.
+    pub fn inc_late(&self, count: u64) {
+        if let Some(stats) = &self.link_stats {
+            stats.late.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Records `count` messages that a connector sent again.
+    pub fn inc_retransmissions(&self, count: u64) {
+        if let Some(stats) = &self.link_stats {
+            stats.retransmissions.fetch_add(count, Ordering::Relaxed);
+        }
+    }
+
+    /// Records the number of messages waiting in the link connected to `port_id`.
+    pub fn observe_queue_depth(&self, port_id: &PortId, depth: usize) {
+        if let Some(histogram) = self.queue_depth.get(port_id) {
+            histogram.observe(depth as u64);
+        }
+    }
+
+    pub fn snapshot(&self, flow_id: FlowId, instance_id: Uuid, node_id: NodeId) -> NodeMetrics {
+        NodeMetrics {
+            flow_id,
+            instance_id,
+            node_id,
+            messages_in: self.messages_in.load(Ordering::Relaxed),
+            messages_out: self.messages_out.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            restarts: self.restarts.load(Ordering::Relaxed),
+            missed_ticks: self.missed_ticks.load(Ordering::Relaxed),
+            run_duration: self.run_duration.snapshot(),
+            queue_depth: self
+                .queue_depth
+                .iter()
+                .map(|(port_id, histogram)| (port_id.clone(), histogram.snapshot()))
+                .collect(),
+            tick_jitter: self
+                .tick_jitter
+                .as_ref()
+                .map(|histogram| histogram.snapshot()),
+            link_stats: self.link_stats.as_ref().map(|stats| LinkStatistics {
+                gaps: stats.gaps.load(Ordering::Relaxed),
+                duplicates: stats.duplicates.load(Ordering::Relaxed),
+                late: stats.late.load(Ordering::Relaxed),
+                retransmissions: stats.retransmissions.load(Ordering::Relaxed),
+            }),
+        }
+    }
+}
+
+impl Default for RunnerMetrics {
+    fn default() -> Self {
+        Self::new(&[])
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub bounds: Vec<f64>,
+    // Cumulative count of the observations lower or equal to the bound at the same index.
+    pub buckets: Vec<u64>,
+    pub count: u64,
+    pub sum: f64,
+}
+
+/// The statistics of a janu connector, on the sending or the receiving end of a link between two
+/// runtimes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct LinkStatistics {
+    // Messages found missing when a later one was received. On a reliable link they are sent
+    // again, on the others they are lost.
+    pub gaps: u64,
+    // Messages received more than once on a reliable link.
+    pub duplicates: u64,
+    // Messages received after a later one on an unreliable link, they are discarded.
+    #[serde(default)]
+    pub late: u64,
+    // Messages sent again because they were not acknowledged in time or a gap was reported.
+    pub retransmissions: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeMetrics {
+    pub flow_id: FlowId,
+    pub instance_id: Uuid,
+    pub node_id: NodeId,
+    pub messages_in: u64,
+    pub messages_out: u64,
+    pub errors: u64,
+    pub restarts: u64,
+    // Ticks skipped by a periodic Source, always 0 for the other nodes.
+    pub missed_ticks: u64,
+    // Execution time of `run`, in seconds.
+    pub run_duration: HistogramSnapshot,
+    // Number of messages waiting in each input link when a message is received on it.
+    pub queue_depth: HashMap<PortId, HistogramSnapshot>,
+    // Delay, in seconds, between the tick of a periodic Source and the moment it actually woke
+    // up. Only periodic Sources have one.
+    pub tick_jitter: Option<HistogramSnapshot>,
+    // Only the connectors have some.
+    pub link_stats: Option<LinkStatistics>,
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+fn labels(metrics: &NodeMetrics) -> String {
+    format!(
+        "flow=\"{}\",instance=\"{}\",node=\"{}\"",
+        escape_label_value(&metrics.flow_id),
+        metrics.instance_id,
+        escape_label_value(&metrics.node_id)
+    )
+}
+
+fn encode_header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+}
+
+fn encode_counter<'a>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    metrics: impl IntoIterator<Item = &'a NodeMetrics>,
+    value: fn(&NodeMetrics) -> u64,
+) {
+    encode_header(out, name, "counter", help);
+    for node_metrics in metrics {
+        let _ = writeln!(
+            out,
+            "{}{{{}}} {}",
+            name,
+            labels(node_metrics),
+            value(node_metrics)
+        );
+    }
+}
+
+fn encode_histogram(out: &mut String, name: &str, labels: &str, histogram: &HistogramSnapshot) {
+    for (bound, bucket) in histogram.bounds.iter().zip(histogram.buckets.iter()) {
+        let _ = writeln!(
+            out,
+            "{}_bucket{{{},le=\"{}\"}} {}",
+            name, labels, bound, bucket
+        );
+    }
+    let _ = writeln!(
+        out,
+        "{}_bucket{{{},le=\"+Inf\"}} {}",
+        name, labels, histogram.count
+    );
+    let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, histogram.sum);
+    let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, histogram.count);
+}
+
+/// Encodes the metrics following the Prometheus text exposition format.
+pub fn encode_prometheus(metrics: &[NodeMetrics]) -> String {
+    let mut out = String::new();
+
+    encode_counter(
+        &mut out,
+        "janu_flow_messages_in_total",
+        "Number of messages received by the node.",
+        metrics,
+        |m| m.messages_in,
+    );
+    encode_counter(
+        &mut out,
+        "janu_flow_messages_out_total",
+        "Number of messages sent by the node, counted once per link.",
+        metrics,
+        |m| m.messages_out,
+    );
+    encode_counter(
+        &mut out,
+        "janu_flow_errors_total",
+        "Number of errors returned by the node.",
+        metrics,
+        |m| m.errors,
+    );
+    encode_counter(
+        &mut out,
+        "janu_flow_restarts_total",
+        "Number of times the node was restarted after an error.",
+        metrics,
+        |m| m.restarts,
+    );
+
+    encode_counter(
+        &mut out,
+        "janu_flow_missed_ticks_total",
+        "Number of ticks skipped by the periodic Source.",
+        metrics.iter().filter(|m| m.tick_jitter.is_some()),
+        |m| m.missed_ticks,
+    );
+
+    let connectors = || metrics.iter().filter(|m| m.link_stats.is_some());
+    encode_counter(
+        &mut out,
+        "janu_flow_link_gaps_total",
+        "Number of messages the connector found missing when receiving a later one.",
+        connectors(),
+        |m| m.link_stats.as_ref().map_or(0, |stats| stats.gaps),
+    );
+    encode_counter(
+        &mut out,
+        "janu_flow_link_duplicates_total",
+        "Number of messages the connector received more than once.",
+        connectors(),
+        |m| m.link_stats.as_ref().map_or(0, |stats| stats.duplicates),
+    );
+    encode_counter(
+        &mut out,
+        "janu_flow_link_late_total",
+        "Number of messages the connector received after a later one and discarded.",
+        connectors(),
+        |m| m.link_stats.as_ref().map_or(0, |stats| stats.late),
+    );
+    encode_counter(
+        &mut out,
+        "janu_flow_link_retransmissions_total",
+        "Number of messages the connector sent again.",
+        connectors(),
+        |m| {
+            m.link_stats
+                .as_ref()
+                .map_or(0, |stats| stats.retransmissions)
+        },
+    );
+
+    let name = "janu_flow_run_duration_seconds";
+    encode_header(
+        &mut out,
+        name,
+        "histogram",
+        "Execution time of the `run` function of the node.",
+    );
+    for node_metrics in metrics {
+        encode_histogram(
+            &mut out,
+            name,
+            &labels(node_metrics),
+            &node_metrics.run_duration,
+        );
+    }
+
+    let name = "janu_flow_tick_jitter_seconds";
+    encode_header(
+        &mut out,
+        name,
+        "histogram",
+        "Delay between the tick of the periodic Source and the moment it woke up.",
+    );
+    for node_metrics in metrics {
+        if let Some(tick_jitter) = &node_metrics.tick_jitter {
+            encode_histogram(&mut out, name, &labels(node_metrics), tick_jitter);
+        }
+    }
+
+    let name = "janu_flow_link_queue_depth";
+    encode_header(
+        &mut out,
+        name,
+        "histogram",
+        "Number of messages waiting in an input link of the node when one is received.",
+    );
+    for node_metrics in metrics {
+        let mut ports: Vec<&PortId> = node_metrics.queue_depth.keys().collect();
+        ports.sort();
+        for port_id in ports {
+            let labels = format!(
+                "{},port=\"{}\"",
+                labels(node_metrics),
+                escape_label_value(port_id)
+            );
+            encode_histogram(&mut out, name, &labels, &node_metrics.queue_depth[port_id]);
+        }
+    }
+
+    out
+}
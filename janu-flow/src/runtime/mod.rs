@@ -19,29 +19,42 @@ use crate::model::dataflow::descriptor::{DataFlowDescriptor, Mapping};
 use crate::{
     model::{
         dataflow::record::DataFlowRecord,
+        link::LinkDescriptor,
         node::{OperatorDescriptor, SinkDescriptor, SourceDescriptor},
     },
     serde::{Deserialize, Serialize},
     FlowId,
 };
 use async_std::sync::Arc;
+use std::collections::HashMap;
+use std::time::Duration;
 use uuid::Uuid;
 
+use crate::runtime::checkpoint::{CheckpointConfig, Checkpoints};
 use crate::runtime::dataflow::loader::Loader;
-use crate::runtime::message::ControlMessage;
-use crate::{NodeId, RuntimeId, ZFError, ZFResult};
+use crate::runtime::message::{ControlMessage, Message};
+use crate::{DurationDescriptor, NodeId, PortId, RuntimeId, ZFError, ZFResult};
 use uhlc::HLC;
 use janu::Session;
 use jnrpc_macros::znservice;
 use jrpc::jrpcresult::{JRPCError, JRPCResult};
 
-use self::dataflow::loader::LoaderConfig;
+use self::dataflow::loader::{LoaderConfig, WasmLimits};
 
+pub mod checkpoint;
 pub mod dataflow;
 pub mod deadline;
+pub mod joiner;
 pub mod message;
+pub mod metrics;
 pub mod resources;
+pub mod timer;
 pub mod token;
+pub mod window;
+
+/// How long the links of an instance are given to be drained when it is stopped, if the
+/// `drain_timeout` of the `RuntimeConfig` is not set.
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Clone)]
 pub struct RuntimeContext {
@@ -50,6 +63,8 @@ pub struct RuntimeContext {
     pub hlc: Arc<HLC>,
     pub runtime_name: RuntimeId,
     pub runtime_uuid: Uuid,
+    // Where the states of the nodes are checkpointed, they are not if `None`.
+    pub checkpoints: Option<Arc<Checkpoints>>,
 }
 
 #[derive(Clone)]
@@ -144,6 +159,83 @@ pub struct RuntimeStatus {
     pub running_sources: usize,
     pub running_sinks: usize,
     pub running_connectors: usize,
+    pub failed_nodes: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum NodeState {
+    Running,
+    Stopped,
+    // The restart policy of the node does not allow any more restart.
+    Error(ZFError),
+}
+
+impl std::fmt::Display for NodeState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            NodeState::Running => write!(f, "Running"),
+            NodeState::Stopped => write!(f, "Stopped"),
+            NodeState::Error(err) => write!(f, "Error({:?})", err),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct NodeStatus {
+    pub id: NodeId,
+    pub state: NodeState,
+    // Connectors have no `Context`, hence no mode.
+    pub mode: Option<usize>,
+    pub restarts: usize,
+    pub last_error: Option<ZFError>,
+}
+
+/// The outcome of draining the links of an instance while stopping its nodes.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct DrainReport {
+    // The links were not drained, or a node did not stop, before the timeout expired.
+    pub timed_out: bool,
+    // The number of messages still in the input links of a node when it was stopped.
+    pub dropped: HashMap<NodeId, usize>,
+}
+
+impl DrainReport {
+    /// Returns the number of messages lost while stopping the nodes.
+    pub fn total_dropped(&self) -> usize {
+        self.dropped.values().sum()
+    }
+
+    /// Merges the report of another runtime (or instance) into this one.
+    pub fn merge(&mut self, other: DrainReport) {
+        self.timed_out |= other.timed_out;
+        for (node, dropped) in other.dropped {
+            *self.dropped.entry(node).or_default() += dropped;
+        }
+    }
+}
+
+impl std::fmt::Display for DrainReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.timed_out {
+            write!(f, "Drain timed out, ")?;
+        }
+        if self.total_dropped() == 0 {
+            return write!(f, "no message dropped");
+        }
+
+        let mut dropped: Vec<String> = self
+            .dropped
+            .iter()
+            .map(|(node, dropped)| format!("{}: {}", node, dropped))
+            .collect();
+        dropped.sort();
+        write!(
+            f,
+            "{} message(s) dropped [{}]",
+            self.total_dropped(),
+            dropped.join(", ")
+        )
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -198,6 +290,34 @@ pub struct RuntimeConfig {
     pub uuid: Option<Uuid>,
     pub janu: JanuConfig,
     pub loader: LoaderConfig,
+    // How long to wait for the links to be drained when stopping an instance.
+    #[serde(default)]
+    pub drain_timeout: Option<DurationDescriptor>,
+    // Address (e.g. "127.0.0.1:9100") on which the metrics are served in the Prometheus text
+    // format, they are not served if unset.
+    #[serde(default)]
+    pub metrics_endpoint: Option<String>,
+    // Hex-encoded ed25519 public keys the libraries of the nodes must be signed with, only their
+    // checksums are checked if empty.
+    #[serde(default)]
+    pub trusted_keys: Vec<String>,
+    // Memory and fuel available to the WebAssembly nodes.
+    #[serde(default)]
+    pub wasm_limits: WasmLimits,
+    // Executable hosting the isolated nodes, `janu-flow-node-host` in the `PATH` if not set.
+    #[serde(default)]
+    pub node_host: Option<String>,
+    // Where, and how often, the states of the nodes are checkpointed. They are not if not set.
+    #[serde(default)]
+    pub checkpoints: Option<CheckpointConfig>,
+}
+
+impl RuntimeConfig {
+    pub fn get_drain_timeout(&self) -> Duration {
+        self.drain_timeout
+            .as_ref()
+            .map_or(DEFAULT_DRAIN_TIMEOUT, |timeout| timeout.to_duration())
+    }
 }
 
 /// The interface the Runtime expose to a client
@@ -242,7 +362,10 @@ pub trait Runtime {
     /// Stops the sinks, connectors, and operators for the given record.
     /// Note that this should be called after the `stop_sources(record)` has returned
     /// successfully otherwise data may be lost.
-    async fn stop(&self, record_id: Uuid) -> ZFResult<()>;
+    /// The nodes are stopped once their input links are drained, or once the `drain_timeout`
+    /// of the runtime has expired. The returned [`DrainReport`] accounts for the messages that
+    /// were dropped.
+    async fn stop(&self, record_id: Uuid) -> ZFResult<DrainReport>;
 
     /// Stops the sources for the given record.
     async fn stop_sources(&self, record_id: Uuid) -> ZFResult<()>;
@@ -280,11 +403,125 @@ pub trait Runtime {
         replay_id: NodeId,
     ) -> ZFResult<NodeId>;
 
+    /// Requests the given graph node of the given instance to switch to `mode`.
+    /// The mode is applied in between two executions of the node, a node never sees
+    /// its mode change while it is running.
+    /// Only the given node switches: a node can also request the nodes downstream of it to
+    /// switch, in order with its data, through a `ControlMessage::ChangeMode` targeting them.
+    /// Connectors do not have a mode.
+    async fn change_mode(&self, instance_id: Uuid, node: NodeId, mode: usize) -> ZFResult<()>;
+
+    /// Reloads the library of the operator `operator.id` of the given instance, its links
+    /// staying in place.
+    /// The URI, checksum, signature and configuration are taken from `operator`, its ports
+    /// cannot change.
+    /// If `transfer_state` is set, the state saved by the current version of the operator is
+    /// restored by the new one, otherwise the new version starts from a fresh state.
+    /// The operator is stopped once its current iteration completes, the inputs it received are
+    /// kept. Its library must be a new file: a library overwritten in place, or loaded again from
+    /// the same path, is the one already loaded and is rejected.
+    async fn reload_node(
+        &self,
+        instance_id: Uuid,
+        operator: OperatorDescriptor,
+        transfer_state: bool,
+    ) -> ZFResult<()>;
+
+    /// Checkpoints the state of the given graph node of the given instance, see
+    /// `runtime::checkpoint`.
+    /// Returns `false` if the node did not save anything, e.g. its state is not serializable.
+    /// Fails if the runtime is not configured to checkpoint the nodes.
+    async fn checkpoint_node(&self, instance_id: Uuid, node: NodeId) -> ZFResult<bool>;
+
+    /// Migrates the given graph node of the given instance to the runtime named `runtime`,
+    /// returning the updated [`DataFlowRecord`].
+    /// The runtime that receives the request coordinates the migration, only the edges of the
+    /// node are paused, each node being stopped once its current iteration completes: the nodes
+    /// sending it messages are stopped, the node is stopped once the links towards it are
+    /// drained and the nodes whose links change are stopped once the links leaving it are
+    /// drained. The state of the node and the messages left in its input links are then taken,
+    /// every runtime involved is rewired following the updated record and both are handed to
+    /// the node on `runtime`. The nodes stopped, and the ones created, are finally started
+    /// again, the nodes sending messages to the node once the others are running.
+    /// Should a link not be drained in time, or the node not be moved, the migration is rolled
+    /// back: the runtimes are rewired following the current record, the node gets back its
+    /// state and its messages, the nodes stopped are started again and the error is returned.
+    /// Only the serializable states can be transferred, see `Node::save_state`: the node
+    /// otherwise starts from a fresh state on `runtime`.
+    async fn migrate_node(
+        &self,
+        instance_id: Uuid,
+        node: NodeId,
+        runtime: String,
+    ) -> ZFResult<DataFlowRecord>;
+
+    /// Saves the state of the given graph node of the given instance, see `Node::save_state`.
+    /// Used when the node is migrated.
+    async fn save_node_state(&self, instance_id: Uuid, node: NodeId) -> ZFResult<Option<Vec<u8>>>;
+
+    /// Restores the state of the given graph node of the given instance from `state`, as
+    /// saved by `save_node_state` on the runtime it was migrated from.
+    /// Note that this should be called before the node is started.
+    async fn transfer_node_state(
+        &self,
+        instance_id: Uuid,
+        node: NodeId,
+        state: Vec<u8>,
+    ) -> ZFResult<()>;
+
+    /// Rewires the runtime following `record`, the updated record of one of its instances:
+    /// the nodes no longer mapped to the runtime are removed, the ones newly mapped to it are
+    /// prepared and the nodes whose links change are wired again, the links that do not change
+    /// keep their messages. The instance is prepared if the runtime was not involved in it yet,
+    /// and cleaned if the runtime is no longer involved.
+    /// Note that the nodes removed, or whose links change, must be stopped first, see
+    /// `pause_node`.
+    async fn rewire(&self, record: DataFlowRecord) -> ZFResult<()>;
+
+    /// Stops the given graph node of the given instance if it is running, once its current
+    /// iteration completes or the drain timeout of the runtime expires.
+    /// Returns `true` if it was, i.e. if it should be started again.
+    async fn pause_node(&self, instance_id: Uuid, node: NodeId) -> ZFResult<bool>;
+
+    /// Waits until the `links` of the given instance that are on the runtime are drained.
+    /// Fails with a `DrainTimeout` error if they are not drained within the drain timeout of
+    /// the runtime.
+    async fn drain_links(&self, instance_id: Uuid, links: Vec<LinkDescriptor>) -> ZFResult<()>;
+
+    /// Takes the messages left in the input links of the given graph node of the given
+    /// instance, along with the input they were sent to. Used when the node is migrated.
+    /// Note that the node must be stopped.
+    async fn take_node_inputs(
+        &self,
+        instance_id: Uuid,
+        node: NodeId,
+    ) -> ZFResult<Vec<(PortId, Message)>>;
+
+    /// Sends `messages`, as taken by `take_node_inputs` on the runtime the node was migrated
+    /// from, on the input links of the given graph node of the given instance.
+    /// Note that this should be called before the nodes feeding it are started.
+    async fn forward_node_inputs(
+        &self,
+        instance_id: Uuid,
+        node: NodeId,
+        messages: Vec<(PortId, Message)>,
+    ) -> ZFResult<()>;
+
+    /// Waits until the given graph nodes of the given instance are running, e.g. until the
+    /// receivers have subscribed to their resource.
+    /// Fails if they are not running within the drain timeout of the runtime.
+    async fn wait_for_nodes(&self, instance_id: Uuid, nodes: Vec<NodeId>) -> ZFResult<()>;
+
+    /// Gets the status of the given graph node for the given instance.
+    /// The status contains the state of the node, the mode it is in, the number of times
+    /// it was restarted since it was started and the last error it encountered.
+    async fn get_node_status(&self, instance_id: Uuid, node: NodeId) -> ZFResult<NodeStatus>;
+
     /// Gets the state of the given graph node for the given instance.
     /// A graph node can be a source, a sink, a connector, or an operator.
     /// The node state represents the current state of the node:
     /// `enum NodeState { Running, Stopped, Error(err) }`
-    // async fn get_node_state(&self, record_id: Uuid, node: String) -> ZFResult<NodeState>;
+    async fn get_node_state(&self, instance_id: Uuid, node: NodeId) -> ZFResult<NodeState>;
 
     /// Sends the `message` to `node` for the given record.
     /// This is useful for sending out-of-band notification to a node.
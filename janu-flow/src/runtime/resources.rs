@@ -25,7 +25,8 @@ extern crate serde_json;
 
 use crate::model::dataflow::record::DataFlowRecord;
 use crate::model::RegistryNode;
-use crate::runtime::{RuntimeConfig, RuntimeInfo, RuntimeStatus};
+use crate::runtime::metrics::NodeMetrics;
+use crate::runtime::{NodeStatus, RuntimeConfig, RuntimeInfo, RuntimeStatus};
 use crate::serde::{de::DeserializeOwned, Serialize};
 use crate::{async_std::sync::Arc, ZFError, ZFResult};
 use async_std::pin::Pin;
@@ -47,10 +48,13 @@ pub static KEY_REGISTRY: &str = "registry";
 
 pub static KEY_FLOWS: &str = "flows";
 pub static KEY_GRAPHS: &str = "graphs";
+pub static KEY_NODES: &str = "nodes";
+pub static KEY_CHECKPOINTS: &str = "checkpoints";
 
 pub static KEY_INFO: &str = "info";
 pub static KEY_STATUS: &str = "status";
 pub static KEY_CONFIGURATION: &str = "configuration";
+pub static KEY_METRICS: &str = "metrics";
 
 #[macro_export]
 macro_rules! RT_INFO_PATH {
@@ -78,6 +82,19 @@ macro_rules! RT_STATUS_PATH {
     };
 }
 
+#[macro_export]
+macro_rules! RT_METRICS_PATH {
+    ($prefix:expr, $rtid:expr) => {
+        format!(
+            "{}/{}/{}/{}",
+            $prefix,
+            $crate::runtime::resources::KEY_RUNTIMES,
+            $rtid,
+            $crate::runtime::resources::KEY_METRICS
+        )
+    };
+}
+
 #[macro_export]
 macro_rules! RT_CONFIGURATION_PATH {
     ($prefix:expr, $rtid:expr) => {
@@ -147,6 +164,38 @@ macro_rules! RT_FLOW_SELECTOR_ALL {
     };
 }
 
+#[macro_export]
+macro_rules! RT_FLOW_NODE_STATUS_PATH {
+    ($prefix:expr, $rtid:expr, $fid:expr, $iid:expr, $nid:expr) => {
+        format!(
+            "{}/{}/{}/{}/{}/{}/{}/{}",
+            $prefix,
+            $crate::runtime::resources::KEY_RUNTIMES,
+            $rtid,
+            $crate::runtime::resources::KEY_FLOWS,
+            $fid,
+            $iid,
+            $crate::runtime::resources::KEY_NODES,
+            $nid
+        )
+    };
+}
+
+// Node ids can contain '/' (e.g. nodes of composites), hence the `**`.
+#[macro_export]
+macro_rules! FLOW_NODE_STATUS_SELECTOR_BY_INSTANCE {
+    ($prefix:expr, $iid:expr) => {
+        format!(
+            "{}/{}/*/{}/*/{}/{}/**",
+            $prefix,
+            $crate::runtime::resources::KEY_RUNTIMES,
+            $crate::runtime::resources::KEY_FLOWS,
+            $iid,
+            $crate::runtime::resources::KEY_NODES
+        )
+    };
+}
+
 #[macro_export]
 macro_rules! FLOW_SELECTOR_BY_INSTANCE {
     ($prefix:expr, $iid:expr) => {
@@ -173,6 +222,20 @@ macro_rules! FLOW_SELECTOR_BY_FLOW {
     };
 }
 
+// Checkpoints are kept per flow, not per instance: a new instance of the flow restores them.
+#[macro_export]
+macro_rules! FLOW_NODE_CHECKPOINT_PATH {
+    ($prefix:expr, $fid:expr, $nid:expr) => {
+        format!(
+            "{}/{}/{}/{}",
+            $prefix,
+            $crate::runtime::resources::KEY_CHECKPOINTS,
+            $fid,
+            $nid
+        )
+    };
+}
+
 #[macro_export]
 macro_rules! REG_GRAPH_SELECTOR {
     ($prefix:expr, $fid:expr) => {
@@ -301,9 +364,11 @@ impl DataStore {
     }
 
     pub async fn get_runtime_info_by_name(&self, rtid: &str) -> ZFResult<RuntimeInfo> {
-        let selector = RT_INFO_PATH!(ROOT_STANDALONE, "*");
-
-        self.get_from_janu::<RuntimeInfo>(&selector).await
+        self.get_all_runtime_info()
+            .await?
+            .into_iter()
+            .find(|rt_info| rt_info.name.as_ref() == rtid)
+            .ok_or(ZFError::Empty)
     }
 
     pub async fn remove_runtime_info(&self, rtid: &Uuid) -> ZFResult<()> {
@@ -365,6 +430,51 @@ impl DataStore {
         Ok(self.z.put(&path, encoded_info).await?)
     }
 
+    pub async fn get_runtime_metrics(&self, rtid: &Uuid) -> ZFResult<Vec<NodeMetrics>> {
+        let selector = RT_METRICS_PATH!(ROOT_STANDALONE, rtid);
+        self.get_from_janu::<Vec<NodeMetrics>>(&selector).await
+    }
+
+    pub async fn remove_runtime_metrics(&self, rtid: &Uuid) -> ZFResult<()> {
+        let path = RT_METRICS_PATH!(ROOT_STANDALONE, rtid);
+
+        Ok(self.z.delete(&path).await?)
+    }
+
+    pub async fn add_runtime_metrics(&self, rtid: &Uuid, metrics: &[NodeMetrics]) -> ZFResult<()> {
+        let path = RT_METRICS_PATH!(ROOT_STANDALONE, rtid);
+
+        let encoded_metrics = serialize_data(metrics)?;
+        Ok(self.z.put(&path, encoded_metrics).await?)
+    }
+
+    pub async fn get_node_checkpoint(&self, fid: &str, nid: &str) -> ZFResult<Option<Vec<u8>>> {
+        let selector = FLOW_NODE_CHECKPOINT_PATH!(ROOT_STANDALONE, fid, nid);
+        match self.get_from_janu::<Vec<u8>>(&selector).await {
+            Ok(checkpoint) => Ok(Some(checkpoint)),
+            Err(ZFError::Empty) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn remove_node_checkpoint(&self, fid: &str, nid: &str) -> ZFResult<()> {
+        let path = FLOW_NODE_CHECKPOINT_PATH!(ROOT_STANDALONE, fid, nid);
+
+        Ok(self.z.delete(&path).await?)
+    }
+
+    pub async fn add_node_checkpoint(
+        &self,
+        fid: &str,
+        nid: &str,
+        checkpoint: &[u8],
+    ) -> ZFResult<()> {
+        let path = FLOW_NODE_CHECKPOINT_PATH!(ROOT_STANDALONE, fid, nid);
+
+        let encoded_checkpoint = serialize_data(checkpoint)?;
+        Ok(self.z.put(&path, encoded_checkpoint).await?)
+    }
+
     pub async fn get_runtime_flow_by_instance(
         &self,
         rtid: &Uuid,
@@ -444,6 +554,36 @@ impl DataStore {
         Ok(self.z.put(&path, encoded_info).await?)
     }
 
+    pub async fn get_instance_node_statuses(&self, iid: &Uuid) -> ZFResult<Vec<NodeStatus>> {
+        let selector = FLOW_NODE_STATUS_SELECTOR_BY_INSTANCE!(ROOT_STANDALONE, iid);
+        self.get_vec_from_janu::<NodeStatus>(&selector).await
+    }
+
+    pub async fn remove_runtime_flow_node_status(
+        &self,
+        rtid: &Uuid,
+        fid: &str,
+        iid: &Uuid,
+        nid: &str,
+    ) -> ZFResult<()> {
+        let path = RT_FLOW_NODE_STATUS_PATH!(ROOT_STANDALONE, rtid, fid, iid, nid);
+
+        Ok(self.z.delete(&path).await?)
+    }
+
+    pub async fn add_runtime_flow_node_status(
+        &self,
+        rtid: &Uuid,
+        fid: &str,
+        iid: &Uuid,
+        node_status: &NodeStatus,
+    ) -> ZFResult<()> {
+        let path = RT_FLOW_NODE_STATUS_PATH!(ROOT_STANDALONE, rtid, fid, iid, node_status.id);
+
+        let encoded_info = serialize_data(node_status)?;
+        Ok(self.z.put(&path, encoded_info).await?)
+    }
+
     // Registry Related
 
     pub async fn add_graph(&self, graph: &RegistryNode) -> ZFResult<()> {
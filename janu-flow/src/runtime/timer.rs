@@ -0,0 +1,182 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use crate::TimerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TimerKind {
+    /// The timer expires every `period`, starting one `period` after it is set. A null period
+    /// makes it expire only once.
+    Periodic(Duration),
+    /// The timer expires once, `delay` after it is set.
+    OneShot(Duration),
+}
+
+/// A timer wakes up an Operator, even if no data was received: its `Input Rule` is called once
+/// the timer expires.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timer {
+    pub id: TimerId,
+    pub kind: TimerKind,
+}
+
+impl Timer {
+    pub fn periodic(id: impl Into<TimerId>, period: Duration) -> Self {
+        Self {
+            id: id.into(),
+            kind: TimerKind::Periodic(period),
+        }
+    }
+
+    pub fn one_shot(id: impl Into<TimerId>, delay: Duration) -> Self {
+        Self {
+            id: id.into(),
+            kind: TimerKind::OneShot(delay),
+        }
+    }
+}
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Returns `instant` moved `periods` times `period` later, if it can be represented.
+pub(crate) fn add_periods(instant: Instant, period: Duration, periods: u128) -> Option<Instant> {
+    let nanos = period.as_nanos().checked_mul(periods)?;
+    let secs = u64::try_from(nanos / NANOS_PER_SEC).ok()?;
+    instant.checked_add(Duration::new(secs, (nanos % NANOS_PER_SEC) as u32))
+}
+
+#[derive(Debug)]
+struct ArmedTimer {
+    kind: TimerKind,
+    deadline: Instant,
+}
+
+/// The timers of an Operator, stored in its `Context`.
+#[derive(Debug, Default)]
+pub(crate) struct Timers {
+    armed: HashMap<TimerId, ArmedTimer>,
+    expired: Vec<TimerId>,
+}
+
+impl Timers {
+    /// Arms the timer, replacing the timer with the same id if there is one.
+    pub(crate) fn set(&mut self, timer: Timer, now: Instant) {
+        let deadline = match timer.kind {
+            TimerKind::Periodic(duration) | TimerKind::OneShot(duration) => now + duration,
+        };
+        self.armed.insert(
+            timer.id,
+            ArmedTimer {
+                kind: timer.kind,
+                deadline,
+            },
+        );
+    }
+
+    pub(crate) fn cancel(&mut self, id: &str) -> bool {
+        self.armed.remove(id).is_some()
+    }
+
+    pub(crate) fn next_deadline(&self) -> Option<Instant> {
+        self.armed.values().map(|timer| timer.deadline).min()
+    }
+
+    /// Marks as expired all the timers whose deadline is reached.
+    ///
+    /// One-shot timers are disarmed. The next deadline of a periodic timer is computed from its
+    /// previous deadline, not from `now`, so that it does not drift. The ticks missed while the
+    /// Operator was busy are skipped: a periodic timer is reported as expired only once.
+    pub(crate) fn expire(&mut self, now: Instant) {
+        let mut disarmed = vec![];
+        for (id, timer) in self.armed.iter_mut() {
+            if timer.deadline > now {
+                continue;
+            }
+
+            if !self.expired.contains(id) {
+                self.expired.push(id.clone());
+            }
+
+            match timer.kind {
+                TimerKind::Periodic(period) if period > Duration::ZERO => {
+                    let missed = (now - timer.deadline).as_nanos() / period.as_nanos();
+                    match add_periods(timer.deadline, period, missed + 1) {
+                        Some(deadline) => timer.deadline = deadline,
+                        // The next deadline cannot be represented, it would never be reached.
+                        None => disarmed.push(id.clone()),
+                    }
+                }
+                // A periodic timer with a null period would expire continuously.
+                TimerKind::Periodic(_) | TimerKind::OneShot(_) => disarmed.push(id.clone()),
+            }
+        }
+
+        for id in disarmed {
+            self.armed.remove(&id);
+        }
+    }
+
+    pub(crate) fn expired(&self) -> &[TimerId] {
+        &self.expired
+    }
+
+    /// Returns the armed timers, with the time remaining until their deadline, and the expired
+    /// timers. See `restore`.
+    pub(crate) fn snapshot(&self, now: Instant) -> (Vec<(Timer, Duration)>, Vec<TimerId>) {
+        let armed = self
+            .armed
+            .iter()
+            .map(|(id, timer)| {
+                (
+                    Timer {
+                        id: id.clone(),
+                        kind: timer.kind.clone(),
+                    },
+                    timer.deadline.saturating_duration_since(now),
+                )
+            })
+            .collect();
+        (armed, self.expired.clone())
+    }
+
+    /// Replaces the timers by the ones returned by `snapshot`.
+    pub(crate) fn restore(
+        &mut self,
+        armed: Vec<(Timer, Duration)>,
+        expired: Vec<TimerId>,
+        now: Instant,
+    ) {
+        self.armed = armed
+            .into_iter()
+            .map(|(timer, remaining)| {
+                (
+                    timer.id,
+                    ArmedTimer {
+                        kind: timer.kind,
+                        deadline: now + remaining,
+                    },
+                )
+            })
+            .collect();
+        self.expired = expired;
+    }
+
+    pub(crate) fn clear_expired(&mut self) {
+        self.expired.clear();
+    }
+}
@@ -14,6 +14,7 @@
 
 use crate::runtime::deadline::E2EDeadlineMiss;
 use crate::{Data, DataMessage, PortId};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uhlc::Timestamp;
 
@@ -34,7 +35,7 @@ impl Tokens {
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TokenAction {
     Consume,
     Drop,
@@ -71,7 +72,9 @@ impl ReadyToken {
     ///
     /// The data associated to the `Token` will be transmitted to the `Run` method of the `Operator`
     /// the next time `Run` is called, i.e. the next time the `Input Rule` returns `True`.
-    /// Once `Run` is over, the data is kept, _preventing data from being received on that link_.
+    /// Once `Run` is over, the data is kept, _preventing data from being received on that link_
+    /// until the `Input Rule` returns `False` with every `Token` kept: new data then replaces the
+    /// kept data.
     pub fn set_action_keep(&mut self) {
         self.action = TokenAction::Keep
     }
@@ -109,6 +112,10 @@ pub enum Token {
 }
 
 impl Token {
+    pub fn is_pending(&self) -> bool {
+        matches!(self, Token::Pending)
+    }
+
     pub(crate) fn should_drop(&self) -> bool {
         if let Token::Ready(token_ready) = self {
             if let TokenAction::Drop = token_ready.action {
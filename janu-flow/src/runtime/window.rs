@@ -0,0 +1,264 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use crate::{
+    Context, ControlMessage, DataMessage, PortId, Timer, TimerId, Token, ZFError, ZFResult,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uhlc::Timestamp;
+
+/// The id of the timer that, when it expires, closes the windows based on the current time.
+///
+/// Setting a periodic timer with this id emits the windows even if no data or watermark is
+/// received.
+pub const WINDOWS_TIMER: &str = "janu-flow-windows";
+
+// The id of the timer set to call the `Input Rule` once windows were closed by a watermark.
+const WINDOWS_CLOSED_TIMER: &str = "janu-flow-windows-closed";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WindowKind {
+    /// Consecutive windows of `size`, each sample belongs to exactly one window.
+    Tumbling(Duration),
+    /// Windows of `size` starting every `slide`: a sample belongs to `size / slide` windows.
+    Sliding { size: Duration, slide: Duration },
+}
+
+impl WindowKind {
+    fn size(&self) -> Duration {
+        match self {
+            Self::Tumbling(size) => *size,
+            Self::Sliding { size, .. } => *size,
+        }
+    }
+
+    fn slide(&self) -> Duration {
+        match self {
+            Self::Tumbling(size) => *size,
+            Self::Sliding { slide, .. } => *slide,
+        }
+    }
+}
+
+/// A closed window: the samples whose timestamp is in `[start, end)`, in the order they were
+/// received.
+///
+/// `start` and `end` are HLC times, i.e. durations since the UNIX epoch.
+#[derive(Debug, Clone)]
+pub struct Window<T> {
+    pub start: Duration,
+    pub end: Duration,
+    pub items: Vec<T>,
+}
+
+/// Groups samples in time windows, based on their timestamp, and emits the windows once they are
+/// closed.
+///
+/// `Windows` is meant to be stored in the `State` of an Operator. Windows are aligned on the UNIX
+/// epoch and a window `[start, end)` closes once the watermark reaches `end + allowed_lateness`.
+/// The watermark advances with:
+/// - the timestamps of the samples inserted,
+/// - the watermarks received (see `on_watermark`),
+/// - the current time, when the timer `WINDOWS_TIMER` expires (see `input_rule`).
+///
+/// A sample arriving after all its windows are closed is late: it is discarded and counted.
+/// Windows without samples are not emitted.
+#[derive(Debug)]
+pub struct Windows<T> {
+    kind: WindowKind,
+    allowed_lateness: Duration,
+    watermark: Duration,
+    open: BTreeMap<Duration, Vec<T>>,
+    closed: Vec<Window<T>>,
+    late_count: usize,
+}
+
+impl<T: Clone> Windows<T> {
+    pub fn try_new(kind: WindowKind) -> ZFResult<Self> {
+        if kind.size() == Duration::ZERO || kind.slide() == Duration::ZERO {
+            return Err(ZFError::InvalidData(format!(
+                "The size and slide of windows must be strictly positive: {:?}",
+                kind
+            )));
+        }
+
+        Ok(Self {
+            kind,
+            allowed_lateness: Duration::ZERO,
+            watermark: Duration::ZERO,
+            open: BTreeMap::new(),
+            closed: vec![],
+            late_count: 0,
+        })
+    }
+
+    /// Sets for how long, after their end, windows accept samples.
+    pub fn with_allowed_lateness(mut self, allowed_lateness: Duration) -> Self {
+        self.allowed_lateness = allowed_lateness;
+        self
+    }
+
+    /// Inserts `item` in the windows `timestamp` belongs to, then advances the watermark to
+    /// `timestamp`.
+    ///
+    /// Returns `false` if the item is late, i.e. all its windows are already closed.
+    pub fn insert(&mut self, timestamp: &Timestamp, item: T) -> bool {
+        let time = timestamp.get_time().to_duration();
+        let size = self.kind.size().as_nanos();
+        let slide = self.kind.slide().as_nanos();
+
+        // The last window `time` belongs to starts at the multiple of `slide` preceding it; the
+        // previous ones every `slide` before, as long as they end after `time`.
+        let mut start = time.as_nanos() - time.as_nanos() % slide;
+        let mut inserted = false;
+        loop {
+            let end = start + size;
+            if end <= time.as_nanos() {
+                break;
+            }
+
+            let start_duration = nanos_to_duration(start);
+            if !self.is_closed(nanos_to_duration(end)) {
+                self.open
+                    .entry(start_duration)
+                    .or_default()
+                    .push(item.clone());
+                inserted = true;
+            }
+
+            if start < slide {
+                break;
+            }
+            start -= slide;
+        }
+
+        if !inserted {
+            self.late_count += 1;
+            log::debug!("[Windows] Late sample discarded: {:?}", time);
+        }
+
+        self.advance_to(time);
+        inserted
+    }
+
+    /// Advances the watermark to `watermark`, closing the windows that end before it minus the
+    /// allowed lateness.
+    ///
+    /// Returns `true` if windows were closed and are waiting to be taken.
+    pub fn advance(&mut self, watermark: &Timestamp) -> bool {
+        self.advance_to(watermark.get_time().to_duration())
+    }
+
+    /// Same as `advance` with a time since the UNIX epoch.
+    pub fn advance_to(&mut self, watermark: Duration) -> bool {
+        if watermark > self.watermark {
+            self.watermark = watermark;
+        }
+
+        let size = self.kind.size();
+        while let Some((&start, _)) = self.open.iter().next() {
+            if !self.is_closed(start + size) {
+                break;
+            }
+
+            if let Some(items) = self.open.remove(&start) {
+                self.closed.push(Window {
+                    start,
+                    end: start + size,
+                    items,
+                });
+            }
+        }
+
+        !self.closed.is_empty()
+    }
+
+    /// Returns the closed windows, by order of closing.
+    pub fn take_closed(&mut self) -> Vec<Window<T>> {
+        std::mem::take(&mut self.closed)
+    }
+
+    pub fn get_watermark(&self) -> Duration {
+        self.watermark
+    }
+
+    /// Returns how many samples were discarded because they were late.
+    pub fn get_late_count(&self) -> usize {
+        self.late_count
+    }
+
+    /// Returns how many windows are still open.
+    pub fn get_open_count(&self) -> usize {
+        self.open.len()
+    }
+
+    fn is_closed(&self, end: Duration) -> bool {
+        end + self.allowed_lateness <= self.watermark
+    }
+}
+
+impl Windows<DataMessage> {
+    /// The `Input Rule` of the windows.
+    ///
+    /// The data of all the `Ready` tokens is inserted and the tokens are dropped, allowing for new
+    /// data to be received. If the timer `WINDOWS_TIMER` expired, the watermark advances to the
+    /// current time. Returns `true` if windows are closed: `Run` is then called, without inputs,
+    /// and should retrieve them with `take_closed`.
+    pub fn input_rule(
+        &mut self,
+        context: &mut Context,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        for token in tokens.values_mut() {
+            if let Token::Ready(ready_token) = token {
+                self.insert(&ready_token.data.timestamp, ready_token.data.clone());
+                ready_token.set_action_drop();
+            }
+        }
+
+        let timer: TimerId = WINDOWS_TIMER.into();
+        if context.get_expired_timers().contains(&timer) {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            self.advance_to(now);
+        }
+
+        Ok(!self.closed.is_empty())
+    }
+
+    /// Advances the watermark if `message` is a `ControlMessage::Watermark`, to be called from
+    /// `Node::on_control_message`.
+    ///
+    /// As a control message does not trigger the `Input Rule`, a timer is set to call it
+    /// immediately if windows were closed.
+    pub fn on_watermark(&mut self, context: &mut Context, message: &ControlMessage) -> bool {
+        if let ControlMessage::Watermark(timestamp) = message {
+            if self.advance(timestamp) {
+                context.set_timer(Timer::one_shot(WINDOWS_CLOSED_TIMER, Duration::ZERO));
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+fn nanos_to_duration(nanos: u128) -> Duration {
+    Duration::new(
+        (nanos / 1_000_000_000) as u64,
+        (nanos % 1_000_000_000) as u32,
+    )
+}
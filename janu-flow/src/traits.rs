@@ -12,9 +12,10 @@
 //   open
 //
 
-use crate::runtime::message::DataMessage;
+use crate::runtime::message::{ControlMessage, DataMessage};
 use crate::{
-    Configuration, Context, Data, LocalDeadlineMiss, NodeOutput, PortId, State, Token, ZFResult,
+    Configuration, Context, Data, LocalDeadlineMiss, NodeOutput, PortId, State, Timer, Token,
+    ZFError, ZFResult,
 };
 use async_trait::async_trait;
 use std::any::Any;
@@ -41,15 +42,70 @@ pub trait Deserializable {
 pub trait ZFState: Debug + Send + Sync {
     fn as_any(&self) -> &dyn Any;
     fn as_mut_any(&mut self) -> &mut dyn Any;
+
+    /// Serializes the state, for it to be checkpointed or handed over to a reloaded node.
+    ///
+    /// States are not serializable by default, `None` is returned. The `ZFState` derive
+    /// implements it with `bincode` when the state is marked `#[zf_state(serializable)]`.
+    fn try_serialize(&self) -> ZFResult<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    /// Replaces the state by the one serialized in `bytes` by `try_serialize`.
+    fn try_deserialize(&mut self, _bytes: &[u8]) -> ZFResult<()> {
+        Err(ZFError::Unsupported)
+    }
 }
 
 pub trait Node {
     fn initialize(&self, configuration: &Option<Configuration>) -> ZFResult<State>;
 
     fn finalize(&self, state: &mut State) -> ZFResult<()>;
+
+    /// Called when a control message is received on the input `port_id`, after the runtime has
+    /// processed it.
+    ///
+    /// The returned boolean indicates if the message should be forwarded to all the outputs of the
+    /// node (it has no effect for a Sink). The default implementation forwards everything.
+    /// An Operator forwards watermarks only once each of its inputs received one, the minimum
+    /// across its inputs, and then whenever that minimum advances.
+    fn on_control_message(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        _port_id: &PortId,
+        _message: &ControlMessage,
+    ) -> ZFResult<bool> {
+        Ok(true)
+    }
+
+    /// Called before the node is finalized when its library is reloaded, the returned bytes are
+    /// handed over to `restore_state` of the new version of the node. Also called to checkpoint
+    /// the state of the node, see `runtime::checkpoint`, and when it is migrated to another
+    /// runtime.
+    ///
+    /// The default implementation serializes the state, see `ZFState::try_serialize`: if the
+    /// state is not serializable nothing is saved and the new version starts from the state
+    /// returned by its `initialize`.
+    fn save_state(&self, state: &mut State) -> ZFResult<Option<Vec<u8>>> {
+        state.try_serialize()
+    }
+
+    /// Called after the node is initialized when its library is reloaded, with the bytes returned
+    /// by `save_state` of the previous version of the node, when it is restored from its latest
+    /// checkpoint or when it was migrated from another runtime.
+    fn restore_state(&self, state: &mut State, saved_state: &[u8]) -> ZFResult<()> {
+        state.try_deserialize(saved_state)
+    }
 }
 
 pub trait Operator: Node + Send + Sync {
+    /// The timers set when the Operator starts. More timers can be set, or canceled, through the
+    /// `Context`.
+    fn timers(&self) -> Vec<Timer> {
+        vec![]
+    }
+
     fn input_rule(
         &self,
         context: &mut Context,
@@ -73,6 +129,79 @@ pub trait Operator: Node + Send + Sync {
     ) -> ZFResult<HashMap<PortId, NodeOutput>>;
 }
 
+/// Asynchronous variant of an `Operator`.
+///
+/// The runtime awaits `input_rule`, `run` and `output_rule` instead of calling them: an
+/// asynchronous operator can thus wait on I/O without blocking the task that drives it. The
+/// execution time compared against the local deadline includes the time spent awaiting in `run`.
+///
+/// Every `Operator` is also an `AsyncOperator`.
+#[async_trait]
+pub trait AsyncOperator: Node + Send + Sync {
+    /// The timers set when the Operator starts. More timers can be set, or canceled, through the
+    /// `Context`.
+    fn timers(&self) -> Vec<Timer> {
+        vec![]
+    }
+
+    async fn input_rule(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool>;
+
+    async fn run(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>>;
+
+    async fn output_rule(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>>;
+}
+
+#[async_trait]
+impl<T: Operator> AsyncOperator for T {
+    fn timers(&self) -> Vec<Timer> {
+        Operator::timers(self)
+    }
+
+    async fn input_rule(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        Operator::input_rule(self, context, state, tokens)
+    }
+
+    async fn run(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        Operator::run(self, context, state, inputs)
+    }
+
+    async fn output_rule(
+        &self,
+        context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        Operator::output_rule(self, context, state, outputs, deadline_miss)
+    }
+}
+
 #[async_trait]
 pub trait Source: Node + Send + Sync {
     async fn run(&self, context: &mut Context, state: &mut State) -> ZFResult<Data>;
@@ -13,16 +13,18 @@
 //
 
 use crate::async_std::sync::Arc;
+use crate::runtime::timer::Timers;
 use crate::serde::{Deserialize, Serialize};
-use crate::{ControlMessage, DataMessage, Token, ZFData, ZFState};
+use crate::{ControlMessage, DataMessage, Timer, Token, TokenAction, ZFData, ZFState};
 use std::collections::HashMap;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 pub type NodeId = Arc<str>;
 pub type PortId = Arc<str>;
 pub type RuntimeId = Arc<str>;
 pub type FlowId = Arc<str>;
 pub type PortType = Arc<str>;
+pub type TimerId = Arc<str>;
 
 pub type ZFResult<T> = Result<T, ZFError>;
 
@@ -33,6 +35,48 @@ pub use crate::ZFError;
 #[derive(Default, Debug)]
 pub struct Context {
     pub mode: usize,
+    pub(crate) timers: Timers,
+    pub(crate) pending_since: HashMap<PortId, Instant>,
+}
+
+impl Context {
+    /// Sets a timer, replacing the timer with the same id if there is one.
+    ///
+    /// Only Operators have timers: once a timer expires, the `Input Rule` of the Operator is
+    /// called even if no data was received.
+    pub fn set_timer(&mut self, timer: Timer) {
+        self.timers.set(timer, Instant::now());
+    }
+
+    /// Cancels the timer `id`, returns `false` if it was not set.
+    pub fn cancel_timer(&mut self, id: &str) -> bool {
+        self.timers.cancel(id)
+    }
+
+    /// Returns the timers that expired since the last execution of `Run`.
+    pub fn get_expired_timers(&self) -> &[TimerId] {
+        self.timers.expired()
+    }
+
+    /// Returns for how long the `Token` of the input `port_id` has been pending, i.e. the time
+    /// elapsed since the data previously received on that input was consumed or dropped. Returns
+    /// `None` if the `Token` is ready.
+    ///
+    /// Combined with a timer, it allows an `Input Rule` to trigger the `Run` method on partial
+    /// inputs when some data is late.
+    pub fn get_pending_duration(&self, port_id: &str) -> Option<Duration> {
+        self.pending_since.get(port_id).map(|since| since.elapsed())
+    }
+
+    // Marks the `Token` of the input `port_id` as pending from now on.
+    pub(crate) fn set_pending(&mut self, port_id: &PortId) {
+        self.pending_since.insert(port_id.clone(), Instant::now());
+    }
+
+    // Marks the `Token` of the input `port_id` as ready.
+    pub(crate) fn set_ready(&mut self, port_id: &str) {
+        self.pending_since.remove(port_id);
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -131,9 +175,19 @@ impl State {
             .downcast_mut::<S>()
             .ok_or_else(|| ZFError::InvalidData("Could not downcast.".to_string()))
     }
+
+    /// Serializes the state, `None` if it is not serializable, see `ZFState::try_serialize`.
+    pub fn try_serialize(&self) -> ZFResult<Option<Vec<u8>>> {
+        self.state.try_serialize()
+    }
+
+    /// Replaces the state by the one serialized in `bytes`.
+    pub fn try_deserialize(&mut self, bytes: &[u8]) -> ZFResult<()> {
+        self.state.try_deserialize(bytes)
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum NodeOutput {
     Data(Data),
     // TODO Users should not have access to all control messages. When implementing the control
@@ -216,6 +270,48 @@ pub fn default_input_rule(
     Ok(true)
 }
 
+/// Input rule triggering `run` as soon as data is available on any input.
+pub fn any_input_rule(_state: &mut State, tokens: &mut HashMap<PortId, Token>) -> ZFResult<bool> {
+    Ok(tokens.values().any(|token| !token.is_pending()))
+}
+
+/// Input rule triggering `run` when data is available on at least `k` inputs.
+pub fn k_of_n_input_rule(
+    _state: &mut State,
+    tokens: &mut HashMap<PortId, Token>,
+    k: usize,
+) -> ZFResult<bool> {
+    Ok(tokens.values().filter(|token| !token.is_pending()).count() >= k)
+}
+
+/// Sample-and-hold input rule: `run` is triggered each time new data is received on an input,
+/// once data was received at least once on all inputs. The latest data of each input is kept and
+/// given to all subsequent executions of `run`, until newer data replaces it.
+///
+/// The data is kept with `TokenAction::Keep`. When the rule returns `false` because no new data
+/// was received, the runtime waits for data on all the inputs, the new data replacing the kept
+/// data.
+pub fn latest_input_rule(
+    _state: &mut State,
+    tokens: &mut HashMap<PortId, Token>,
+) -> ZFResult<bool> {
+    let mut fresh = false;
+    for token in tokens.values() {
+        match token {
+            Token::Ready(ready_token) => fresh |= ready_token.action != TokenAction::Keep,
+            Token::Pending => return Ok(false),
+        }
+    }
+
+    for token in tokens.values_mut() {
+        if let Token::Ready(ready_token) = token {
+            ready_token.set_action_keep();
+        }
+    }
+
+    Ok(fresh)
+}
+
 pub type Configuration = serde_json::Value;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
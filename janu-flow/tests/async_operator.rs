@@ -0,0 +1,220 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+mod types;
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use janu_flow::model::link::PortDescriptor;
+use janu_flow::model::{InputDescriptor, OutputDescriptor};
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::RuntimeContext;
+use janu_flow::{
+    default_input_rule, default_output_rule, zf_empty_state, AsyncOperator, Configuration, Data,
+    LocalDeadlineMiss, Node, NodeOutput, PortId, State, ZFError, ZFResult,
+};
+use std::collections::HashMap;
+use std::time::Duration;
+use types::{VecSink, VecSource, ZFUsize};
+
+static SOURCE: &str = "Source";
+static OPERATOR: &str = "AsyncOperator";
+static SINK: &str = "Sink";
+
+// Doubles its input after waiting, without blocking, for longer than its local deadline.
+#[derive(Debug)]
+struct AsyncDouble;
+
+impl Node for AsyncDouble {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl AsyncOperator for AsyncDouble {
+    async fn input_rule(
+        &self,
+        _context: &mut janu_flow::Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, janu_flow::Token>,
+    ) -> ZFResult<bool> {
+        default_input_rule(state, tokens)
+    }
+
+    async fn run(
+        &self,
+        _context: &mut janu_flow::Context,
+        _state: &mut State,
+        inputs: &mut HashMap<PortId, janu_flow::runtime::message::DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let mut results: HashMap<PortId, Data> = HashMap::new();
+
+        async_std::task::sleep(Duration::from_millis(50)).await;
+
+        let mut data_msg = inputs
+            .remove(SOURCE)
+            .ok_or_else(|| ZFError::InvalidData("No data".to_string()))?;
+        let data = data_msg.get_inner_data().try_get::<ZFUsize>()?;
+
+        results.insert(SINK.into(), Data::from::<ZFUsize>(ZFUsize(data.0 * 2)));
+
+        Ok(results)
+    }
+
+    async fn output_rule(
+        &self,
+        _context: &mut janu_flow::Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        assert!(
+            deadline_miss.is_some(),
+            "Expected `deadline_miss` to be `Some`"
+        );
+        default_output_rule(state, outputs)
+    }
+}
+
+async fn single_runtime() {
+    let (tx_sink, rx_sink) = flume::bounded::<()>(1);
+
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let hlc = async_std::sync::Arc::new(uhlc::HLC::default());
+    let rt_uuid = uuid::Uuid::new_v4();
+    let ctx = RuntimeContext {
+        session,
+        hlc,
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
+        runtime_name: format!("test-runtime-{}", rt_uuid).into(),
+        runtime_uuid: rt_uuid,
+        checkpoints: None,
+    };
+
+    let mut dataflow =
+        janu_flow::runtime::dataflow::Dataflow::new(ctx.clone(), "test".into(), None);
+
+    // Both vectors are consumed from the end.
+    let source = Arc::new(VecSource::new(vec![3, 2, 1]));
+    let sink = Arc::new(VecSink::new(tx_sink, vec![6, 4, 2]));
+    let operator = Arc::new(AsyncDouble {});
+
+    dataflow
+        .try_add_static_source(
+            SOURCE.into(),
+            None,
+            PortDescriptor {
+                port_id: SOURCE.into(),
+                port_type: "int".into(),
+            },
+            source.initialize(&None).unwrap(),
+            source,
+        )
+        .unwrap();
+
+    dataflow
+        .try_add_static_sink(
+            SINK.into(),
+            PortDescriptor {
+                port_id: SINK.into(),
+                port_type: "int".into(),
+            },
+            sink.initialize(&None).unwrap(),
+            sink,
+        )
+        .unwrap();
+
+    dataflow
+        .try_add_static_async_operator(
+            OPERATOR.into(),
+            vec![PortDescriptor {
+                port_id: SOURCE.into(),
+                port_type: "int".into(),
+            }],
+            vec![PortDescriptor {
+                port_id: SINK.into(),
+                port_type: "int".into(),
+            }],
+            Some(Duration::from_millis(10)),
+            operator.initialize(&None).unwrap(),
+            operator,
+        )
+        .unwrap();
+
+    dataflow
+        .try_add_link(
+            OutputDescriptor {
+                node: SOURCE.into(),
+                output: SOURCE.into(),
+            },
+            InputDescriptor {
+                node: OPERATOR.into(),
+                input: SOURCE.into(),
+            },
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+    dataflow
+        .try_add_link(
+            OutputDescriptor {
+                node: OPERATOR.into(),
+                output: SINK.into(),
+            },
+            InputDescriptor {
+                node: SINK.into(),
+                input: SINK.into(),
+            },
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    instance.start_nodes().await.unwrap();
+    instance.start_sources().await.unwrap();
+
+    // Wait for the Sink to receive all the values.
+    rx_sink.recv_async().await.unwrap();
+
+    let metrics = instance.get_metrics();
+    let operator_metrics = metrics
+        .iter()
+        .find(|m| m.node_id.as_ref() == OPERATOR)
+        .unwrap();
+    assert_eq!(operator_metrics.messages_in, 3);
+    assert_eq!(operator_metrics.messages_out, 3);
+    assert_eq!(operator_metrics.errors, 0);
+
+    instance.stop_sources().await.unwrap();
+    instance
+        .stop_nodes(janu_flow::runtime::DEFAULT_DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn async_operator() {
+    async_std::task::block_on(async move { single_runtime().await })
+}
@@ -0,0 +1,281 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use async_std::sync::Arc;
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::metrics::NodeMetrics;
+use janu_flow::runtime::RuntimeContext;
+use janu_flow::{NodeId, ZFError, ZFResult};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+static RUNTIME: &str = "builtin-runtime";
+
+// Visual representation of the dataflow:
+//
+//  ┌───────┐   ┌────────┐   ┌───────┐   ┌──────────────┐   ┌─────┐
+//  │ Timer ├──►│ Sample ├──►│ Dedup ├──►│ Pass-Through ├─┬►│ Log │
+//  └───┬───┘   └────────┘   └───────┘   └──────────────┘ │ └─────┘
+//      │                                                 │ ┌──────┐
+//      │                                                 └►│ Null │
+//      │                                                   └──────┘
+//      │  ┌──────────┐   ┌─────────┐
+//      └─►│ Throttle ├──►│ Discard │
+//         └──────────┘   └─────────┘
+fn descriptor(runtime: &str) -> String {
+    format!(
+        r#"
+flow: Builtin
+sources:
+  - id : Timer
+    uri: builtin://timer
+    period:
+      length: 5
+      unit: ms
+    output:
+      id: out
+      type: u64
+operators:
+  - id : Sample
+    uri: builtin://sample
+    configuration:
+      rate: 3
+    inputs:
+      - id: in
+        type: u64
+    outputs:
+      - id: out
+        type: u64
+  - id : Dedup
+    uri: builtin://dedup
+    inputs:
+      - id: in
+        type: u64
+    outputs:
+      - id: out
+        type: u64
+  - id : Throttle
+    uri: builtin://throttle
+    configuration:
+      interval:
+        length: 40
+        unit: ms
+    inputs:
+      - id: in
+        type: u64
+    outputs:
+      - id: out
+        type: u64
+  - id : PassThrough
+    uri: builtin://pass-through
+    inputs:
+      - id: in
+        type: u64
+    outputs:
+      - id: out
+        type: u64
+sinks:
+  - id : Log
+    uri: builtin://log
+    input:
+      id: in
+      type: u64
+  - id : Null
+    uri: builtin://null
+    input:
+      id: in
+      type: u64
+  - id : Discard
+    uri: builtin://null
+    input:
+      id: in
+      type: u64
+
+links:
+- from:
+    node : Timer
+    output : out
+  to:
+    node : Sample
+    input : in
+- from:
+    node : Sample
+    output : out
+  to:
+    node : Dedup
+    input : in
+- from:
+    node : Dedup
+    output : out
+  to:
+    node : PassThrough
+    input : in
+- from:
+    node : PassThrough
+    output : out
+  to:
+    node : Log
+    input : in
+- from:
+    node : PassThrough
+    output : out
+  to:
+    node : Null
+    input : in
+- from:
+    node : Timer
+    output : out
+  to:
+    node : Throttle
+    input : in
+- from:
+    node : Throttle
+    output : out
+  to:
+    node : Discard
+    input : in
+
+mapping:
+  - id: Timer
+    runtime: {runtime}
+  - id: Sample
+    runtime: {runtime}
+  - id: Dedup
+    runtime: {runtime}
+  - id: Throttle
+    runtime: {runtime}
+  - id: PassThrough
+    runtime: {runtime}
+  - id: Log
+    runtime: {runtime}
+  - id: Null
+    runtime: {runtime}
+  - id: Discard
+    runtime: {runtime}
+"#,
+        runtime = runtime
+    )
+}
+
+async fn runtime_context() -> RuntimeContext {
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    RuntimeContext {
+        session,
+        hlc: Arc::new(uhlc::HLC::default()),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
+        runtime_name: RUNTIME.into(),
+        runtime_uuid: uuid::Uuid::new_v4(),
+        checkpoints: None,
+    }
+}
+
+fn record(descriptor: &str) -> ZFResult<DataFlowRecord> {
+    let descriptor = DataFlowDescriptor::from_yaml(descriptor)?;
+    DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4()))
+}
+
+// Runs the dataflow for `duration` and returns the metrics of its nodes.
+async fn run_for(duration: Duration) -> HashMap<NodeId, NodeMetrics> {
+    let ctx = runtime_context().await;
+    let dataflow = Dataflow::try_new(ctx, record(&descriptor(RUNTIME)).unwrap()).unwrap();
+
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+    for id in &instance.get_nodes() {
+        instance.start_node(id).await.unwrap();
+    }
+
+    async_std::task::sleep(duration).await;
+
+    for id in &instance.get_sources() {
+        instance.stop_node(id).await.unwrap()
+    }
+
+    for id in &instance.get_operators() {
+        instance.stop_node(id).await.unwrap()
+    }
+
+    for id in &instance.get_sinks() {
+        instance.stop_node(id).await.unwrap()
+    }
+
+    assert!(instance.get_failed_nodes().await.is_empty());
+    instance
+        .get_metrics()
+        .into_iter()
+        .map(|metrics| (metrics.node_id.clone(), metrics))
+        .collect()
+}
+
+#[test]
+fn builtin_dataflow() {
+    let duration = Duration::from_millis(300);
+    let metrics = async_std::task::block_on(run_for(duration));
+
+    let timer = &metrics["Timer"];
+    assert!(timer.messages_out > 0);
+
+    // One tick out of three is forwarded, starting with the first. The data received last may
+    // not have been processed when the Operator was stopped.
+    let sample = &metrics["Sample"];
+    let expected = sample.messages_in / 3 + u64::from(sample.messages_in % 3 != 0);
+    assert!(sample.messages_out <= expected && sample.messages_out + 1 >= expected);
+
+    // The ticks are all different: none is dropped.
+    let dedup = &metrics["Dedup"];
+    assert!(dedup.messages_in > 0);
+    assert!(dedup.messages_out <= dedup.messages_in && dedup.messages_out + 1 >= dedup.messages_in);
+
+    // The Pass-Through forwards everything, to two Sinks.
+    let pass_through = &metrics["PassThrough"];
+    assert!(pass_through.messages_in > 0);
+    assert!(pass_through.messages_out <= 2 * pass_through.messages_in);
+    assert!(pass_through.messages_out + 2 >= 2 * pass_through.messages_in);
+    assert!(metrics["Log"].messages_in > 0);
+    assert!(metrics["Null"].messages_in > 0);
+
+    // A tick is produced every 5ms, at most one every 40ms is forwarded.
+    let throttle = &metrics["Throttle"];
+    assert!(throttle.messages_out > 0);
+    assert!(throttle.messages_out < throttle.messages_in);
+    assert!(throttle.messages_out <= (duration.as_millis() / 40) as u64 + 1);
+}
+
+#[test]
+fn builtin_ko() {
+    let runtime = async_std::task::block_on(runtime_context());
+
+    let unknown = descriptor(RUNTIME).replace("builtin://dedup", "builtin://unknown");
+    match Dataflow::try_new(runtime.clone(), record(&unknown).unwrap()) {
+        Err(ZFError::LoadingError(_)) => (),
+        _ => panic!("Expected a `LoadingError` for an unknown built-in node."),
+    }
+
+    let missing_configuration = descriptor(RUNTIME).replace("rate: 3", "");
+    match Dataflow::try_new(runtime.clone(), record(&missing_configuration).unwrap()) {
+        Err(ZFError::MissingConfiguration) => (),
+        _ => panic!("Expected a `MissingConfiguration` for the built-in sample."),
+    }
+
+    let missing_period =
+        descriptor(RUNTIME).replace("    period:\n      length: 5\n      unit: ms\n", "");
+    match Dataflow::try_new(runtime, record(&missing_period).unwrap()) {
+        Err(ZFError::LoadingError(_)) => (),
+        _ => panic!("Expected a `LoadingError` for a timer without period."),
+    }
+}
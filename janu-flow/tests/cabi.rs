@@ -0,0 +1,226 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+mod types;
+
+use async_std::sync::Arc;
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::runtime::dataflow::cabi::{
+    COperator, ZFCEmitFn, ZFCInput, ZFCOperatorVTable, C_HEADER, ZFC_ABI_VERSION, ZFC_OK,
+};
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::dataflow::registry::NodeRegistry;
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::RuntimeContext;
+use janu_flow::{Node, ZFError};
+use std::convert::{TryFrom, TryInto};
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use types::{VecSink, VecSource};
+
+static RUNTIME: &str = "cabi-runtime";
+
+static DESCRIPTOR: &str = r#"
+flow: CABI
+sources:
+  - id : Source
+    output:
+      id: out
+      type: usize
+operators:
+  - id : Double
+    inputs:
+      - id: in
+        type: usize
+    outputs:
+      - id: out
+        type: usize
+sinks:
+  - id : Sink
+    input:
+      id: in
+      type: usize
+
+links:
+- from:
+    node : Source
+    output : out
+  to:
+    node : Double
+    input : in
+- from:
+    node : Double
+    output : out
+  to:
+    node : Sink
+    input : in
+
+mapping:
+  - id: Source
+    runtime: cabi-runtime
+  - id: Double
+    runtime: cabi-runtime
+  - id: Sink
+    runtime: cabi-runtime
+"#;
+
+// OPERATOR, written as a C library would. Its state counts the calls to `run`.
+
+unsafe extern "C" fn double_initialize(
+    _configuration: *const c_char,
+    state: *mut *mut c_void,
+) -> i32 {
+    *state = Box::into_raw(Box::new(0usize)) as *mut c_void;
+    ZFC_OK
+}
+
+unsafe extern "C" fn double_run(
+    state: *mut c_void,
+    inputs: *const ZFCInput,
+    inputs_len: usize,
+    emitter: *mut c_void,
+    emit: ZFCEmitFn,
+) -> i32 {
+    *(state as *mut usize) += 1;
+
+    let inputs = std::slice::from_raw_parts(inputs, inputs_len);
+    let input = match inputs
+        .iter()
+        .find(|input| CStr::from_ptr(input.port_id).to_bytes() == b"in")
+    {
+        Some(input) => input,
+        None => return 1,
+    };
+    let bytes = std::slice::from_raw_parts(input.data, input.len);
+    let value = match bytes.try_into() {
+        Ok(bytes) => usize::from_ne_bytes(bytes),
+        Err(_) => return 2,
+    };
+
+    let output = (value * 2).to_ne_bytes();
+    emit(
+        emitter,
+        b"out\0".as_ptr() as *const c_char,
+        output.as_ptr(),
+        output.len(),
+    )
+}
+
+unsafe extern "C" fn double_finalize(state: *mut c_void) -> i32 {
+    drop(Box::from_raw(state as *mut usize));
+    ZFC_OK
+}
+
+unsafe extern "C" fn failing_initialize(
+    _configuration: *const c_char,
+    _state: *mut *mut c_void,
+) -> i32 {
+    42
+}
+
+fn double_vtable() -> ZFCOperatorVTable {
+    ZFCOperatorVTable {
+        abi_version: ZFC_ABI_VERSION,
+        initialize: double_initialize,
+        run: double_run,
+        finalize: double_finalize,
+    }
+}
+
+async fn single_runtime() {
+    let (tx_sink, rx_sink) = flume::bounded::<()>(1);
+
+    let mut registry = NodeRegistry::new();
+    registry
+        .try_register_source("Source", || Ok(Arc::new(VecSource::new(vec![3, 2, 1]))))
+        .unwrap();
+    registry
+        .try_register_operator("Double", || {
+            Ok(Arc::new(COperator::try_new(double_vtable())?))
+        })
+        .unwrap();
+    registry
+        .try_register_sink("Sink", move || {
+            Ok(Arc::new(VecSink::new(tx_sink.clone(), vec![6, 4, 2])))
+        })
+        .unwrap();
+
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let ctx = RuntimeContext {
+        session,
+        hlc: Arc::new(uhlc::HLC::default()),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] }).with_registry(registry)),
+        runtime_name: RUNTIME.into(),
+        runtime_uuid: uuid::Uuid::new_v4(),
+        checkpoints: None,
+    };
+
+    let descriptor = DataFlowDescriptor::from_yaml(DESCRIPTOR).unwrap();
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let dataflow = Dataflow::try_new(ctx, record).unwrap();
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    instance.start_nodes().await.unwrap();
+    instance.start_sources().await.unwrap();
+
+    // Wait for the Sink to receive all the values.
+    rx_sink.recv_async().await.unwrap();
+
+    instance.stop_sources().await.unwrap();
+    instance
+        .stop_nodes(janu_flow::runtime::DEFAULT_DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn c_operator_dataflow() {
+    async_std::task::block_on(single_runtime())
+}
+
+#[test]
+fn c_operator_ko() {
+    let mut vtable = double_vtable();
+    vtable.abi_version = ZFC_ABI_VERSION + 1;
+    match COperator::try_new(vtable) {
+        Err(ZFError::VersionMismatch) => (),
+        _ => panic!("Expected a `VersionMismatch` for another version of the vtable."),
+    }
+
+    let mut vtable = double_vtable();
+    vtable.initialize = failing_initialize;
+    match COperator::try_new(vtable).unwrap().initialize(&None) {
+        Err(ZFError::ForeignError(_)) => (),
+        _ => panic!("Expected a `ForeignError` when `initialize` returns an error code."),
+    }
+}
+
+#[test]
+fn c_header() {
+    for item in [
+        "ZFC_ABI_VERSION",
+        "ZFCOperatorVTable",
+        "ZFCSourceVTable",
+        "ZFCSinkVTable",
+        "zfc_operator_vtable",
+    ] {
+        assert!(
+            C_HEADER.contains(item),
+            "`{}` is missing in the header",
+            item
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+mod types;
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use flume::Sender;
+use janu_flow::janu_flow_derive::ZFState;
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::runtime::checkpoint::Checkpoints;
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::dataflow::registry::NodeRegistry;
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::RuntimeContext;
+use janu_flow::{Configuration, Context, DataMessage, Node, Sink, State, ZFError, ZFResult};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::time::Duration;
+use types::{VecSource, ZFUsize};
+
+static RUNTIME: &str = "checkpoint-runtime";
+
+static DESCRIPTOR: &str = r#"
+flow: Checkpoint
+sources:
+  - id : Source
+    output:
+      id: out
+      type: usize
+operators: []
+sinks:
+  - id : Sink
+    input:
+      id: in
+      type: usize
+
+links:
+- from:
+    node : Source
+    output : out
+  to:
+    node : Sink
+    input : in
+
+mapping:
+  - id: Source
+    runtime: checkpoint-runtime
+  - id: Sink
+    runtime: checkpoint-runtime
+"#;
+
+// SINK, summing the values it receives until it reaches `target`.
+
+#[derive(Debug, ZFState, Serialize, Deserialize)]
+#[zf_state(serializable)]
+struct SumState {
+    sum: usize,
+}
+
+struct SumSink {
+    target: usize,
+    tx: Sender<()>,
+}
+
+impl Node for SumSink {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from(SumState { sum: 0 }))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for SumSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        mut input: DataMessage,
+    ) -> ZFResult<()> {
+        let value = input.get_inner_data().try_get::<ZFUsize>()?.0;
+        let state = state.try_get::<SumState>()?;
+        state.sum += value;
+
+        if state.sum == self.target {
+            self.tx
+                .send_async(())
+                .await
+                .map_err(|e| ZFError::IOError(e.to_string()))?;
+        }
+        Ok(())
+    }
+}
+
+fn checkpoints_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("checkpoints-{}", uuid::Uuid::new_v4()))
+}
+
+// Runs a new instance of the flow, the Source sending `values`, until the Sink reaches `target`.
+async fn run_instance(checkpoints: &Arc<Checkpoints>, values: Vec<usize>, target: usize) {
+    let (tx_sink, rx_sink) = flume::bounded::<()>(1);
+
+    let mut registry = NodeRegistry::new();
+    registry
+        .try_register_source("Source", move || {
+            Ok(Arc::new(VecSource::new(values.clone())))
+        })
+        .unwrap();
+    registry
+        .try_register_sink("Sink", move || {
+            Ok(Arc::new(SumSink {
+                target,
+                tx: tx_sink.clone(),
+            }))
+        })
+        .unwrap();
+
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let ctx = RuntimeContext {
+        session,
+        hlc: Arc::new(uhlc::HLC::default()),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] }).with_registry(registry)),
+        runtime_name: RUNTIME.into(),
+        runtime_uuid: uuid::Uuid::new_v4(),
+        checkpoints: Some(checkpoints.clone()),
+    };
+
+    let descriptor = DataFlowDescriptor::from_yaml(DESCRIPTOR).unwrap();
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let dataflow = Dataflow::try_new(ctx, record).unwrap();
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    instance.start_nodes().await.unwrap();
+    instance.start_sources().await.unwrap();
+
+    async_std::future::timeout(Duration::from_secs(10), rx_sink.recv_async())
+        .await
+        .expect("The Sink did not reach its target.")
+        .unwrap();
+
+    // The state of the Source is not serializable.
+    assert!(instance.checkpoint_node(&"Sink".into()).await.unwrap());
+    assert!(!instance.checkpoint_node(&"Source".into()).await.unwrap());
+
+    instance.stop_sources().await.unwrap();
+    instance
+        .stop_nodes(janu_flow::runtime::DEFAULT_DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn checkpoint_restore() {
+    let dir = checkpoints_dir();
+    let checkpoints = Arc::new(Checkpoints::local(&dir));
+
+    async_std::task::block_on(async {
+        run_instance(&checkpoints, vec![3, 2, 1], 6).await;
+        // The Sink of the new instance starts from the sum of the previous one.
+        run_instance(&checkpoints, vec![4], 10).await;
+    });
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn local_checkpoints() {
+    let dir = checkpoints_dir();
+    let checkpoints = Checkpoints::local(&dir);
+
+    async_std::task::block_on(async {
+        assert_eq!(None, checkpoints.load("Flow", "Node").await.unwrap());
+
+        checkpoints.save("Flow", "Node", &[1, 2]).await.unwrap();
+        checkpoints.save("Flow", "Node", &[3]).await.unwrap();
+        // The ids of the nodes of composites contain '/'.
+        checkpoints
+            .save("Flow", "Composite/Node", &[4])
+            .await
+            .unwrap();
+
+        assert_eq!(
+            Some(vec![3]),
+            checkpoints.load("Flow", "Node").await.unwrap()
+        );
+        assert_eq!(
+            Some(vec![4]),
+            checkpoints.load("Flow", "Composite/Node").await.unwrap()
+        );
+        assert_eq!(None, checkpoints.load("Other", "Node").await.unwrap());
+
+        // The ids cannot lead out of the directory.
+        for (flow_id, node_id) in [("..", "Node"), ("Flow", "../../Node"), ("Flow", "/Node")] {
+            match checkpoints.save(flow_id, node_id, &[5]).await {
+                Err(ZFError::InvalidData(_)) => (),
+                _ => panic!(
+                    "Expected an `InvalidData` error for < {}/{} >.",
+                    flow_id, node_id
+                ),
+            }
+        }
+    });
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,242 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::ZFError;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+// Visual representation of the composite node:
+//
+//              in     out     in     out     in     out
+//              ┌───────┐      ┌───────┐      ┌───────┐
+//  Composite ─►│ Scale ├─────►│ Clamp ├─────►│ Round ├─► Composite
+//  .in         └───────┘      └───────┘      └───────┘   .out
+//
+// (drawing made with: https://asciiflow.com/)
+static INNER_DESCRIPTOR: &str = r#"
+flow: Preprocessing
+sources: []
+sinks: []
+operators:
+  - id : Scale
+    uri: file://./scale.dylib
+    inputs:
+      - id: in
+        type: usize
+    outputs:
+      - id: out
+        type: usize
+  - id : Clamp
+    uri: file://./clamp.dylib
+    inputs:
+      - id: in
+        type: usize
+    outputs:
+      - id: out
+        type: usize
+  - id : Round
+    uri: file://./round.dylib
+    inputs:
+      - id: in
+        type: usize
+    outputs:
+      - id: out
+        type: usize
+
+links:
+- from:
+    node : Scale
+    output : out
+  to:
+    node : Clamp
+    input : in
+- from:
+    node : Clamp
+    output : out
+  to:
+    node : Round
+    input : in
+
+mapping:
+  - id: Round
+    runtime: inner-runtime
+
+deadlines:
+- from:
+    node: Scale
+    output: out
+  to:
+    node: Round
+    input: in
+  duration:
+    length: 10
+    unit: ms
+"#;
+
+fn outer_descriptor(inner_path: &str) -> String {
+    format!(
+        r#"
+flow: Composite
+operators: []
+sources:
+  - id : Counter
+    uri: file://./counter.dylib
+    output:
+      id: out
+      type: usize
+sinks:
+  - id : Print
+    uri: file://./print.dylib
+    input:
+      id: in
+      type: usize
+
+composites:
+  - id: Preprocessing
+    uri: file://{}
+    inputs:
+      - id: in
+        node: Scale
+        port: in
+    outputs:
+      - id: out
+        node: Round
+        port: out
+
+links:
+- from:
+    node : Counter
+    output : out
+  to:
+    node : Preprocessing
+    input : in
+- from:
+    node : Preprocessing
+    output : out
+  to:
+    node : Print
+    input : in
+
+mapping:
+  - id: Counter
+    runtime: outer-runtime
+  - id: Print
+    runtime: outer-runtime
+  - id: Preprocessing
+    runtime: outer-runtime
+
+deadlines:
+- from:
+    node: Counter
+    output: out
+  to:
+    node: Print
+    input: in
+  duration:
+    length: 100
+    unit: ms
+"#,
+        inner_path
+    )
+}
+
+fn write_descriptor(name: &str, content: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{}.yaml", name, uuid::Uuid::new_v4()));
+    std::fs::write(&path, content).unwrap();
+    path
+}
+
+#[test]
+fn composite_flatten() {
+    let inner_path = write_descriptor("composite-inner", INNER_DESCRIPTOR);
+    let descriptor =
+        DataFlowDescriptor::from_yaml(&outer_descriptor(inner_path.to_str().unwrap())).unwrap();
+
+    assert!(descriptor.composites.is_none());
+
+    let mut operators: Vec<&str> = descriptor.operators.iter().map(|o| o.id.as_ref()).collect();
+    operators.sort_unstable();
+    assert_eq!(
+        operators,
+        vec![
+            "Preprocessing/Clamp",
+            "Preprocessing/Round",
+            "Preprocessing/Scale"
+        ]
+    );
+
+    assert!(descriptor
+        .links
+        .iter()
+        .any(|l| l.from.node.as_ref() == "Counter"
+            && l.to.node.as_ref() == "Preprocessing/Scale"
+            && l.to.input.as_ref() == "in"));
+    assert!(descriptor
+        .links
+        .iter()
+        .any(|l| l.from.node.as_ref() == "Preprocessing/Round"
+            && l.from.output.as_ref() == "out"
+            && l.to.node.as_ref() == "Print"));
+
+    assert_eq!(descriptor.get_mapping("Preprocessing"), None);
+    assert_eq!(
+        descriptor.get_mapping("Preprocessing/Scale"),
+        Some("outer-runtime".into())
+    );
+    assert_eq!(
+        descriptor.get_mapping("Preprocessing/Round"),
+        Some("inner-runtime".into())
+    );
+
+    assert_eq!(descriptor.deadlines.as_ref().map(|d| d.len()), Some(2));
+
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    assert_eq!(record.operators.len(), 3);
+    assert_eq!(record.end_to_end_deadlines.map(|d| d.len()), Some(2));
+
+    std::fs::remove_file(inner_path).unwrap();
+}
+
+#[test]
+fn composite_ko_port_not_found() {
+    let inner_path = write_descriptor("composite-inner", INNER_DESCRIPTOR);
+    let outer = outer_descriptor(inner_path.to_str().unwrap()).replace(
+        "    node : Preprocessing\n    input : in",
+        "    node : Preprocessing\n    input : unknown",
+    );
+
+    let r = DataFlowDescriptor::from_yaml(&outer);
+    assert_eq!(
+        r,
+        Err(ZFError::PortNotFound((
+            "Preprocessing".into(),
+            "unknown".into()
+        )))
+    );
+
+    std::fs::remove_file(inner_path).unwrap();
+}
+
+#[test]
+fn composite_ko_recursive() {
+    let path = std::env::temp_dir().join(format!("composite-self-{}.yaml", uuid::Uuid::new_v4()));
+    std::fs::write(&path, outer_descriptor(path.to_str().unwrap())).unwrap();
+
+    let r = DataFlowDescriptor::from_yaml(&std::fs::read_to_string(&path).unwrap());
+    assert!(matches!(r, Err(ZFError::ParsingError(_))));
+
+    std::fs::remove_file(path).unwrap();
+}
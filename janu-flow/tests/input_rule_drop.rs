@@ -189,6 +189,7 @@ async fn single_runtime() {
         loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
         runtime_name: format!("test-runtime-{}", rt_uuid).into(),
         runtime_uuid: rt_uuid,
+        checkpoints: None,
     };
 
     let mut dataflow =
@@ -0,0 +1,147 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::model::node::SourceRecord;
+use janu_flow::runtime::dataflow::integrity;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::ZFError;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+static TRUSTED_SIGNING_KEY: &str =
+    "0101010101010101010101010101010101010101010101010101010101010101";
+static OTHER_SIGNING_KEY: &str = "0202020202020202020202020202020202020202020202020202020202020202";
+
+// Not an actual library: the checks happen before the library is opened, opening it then fails
+// with a `LoadingError`.
+fn library() -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "{}integrity-{}.{}",
+        std::env::consts::DLL_PREFIX,
+        uuid::Uuid::new_v4(),
+        std::env::consts::DLL_EXTENSION
+    ));
+    std::fs::write(&path, b"not a library").unwrap();
+    path
+}
+
+fn source_record(library: &Path, checksum: Option<&str>, signature: Option<&str>) -> SourceRecord {
+    let descriptor = format!(
+        r#"
+flow: Integrity
+operators: []
+sources:
+  - id : Source
+    uri: file://{}
+    output:
+      id: out
+      type: usize
+sinks: []
+links: []
+mapping:
+  - id: Source
+    runtime: integrity-runtime
+"#,
+        library.display()
+    );
+    let mut descriptor = DataFlowDescriptor::from_yaml(&descriptor).unwrap();
+    descriptor.sources[0].checksum = checksum.map(String::from);
+    descriptor.sources[0].signature = signature.map(String::from);
+
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    record.sources[0].clone()
+}
+
+fn loader(trusted_signing_keys: &[&str]) -> Loader {
+    let trusted_keys = trusted_signing_keys
+        .iter()
+        .map(|key| integrity::verifying_key(key).unwrap())
+        .collect::<Vec<_>>();
+    Loader::new(LoaderConfig { extensions: vec![] })
+        .with_trusted_keys(&trusted_keys)
+        .unwrap()
+}
+
+#[test]
+fn checksum() {
+    let library = library();
+    let checksum = integrity::compute_checksum(&library).unwrap();
+    let wrong_checksum = integrity::compute_checksum(&std::env::current_exe().unwrap()).unwrap();
+
+    match loader(&[]).load_source(source_record(&library, Some(&wrong_checksum), None)) {
+        Err(ZFError::IntegrityError(_)) => (),
+        _ => panic!("Expected an `IntegrityError` for a library with a wrong checksum."),
+    }
+
+    match loader(&[]).load_source(source_record(&library, Some("not hex"), None)) {
+        Err(ZFError::IntegrityError(_)) => (),
+        _ => panic!("Expected an `IntegrityError` for an invalid checksum."),
+    }
+
+    // Without trusted keys, neither the checksum nor the signature are required.
+    for checksum in [Some(checksum.as_str()), None] {
+        match loader(&[]).load_source(source_record(&library, checksum, None)) {
+            Err(ZFError::LoadingError(_)) => (),
+            _ => panic!("Expected the checks to pass and the loading to fail."),
+        }
+    }
+
+    std::fs::remove_file(library).unwrap();
+}
+
+#[test]
+fn signature() {
+    let library = library();
+    let checksum = integrity::compute_checksum(&library).unwrap();
+    let signature = integrity::sign_checksum(&checksum, TRUSTED_SIGNING_KEY).unwrap();
+    let other_signature = integrity::sign_checksum(&checksum, OTHER_SIGNING_KEY).unwrap();
+
+    let loader = loader(&[TRUSTED_SIGNING_KEY]);
+
+    match loader.load_source(source_record(&library, Some(&checksum), None)) {
+        Err(ZFError::IntegrityError(_)) => (),
+        _ => panic!("Expected an `IntegrityError` for an unsigned library."),
+    }
+
+    match loader.load_source(source_record(
+        &library,
+        Some(&checksum),
+        Some(&other_signature),
+    )) {
+        Err(ZFError::IntegrityError(_)) => (),
+        _ => panic!("Expected an `IntegrityError` for a library signed by an untrusted key."),
+    }
+
+    // The signature covers the content of the library, the checksum is optional.
+    for checksum in [Some(checksum.as_str()), None] {
+        match loader.load_source(source_record(&library, checksum, Some(&signature))) {
+            Err(ZFError::LoadingError(_)) => (),
+            _ => panic!("Expected the checks to pass and the loading to fail."),
+        }
+    }
+
+    std::fs::remove_file(library).unwrap();
+}
+
+#[test]
+fn invalid_trusted_key() {
+    match Loader::new(LoaderConfig { extensions: vec![] })
+        .with_trusted_keys(&[String::from("not a key")])
+    {
+        Err(ZFError::IntegrityError(_)) => (),
+        _ => panic!("Expected an `IntegrityError` for an invalid trusted key."),
+    }
+}
@@ -0,0 +1,142 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+#![cfg(target_family = "unix")]
+
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::model::node::SourceRecord;
+use janu_flow::runtime::dataflow::integrity;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::ZFError;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+
+// Not an actual library: opening it fails, in the child process, with a `LoadingError`.
+fn library() -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "{}isolation-{}.{}",
+        std::env::consts::DLL_PREFIX,
+        uuid::Uuid::new_v4(),
+        std::env::consts::DLL_EXTENSION
+    ));
+    std::fs::write(&path, b"not a library").unwrap();
+    path
+}
+
+fn source_record(library: &Path, checksum: Option<&str>) -> SourceRecord {
+    let descriptor = format!(
+        r#"
+flow: Isolation
+operators: []
+sources:
+  - id : Source
+    uri: file://{}
+    isolated: true
+    output:
+      id: out
+      type: usize
+sinks: []
+links: []
+mapping:
+  - id: Source
+    runtime: isolation-runtime
+"#,
+        library.display()
+    );
+    let mut descriptor = DataFlowDescriptor::from_yaml(&descriptor).unwrap();
+    descriptor.sources[0].checksum = checksum.map(String::from);
+
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    record.sources[0].clone()
+}
+
+fn loader() -> Loader {
+    Loader::new(LoaderConfig { extensions: vec![] })
+        .with_node_host(env!("CARGO_BIN_EXE_janu-flow-node-host"))
+}
+
+#[test]
+fn isolated_loading_error() {
+    let library = library();
+    let checksum = integrity::compute_checksum(&library).unwrap();
+
+    match loader().load_source(source_record(&library, Some(&checksum))) {
+        Err(ZFError::LoadingError(_)) => (),
+        _ => panic!("Expected the `LoadingError` of the node process."),
+    }
+
+    // The library is checked before spawning the node process.
+    let wrong_checksum = integrity::compute_checksum(&std::env::current_exe().unwrap()).unwrap();
+    match loader().load_source(source_record(&library, Some(&wrong_checksum))) {
+        Err(ZFError::IntegrityError(_)) => (),
+        _ => panic!("Expected an `IntegrityError` for a library with a wrong checksum."),
+    }
+
+    std::fs::remove_file(library).unwrap();
+}
+
+#[test]
+fn isolated_trusted_keys() {
+    let signing_key = "0101010101010101010101010101010101010101010101010101010101010101";
+    let library = library();
+    let checksum = integrity::compute_checksum(&library).unwrap();
+    let signature = integrity::sign_checksum(&checksum, signing_key).unwrap();
+    let loader = loader()
+        .with_trusted_keys(&[integrity::verifying_key(signing_key).unwrap()])
+        .unwrap();
+
+    // The signature is checked by the runtime then, with the same keys, by the node process.
+    let mut record = source_record(&library, None);
+    record.signature = Some(signature);
+    match loader.load_source(record) {
+        Err(ZFError::LoadingError(_)) => (),
+        _ => panic!("Expected the `LoadingError` of the node process."),
+    }
+
+    match loader.load_source(source_record(&library, None)) {
+        Err(ZFError::IntegrityError(_)) => (),
+        _ => panic!("Expected an `IntegrityError` for an unsigned library."),
+    }
+
+    std::fs::remove_file(library).unwrap();
+}
+
+#[test]
+fn isolated_extension_unsupported() {
+    // Only the nodes in a library can be isolated, not the ones loaded through an extension.
+    let path = std::env::temp_dir().join(format!("isolation-{}.py", uuid::Uuid::new_v4()));
+    std::fs::write(&path, b"").unwrap();
+
+    match loader().load_source(source_record(&path, None)) {
+        Err(ZFError::Unsupported) => (),
+        _ => panic!("Expected `Unsupported` for an isolated node that is not a library."),
+    }
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn missing_node_host() {
+    let library = library();
+    let loader = Loader::new(LoaderConfig { extensions: vec![] })
+        .with_node_host("/nonexistent/janu-flow-node-host");
+
+    match loader.load_source(source_record(&library, None)) {
+        Err(ZFError::LoadingError(_)) => (),
+        _ => panic!("Expected a `LoadingError` without a node host."),
+    }
+
+    std::fs::remove_file(library).unwrap();
+}
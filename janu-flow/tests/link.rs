@@ -13,7 +13,11 @@
 //
 
 use janu_flow::async_std::sync::Arc;
-use janu_flow::runtime::dataflow::instance::link::{link, LinkReceiver, LinkSender};
+use janu_flow::model::link::{QueueingPolicy, Reliability};
+use janu_flow::runtime::dataflow::instance::link::{
+    link, link_with_policy, LinkReceiver, LinkSender,
+};
+use janu_flow::{ControlMessage, Data, Message};
 
 async fn same_task_simple() {
     let size = 2;
@@ -115,3 +119,192 @@ fn ordered_fifo_more_two_task_async() {
         h2.await;
     })
 }
+
+#[test]
+fn bounded_drop_newest() {
+    let (sender, receiver) = link_with_policy::<u8>(
+        Some(2),
+        QueueingPolicy::DropNewest,
+        0,
+        "0".into(),
+        "10".into(),
+    );
+
+    async_std::task::block_on(async move {
+        for n in 0u8..5u8 {
+            assert_eq!(sender.send(Arc::new(n)).await, Ok(()));
+        }
+        assert_eq!(sender.len(), 2);
+        assert_eq!(sender.dropped(), 3);
+
+        assert_eq!(receiver.recv().await, Ok(("10".into(), Arc::new(0u8))));
+        assert_eq!(receiver.recv().await, Ok(("10".into(), Arc::new(1u8))));
+    })
+}
+
+#[test]
+fn bounded_drop_oldest() {
+    let (sender, receiver) = link_with_policy::<u8>(
+        Some(2),
+        QueueingPolicy::DropOldest,
+        0,
+        "0".into(),
+        "10".into(),
+    );
+
+    async_std::task::block_on(async move {
+        for n in 0u8..5u8 {
+            assert_eq!(sender.send(Arc::new(n)).await, Ok(()));
+        }
+        assert_eq!(sender.len(), 2);
+        assert_eq!(sender.dropped(), 3);
+
+        assert_eq!(receiver.recv().await, Ok(("10".into(), Arc::new(3u8))));
+        assert_eq!(receiver.recv().await, Ok(("10".into(), Arc::new(4u8))));
+    })
+}
+
+#[test]
+fn bounded_drop_control() {
+    let hlc = uhlc::HLC::default();
+    let data = |n: u8| {
+        Arc::new(Message::from_serdedata(
+            Data::Bytes(Arc::new(vec![n])),
+            hlc.new_timestamp(),
+            vec![],
+        ))
+    };
+    let watermark = hlc.new_timestamp();
+
+    for policy in [QueueingPolicy::DropNewest, QueueingPolicy::DropOldest] {
+        let (sender, receiver) =
+            link_with_policy::<Message>(Some(1), policy, 0, "0".into(), "10".into());
+
+        async_std::task::block_on(async {
+            assert_eq!(sender.send(data(0)).await, Ok(()));
+            assert_eq!(sender.send(data(1)).await, Ok(()));
+            assert_eq!(sender.dropped(), 1);
+
+            // The control message waits for room in the link instead of being discarded.
+            let control_sender = sender.clone();
+            let control = async_std::task::spawn(async move {
+                control_sender
+                    .send(Arc::new(Message::Control(ControlMessage::Watermark(
+                        watermark,
+                    ))))
+                    .await
+            });
+            assert!(matches!(
+                receiver.recv().await.unwrap().1.as_ref(),
+                Message::Data(_)
+            ));
+            assert!(matches!(
+                receiver.recv().await.unwrap().1.as_ref(),
+                Message::Control(ControlMessage::Watermark(timestamp)) if *timestamp == watermark
+            ));
+            assert_eq!(control.await, Ok(()));
+            assert_eq!(sender.dropped(), 1);
+        })
+    }
+}
+
+#[test]
+fn bounded_drop_oldest_keeps_control() {
+    let hlc = uhlc::HLC::default();
+    let data = |n: u8| {
+        Arc::new(Message::from_serdedata(
+            Data::Bytes(Arc::new(vec![n])),
+            hlc.new_timestamp(),
+            vec![],
+        ))
+    };
+    let watermark = hlc.new_timestamp();
+
+    let (sender, receiver) = link_with_policy::<Message>(
+        Some(2),
+        QueueingPolicy::DropOldest,
+        0,
+        "0".into(),
+        "10".into(),
+    );
+
+    async_std::task::block_on(async {
+        assert_eq!(
+            sender
+                .send(Arc::new(Message::Control(ControlMessage::Watermark(
+                    watermark
+                ))))
+                .await,
+            Ok(())
+        );
+        assert_eq!(sender.send(data(0)).await, Ok(()));
+
+        // The oldest message is the watermark: the data waits for room instead of evicting it.
+        let (data_sender, next) = (sender.clone(), data(1));
+        let blocked = async_std::task::spawn(async move { data_sender.send(next).await });
+        async_std::task::sleep(std::time::Duration::from_millis(100)).await;
+        assert_eq!(sender.len(), 2);
+
+        assert!(matches!(
+            receiver.recv().await.unwrap().1.as_ref(),
+            Message::Control(ControlMessage::Watermark(timestamp)) if *timestamp == watermark
+        ));
+        assert_eq!(blocked.await, Ok(()));
+
+        // Once the watermark is consumed, the oldest data is dropped again.
+        let last = data(2);
+        assert_eq!(sender.send(last.clone()).await, Ok(()));
+        assert_eq!(sender.dropped(), 1);
+        assert!(matches!(
+            receiver.recv().await.unwrap().1.as_ref(),
+            Message::Data(_)
+        ));
+        assert_eq!(receiver.recv().await, Ok(("10".into(), last)));
+    })
+}
+
+#[test]
+fn bounded_block() {
+    let (sender, receiver) =
+        link_with_policy::<u8>(Some(1), QueueingPolicy::Block, 0, "0".into(), "10".into());
+
+    async_std::task::block_on(async move {
+        assert_eq!(sender.send(Arc::new(0u8)).await, Ok(()));
+
+        // The link is full: the second send must wait until the receiver consumes a message.
+        let blocked = async_std::future::timeout(
+            std::time::Duration::from_millis(100),
+            sender.send(Arc::new(1u8)),
+        )
+        .await;
+        assert!(blocked.is_err());
+
+        let h = async_std::task::spawn(async move { recv_task_ordered(receiver, 2).await });
+        assert_eq!(sender.send(Arc::new(1u8)).await, Ok(()));
+        assert_eq!(sender.dropped(), 0);
+        h.await;
+    })
+}
+
+async fn recv_task_ordered(receiver: LinkReceiver<u8>, count: u8) {
+    for n in 0u8..count {
+        let res = receiver.recv().await;
+        assert_eq!(res, Ok(("10".into(), Arc::new(n))));
+    }
+}
+
+#[test]
+fn queueing_policy_from_str() {
+    assert_eq!("block".parse(), Ok(QueueingPolicy::Block));
+    assert_eq!("drop-oldest".parse(), Ok(QueueingPolicy::DropOldest));
+    assert_eq!("drop_newest".parse(), Ok(QueueingPolicy::DropNewest));
+    assert!("drop".parse::<QueueingPolicy>().is_err());
+}
+
+#[test]
+fn reliability_from_str() {
+    assert_eq!("at-most-once".parse(), Ok(Reliability::AtMostOnce));
+    assert_eq!("at_least_once".parse(), Ok(Reliability::AtLeastOnce));
+    assert_eq!("Exactly-Once".parse(), Ok(Reliability::ExactlyOnce));
+    assert!("once".parse::<Reliability>().is_err());
+}
@@ -0,0 +1,178 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use janu_flow::runtime::metrics::{encode_prometheus, Histogram, LinkStatistics, RunnerMetrics};
+use janu_flow::PortId;
+use std::time::Duration;
+
+#[test]
+fn histogram_buckets() {
+    let histogram = Histogram::new(&[1, 10, 100], 1.0);
+    for value in [0, 1, 5, 50, 500] {
+        histogram.observe(value);
+    }
+
+    let snapshot = histogram.snapshot();
+    assert_eq!(snapshot.bounds, vec![1.0, 10.0, 100.0]);
+    // Buckets are cumulative, the observation above the last bound only appears in `count`.
+    assert_eq!(snapshot.buckets, vec![2, 3, 4]);
+    assert_eq!(snapshot.count, 5);
+    assert_eq!(snapshot.sum, 556.0);
+}
+
+#[test]
+fn metrics_prometheus_encoding() {
+    let port: PortId = "in".into();
+    let runner_metrics = RunnerMetrics::new(std::iter::once(&port));
+    runner_metrics.inc_messages_in();
+    runner_metrics.inc_messages_in();
+    runner_metrics.inc_messages_out();
+    runner_metrics.inc_errors();
+    runner_metrics.inc_restarts();
+    runner_metrics.observe_run_duration(Duration::from_micros(30));
+    runner_metrics.observe_queue_depth(&port, 3);
+    // Unknown ports are ignored.
+    runner_metrics.observe_queue_depth(&"unknown".into(), 3);
+
+    let instance_id = uuid::Uuid::nil();
+    let metrics = runner_metrics.snapshot("flow".into(), instance_id, "composite/\"node\"".into());
+    assert_eq!(metrics.queue_depth.len(), 1);
+
+    let text = encode_prometheus(&[metrics]);
+    let labels = format!(
+        "flow=\"flow\",instance=\"{}\",node=\"composite/\\\"node\\\"\"",
+        instance_id
+    );
+
+    assert!(text.contains("# TYPE janu_flow_messages_in_total counter\n"));
+    assert!(text.contains(&format!("janu_flow_messages_in_total{{{}}} 2\n", labels)));
+    assert!(text.contains(&format!("janu_flow_messages_out_total{{{}}} 1\n", labels)));
+    assert!(text.contains(&format!("janu_flow_errors_total{{{}}} 1\n", labels)));
+    assert!(text.contains(&format!("janu_flow_restarts_total{{{}}} 1\n", labels)));
+
+    assert!(text.contains("# TYPE janu_flow_run_duration_seconds histogram\n"));
+    assert!(text.contains(&format!(
+        "janu_flow_run_duration_seconds_bucket{{{},le=\"0.00001\"}} 0\n",
+        labels
+    )));
+    assert!(text.contains(&format!(
+        "janu_flow_run_duration_seconds_bucket{{{},le=\"0.00005\"}} 1\n",
+        labels
+    )));
+    assert!(text.contains(&format!(
+        "janu_flow_run_duration_seconds_bucket{{{},le=\"+Inf\"}} 1\n",
+        labels
+    )));
+    assert!(text.contains(&format!(
+        "janu_flow_run_duration_seconds_count{{{}}} 1\n",
+        labels
+    )));
+
+    assert!(text.contains(&format!(
+        "janu_flow_link_queue_depth_bucket{{{},port=\"in\",le=\"2\"}} 0\n",
+        labels
+    )));
+    assert!(text.contains(&format!(
+        "janu_flow_link_queue_depth_bucket{{{},port=\"in\",le=\"5\"}} 1\n",
+        labels
+    )));
+    assert!(text.contains(&format!(
+        "janu_flow_link_queue_depth_sum{{{},port=\"in\"}} 3\n",
+        labels
+    )));
+}
+
+#[test]
+fn metrics_prometheus_ticks() {
+    let source_metrics = RunnerMetrics::default().with_ticks();
+    source_metrics.observe_tick(Duration::from_micros(40), 0);
+    source_metrics.observe_tick(Duration::from_micros(200), 3);
+    let sink_metrics = RunnerMetrics::default();
+
+    let instance_id = uuid::Uuid::nil();
+    let metrics = vec![
+        source_metrics.snapshot("flow".into(), instance_id, "source".into()),
+        sink_metrics.snapshot("flow".into(), instance_id, "sink".into()),
+    ];
+    assert_eq!(metrics[0].missed_ticks, 3);
+    assert_eq!(metrics[0].tick_jitter.as_ref().map(|h| h.count), Some(2));
+    assert!(metrics[1].tick_jitter.is_none());
+
+    let text = encode_prometheus(&metrics);
+    let labels = format!("flow=\"flow\",instance=\"{}\",node=\"source\"", instance_id);
+
+    assert!(text.contains(&format!("janu_flow_missed_ticks_total{{{}}} 3\n", labels)));
+    assert!(text.contains(&format!(
+        "janu_flow_tick_jitter_seconds_bucket{{{},le=\"0.00005\"}} 1\n",
+        labels
+    )));
+    assert!(text.contains(&format!(
+        "janu_flow_tick_jitter_seconds_count{{{}}} 2\n",
+        labels
+    )));
+    // Only periodic Sources have ticks.
+    let sink_labels = format!("flow=\"flow\",instance=\"{}\",node=\"sink\"", instance_id);
+    assert!(!text.contains(&format!("janu_flow_missed_ticks_total{{{}}}", sink_labels)));
+    assert!(!text.contains(&format!(
+        "janu_flow_tick_jitter_seconds_count{{{}}}",
+        sink_labels
+    )));
+}
+
+#[test]
+fn metrics_prometheus_link_stats() {
+    let receiver_metrics = RunnerMetrics::default().with_link_stats();
+    receiver_metrics.inc_gaps(2);
+    receiver_metrics.inc_duplicates(1);
+    receiver_metrics.inc_late(3);
+    let sink_metrics = RunnerMetrics::default();
+    // Ignored by the runners that are not connectors.
+    sink_metrics.inc_gaps(1);
+
+    let instance_id = uuid::Uuid::nil();
+    let metrics = vec![
+        receiver_metrics.snapshot("flow".into(), instance_id, "receiver".into()),
+        sink_metrics.snapshot("flow".into(), instance_id, "sink".into()),
+    ];
+    assert_eq!(
+        metrics[0].link_stats,
+        Some(LinkStatistics {
+            gaps: 2,
+            duplicates: 1,
+            late: 3,
+            retransmissions: 0,
+        })
+    );
+    assert!(metrics[1].link_stats.is_none());
+
+    let text = encode_prometheus(&metrics);
+    let labels = format!(
+        "flow=\"flow\",instance=\"{}\",node=\"receiver\"",
+        instance_id
+    );
+
+    assert!(text.contains(&format!("janu_flow_link_gaps_total{{{}}} 2\n", labels)));
+    assert!(text.contains(&format!(
+        "janu_flow_link_duplicates_total{{{}}} 1\n",
+        labels
+    )));
+    assert!(text.contains(&format!("janu_flow_link_late_total{{{}}} 3\n", labels)));
+    assert!(text.contains(&format!(
+        "janu_flow_link_retransmissions_total{{{}}} 0\n",
+        labels
+    )));
+    // Only the connectors have link statistics.
+    let sink_labels = format!("flow=\"flow\",instance=\"{}\",node=\"sink\"", instance_id);
+    assert!(!text.contains(&format!("janu_flow_link_gaps_total{{{}}}", sink_labels)));
+}
@@ -0,0 +1,594 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+mod types;
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use flume::{Receiver, Sender};
+use janu_flow::janu_flow_derive::ZFState;
+use janu_flow::model::connector::ZFConnectorKind;
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::model::link::Reliability;
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::dataflow::registry::NodeRegistry;
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::RuntimeContext;
+use janu_flow::{
+    Configuration, Context, Data, DataMessage, EmptyState, Node, NodeId, Sink, Source, State,
+    ZFError, ZFResult,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::time::Duration;
+use types::ZFUsize;
+
+const DRAIN_TIMEOUT: Duration = janu_flow::runtime::DEFAULT_DRAIN_TIMEOUT;
+
+// Messages sent while the Sink is migrated.
+const MESSAGES: usize = 200;
+
+static DESCRIPTOR: &str = r#"
+flow: Migration
+sources:
+  - id : Source
+    output:
+      id: out
+      type: usize
+operators:
+  - id : Operator
+    inputs:
+      - id: in
+        type: usize
+    outputs:
+      - id: out
+        type: usize
+sinks:
+  - id : Sink
+    input:
+      id: in
+      type: usize
+
+links:
+- from:
+    node : Source
+    output : out
+  to:
+    node : Operator
+    input : in
+- from:
+    node : Operator
+    output : out
+  to:
+    node : Sink
+    input : in
+  size: 4
+  queueing_policy: drop-oldest
+
+mapping:
+  - id: Source
+    runtime: runtime-a
+  - id: Operator
+    runtime: runtime-a
+  - id: Sink
+    runtime: runtime-a
+"#;
+
+static FLOW: &str = r#"
+flow: Migration
+sources:
+  - id : Source
+    output:
+      id: out
+      type: usize
+operators: []
+sinks:
+  - id : Sink
+    input:
+      id: in
+      type: usize
+
+links:
+- from:
+    node : Source
+    output : out
+  to:
+    node : Sink
+    input : in
+
+mapping:
+  - id: Source
+    runtime: runtime-a
+  - id: Sink
+    runtime: runtime-a
+"#;
+
+fn count_connectors(record: &DataFlowRecord, kind: ZFConnectorKind) -> usize {
+    record
+        .connectors
+        .iter()
+        .filter(|connector| connector.kind == kind)
+        .count()
+}
+
+#[test]
+fn migrate_node_record() {
+    let descriptor = DataFlowDescriptor::from_yaml(DESCRIPTOR).unwrap();
+    let mut record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let links = record.links.clone();
+    assert!(record.connectors.is_empty());
+
+    record
+        .try_migrate_node("Operator", "runtime-b".into())
+        .unwrap();
+    assert_eq!(
+        Some("runtime-b".into()),
+        record.find_node_runtime("Operator")
+    );
+    assert_eq!(2, count_connectors(&record, ZFConnectorKind::Sender));
+    assert_eq!(2, count_connectors(&record, ZFConnectorKind::Receiver));
+    assert_eq!(4, record.links.len());
+    // The link feeding the Sink keeps its size and queueing policy.
+    let to_sink = record
+        .links
+        .iter()
+        .find(|link| link.to.node.as_ref() == "Sink")
+        .unwrap();
+    assert_eq!(Some(4), to_sink.size);
+    assert_eq!(Some("drop-oldest".to_string()), to_sink.queueing_policy);
+
+    record
+        .try_migrate_node("Operator", "runtime-a".into())
+        .unwrap();
+    assert!(record.connectors.is_empty());
+    assert_eq!(format!("{:?}", links), format!("{:?}", record.links));
+
+    assert!(record
+        .try_migrate_node("Unknown", "runtime-b".into())
+        .is_err());
+}
+
+#[test]
+fn migrate_node_reliable_record() {
+    let descriptor = DataFlowDescriptor::from_yaml(&DESCRIPTOR.replace(
+        "queueing_policy: drop-oldest",
+        "queueing_policy: drop-oldest\n  reliability: exactly-once",
+    ))
+    .unwrap();
+    let mut record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let links = record.links.clone();
+
+    record
+        .try_migrate_node("Operator", "runtime-b".into())
+        .unwrap();
+    // The reliable link has a sender of its own, both of its connectors agree on the reliability.
+    let resource = format!("/zf/data/Migration/{}/Operator/out/Sink/in", record.uuid);
+    let connectors: Vec<_> = record
+        .connectors
+        .iter()
+        .filter(|connector| connector.resource == resource)
+        .collect();
+    assert_eq!(2, connectors.len());
+    assert!(connectors
+        .iter()
+        .all(|connector| connector.reliability == Reliability::ExactlyOnce));
+    assert!(record
+        .connectors
+        .iter()
+        .filter(|connector| connector.resource != resource)
+        .all(|connector| connector.reliability == Reliability::AtMostOnce));
+
+    record
+        .try_migrate_node("Operator", "runtime-a".into())
+        .unwrap();
+    assert_eq!(format!("{:?}", links), format!("{:?}", record.links));
+}
+
+#[test]
+fn migration_edges() {
+    let descriptor = DataFlowDescriptor::from_yaml(DESCRIPTOR).unwrap();
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let upstream: Vec<(NodeId, janu_flow::RuntimeId)> = vec![("Source".into(), "runtime-a".into())];
+    assert_eq!(upstream, record.get_upstream_nodes("Operator"));
+    assert_eq!(1, record.get_upstream_links("Operator").len());
+    assert_eq!(1, record.get_downstream_links("Operator").len());
+
+    let mut migrated = record.clone();
+    migrated
+        .try_migrate_node("Operator", "runtime-b".into())
+        .unwrap();
+    // The links of the three nodes change.
+    let rewired: Vec<(NodeId, janu_flow::RuntimeId)> = vec![
+        ("Operator".into(), "runtime-a".into()),
+        ("Sink".into(), "runtime-a".into()),
+        ("Source".into(), "runtime-a".into()),
+    ];
+    assert_eq!(rewired, record.get_rewired_nodes(&migrated));
+    assert!(record.get_rewired_nodes(&record).is_empty());
+
+    // The messages now go through a pair of connectors on each side.
+    assert_eq!(upstream, migrated.get_upstream_nodes("Operator"));
+    assert_eq!(2, migrated.get_upstream_links("Operator").len());
+    assert_eq!(2, migrated.get_downstream_links("Operator").len());
+}
+
+#[test]
+fn runtimes_in_order() {
+    let descriptor = DataFlowDescriptor::from_yaml(DESCRIPTOR).unwrap();
+    let mut record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    assert_eq!(
+        vec![janu_flow::RuntimeId::from("runtime-a")],
+        record.get_runtimes_in_order()
+    );
+
+    // A runtime comes after the runtimes sending it messages through connectors.
+    record.try_migrate_node("Sink", "runtime-b".into()).unwrap();
+    record
+        .try_migrate_node("Source", "runtime-c".into())
+        .unwrap();
+    let expected: Vec<janu_flow::RuntimeId> =
+        vec!["runtime-c".into(), "runtime-a".into(), "runtime-b".into()];
+    assert_eq!(expected, record.get_runtimes_in_order());
+}
+
+// SOURCE, sending the values it receives on a channel.
+
+struct ChannelSource {
+    rx: Receiver<usize>,
+}
+
+impl Node for ChannelSource {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from(EmptyState {}))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Source for ChannelSource {
+    async fn run(&self, _context: &mut Context, _state: &mut State) -> ZFResult<Data> {
+        let value = self
+            .rx
+            .recv_async()
+            .await
+            .map_err(|e| ZFError::IOError(e.to_string()))?;
+        Ok(Data::from::<ZFUsize>(ZFUsize(value)))
+    }
+}
+
+// SINK, summing the values it receives and sending the sum.
+
+#[derive(Debug, ZFState, Serialize, Deserialize)]
+#[zf_state(serializable)]
+struct SumState {
+    sum: usize,
+}
+
+struct SumSink {
+    tx: Sender<usize>,
+}
+
+impl Node for SumSink {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from(SumState { sum: 0 }))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for SumSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        mut input: DataMessage,
+    ) -> ZFResult<()> {
+        let value = input.get_inner_data().try_get::<ZFUsize>()?.0;
+        let state = state.try_get::<SumState>()?;
+        state.sum += value;
+
+        self.tx
+            .send_async(state.sum)
+            .await
+            .map_err(|e| ZFError::IOError(e.to_string()))
+    }
+}
+
+// Both runtimes share the session, the connectors go through it.
+fn runtime_context(
+    session: Arc<janu::Session>,
+    runtime_name: &str,
+    rx_source: Receiver<usize>,
+    tx_sink: Sender<usize>,
+) -> RuntimeContext {
+    let mut registry = NodeRegistry::new();
+    registry
+        .try_register_source("Source", move || {
+            Ok(Arc::new(ChannelSource {
+                rx: rx_source.clone(),
+            }))
+        })
+        .unwrap();
+    registry
+        .try_register_sink("Sink", move || {
+            Ok(Arc::new(SumSink {
+                tx: tx_sink.clone(),
+            }))
+        })
+        .unwrap();
+
+    RuntimeContext {
+        session,
+        hlc: Arc::new(uhlc::HLC::default()),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] }).with_registry(registry)),
+        runtime_name: runtime_name.into(),
+        runtime_uuid: uuid::Uuid::new_v4(),
+        checkpoints: None,
+    }
+}
+
+async fn expect_sum(rx_sink: &Receiver<usize>, sum: usize) {
+    let received = async_std::future::timeout(Duration::from_secs(10), rx_sink.recv_async())
+        .await
+        .expect("The Sink did not receive anything.")
+        .unwrap();
+    assert_eq!(sum, received);
+}
+
+async fn migrate_node_runtimes() {
+    let (tx_source, rx_source) = flume::unbounded::<usize>();
+    let (tx_sink, rx_sink) = flume::unbounded::<usize>();
+
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let ctx_a = runtime_context(
+        session.clone(),
+        "runtime-a",
+        rx_source.clone(),
+        tx_sink.clone(),
+    );
+    let ctx_b = runtime_context(session, "runtime-b", rx_source, tx_sink);
+
+    let descriptor = DataFlowDescriptor::from_yaml(FLOW).unwrap();
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let dataflow = Dataflow::try_new(ctx_a, record.clone()).unwrap();
+    let mut instance_a = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    instance_a.start_nodes().await.unwrap();
+    instance_a.start_sources().await.unwrap();
+
+    for (value, sum) in [(1, 1), (2, 3), (3, 6)] {
+        tx_source.send_async(value).await.unwrap();
+        expect_sum(&rx_sink, sum).await;
+    }
+
+    // Quiesce the instance, then move the Sink along with its state.
+    instance_a.stop_sources().await.unwrap();
+    instance_a.stop_nodes(DRAIN_TIMEOUT).await.unwrap();
+    let state = instance_a
+        .save_node_state(&"Sink".into())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let mut migrated = record.clone();
+    migrated
+        .try_migrate_node("Sink", "runtime-b".into())
+        .unwrap();
+
+    instance_a.try_rewire(migrated.clone()).await.unwrap();
+    assert!(!instance_a.get_nodes().contains(&"Sink".into()));
+    assert_eq!(1, instance_a.get_connectors().len());
+
+    let dataflow = Dataflow::try_new(ctx_b, migrated).unwrap();
+    let mut instance_b = DataflowInstance::try_instantiate(dataflow).unwrap();
+    assert_eq!(vec![NodeId::from("Sink")], instance_b.get_sinks());
+    instance_b
+        .transfer_node_state(&"Sink".into(), &state)
+        .await
+        .unwrap();
+
+    instance_b.start_nodes().await.unwrap();
+    // The receiver is running once it has subscribed.
+    instance_b
+        .wait_for_nodes(&instance_b.get_connectors(), DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+    instance_a.start_nodes().await.unwrap();
+    instance_a.start_sources().await.unwrap();
+
+    // The Sink, now on runtime-b, starts from the sum it reached on runtime-a.
+    tx_source.send_async(4).await.unwrap();
+    expect_sum(&rx_sink, 10).await;
+
+    instance_a.stop_sources().await.unwrap();
+    instance_a.stop_nodes(DRAIN_TIMEOUT).await.unwrap();
+    instance_b.stop_nodes(DRAIN_TIMEOUT).await.unwrap();
+}
+
+#[test]
+fn migrate_node() {
+    async_std::task::block_on(migrate_node_runtimes());
+}
+
+// Migrates the Sink of `record` from `instance_a` to runtime-b the way the daemon does: only its
+// edges are paused, its state and the messages left in its input links follow it.
+async fn migrate_sink(
+    instance_a: &mut DataflowInstance,
+    ctx_b: RuntimeContext,
+    record: &DataFlowRecord,
+) -> DataflowInstance {
+    let sink: NodeId = "Sink".into();
+    let mut migrated = record.clone();
+    migrated
+        .try_migrate_node(&sink, "runtime-b".into())
+        .unwrap();
+
+    let mut paused = vec![];
+    for (id, _) in record.get_upstream_nodes(&sink) {
+        if instance_a.pause_node(&id, DRAIN_TIMEOUT).await.unwrap() {
+            paused.push(id);
+        }
+    }
+    let upstream_links: Vec<_> = record
+        .get_upstream_links(&sink)
+        .into_iter()
+        .filter(|link| link.to.node != sink)
+        .collect();
+    instance_a
+        .drain_links(&upstream_links, DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+    assert!(instance_a.pause_node(&sink, DRAIN_TIMEOUT).await.unwrap());
+    instance_a
+        .drain_links(&record.get_downstream_links(&sink), DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+    for (id, _) in record.get_rewired_nodes(&migrated) {
+        if instance_a.pause_node(&id, DRAIN_TIMEOUT).await.unwrap() {
+            paused.push(id);
+        }
+    }
+    let state = instance_a.save_node_state(&sink).await.unwrap().unwrap();
+    let inputs = instance_a.take_node_inputs(&sink).unwrap();
+
+    instance_a.try_rewire(migrated.clone()).await.unwrap();
+    let dataflow = Dataflow::try_new(ctx_b, migrated).unwrap();
+    let mut instance_b = DataflowInstance::try_instantiate(dataflow).unwrap();
+    instance_b.transfer_node_state(&sink, &state).await.unwrap();
+    instance_b.forward_node_inputs(&sink, inputs).await.unwrap();
+
+    instance_b.start_nodes().await.unwrap();
+    instance_b
+        .wait_for_nodes(&instance_b.get_nodes(), DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+    for id in instance_a.get_connectors() {
+        instance_a.start_node(&id).await.unwrap();
+    }
+    for id in paused {
+        instance_a.start_node(&id).await.unwrap();
+    }
+
+    instance_b
+}
+
+async fn migrate_running_node_runtimes() {
+    let (tx_source, rx_source) = flume::unbounded::<usize>();
+    let (tx_sink, rx_sink) = flume::unbounded::<usize>();
+
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let ctx_a = runtime_context(
+        session.clone(),
+        "runtime-a",
+        rx_source.clone(),
+        tx_sink.clone(),
+    );
+    let ctx_b = runtime_context(session, "runtime-b", rx_source, tx_sink);
+
+    let descriptor = DataFlowDescriptor::from_yaml(FLOW).unwrap();
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let dataflow = Dataflow::try_new(ctx_a, record.clone()).unwrap();
+    let mut instance_a = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    instance_a.start_nodes().await.unwrap();
+    instance_a.start_sources().await.unwrap();
+
+    // The Sink sends its sum after each message: every message must be counted, once.
+    let producer = async_std::task::spawn(async move {
+        for _ in 0..MESSAGES {
+            tx_source.send_async(1).await.unwrap();
+            async_std::task::sleep(Duration::from_millis(1)).await;
+        }
+    });
+    expect_sum(&rx_sink, 1).await;
+
+    let mut instance_b = migrate_sink(&mut instance_a, ctx_b, &record).await;
+    assert!(!instance_a.get_nodes().contains(&"Sink".into()));
+    producer.await;
+
+    for sum in 2..=MESSAGES {
+        expect_sum(&rx_sink, sum).await;
+    }
+
+    instance_a.stop_sources().await.unwrap();
+    instance_a.stop_nodes(DRAIN_TIMEOUT).await.unwrap();
+    instance_b.stop_nodes(DRAIN_TIMEOUT).await.unwrap();
+    assert!(rx_sink.try_recv().is_err());
+}
+
+#[test]
+fn migrate_running_node() {
+    async_std::task::block_on(migrate_running_node_runtimes());
+}
+
+async fn drain_links_runtime() {
+    let (tx_source, rx_source) = flume::unbounded::<usize>();
+    let (tx_sink, rx_sink) = flume::unbounded::<usize>();
+
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let ctx = runtime_context(session, "runtime-a", rx_source, tx_sink);
+
+    let descriptor = DataFlowDescriptor::from_yaml(FLOW).unwrap();
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let dataflow = Dataflow::try_new(ctx, record.clone()).unwrap();
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    instance.start_nodes().await.unwrap();
+    instance.start_sources().await.unwrap();
+    tx_source.send_async(1).await.unwrap();
+    expect_sum(&rx_sink, 1).await;
+
+    let sink: NodeId = "Sink".into();
+    assert!(instance.pause_node(&sink, DRAIN_TIMEOUT).await.unwrap());
+    assert!(!instance.pause_node(&sink, DRAIN_TIMEOUT).await.unwrap());
+    tx_source.send_async(2).await.unwrap();
+    // Once sent by the Source, the message waits in the link of the stopped Sink.
+    let source: NodeId = "Source".into();
+    while instance
+        .get_metrics()
+        .iter()
+        .any(|metrics| metrics.node_id == source && metrics.messages_out < 2)
+    {
+        async_std::task::sleep(Duration::from_millis(10)).await;
+    }
+
+    let links = record.get_upstream_links(&sink);
+    assert_eq!(
+        Err(ZFError::DrainTimeout(sink.clone())),
+        instance
+            .drain_links(&links, Duration::from_millis(100))
+            .await
+    );
+
+    // The message is kept, the Sink consumes it once started again.
+    instance.start_node(&sink).await.unwrap();
+    expect_sum(&rx_sink, 3).await;
+    instance.drain_links(&links, DRAIN_TIMEOUT).await.unwrap();
+
+    instance.stop_sources().await.unwrap();
+    instance.stop_nodes(DRAIN_TIMEOUT).await.unwrap();
+}
+
+#[test]
+fn drain_links() {
+    async_std::task::block_on(drain_links_runtime());
+}
@@ -0,0 +1,214 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use janu_flow::model::link::PortDescriptor;
+use janu_flow::model::node::MissedTickPolicy;
+use janu_flow::model::{InputDescriptor, OutputDescriptor};
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::metrics::NodeMetrics;
+use janu_flow::runtime::{RuntimeContext, DEFAULT_DRAIN_TIMEOUT};
+use janu_flow::{
+    zf_empty_state, Configuration, Context, Data, DataMessage, DurationDescriptor, Node, Sink,
+    Source, State, ZFResult,
+};
+use std::time::Duration;
+
+static PORT: &str = "data";
+static PERIOD_MS: u64 = 20;
+
+// Source whose `run` lasts `duration`: it never sleeps to respect its period.
+struct BusySource {
+    duration: Duration,
+}
+
+#[async_trait]
+impl Source for BusySource {
+    async fn run(&self, _context: &mut Context, _state: &mut State) -> ZFResult<Data> {
+        async_std::task::sleep(self.duration).await;
+        Ok(Data::from_bytes(vec![0]))
+    }
+}
+
+impl Node for BusySource {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+struct DiscardSink;
+
+#[async_trait]
+impl Sink for DiscardSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        _input: DataMessage,
+    ) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+impl Node for DiscardSink {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+fn port() -> PortDescriptor {
+    PortDescriptor {
+        port_id: PORT.into(),
+        port_type: "bytes".into(),
+    }
+}
+
+// Runs a Source with a period of `PERIOD_MS` for `duration` and returns its metrics.
+async fn run_periodic(
+    run_duration: Duration,
+    missed_ticks: MissedTickPolicy,
+    duration: Duration,
+) -> NodeMetrics {
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let rt_uuid = uuid::Uuid::new_v4();
+    let ctx = RuntimeContext {
+        session,
+        hlc: Arc::new(uhlc::HLC::default()),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
+        runtime_name: format!("test-runtime-{}", rt_uuid).into(),
+        runtime_uuid: rt_uuid,
+        checkpoints: None,
+    };
+
+    let mut dataflow = Dataflow::new(ctx, "periodic-source".into(), None);
+
+    let source = Arc::new(BusySource {
+        duration: run_duration,
+    });
+    let sink = Arc::new(DiscardSink {});
+    let period: DurationDescriptor =
+        serde_yaml::from_str(&format!("length: {}\nunit: ms", PERIOD_MS)).unwrap();
+
+    dataflow
+        .try_add_static_source(
+            "source".into(),
+            Some(period),
+            port(),
+            source.initialize(&None).unwrap(),
+            source,
+        )
+        .unwrap();
+    dataflow
+        .try_set_missed_tick_policy(&"source".into(), missed_ticks)
+        .unwrap();
+    dataflow
+        .try_add_static_sink("sink".into(), port(), sink.initialize(&None).unwrap(), sink)
+        .unwrap();
+    dataflow
+        .try_add_link(
+            OutputDescriptor {
+                node: "source".into(),
+                output: PORT.into(),
+            },
+            InputDescriptor {
+                node: "sink".into(),
+                input: PORT.into(),
+            },
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+    instance.start_nodes().await.unwrap();
+    instance.start_sources().await.unwrap();
+    async_std::task::sleep(duration).await;
+    instance.stop_sources().await.unwrap();
+    instance.stop_nodes(DEFAULT_DRAIN_TIMEOUT).await.unwrap();
+
+    instance
+        .get_metrics()
+        .into_iter()
+        .find(|metrics| metrics.node_id.as_ref() == "source")
+        .unwrap()
+}
+
+#[test]
+fn periodic_source_paced() {
+    async_std::task::block_on(async {
+        let metrics = run_periodic(
+            Duration::ZERO,
+            MissedTickPolicy::Skip,
+            Duration::from_millis(10 * PERIOD_MS),
+        )
+        .await;
+
+        // One execution per tick: without pacing, the source would have run continuously.
+        assert!(metrics.messages_out >= 5, "{}", metrics.messages_out);
+        assert!(metrics.messages_out <= 11, "{}", metrics.messages_out);
+        assert_eq!(metrics.missed_ticks, 0);
+
+        let tick_jitter = metrics.tick_jitter.unwrap();
+        assert!(tick_jitter.count >= metrics.messages_out);
+    });
+}
+
+#[test]
+fn periodic_source_skip_missed_ticks() {
+    async_std::task::block_on(async {
+        // Each execution lasts 2.5 periods: at least one tick is missed each time.
+        let metrics = run_periodic(
+            Duration::from_millis(PERIOD_MS * 5 / 2),
+            MissedTickPolicy::Skip,
+            Duration::from_millis(15 * PERIOD_MS),
+        )
+        .await;
+
+        assert!(metrics.messages_out >= 2, "{}", metrics.messages_out);
+        assert!(metrics.missed_ticks >= metrics.messages_out - 1);
+        // The executions are aligned on the ticks: the source never wakes up late.
+        let tick_jitter = metrics.tick_jitter.unwrap();
+        assert!(tick_jitter.sum / (tick_jitter.count as f64) < (PERIOD_MS as f64) / 1e3);
+    });
+}
+
+#[test]
+fn periodic_source_catch_up_missed_ticks() {
+    async_std::task::block_on(async {
+        let metrics = run_periodic(
+            Duration::from_millis(PERIOD_MS * 5 / 2),
+            MissedTickPolicy::CatchUp,
+            Duration::from_millis(15 * PERIOD_MS),
+        )
+        .await;
+
+        assert!(metrics.messages_out >= 2, "{}", metrics.messages_out);
+        assert_eq!(metrics.missed_ticks, 0);
+        // The source is more and more late.
+        let tick_jitter = metrics.tick_jitter.unwrap();
+        assert!(tick_jitter.sum / (tick_jitter.count as f64) > (PERIOD_MS as f64) / 1e3);
+    });
+}
@@ -0,0 +1,208 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+mod types;
+
+use async_std::sync::Arc;
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::dataflow::registry::NodeRegistry;
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::RuntimeContext;
+use janu_flow::{
+    default_input_rule, default_output_rule, zf_empty_state, Configuration, Context, Data,
+    DataMessage, LocalDeadlineMiss, Node, NodeOutput, Operator, PortId, State, Token, ZFError,
+    ZFResult,
+};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use types::{VecSink, VecSource, ZFUsize};
+
+static RUNTIME: &str = "registry-runtime";
+
+// The Source is registered under its URI, the Operator and the Sink under their id.
+static DESCRIPTOR: &str = r#"
+flow: Registry
+sources:
+  - id : Source
+    uri: static://vec-source
+    output:
+      id: out
+      type: usize
+operators:
+  - id : Double
+    inputs:
+      - id: in
+        type: usize
+    outputs:
+      - id: out
+        type: usize
+sinks:
+  - id : Sink
+    input:
+      id: in
+      type: usize
+
+links:
+- from:
+    node : Source
+    output : out
+  to:
+    node : Double
+    input : in
+- from:
+    node : Double
+    output : out
+  to:
+    node : Sink
+    input : in
+
+mapping:
+  - id: Source
+    runtime: registry-runtime
+  - id: Double
+    runtime: registry-runtime
+  - id: Sink
+    runtime: registry-runtime
+"#;
+
+// OPERATOR
+
+struct Double;
+
+impl Operator for Double {
+    fn input_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        default_input_rule(state, tokens)
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let mut data_msg = inputs
+            .remove("in")
+            .ok_or_else(|| ZFError::InvalidData("No data".to_string()))?;
+        let data = data_msg.get_inner_data().try_get::<ZFUsize>()?;
+
+        let mut results: HashMap<PortId, Data> = HashMap::new();
+        results.insert("out".into(), Data::from::<ZFUsize>(ZFUsize(data.0 * 2)));
+        Ok(results)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+impl Node for Double {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+fn record(descriptor: &str) -> DataFlowRecord {
+    let descriptor = DataFlowDescriptor::from_yaml(descriptor).unwrap();
+    DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap()
+}
+
+async fn runtime_context(registry: NodeRegistry) -> RuntimeContext {
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    RuntimeContext {
+        session,
+        hlc: Arc::new(uhlc::HLC::default()),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] }).with_registry(registry)),
+        runtime_name: RUNTIME.into(),
+        runtime_uuid: uuid::Uuid::new_v4(),
+        checkpoints: None,
+    }
+}
+
+async fn single_runtime() {
+    let (tx_sink, rx_sink) = flume::bounded::<()>(1);
+
+    let mut registry = NodeRegistry::new();
+    registry
+        .try_register_source("static://vec-source", || {
+            Ok(Arc::new(VecSource::new(vec![3, 2, 1])))
+        })
+        .unwrap();
+    registry
+        .try_register_operator("Double", || Ok(Arc::new(Double)))
+        .unwrap();
+    registry
+        .try_register_sink("Sink", move || {
+            Ok(Arc::new(VecSink::new(tx_sink.clone(), vec![6, 4, 2])))
+        })
+        .unwrap();
+
+    let ctx = runtime_context(registry).await;
+    let dataflow = Dataflow::try_new(ctx, record(DESCRIPTOR)).unwrap();
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    instance.start_nodes().await.unwrap();
+    instance.start_sources().await.unwrap();
+
+    // Wait for the Sink to receive all the values.
+    rx_sink.recv_async().await.unwrap();
+
+    instance.stop_sources().await.unwrap();
+    instance
+        .stop_nodes(janu_flow::runtime::DEFAULT_DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+}
+
+#[test]
+fn registry_dataflow() {
+    async_std::task::block_on(single_runtime())
+}
+
+#[test]
+fn registry_ko() {
+    let mut registry = NodeRegistry::new();
+    registry
+        .try_register_operator("Double", || Ok(Arc::new(Double)))
+        .unwrap();
+    match registry.try_register_operator("Double", || Ok(Arc::new(Double))) {
+        Err(ZFError::DuplicatedNodeId(id)) => assert_eq!("Double", id.as_ref()),
+        _ => panic!("Expected a `DuplicatedNodeId` when registering twice the same key."),
+    }
+
+    // The Source and the Sink are not registered: the `Loader` is used and they have no URI.
+    let ctx = async_std::task::block_on(runtime_context(registry));
+    let descriptor = DESCRIPTOR.replace("    uri: static://vec-source\n", "");
+    match Dataflow::try_new(ctx, record(&descriptor)) {
+        Err(ZFError::LoadingError(_)) => (),
+        _ => panic!("Expected a `LoadingError` for a node neither registered nor with a URI."),
+    }
+}
@@ -0,0 +1,301 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::link::PortDescriptor;
+use janu_flow::model::node::{RestartPolicy, DEFAULT_RESTART_BACKOFF, DEFAULT_RESTART_MAX_BACKOFF};
+use janu_flow::model::{InputDescriptor, OutputDescriptor};
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::{NodeState, RuntimeContext};
+use janu_flow::{
+    zf_empty_state, Configuration, Context, Data, Node, Sink, Source, State, ZFError, ZFResult,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+static FAILING_SOURCE_RUNS: AtomicUsize = AtomicUsize::new(0);
+
+struct FailingSource;
+
+#[async_trait]
+impl Source for FailingSource {
+    async fn run(&self, _context: &mut Context, _state: &mut State) -> ZFResult<Data> {
+        FAILING_SOURCE_RUNS.fetch_add(1, Ordering::AcqRel);
+        Err(ZFError::InvalidData("Failing source".to_string()))
+    }
+}
+
+impl Node for FailingSource {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+struct NoOpSink;
+
+#[async_trait]
+impl Sink for NoOpSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        _input: janu_flow::runtime::message::DataMessage,
+    ) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+impl Node for NoOpSink {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+static DESCRIPTOR: &str = r#"
+flow: RestartPolicy
+operators:
+  - id : Operator
+    uri: file://./operator.dylib
+    inputs:
+      - id: in
+        type: usize
+    outputs:
+      - id: out
+        type: usize
+    restart_policy:
+      policy: on-failure
+      max_retries: 4
+      backoff:
+        length: 10
+        unit: ms
+      max_backoff:
+        length: 50
+        unit: ms
+sources:
+  - id : Source
+    uri: file://./source.dylib
+    output:
+      id: out
+      type: usize
+    restart_policy:
+      policy: never
+sinks:
+  - id : Sink
+    uri: file://./sink.dylib
+    input:
+      id: in
+      type: usize
+
+links:
+- from:
+    node : Source
+    output : out
+  to:
+    node : Operator
+    input : in
+- from:
+    node : Operator
+    output : out
+  to:
+    node : Sink
+    input : in
+"#;
+
+#[test]
+fn restart_policy_from_descriptor() {
+    let descriptor = DataFlowDescriptor::from_yaml(DESCRIPTOR).unwrap();
+
+    let operator_policy = descriptor.operators[0].restart_policy.clone().unwrap();
+    let delays: Vec<Option<Duration>> = (0..5)
+        .map(|restarts| operator_policy.next_restart(restarts))
+        .collect();
+    assert_eq!(
+        delays,
+        vec![
+            Some(Duration::from_millis(10)),
+            Some(Duration::from_millis(20)),
+            Some(Duration::from_millis(40)),
+            Some(Duration::from_millis(50)),
+            None
+        ]
+    );
+
+    assert_eq!(
+        operator_policy.get_healthy_run(),
+        Some(Duration::from_millis(50))
+    );
+
+    let source_policy = descriptor.sources[0].restart_policy.clone().unwrap();
+    assert_eq!(source_policy.next_restart(0), None);
+    assert_eq!(source_policy.get_healthy_run(), None);
+
+    assert!(descriptor.sinks[0].restart_policy.is_none());
+    let delays: Vec<Option<Duration>> = [0, 1, usize::MAX]
+        .iter()
+        .map(|restarts| RestartPolicy::default().next_restart(*restarts))
+        .collect();
+    assert_eq!(
+        delays,
+        vec![
+            Some(DEFAULT_RESTART_BACKOFF),
+            Some(DEFAULT_RESTART_BACKOFF * 2),
+            Some(DEFAULT_RESTART_MAX_BACKOFF)
+        ]
+    );
+
+    let always_policy: RestartPolicy =
+        serde_yaml::from_str("policy: always\nbackoff:\n  length: 10\n  unit: ms\n").unwrap();
+    assert_eq!(
+        always_policy.next_restart(usize::MAX),
+        Some(DEFAULT_RESTART_MAX_BACKOFF)
+    );
+    assert_eq!(
+        always_policy.next_restart(2),
+        Some(Duration::from_millis(40))
+    );
+}
+
+#[test]
+fn restart_policy_terminal_error() {
+    async_std::task::block_on(async {
+        let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+        let rt_uuid = uuid::Uuid::new_v4();
+        let ctx = RuntimeContext {
+            session,
+            hlc: Arc::new(uhlc::HLC::default()),
+            loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
+            runtime_name: format!("test-runtime-{}", rt_uuid).into(),
+            runtime_uuid: rt_uuid,
+            checkpoints: None,
+        };
+
+        let mut dataflow = Dataflow::new(ctx, "restart-policy".into(), None);
+
+        let source = Arc::new(FailingSource {});
+        let sink = Arc::new(NoOpSink {});
+        let port = PortDescriptor {
+            port_id: "data".into(),
+            port_type: "usize".into(),
+        };
+
+        dataflow
+            .try_add_static_source(
+                "failing-source".into(),
+                None,
+                port.clone(),
+                source.initialize(&None).unwrap(),
+                source,
+            )
+            .unwrap();
+        dataflow
+            .try_add_static_sink("sink".into(), port, sink.initialize(&None).unwrap(), sink)
+            .unwrap();
+        dataflow
+            .try_add_link(
+                OutputDescriptor {
+                    node: "failing-source".into(),
+                    output: "data".into(),
+                },
+                InputDescriptor {
+                    node: "sink".into(),
+                    input: "data".into(),
+                },
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        let restart_policy: RestartPolicy = serde_yaml::from_str(
+            "policy: on-failure\nmax_retries: 2\nbackoff:\n  length: 10\n  unit: ms\n",
+        )
+        .unwrap();
+        dataflow
+            .try_set_restart_policy(&"failing-source".into(), restart_policy)
+            .unwrap();
+        assert_eq!(
+            dataflow.try_set_restart_policy(&"unknown".into(), RestartPolicy::Never),
+            Err(ZFError::NodeNotFound("unknown".into()))
+        );
+
+        let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+        for id in &instance.get_nodes() {
+            instance.start_node(id).await.unwrap();
+        }
+
+        async_std::task::sleep(Duration::from_millis(500)).await;
+
+        // One run and two restarts.
+        assert_eq!(FAILING_SOURCE_RUNS.load(Ordering::Acquire), 3);
+        assert!(!instance
+            .is_node_running(&"failing-source".into())
+            .await
+            .unwrap());
+        assert_eq!(
+            instance.get_failed_nodes().await,
+            vec![(
+                "failing-source".into(),
+                ZFError::InvalidData("Failing source".to_string())
+            )]
+        );
+        assert!(instance.is_node_running(&"sink".into()).await.unwrap());
+
+        let source_status = instance
+            .get_node_status(&"failing-source".into())
+            .await
+            .unwrap();
+        assert_eq!(
+            source_status.state,
+            NodeState::Error(ZFError::InvalidData("Failing source".to_string()))
+        );
+        assert_eq!(source_status.restarts, 2);
+        assert_eq!(
+            source_status.last_error,
+            Some(ZFError::InvalidData("Failing source".to_string()))
+        );
+        assert_eq!(
+            instance.get_node_state(&"sink".into()).await.unwrap(),
+            NodeState::Running
+        );
+
+        for id in &instance.get_nodes() {
+            instance.stop_node(id).await.unwrap();
+        }
+
+        // A node in a terminal error state stays in it, a stopped node is reported as such.
+        assert!(matches!(
+            instance
+                .get_node_state(&"failing-source".into())
+                .await
+                .unwrap(),
+            NodeState::Error(_)
+        ));
+        assert_eq!(
+            instance.get_node_state(&"sink".into()).await.unwrap(),
+            NodeState::Stopped
+        );
+    });
+}
@@ -0,0 +1,305 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+mod types;
+
+use async_std::sync::{Arc, Mutex};
+use async_trait::async_trait;
+use janu_flow::model::link::PortDescriptor;
+use janu_flow::model::{InputDescriptor, OutputDescriptor};
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::{DrainReport, RuntimeContext, DEFAULT_DRAIN_TIMEOUT};
+use janu_flow::{
+    default_input_rule, default_output_rule, zf_empty_state, Configuration, Context, Data,
+    LocalDeadlineMiss, Node, NodeId, NodeOutput, Operator, PortId, Sink, Source, State, ZFError,
+    ZFResult,
+};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use types::ZFUsize;
+
+static PORT: &str = "data";
+static MESSAGES: usize = 10;
+
+// SOURCE
+
+// Sends `MESSAGES` messages as fast as possible, then waits forever.
+struct BurstSource {
+    sent: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl Source for BurstSource {
+    async fn run(&self, _context: &mut Context, _state: &mut State) -> ZFResult<Data> {
+        if self.sent.load(Ordering::Acquire) == MESSAGES {
+            async_std::future::pending::<()>().await;
+        }
+        let value = self.sent.fetch_add(1, Ordering::AcqRel) + 1;
+        Ok(Data::from::<ZFUsize>(ZFUsize(value)))
+    }
+}
+
+impl Node for BurstSource {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+// OPERATOR
+
+struct NoOp;
+
+impl Operator for NoOp {
+    fn input_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, janu_flow::Token>,
+    ) -> ZFResult<bool> {
+        default_input_rule(state, tokens)
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        inputs: &mut HashMap<PortId, janu_flow::runtime::message::DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let data = inputs
+            .get_mut(PORT)
+            .ok_or_else(|| ZFError::InvalidData("No data".to_string()))?
+            .get_inner_data()
+            .try_get::<ZFUsize>()?;
+
+        let mut results: HashMap<PortId, Data> = HashMap::new();
+        results.insert(PORT.into(), Data::from::<ZFUsize>(data.clone()));
+        Ok(results)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+impl Node for NoOp {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+// SINK
+
+// Slow sink: the messages pile up in its input link.
+struct SlowSink {
+    received: Arc<Mutex<Vec<usize>>>,
+    delay: Duration,
+}
+
+#[async_trait]
+impl Sink for SlowSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        mut input: janu_flow::runtime::message::DataMessage,
+    ) -> ZFResult<()> {
+        let data = input.get_inner_data().try_get::<ZFUsize>()?;
+        self.received.lock().await.push(data.0);
+        async_std::task::sleep(self.delay).await;
+        Ok(())
+    }
+}
+
+impl Node for SlowSink {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+fn port() -> PortDescriptor {
+    PortDescriptor {
+        port_id: PORT.into(),
+        port_type: "usize".into(),
+    }
+}
+
+fn link(dataflow: &mut Dataflow, from: &str, to: &str) {
+    dataflow
+        .try_add_link(
+            OutputDescriptor {
+                node: from.into(),
+                output: PORT.into(),
+            },
+            InputDescriptor {
+                node: to.into(),
+                input: PORT.into(),
+            },
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+}
+
+// Instantiates `source -> operator -> sink` and runs it until the source has sent all its
+// messages, the sources are then stopped.
+async fn run_until_sent(
+    sink_delay: Duration,
+) -> (DataflowInstance, Arc<AtomicUsize>, Arc<Mutex<Vec<usize>>>) {
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let rt_uuid = uuid::Uuid::new_v4();
+    let ctx = RuntimeContext {
+        session,
+        hlc: Arc::new(uhlc::HLC::default()),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
+        runtime_name: format!("test-runtime-{}", rt_uuid).into(),
+        runtime_uuid: rt_uuid,
+        checkpoints: None,
+    };
+
+    let mut dataflow = Dataflow::new(ctx, "start-stop".into(), None);
+
+    let sent = Arc::new(AtomicUsize::new(0));
+    let source = Arc::new(BurstSource { sent: sent.clone() });
+    let operator = Arc::new(NoOp {});
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let sink = Arc::new(SlowSink {
+        received: received.clone(),
+        delay: sink_delay,
+    });
+
+    dataflow
+        .try_add_static_sink("sink".into(), port(), sink.initialize(&None).unwrap(), sink)
+        .unwrap();
+    dataflow
+        .try_add_static_operator(
+            "operator".into(),
+            vec![port()],
+            vec![port()],
+            None,
+            operator.initialize(&None).unwrap(),
+            operator,
+        )
+        .unwrap();
+    dataflow
+        .try_add_static_source(
+            "source".into(),
+            None,
+            port(),
+            source.initialize(&None).unwrap(),
+            source,
+        )
+        .unwrap();
+    link(&mut dataflow, "source", "operator");
+    link(&mut dataflow, "operator", "sink");
+
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+    assert_eq!(
+        instance.get_topological_order(),
+        vec![
+            NodeId::from("source"),
+            NodeId::from("operator"),
+            NodeId::from("sink")
+        ]
+    );
+
+    instance.start_nodes().await.unwrap();
+    assert!(!instance.is_node_running(&"source".into()).await.unwrap());
+
+    instance.start_sources().await.unwrap();
+    while sent.load(Ordering::Acquire) < MESSAGES {
+        async_std::task::sleep(Duration::from_millis(1)).await;
+    }
+    instance.stop_sources().await.unwrap();
+
+    (instance, sent, received)
+}
+
+#[test]
+fn start_stop_drains_links() {
+    async_std::task::block_on(async {
+        let (mut instance, _sent, received) = run_until_sent(Duration::from_millis(20)).await;
+
+        let report = instance.stop_nodes(DEFAULT_DRAIN_TIMEOUT).await.unwrap();
+        assert_eq!(report, DrainReport::default());
+
+        for id in instance.get_nodes() {
+            assert!(!instance.is_node_running(&id).await.unwrap());
+        }
+        // The sink is slow: without draining the links most messages would have been lost.
+        assert_eq!(*received.lock().await, (1..=MESSAGES).collect::<Vec<_>>());
+
+        let metrics = instance.get_metrics();
+        let counters: Vec<(&str, u64, u64)> = metrics
+            .iter()
+            .map(|m| (m.node_id.as_ref(), m.messages_in, m.messages_out))
+            .collect();
+        let messages = MESSAGES as u64;
+        assert_eq!(
+            counters,
+            vec![
+                ("operator", messages, messages),
+                ("sink", messages, 0),
+                ("source", 0, messages)
+            ]
+        );
+        assert!(metrics.iter().all(|m| m.errors == 0 && m.restarts == 0));
+        // The sink was slow: messages piled up in its link.
+        let sink_queue_depth = &metrics[1].queue_depth["data"];
+        assert_eq!(sink_queue_depth.count, messages);
+        assert!(sink_queue_depth.sum > 0.0);
+        // The sink is stopped once its last execution of `run` completes.
+        assert_eq!(metrics[1].run_duration.count, messages);
+    });
+}
+
+#[test]
+fn start_stop_drain_timeout() {
+    async_std::task::block_on(async {
+        let (mut instance, _sent, received) = run_until_sent(Duration::from_millis(100)).await;
+
+        let report = instance
+            .stop_nodes(Duration::from_millis(150))
+            .await
+            .unwrap();
+        assert!(report.timed_out);
+
+        // The messages not received by the sink are the ones reported as dropped.
+        let received = received.lock().await.len();
+        assert!(received < MESSAGES);
+        assert_eq!(report.total_dropped(), MESSAGES - received);
+        assert_eq!(report.dropped.get("sink"), Some(&(MESSAGES - received)));
+    });
+}
@@ -13,7 +13,8 @@
 //
 
 use janu_flow::janu_flow_derive::ZFState;
-use janu_flow::State;
+use janu_flow::{EmptyState, State, ZFError};
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, ZFState, Clone)]
 struct TestState {
@@ -47,3 +48,33 @@ fn state_wrapping_unwrapping() {
     assert_eq!(unwrapped_state.field2, test_state.field2);
     assert!((unwrapped_state.field3 - test_state.field3).abs() < f64::EPSILON);
 }
+
+#[derive(Debug, ZFState, Serialize, Deserialize)]
+#[zf_state(serializable)]
+struct SerializableState {
+    pub counter: usize,
+    pub label: String,
+}
+
+#[test]
+fn state_serialization() {
+    let state = State::from(SerializableState {
+        counter: 42,
+        label: String::from("TestString"),
+    });
+    let bytes = state.try_serialize().unwrap().unwrap();
+
+    let mut restored_state = State::from(SerializableState {
+        counter: 0,
+        label: String::new(),
+    });
+    restored_state.try_deserialize(&bytes).unwrap();
+    let restored_state = restored_state.try_get::<SerializableState>().unwrap();
+    assert_eq!(restored_state.counter, 42);
+    assert_eq!(restored_state.label, "TestString");
+
+    // States are not serializable unless marked so.
+    let mut state = State::from(EmptyState {});
+    assert_eq!(state.try_serialize(), Ok(None));
+    assert_eq!(state.try_deserialize(&bytes), Err(ZFError::Unsupported));
+}
@@ -0,0 +1,414 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+mod types;
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use flume::{unbounded, Receiver, Sender};
+use janu_flow::model::link::PortDescriptor;
+use janu_flow::model::{InputDescriptor, OutputDescriptor};
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig};
+use janu_flow::runtime::RuntimeContext;
+use janu_flow::{
+    default_output_rule, zf_empty_state, Configuration, Context, Data, DataMessage, Joiner,
+    LocalDeadlineMiss, Node, NodeOutput, Operator, PortId, Sink, Source, State, Token,
+    UnmatchedPolicy, ZFError, ZFResult,
+};
+use janu_flow_derive::ZFState;
+use std::collections::HashMap;
+use std::time::Duration;
+use types::ZFUsize;
+use uhlc::{Timestamp, HLC, NTP64};
+
+static LEFT: &str = "Left";
+static RIGHT: &str = "Right";
+static JOINED: &str = "Joined";
+
+// JOINER
+
+fn ready(hlc: &HLC, value: usize, time_ms: u64) -> Token {
+    let timestamp = Timestamp::new(
+        NTP64::from(Duration::from_millis(time_ms)),
+        hlc.new_timestamp().get_id().to_owned(),
+    );
+    Token::from(DataMessage::new(
+        Data::from::<ZFUsize>(ZFUsize(value)),
+        timestamp,
+        vec![],
+    ))
+}
+
+fn pending_tokens() -> HashMap<PortId, Token> {
+    let mut tokens = HashMap::new();
+    tokens.insert(LEFT.into(), Token::new_pending());
+    tokens.insert(RIGHT.into(), Token::new_pending());
+    tokens
+}
+
+fn value(tokens: &mut HashMap<PortId, Token>, port_id: &str) -> usize {
+    match tokens.get_mut(port_id) {
+        Some(Token::Ready(ready_token)) => {
+            ready_token.get_data_mut().try_get::<ZFUsize>().unwrap().0
+        }
+        _ => panic!("Expected a `Ready` token on < {} >.", port_id),
+    }
+}
+
+// Feeds `token` on `port_id`, the other tokens being pending, as the runner does once the tokens
+// of the previous call were dropped.
+fn feed(joiner: &mut Joiner, port_id: &str, token: Token) -> (bool, HashMap<PortId, Token>) {
+    let mut tokens = pending_tokens();
+    tokens.insert(port_id.into(), token);
+    let result = joiner.input_rule(&mut tokens).unwrap();
+    (result, tokens)
+}
+
+#[test]
+fn joiner_nearest() {
+    let hlc = HLC::default();
+    let mut joiner = Joiner::new(Duration::from_millis(10));
+
+    assert!(!feed(&mut joiner, RIGHT, ready(&hlc, 95, 95)).0);
+    assert!(!feed(&mut joiner, RIGHT, ready(&hlc, 103, 103)).0);
+    assert!(!feed(&mut joiner, RIGHT, ready(&hlc, 130, 130)).0);
+
+    let (result, mut tokens) = feed(&mut joiner, LEFT, ready(&hlc, 100, 100));
+    assert!(result);
+    assert_eq!(100, value(&mut tokens, LEFT));
+    assert_eq!(103, value(&mut tokens, RIGHT));
+
+    // The sample at 95 is older than the one matched: it is discarded. The one at 130 is kept.
+    assert_eq!(1, joiner.get_unmatched_count());
+    assert_eq!(0, joiner.get_buffered_count(LEFT));
+    assert_eq!(1, joiner.get_buffered_count(RIGHT));
+}
+
+#[test]
+fn joiner_window() {
+    let hlc = HLC::default();
+    let mut joiner =
+        Joiner::new(Duration::from_millis(10)).with_unmatched_policy(UnmatchedPolicy::Report);
+
+    assert!(!feed(&mut joiner, LEFT, ready(&hlc, 100, 100)).0);
+    // The sample on the left is too old to ever be matched.
+    assert!(!feed(&mut joiner, RIGHT, ready(&hlc, 200, 200)).0);
+    // The sample on the left is too recent to be matched with the one on the right.
+    assert!(!feed(&mut joiner, LEFT, ready(&hlc, 220, 220)).0);
+
+    let mut unmatched = joiner.take_unmatched();
+    assert_eq!(2, unmatched.len());
+    assert_eq!(LEFT, unmatched[0].0.as_ref());
+    assert_eq!(
+        100,
+        unmatched[0]
+            .1
+            .get_inner_data()
+            .try_get::<ZFUsize>()
+            .unwrap()
+            .0
+    );
+    assert_eq!(RIGHT, unmatched[1].0.as_ref());
+    assert_eq!(
+        200,
+        unmatched[1]
+            .1
+            .get_inner_data()
+            .try_get::<ZFUsize>()
+            .unwrap()
+            .0
+    );
+    assert!(joiner.take_unmatched().is_empty());
+
+    let (result, mut tokens) = feed(&mut joiner, RIGHT, ready(&hlc, 225, 225));
+    assert!(result);
+    assert_eq!(220, value(&mut tokens, LEFT));
+    assert_eq!(225, value(&mut tokens, RIGHT));
+    assert_eq!(2, joiner.get_unmatched_count());
+}
+
+#[test]
+fn joiner_capacity() {
+    let hlc = HLC::default();
+    let mut joiner = Joiner::new(Duration::from_millis(10)).with_capacity(2);
+
+    assert!(!feed(&mut joiner, LEFT, ready(&hlc, 1, 100)).0);
+    assert!(!feed(&mut joiner, LEFT, ready(&hlc, 2, 110)).0);
+    assert!(!feed(&mut joiner, LEFT, ready(&hlc, 3, 120)).0);
+
+    // The oldest sample was evicted and, with the policy `Drop`, is not reported.
+    assert_eq!(1, joiner.get_unmatched_count());
+    assert_eq!(2, joiner.get_buffered_count(LEFT));
+    assert!(joiner.take_unmatched().is_empty());
+
+    let (result, mut tokens) = feed(&mut joiner, RIGHT, ready(&hlc, 10, 108));
+    assert!(result);
+    assert_eq!(2, value(&mut tokens, LEFT));
+    assert_eq!(10, value(&mut tokens, RIGHT));
+}
+
+// SOURCE
+
+struct TriggeredSource {
+    rx: Receiver<usize>,
+}
+
+#[async_trait]
+impl Source for TriggeredSource {
+    async fn run(&self, _context: &mut Context, _state: &mut State) -> ZFResult<Data> {
+        let value = self
+            .rx
+            .recv_async()
+            .await
+            .map_err(|e| ZFError::RecvError(format!("{}", e)))?;
+        Ok(Data::from::<ZFUsize>(ZFUsize(value)))
+    }
+}
+
+impl Node for TriggeredSource {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+// SINK
+
+struct ForwardSink {
+    tx: Sender<usize>,
+}
+
+#[async_trait]
+impl Sink for ForwardSink {
+    async fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        mut input: DataMessage,
+    ) -> ZFResult<()> {
+        let data = input.get_inner_data().try_get::<ZFUsize>()?;
+        self.tx.send_async(data.0).await.unwrap();
+        Ok(())
+    }
+}
+
+impl Node for ForwardSink {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        zf_empty_state!()
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+// OPERATOR
+
+#[derive(Debug, ZFState)]
+struct JoinState {
+    joiner: Joiner,
+}
+
+struct JoinSum;
+
+impl Operator for JoinSum {
+    fn input_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        tokens: &mut HashMap<PortId, Token>,
+    ) -> ZFResult<bool> {
+        state.try_get::<JoinState>()?.joiner.input_rule(tokens)
+    }
+
+    fn run(
+        &self,
+        _context: &mut Context,
+        _state: &mut State,
+        inputs: &mut HashMap<PortId, DataMessage>,
+    ) -> ZFResult<HashMap<PortId, Data>> {
+        let mut sum = 0;
+        for data_message in inputs.values_mut() {
+            sum += data_message.get_inner_data().try_get::<ZFUsize>()?.0;
+        }
+
+        let mut results: HashMap<PortId, Data> = HashMap::new();
+        results.insert(JOINED.into(), Data::from::<ZFUsize>(ZFUsize(sum)));
+        Ok(results)
+    }
+
+    fn output_rule(
+        &self,
+        _context: &mut Context,
+        state: &mut State,
+        outputs: HashMap<PortId, Data>,
+        _deadline_miss: Option<LocalDeadlineMiss>,
+    ) -> ZFResult<HashMap<PortId, NodeOutput>> {
+        default_output_rule(state, outputs)
+    }
+}
+
+impl Node for JoinSum {
+    fn initialize(&self, _configuration: &Option<Configuration>) -> ZFResult<State> {
+        Ok(State::from(JoinState {
+            joiner: Joiner::new(Duration::from_millis(100)),
+        }))
+    }
+
+    fn finalize(&self, _state: &mut State) -> ZFResult<()> {
+        Ok(())
+    }
+}
+
+fn port(port_id: &str) -> PortDescriptor {
+    PortDescriptor {
+        port_id: port_id.into(),
+        port_type: "int".into(),
+    }
+}
+
+// Run dataflow in single runtime
+async fn single_runtime() {
+    let (tx_left, rx_left) = unbounded::<usize>();
+    let (tx_right, rx_right) = unbounded::<usize>();
+    let (tx_sink, rx_sink) = unbounded::<usize>();
+
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let hlc = Arc::new(uhlc::HLC::default());
+    let rt_uuid = uuid::Uuid::new_v4();
+    let ctx = RuntimeContext {
+        session,
+        hlc,
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] })),
+        runtime_name: format!("test-runtime-{}", rt_uuid).into(),
+        runtime_uuid: rt_uuid,
+        checkpoints: None,
+    };
+
+    let mut dataflow =
+        janu_flow::runtime::dataflow::Dataflow::new(ctx.clone(), "test".into(), None);
+
+    for (id, rx) in [(LEFT, rx_left), (RIGHT, rx_right)] {
+        let source = Arc::new(TriggeredSource { rx });
+        dataflow
+            .try_add_static_source(
+                id.into(),
+                None,
+                port(id),
+                source.initialize(&None).unwrap(),
+                source,
+            )
+            .unwrap();
+    }
+
+    let sink = Arc::new(ForwardSink { tx: tx_sink });
+    dataflow
+        .try_add_static_sink(
+            "sink".into(),
+            port(JOINED),
+            sink.initialize(&None).unwrap(),
+            sink,
+        )
+        .unwrap();
+
+    let operator = Arc::new(JoinSum);
+    dataflow
+        .try_add_static_operator(
+            "join".into(),
+            vec![port(LEFT), port(RIGHT)],
+            vec![port(JOINED)],
+            None,
+            operator.initialize(&None).unwrap(),
+            operator,
+        )
+        .unwrap();
+
+    for id in [LEFT, RIGHT] {
+        dataflow
+            .try_add_link(
+                OutputDescriptor {
+                    node: id.into(),
+                    output: id.into(),
+                },
+                InputDescriptor {
+                    node: "join".into(),
+                    input: id.into(),
+                },
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+    }
+
+    dataflow
+        .try_add_link(
+            OutputDescriptor {
+                node: "join".into(),
+                output: JOINED.into(),
+            },
+            InputDescriptor {
+                node: "sink".into(),
+                input: JOINED.into(),
+            },
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    let ids = instance.get_nodes();
+    for id in &ids {
+        instance.start_node(id).await.unwrap();
+    }
+
+    tx_left.send_async(1).await.unwrap();
+    tx_right.send_async(10).await.unwrap();
+    assert_eq!(11, rx_sink.recv_async().await.unwrap());
+
+    // The first sample on the left is too old to be matched with the one on the right: it is
+    // dropped.
+    tx_left.send_async(2).await.unwrap();
+    janu_flow::async_std::task::sleep(Duration::from_millis(500)).await;
+    tx_left.send_async(3).await.unwrap();
+    tx_right.send_async(20).await.unwrap();
+    assert_eq!(23, rx_sink.recv_async().await.unwrap());
+
+    for id in &instance.get_sources() {
+        instance.stop_node(id).await.unwrap()
+    }
+
+    for id in &instance.get_operators() {
+        instance.stop_node(id).await.unwrap()
+    }
+
+    for id in &instance.get_sinks() {
+        instance.stop_node(id).await.unwrap()
+    }
+
+    assert!(rx_sink.try_recv().is_err());
+}
+
+#[test]
+fn time_aligned_join() {
+    let h1 = async_std::task::spawn(async move { single_runtime().await });
+
+    async_std::task::block_on(h1)
+}
@@ -898,3 +898,91 @@ fn validate_ko_reverse_deadline() {
         )))
     )
 }
+
+static DESCRIPTOR_KO_QUEUEING_POLICY: &str = r#"
+flow: SimplePipeline
+operators:
+  - id : SumOperator
+    uri: file://./target/release/libsum_and_send.dylib
+    inputs:
+      - id: Number
+        type: usize
+    outputs:
+      - id: Sum
+        type: usize
+sources:
+  - id : Counter
+    uri: file://./target/release/libcounter_source.dylib
+    output:
+      id: Counter
+      type: usize
+sinks:
+  - id : PrintSink
+    uri: file://./target/release/libgeneric_sink.dylib
+    input:
+      id: Data
+      type: usize
+
+links:
+- from:
+    node : Counter
+    output : Counter
+  to:
+    node : SumOperator
+    input : Number
+  size: 10
+  queueing_policy: drop-everything
+- from:
+    node : SumOperator
+    output : Sum
+  to:
+    node : PrintSink
+    input : Data
+"#;
+
+#[test]
+fn validate_ko_queueing_policy() {
+    let r = DataFlowDescriptor::from_yaml(DESCRIPTOR_KO_QUEUEING_POLICY);
+    assert!(matches!(r, Err(ZFError::ParsingError(_))))
+}
+
+#[test]
+fn validate_ko_reliability() {
+    let r = DataFlowDescriptor::from_yaml(
+        &DESCRIPTOR_KO_QUEUEING_POLICY
+            .replace("queueing_policy: drop-everything", "reliability: twice"),
+    );
+    assert!(matches!(r, Err(ZFError::ParsingError(_))))
+}
+
+fn with_input_rule(input_rule: &str) -> String {
+    DESCRIPTOR_OK.replace(
+        "    uri: file://./target/release/libsum_and_send.dylib\n",
+        &format!(
+            "    uri: file://./target/release/libsum_and_send.dylib\n    input_rule: {}\n",
+            input_rule
+        ),
+    )
+}
+
+#[test]
+fn validate_ok_input_rule() {
+    for input_rule in [
+        "{policy: all}",
+        "{policy: latest}",
+        "{policy: k-of-n, k: 1}",
+    ] {
+        let r = DataFlowDescriptor::from_yaml(&with_input_rule(input_rule));
+        assert!(r.is_ok(), "Unexpected error: {:?}", r);
+    }
+}
+
+#[test]
+fn validate_ko_input_rule() {
+    // `SumOperator` has a single input.
+    let r = DataFlowDescriptor::from_yaml(&with_input_rule("{policy: k-of-n, k: 2}"));
+    assert!(matches!(r, Err(ZFError::ParsingError(_))));
+
+    let r = DataFlowDescriptor::from_yaml(&with_input_rule("{policy: unknown}"));
+    assert!(r.is_err());
+}
@@ -0,0 +1,271 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+#![cfg(feature = "wasm")]
+
+mod types;
+
+use async_std::sync::Arc;
+use janu_flow::model::dataflow::descriptor::DataFlowDescriptor;
+use janu_flow::model::dataflow::record::DataFlowRecord;
+use janu_flow::runtime::dataflow::instance::DataflowInstance;
+use janu_flow::runtime::dataflow::loader::{Loader, LoaderConfig, WasmLimits};
+use janu_flow::runtime::dataflow::registry::NodeRegistry;
+use janu_flow::runtime::dataflow::wasm::{WasmNode, WasmOperator};
+use janu_flow::runtime::dataflow::Dataflow;
+use janu_flow::runtime::RuntimeContext;
+use janu_flow::{Context, Data, DataMessage, Node, Operator, PortId, ZFError};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::path::{Path, PathBuf};
+use types::{VecSink, VecSource, ZFUsize};
+
+static RUNTIME: &str = "wasm-runtime";
+
+// Doubles the `usize` received on `in` and sends it on `out`. The memory of WebAssembly is
+// little-endian, as the hosts running the tests.
+static DOUBLE: &str = r#"
+(module
+  (import "janu_flow" "input" (func $input (param i32 i32 i32 i32) (result i32)))
+  (import "janu_flow" "emit" (func $emit (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "in")
+  (data (i32.const 8) "out")
+  (func (export "zf_initialize") (result i32) (i32.const 0))
+  (func (export "zf_finalize") (result i32) (i32.const 0))
+  (func (export "zf_run") (result i32)
+    (if (i32.ne (call $input (i32.const 0) (i32.const 2) (i32.const 16) (i32.const 8)) (i32.const 8))
+      (then (return (i32.const 1))))
+    (i64.store (i32.const 16) (i64.shl (i64.load (i32.const 16)) (i64.const 1)))
+    (call $emit (i32.const 8) (i32.const 3) (i32.const 16) (i32.const 8))))
+"#;
+
+static LOOP: &str = r#"
+(module
+  (memory (export "memory") 1)
+  (func (export "zf_initialize") (result i32) (i32.const 0))
+  (func (export "zf_finalize") (result i32) (i32.const 0))
+  (func (export "zf_run") (result i32)
+    (loop $forever (br $forever))
+    (i32.const 0)))
+"#;
+
+// Asks for 2 pages, 128 KiB, of memory.
+static LARGE_MEMORY: &str = r#"
+(module
+  (memory (export "memory") 2)
+  (func (export "zf_initialize") (result i32) (i32.const 0))
+  (func (export "zf_finalize") (result i32) (i32.const 0))
+  (func (export "zf_run") (result i32) (i32.const 0)))
+"#;
+
+fn module(wat: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("wasm-{}.wasm", uuid::Uuid::new_v4()));
+    std::fs::write(&path, wat::parse_str(wat).unwrap()).unwrap();
+    path
+}
+
+fn descriptor(module: &Path) -> String {
+    format!(
+        r#"
+flow: Wasm
+sources:
+  - id : Source
+    output:
+      id: out
+      type: usize
+operators:
+  - id : Double
+    uri: file://{}
+    inputs:
+      - id: in
+        type: usize
+    outputs:
+      - id: out
+        type: usize
+sinks:
+  - id : Sink
+    input:
+      id: in
+      type: usize
+
+links:
+- from:
+    node : Source
+    output : out
+  to:
+    node : Double
+    input : in
+- from:
+    node : Double
+    output : out
+  to:
+    node : Sink
+    input : in
+
+mapping:
+  - id: Source
+    runtime: wasm-runtime
+  - id: Double
+    runtime: wasm-runtime
+  - id: Sink
+    runtime: wasm-runtime
+"#,
+        module.display()
+    )
+}
+
+async fn single_runtime() {
+    let module = module(DOUBLE);
+    let (tx_sink, rx_sink) = flume::bounded::<()>(1);
+
+    let mut registry = NodeRegistry::new();
+    registry
+        .try_register_source("Source", || Ok(Arc::new(VecSource::new(vec![3, 2, 1]))))
+        .unwrap();
+    registry
+        .try_register_sink("Sink", move || {
+            Ok(Arc::new(VecSink::new(tx_sink.clone(), vec![6, 4, 2])))
+        })
+        .unwrap();
+
+    let session = Arc::new(janu::open(janu::config::Config::default()).await.unwrap());
+    let ctx = RuntimeContext {
+        session,
+        hlc: Arc::new(uhlc::HLC::default()),
+        loader: Arc::new(Loader::new(LoaderConfig { extensions: vec![] }).with_registry(registry)),
+        runtime_name: RUNTIME.into(),
+        runtime_uuid: uuid::Uuid::new_v4(),
+        checkpoints: None,
+    };
+
+    let descriptor = DataFlowDescriptor::from_yaml(&descriptor(&module)).unwrap();
+    let record = DataFlowRecord::try_from((descriptor, uuid::Uuid::new_v4())).unwrap();
+    let dataflow = Dataflow::try_new(ctx, record).unwrap();
+    let mut instance = DataflowInstance::try_instantiate(dataflow).unwrap();
+
+    instance.start_nodes().await.unwrap();
+    instance.start_sources().await.unwrap();
+
+    // Wait for the Sink to receive all the values.
+    rx_sink.recv_async().await.unwrap();
+
+    instance.stop_sources().await.unwrap();
+    instance
+        .stop_nodes(janu_flow::runtime::DEFAULT_DRAIN_TIMEOUT)
+        .await
+        .unwrap();
+
+    std::fs::remove_file(module).unwrap();
+}
+
+#[test]
+fn wasm_dataflow() {
+    async_std::task::block_on(single_runtime())
+}
+
+#[test]
+fn wasm_fuel() {
+    let module = module(LOOP);
+    let limits = WasmLimits {
+        fuel: 10_000,
+        ..Default::default()
+    };
+    let operator = WasmOperator(WasmNode::try_new(&module, limits).unwrap());
+    let mut state = operator.initialize(&None).unwrap();
+
+    let hlc = uhlc::HLC::default();
+    let mut inputs: HashMap<PortId, DataMessage> = HashMap::new();
+    inputs.insert(
+        "in".into(),
+        DataMessage::new(
+            Data::from::<ZFUsize>(ZFUsize(1)),
+            hlc.new_timestamp(),
+            vec![],
+        ),
+    );
+    match operator.run(&mut Context::default(), &mut state, &mut inputs) {
+        Err(ZFError::ForeignError(_)) => (),
+        _ => panic!("Expected a `ForeignError` for a node running out of fuel."),
+    }
+
+    std::fs::remove_file(module).unwrap();
+}
+
+#[test]
+fn wasm_memory() {
+    let module = module(LARGE_MEMORY);
+    let limits = WasmLimits {
+        max_memory: 64 * 1024,
+        ..Default::default()
+    };
+    let operator = WasmOperator(WasmNode::try_new(&module, limits).unwrap());
+    match operator.initialize(&None) {
+        Err(ZFError::LoadingError(_)) => (),
+        _ => panic!("Expected a `LoadingError` for a node using too much memory."),
+    }
+
+    std::fs::remove_file(module).unwrap();
+}
+
+#[test]
+fn wasm_emit() {
+    let module = module(DOUBLE);
+    let hlc = uhlc::HLC::default();
+    let run = |operator: &WasmOperator| {
+        let mut state = operator.initialize(&None).unwrap();
+        let mut inputs: HashMap<PortId, DataMessage> = HashMap::new();
+        inputs.insert(
+            "in".into(),
+            DataMessage::new(
+                Data::from::<ZFUsize>(ZFUsize(1)),
+                hlc.new_timestamp(),
+                vec![],
+            ),
+        );
+        operator.run(&mut Context::default(), &mut state, &mut inputs)
+    };
+
+    // `out` is not an output of the node.
+    let operator = WasmOperator(WasmNode::try_new(&module, WasmLimits::default()).unwrap());
+    match run(&operator) {
+        Err(ZFError::ForeignError(_)) => (),
+        _ => panic!("Expected a `ForeignError` for a node emitting on an unknown port."),
+    }
+
+    let operator = WasmOperator(
+        WasmNode::try_new(&module, WasmLimits::default())
+            .unwrap()
+            .with_outputs(["out".into()]),
+    );
+    let outputs = run(&operator).unwrap();
+    assert!(outputs.contains_key("out"));
+
+    // The node emits 8 bytes.
+    let limits = WasmLimits {
+        max_output: 4,
+        ..Default::default()
+    };
+    let operator = WasmOperator(
+        WasmNode::try_new(&module, limits)
+            .unwrap()
+            .with_outputs(["out".into()]),
+    );
+    match run(&operator) {
+        Err(ZFError::ForeignError(_)) => (),
+        _ => panic!("Expected a `ForeignError` for a node emitting too many bytes."),
+    }
+
+    std::fs::remove_file(module).unwrap();
+}
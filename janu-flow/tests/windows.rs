@@ -0,0 +1,115 @@
+//
+// Copyright (c) 2017, 2021 Tawedge.
+//
+// This program and the accompanying materials are made available under the
+// terms of the TAW Public License 2.0 which is available at
+// http://www.eclipse.org/legal/epl-2.0, or the Apache License, Version 2.0
+// which is available at https://www.apache.org/licenses/LICENSE-2.0.
+//
+// SPDX-License-Identifier: EPL-2.0 OR Apache-2.0
+//
+// Contributors:
+//   open
+//
+
+use janu_flow::{Window, WindowKind, Windows};
+use std::time::Duration;
+use uhlc::{Timestamp, HLC, NTP64};
+
+fn at(hlc: &HLC, time_ms: u64) -> Timestamp {
+    Timestamp::new(
+        NTP64::from(Duration::from_millis(time_ms)),
+        hlc.new_timestamp().get_id().to_owned(),
+    )
+}
+
+fn bounds(window: &Window<u64>) -> (u128, u128) {
+    (window.start.as_millis(), window.end.as_millis())
+}
+
+#[test]
+fn windows_tumbling() {
+    let hlc = HLC::default();
+    let mut windows = Windows::try_new(WindowKind::Tumbling(Duration::from_millis(100))).unwrap();
+
+    assert!(windows.insert(&at(&hlc, 10), 10));
+    assert!(windows.insert(&at(&hlc, 50), 50));
+    assert!(windows.take_closed().is_empty());
+
+    // The timestamp of the sample closes [0, 100).
+    assert!(windows.insert(&at(&hlc, 120), 120));
+    let closed = windows.take_closed();
+    assert_eq!(1, closed.len());
+    assert_eq!((0, 100), bounds(&closed[0]));
+    assert_eq!(vec![10, 50], closed[0].items);
+
+    assert!(!windows.advance(&at(&hlc, 199)));
+    assert!(windows.advance(&at(&hlc, 200)));
+    let closed = windows.take_closed();
+    assert_eq!(1, closed.len());
+    assert_eq!((100, 200), bounds(&closed[0]));
+    assert_eq!(vec![120], closed[0].items);
+    assert_eq!(0, windows.get_open_count());
+}
+
+#[test]
+fn windows_sliding() {
+    let hlc = HLC::default();
+    let mut windows = Windows::try_new(WindowKind::Sliding {
+        size: Duration::from_millis(100),
+        slide: Duration::from_millis(50),
+    })
+    .unwrap();
+
+    assert!(windows.insert(&at(&hlc, 60), 60)); // [0, 100) and [50, 150)
+    assert_eq!(2, windows.get_open_count());
+    assert!(windows.insert(&at(&hlc, 120), 120)); // [50, 150) and [100, 200)
+
+    let closed = windows.take_closed();
+    assert_eq!(1, closed.len());
+    assert_eq!((0, 100), bounds(&closed[0]));
+    assert_eq!(vec![60], closed[0].items);
+
+    assert!(windows.advance_to(Duration::from_millis(250)));
+    let closed = windows.take_closed();
+    assert_eq!(2, closed.len());
+    assert_eq!((50, 150), bounds(&closed[0]));
+    assert_eq!(vec![60, 120], closed[0].items);
+    assert_eq!((100, 200), bounds(&closed[1]));
+    assert_eq!(vec![120], closed[1].items);
+}
+
+#[test]
+fn windows_allowed_lateness() {
+    let hlc = HLC::default();
+    let mut windows = Windows::try_new(WindowKind::Tumbling(Duration::from_millis(100)))
+        .unwrap()
+        .with_allowed_lateness(Duration::from_millis(50));
+
+    assert!(windows.insert(&at(&hlc, 120), 120));
+    // [0, 100) is still open: the watermark is before 100 + 50.
+    assert!(windows.insert(&at(&hlc, 90), 90));
+    assert_eq!(Duration::from_millis(120), windows.get_watermark());
+    assert!(windows.take_closed().is_empty());
+
+    assert!(windows.insert(&at(&hlc, 180), 180));
+    let closed = windows.take_closed();
+    assert_eq!(1, closed.len());
+    assert_eq!((0, 100), bounds(&closed[0]));
+    assert_eq!(vec![90], closed[0].items);
+
+    // [0, 100) is closed: the sample is late.
+    assert!(!windows.insert(&at(&hlc, 95), 95));
+    assert_eq!(1, windows.get_late_count());
+    assert!(windows.take_closed().is_empty());
+}
+
+#[test]
+fn windows_invalid() {
+    assert!(Windows::<u64>::try_new(WindowKind::Tumbling(Duration::ZERO)).is_err());
+    assert!(Windows::<u64>::try_new(WindowKind::Sliding {
+        size: Duration::from_millis(100),
+        slide: Duration::ZERO,
+    })
+    .is_err());
+}